@@ -0,0 +1,39 @@
+-- PostgreSQL version
+CREATE TABLE IF NOT EXISTS coupons (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    code VARCHAR(50) NOT NULL,
+    discount_type VARCHAR(20) NOT NULL, -- percentage, fixed
+    discount_value INTEGER NOT NULL, -- percent (0-100) or cents
+    valid_from TIMESTAMP,
+    valid_until TIMESTAMP,
+    min_basket INTEGER NOT NULL DEFAULT 0, -- in cents
+    max_uses INTEGER, -- NULL = unlimited
+    max_uses_per_customer INTEGER, -- NULL = unlimited
+    times_used INTEGER NOT NULL DEFAULT 0,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    UNIQUE (tenant_id, code)
+);
+
+CREATE TABLE IF NOT EXISTS coupon_redemptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    coupon_id UUID NOT NULL,
+    sale_id UUID NOT NULL,
+    customer_id UUID,
+    discount_amount BIGINT NOT NULL, -- in cents
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (coupon_id) REFERENCES coupons(id) ON DELETE CASCADE,
+    FOREIGN KEY (sale_id) REFERENCES sales(id) ON DELETE CASCADE,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE SET NULL
+);
+
+ALTER TABLE sales ADD COLUMN IF NOT EXISTS discount_amount BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE sales ADD COLUMN IF NOT EXISTS coupon_id UUID;
+ALTER TABLE sales ADD CONSTRAINT fk_sales_coupon
+    FOREIGN KEY (coupon_id) REFERENCES coupons(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_coupons_tenant_id ON coupons(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_coupon_id ON coupon_redemptions(coupon_id);
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_customer_id ON coupon_redemptions(customer_id);
@@ -0,0 +1,37 @@
+-- PostgreSQL version
+ALTER TABLE products ADD COLUMN IF NOT EXISTS is_gift_card BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS gift_cards (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    code VARCHAR(50) NOT NULL,
+    kind VARCHAR(20) NOT NULL DEFAULT 'gift_card', -- gift_card, store_credit
+    initial_balance BIGINT NOT NULL, -- in cents
+    balance BIGINT NOT NULL, -- in cents
+    customer_id UUID,
+    expires_at TIMESTAMP,
+    status VARCHAR(20) NOT NULL DEFAULT 'active', -- active, cancelled
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE SET NULL,
+    UNIQUE (tenant_id, code),
+    CHECK (balance >= 0)
+);
+
+-- Balance ledger: every change to gift_cards.balance has a row here
+CREATE TABLE IF NOT EXISTS gift_card_transactions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    gift_card_id UUID NOT NULL,
+    sale_id UUID,
+    kind VARCHAR(20) NOT NULL, -- activation, redemption, store_credit
+    amount BIGINT NOT NULL, -- signed, in cents
+    balance_after BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (gift_card_id) REFERENCES gift_cards(id) ON DELETE CASCADE,
+    FOREIGN KEY (sale_id) REFERENCES sales(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_gift_cards_tenant_id ON gift_cards(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_gift_cards_customer_id ON gift_cards(customer_id);
+CREATE INDEX IF NOT EXISTS idx_gift_card_transactions_gift_card_id ON gift_card_transactions(gift_card_id);
+CREATE INDEX IF NOT EXISTS idx_gift_card_transactions_sale_id ON gift_card_transactions(sale_id);
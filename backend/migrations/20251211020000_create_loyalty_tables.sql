@@ -0,0 +1,43 @@
+-- PostgreSQL version
+CREATE TABLE IF NOT EXISTS loyalty_programs (
+    tenant_id UUID PRIMARY KEY,
+    points_per_real INTEGER NOT NULL DEFAULT 1, -- points earned per R$ 1,00 spent
+    point_value INTEGER NOT NULL DEFAULT 1, -- in cents, when redeemed
+    expiry_days INTEGER, -- NULL = points never expire
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS loyalty_tiers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    min_points BIGINT NOT NULL, -- lifetime points earned to reach the tier
+    multiplier INTEGER NOT NULL DEFAULT 100, -- percent applied to points earned
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+-- Points ledger. Earn rows track `remaining` so redemptions consume
+-- the oldest points first and expiry only removes what is left.
+CREATE TABLE IF NOT EXISTS loyalty_transactions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    customer_id UUID NOT NULL,
+    sale_id UUID,
+    kind VARCHAR(20) NOT NULL, -- earn, redeem, expire, reversal
+    points BIGINT NOT NULL, -- signed
+    remaining BIGINT NOT NULL DEFAULT 0, -- unspent points (credit rows only)
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+    FOREIGN KEY (sale_id) REFERENCES sales(id) ON DELETE SET NULL
+);
+
+ALTER TABLE sales ADD COLUMN IF NOT EXISTS loyalty_discount BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_loyalty_tiers_tenant_id ON loyalty_tiers(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_loyalty_transactions_customer_id ON loyalty_transactions(customer_id);
+CREATE INDEX IF NOT EXISTS idx_loyalty_transactions_sale_id ON loyalty_transactions(sale_id);
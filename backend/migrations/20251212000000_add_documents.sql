@@ -0,0 +1,12 @@
+-- PostgreSQL version
+-- CPF/CNPJ stored as digits only, with the detected type
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS document VARCHAR(14);
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS document_type VARCHAR(4); -- cpf, cnpj
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_customers_tenant_document
+    ON customers(tenant_id, document) WHERE document IS NOT NULL;
+
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS document VARCHAR(14);
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS document_type VARCHAR(4); -- cpf, cnpj
+
+CREATE INDEX IF NOT EXISTS idx_tenants_document ON tenants(document);
@@ -0,0 +1,21 @@
+-- PostgreSQL version
+-- LGPD: marketing consent and anonymisation on customers
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS marketing_consent BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS consent_updated_at TIMESTAMP;
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMP;
+
+-- Record of every data subject request answered (export, anonymize, consent)
+CREATE TABLE IF NOT EXISTS data_subject_requests (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    customer_id UUID,
+    request_type VARCHAR(20) NOT NULL, -- export, anonymize, consent
+    requested_by UUID NOT NULL, -- user who handled the request
+    details TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_data_subject_requests_tenant_id ON data_subject_requests(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_data_subject_requests_customer_id ON data_subject_requests(customer_id);
@@ -0,0 +1,60 @@
+-- PostgreSQL version
+CREATE TABLE IF NOT EXISTS customer_addresses (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    customer_id UUID NOT NULL,
+    label VARCHAR(50), -- casa, trabalho...
+    street VARCHAR(255) NOT NULL,
+    number VARCHAR(20),
+    complement VARCHAR(100),
+    neighborhood VARCHAR(100),
+    city VARCHAR(100) NOT NULL,
+    state VARCHAR(2) NOT NULL,
+    zip_code VARCHAR(8) NOT NULL, -- CEP, digits only
+    reference TEXT,
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
+);
+
+-- Delivery fee zones by CEP range
+CREATE TABLE IF NOT EXISTS delivery_zones (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    zip_code_start VARCHAR(8) NOT NULL,
+    zip_code_end VARCHAR(8) NOT NULL,
+    fee INTEGER NOT NULL, -- in cents
+    estimated_minutes INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    sale_id UUID NOT NULL UNIQUE,
+    customer_id UUID,
+    address_id UUID,
+    address_text TEXT NOT NULL, -- snapshot at order time
+    zone_id UUID,
+    fee BIGINT NOT NULL DEFAULT 0, -- in cents
+    courier_name VARCHAR(100),
+    status VARCHAR(30) NOT NULL DEFAULT 'received', -- received, preparing, out_for_delivery, delivered, cancelled
+    dispatched_at TIMESTAMP,
+    delivered_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (sale_id) REFERENCES sales(id) ON DELETE CASCADE,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE SET NULL,
+    FOREIGN KEY (address_id) REFERENCES customer_addresses(id) ON DELETE SET NULL,
+    FOREIGN KEY (zone_id) REFERENCES delivery_zones(id) ON DELETE SET NULL
+);
+
+ALTER TABLE sales ADD COLUMN IF NOT EXISTS delivery_fee BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_customer_addresses_customer_id ON customer_addresses(customer_id);
+CREATE INDEX IF NOT EXISTS idx_delivery_zones_tenant_id ON delivery_zones(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_deliveries_tenant_status ON deliveries(tenant_id, status);
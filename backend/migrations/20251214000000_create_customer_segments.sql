@@ -0,0 +1,20 @@
+-- PostgreSQL version
+ALTER TABLE products ADD COLUMN IF NOT EXISTS category VARCHAR(100);
+CREATE INDEX IF NOT EXISTS idx_products_tenant_category ON products(tenant_id, category);
+
+-- Saved customer segments; every filter is optional and they are ANDed together
+CREATE TABLE IF NOT EXISTS customer_segments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    min_spent BIGINT, -- in cents, lifetime spend above this value
+    inactive_days INTEGER, -- no purchase in the last N days
+    category VARCHAR(100), -- bought at least one product of this category
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_customer_segments_tenant_id ON customer_segments(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_sales_tenant_customer ON sales(tenant_id, customer_id);
@@ -0,0 +1,12 @@
+-- PostgreSQL version
+-- Tenant-defined roles; users.role holds the role name
+CREATE TABLE IF NOT EXISTS tenant_roles (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    name VARCHAR(50) NOT NULL,
+    permissions TEXT[] NOT NULL DEFAULT '{}', -- e.g. sales.cancel, products.edit_price
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    UNIQUE (tenant_id, name)
+);
@@ -0,0 +1,22 @@
+-- PostgreSQL version
+-- Failed login counters, used when LOGIN_THROTTLE_STORE=postgres
+CREATE TABLE IF NOT EXISTS login_throttle (
+    key VARCHAR(320) PRIMARY KEY, -- account:<email> or ip:<address>
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP
+);
+
+-- Every failed or blocked login attempt
+CREATE TABLE IF NOT EXISTS login_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    ip VARCHAR(64) NOT NULL,
+    user_id UUID,
+    reason VARCHAR(20) NOT NULL, -- unknown_user, bad_password, locked
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_events_email ON login_events(email, created_at);
+CREATE INDEX IF NOT EXISTS idx_login_events_created_at ON login_events(created_at);
@@ -0,0 +1,17 @@
+-- PostgreSQL version
+-- Users created by an admin or tenant owner count as verified; self sign-up inserts NULL
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP;
+
+-- Single-use tokens sent by email, stored hashed
+CREATE TABLE IF NOT EXISTS user_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL,
+    purpose VARCHAR(30) NOT NULL, -- password_reset, email_verification
+    token_hash VARCHAR(64) NOT NULL UNIQUE, -- sha256, hex
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_tokens_user_id ON user_tokens(user_id, purpose);
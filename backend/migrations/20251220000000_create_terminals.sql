@@ -0,0 +1,26 @@
+-- PostgreSQL version
+-- Shared POS devices; the device token is shown once and stored hashed
+CREATE TABLE IF NOT EXISTS terminals (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    device_token_hash VARCHAR(64) NOT NULL UNIQUE, -- sha256, hex
+    created_by UUID,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_terminals_tenant_id ON terminals(tenant_id);
+
+-- Quick-switch PIN; cleared after too many wrong attempts
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pin_hash TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pin_failed_attempts INTEGER NOT NULL DEFAULT 0;
+
+-- Sessions opened with a PIN are bound to their terminal
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS terminal_id UUID REFERENCES terminals(id) ON DELETE CASCADE;
+
+-- Which device rang the sale up, next to who did (user_id)
+ALTER TABLE sales ADD COLUMN IF NOT EXISTS terminal_id UUID REFERENCES terminals(id) ON DELETE SET NULL;
@@ -0,0 +1,19 @@
+-- PostgreSQL version
+-- Integration credentials; the key is shown once and stored hashed
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    created_by UUID NOT NULL, -- requests act as this user, within the key's scopes
+    name VARCHAR(100) NOT NULL,
+    prefix VARCHAR(16) NOT NULL, -- first characters of the key, to tell keys apart
+    key_hash VARCHAR(64) NOT NULL UNIQUE, -- sha256, hex
+    scopes TEXT[] NOT NULL DEFAULT '{}', -- permission names, e.g. products.view
+    expires_at TIMESTAMP,
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_tenant_id ON api_keys(tenant_id);
@@ -0,0 +1,10 @@
+-- Billing state behind tenants.status: stores are suspended automatically
+-- once paid_until plus the grace period has passed
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS paid_until DATE;
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS suspended_reason VARCHAR(50); -- manual, overdue
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS status_changed_at TIMESTAMP;
+
+UPDATE tenants SET status = 'inactive' WHERE status NOT IN ('active', 'inactive', 'suspended');
+ALTER TABLE tenants ADD CONSTRAINT tenants_status_check CHECK (status IN ('active', 'inactive', 'suspended'));
+
+CREATE INDEX IF NOT EXISTS idx_tenants_paid_until ON tenants(paid_until) WHERE status = 'active';
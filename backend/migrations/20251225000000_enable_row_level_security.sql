@@ -0,0 +1,98 @@
+-- Tenant isolation in the database.
+--
+-- Tenant requests run in transactions that switch to the app_tenant role and
+-- set app.tenant_id (see src/tenant_db.rs). Policies only apply to that role:
+-- the connecting user owns the tables, so platform code (admin, login, jobs)
+-- keeps seeing every row. app_tenant can only reach the tables granted below;
+-- new tenant tables need a grant and a policy here too.
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'app_tenant') THEN
+        CREATE ROLE app_tenant NOLOGIN;
+    END IF;
+END
+$$;
+
+GRANT app_tenant TO CURRENT_USER;
+GRANT USAGE ON SCHEMA public TO app_tenant;
+
+-- NULL when unset, so policies match nothing instead of failing the cast
+CREATE OR REPLACE FUNCTION app_tenant_id() RETURNS UUID
+LANGUAGE sql STABLE AS $$
+    SELECT NULLIF(current_setting('app.tenant_id', true), '')::uuid
+$$;
+
+GRANT EXECUTE ON FUNCTION app_tenant_id() TO app_tenant;
+
+-- Tables with a tenant_id column
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'api_keys', 'coupons', 'customer_addresses', 'customer_segments', 'customers',
+        'data_subject_requests', 'deliveries', 'delivery_zones', 'gift_cards',
+        'loyalty_programs', 'loyalty_tiers', 'loyalty_transactions', 'products', 'sales',
+        'tenant_roles', 'terminals', 'users'
+    ] LOOP
+        EXECUTE format('GRANT SELECT, INSERT, UPDATE, DELETE ON %I TO app_tenant', t);
+        EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I', t);
+        EXECUTE format(
+            'CREATE POLICY tenant_isolation ON %I TO app_tenant USING (tenant_id = app_tenant_id()) WITH CHECK (tenant_id = app_tenant_id())',
+            t
+        );
+    END LOOP;
+END
+$$;
+
+-- Rows owned through their parent, which is itself filtered
+GRANT SELECT, INSERT, UPDATE, DELETE ON sale_items, coupon_redemptions, gift_card_transactions TO app_tenant;
+
+ALTER TABLE sale_items ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON sale_items;
+CREATE POLICY tenant_isolation ON sale_items TO app_tenant
+    USING (EXISTS (SELECT 1 FROM sales s WHERE s.id = sale_id))
+    WITH CHECK (EXISTS (SELECT 1 FROM sales s WHERE s.id = sale_id));
+
+ALTER TABLE coupon_redemptions ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON coupon_redemptions;
+CREATE POLICY tenant_isolation ON coupon_redemptions TO app_tenant
+    USING (EXISTS (SELECT 1 FROM coupons c WHERE c.id = coupon_id))
+    WITH CHECK (EXISTS (SELECT 1 FROM coupons c WHERE c.id = coupon_id));
+
+ALTER TABLE gift_card_transactions ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON gift_card_transactions;
+CREATE POLICY tenant_isolation ON gift_card_transactions TO app_tenant
+    USING (EXISTS (SELECT 1 FROM gift_cards g WHERE g.id = gift_card_id))
+    WITH CHECK (EXISTS (SELECT 1 FROM gift_cards g WHERE g.id = gift_card_id));
+
+-- The tenant's own row; only its settings are writable, never its status,
+-- plan or billing
+GRANT SELECT, UPDATE (two_factor_roles) ON tenants TO app_tenant;
+ALTER TABLE tenants ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON tenants;
+CREATE POLICY tenant_isolation ON tenants TO app_tenant
+    USING (id = app_tenant_id())
+    WITH CHECK (id = app_tenant_id());
+
+-- Append-only; entries about the tenant
+GRANT SELECT, INSERT ON audit_log TO app_tenant;
+ALTER TABLE audit_log ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON audit_log;
+CREATE POLICY tenant_isolation ON audit_log TO app_tenant
+    USING (tenant_id = app_tenant_id())
+    WITH CHECK (tenant_id = app_tenant_id());
+
+-- Catalog, the same for everyone
+GRANT SELECT ON plans TO app_tenant;
+
+-- Sessions of the tenant's users: sales record the terminal they were rung
+-- up on, and role changes and revoked terminals sign users out. Tokens stay
+-- out of reach.
+GRANT SELECT (id, user_id, terminal_id, revoked_at), UPDATE (revoked_at) ON sessions TO app_tenant;
+ALTER TABLE sessions ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON sessions;
+CREATE POLICY tenant_isolation ON sessions TO app_tenant
+    USING (EXISTS (SELECT 1 FROM users u WHERE u.id = user_id));
@@ -0,0 +1,92 @@
+-- Subscription billing. Each store has at most one subscription to a plan;
+-- every billing period produces an invoice, paid through the payment
+-- gateway or recorded by hand. Paying an invoice moves tenants.paid_until to
+-- the end of its period, which is what tenant status enforcement follows.
+CREATE TABLE IF NOT EXISTS subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL UNIQUE REFERENCES tenants(id) ON DELETE CASCADE,
+    plan_id UUID NOT NULL REFERENCES plans(id),
+    billing_cycle VARCHAR(20) NOT NULL DEFAULT 'monthly'
+        CHECK (billing_cycle IN ('monthly', 'yearly')),
+    -- trialing, active, past_due (payment failed or late), suspended (store
+    -- suspended for non-payment), cancelled
+    status VARCHAR(20) NOT NULL DEFAULT 'trialing'
+        CHECK (status IN ('trialing', 'active', 'past_due', 'suspended', 'cancelled')),
+    trial_ends_at DATE,
+    current_period_start DATE NOT NULL,
+    current_period_end DATE NOT NULL, -- last day of the period, inclusive
+    cancel_at_period_end BOOLEAN NOT NULL DEFAULT FALSE,
+    payment_method_token VARCHAR(255), -- gateway token of the store's card
+    credit_balance BIGINT NOT NULL DEFAULT 0, -- cents, from downgrades; taken off the next invoices
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_period_end ON subscriptions(current_period_end)
+    WHERE status <> 'cancelled';
+
+CREATE TABLE IF NOT EXISTS invoices (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    subscription_id UUID NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'paid', 'void')),
+    amount BIGINT NOT NULL CHECK (amount >= 0), -- cents
+    period_start DATE NOT NULL,
+    period_end DATE NOT NULL,
+    due_date DATE NOT NULL,
+    attempt_count INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP, -- next automatic charge, NULL once retries are exhausted
+    paid_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoices_tenant_id ON invoices(tenant_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_invoices_next_attempt ON invoices(next_attempt_at) WHERE status = 'open';
+
+CREATE TABLE IF NOT EXISTS invoice_lines (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    description VARCHAR(255) NOT NULL,
+    amount BIGINT NOT NULL, -- cents, negative for credits
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_lines_invoice_id ON invoice_lines(invoice_id);
+
+CREATE TABLE IF NOT EXISTS payments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    status VARCHAR(20) NOT NULL CHECK (status IN ('succeeded', 'failed')),
+    method VARCHAR(50) NOT NULL, -- gateway name, or how a manual payment was made
+    reference VARCHAR(255), -- gateway charge id, bank slip number, ...
+    failure_reason TEXT,
+    recorded_by UUID REFERENCES users(id) ON DELETE SET NULL, -- manual payments
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_payments_invoice_id ON payments(invoice_id);
+
+-- Stores read their own billing; only the platform writes it
+GRANT SELECT ON subscriptions, invoices, payments, invoice_lines TO app_tenant;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY['subscriptions', 'invoices', 'payments'] LOOP
+        EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I', t);
+        EXECUTE format(
+            'CREATE POLICY tenant_isolation ON %I TO app_tenant USING (tenant_id = app_tenant_id())',
+            t
+        );
+    END LOOP;
+END
+$$;
+
+ALTER TABLE invoice_lines ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON invoice_lines;
+CREATE POLICY tenant_isolation ON invoice_lines TO app_tenant
+    USING (EXISTS (SELECT 1 FROM invoices i WHERE i.id = invoice_id));
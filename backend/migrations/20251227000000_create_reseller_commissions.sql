@@ -0,0 +1,45 @@
+-- Reseller commissions. A reseller's rule sets the share of every paid
+-- invoice of their stores, plus a one-off bonus when a store pays for the
+-- first time. Each payment earns a ledger entry; at the end of a month the
+-- entries are grouped into a payout statement the platform pays out.
+CREATE TABLE IF NOT EXISTS commission_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    reseller_id UUID NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    rate_bps INTEGER NOT NULL DEFAULT 0 CHECK (rate_bps BETWEEN 0 AND 10000), -- basis points: 1500 = 15%
+    activation_bonus BIGINT NOT NULL DEFAULT 0 CHECK (activation_bonus >= 0), -- cents
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS payouts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    reseller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL, -- first day of the month
+    period_end DATE NOT NULL, -- last day of the month
+    amount BIGINT NOT NULL, -- cents
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'paid')),
+    reference VARCHAR(255), -- bank transfer id, ...
+    paid_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (reseller_id, period_start)
+);
+
+CREATE TABLE IF NOT EXISTS commissions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    reseller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tenant_id UUID REFERENCES tenants(id) ON DELETE SET NULL,
+    invoice_id UUID REFERENCES invoices(id) ON DELETE SET NULL,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('subscription', 'activation')),
+    base_amount BIGINT NOT NULL, -- cents the commission was computed on
+    amount BIGINT NOT NULL, -- cents
+    description VARCHAR(255) NOT NULL,
+    earned_on DATE NOT NULL DEFAULT CURRENT_DATE,
+    payout_id UUID REFERENCES payouts(id) ON DELETE SET NULL, -- NULL until the month is closed
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One commission per invoice and one activation bonus per store
+CREATE UNIQUE INDEX IF NOT EXISTS idx_commissions_invoice ON commissions(invoice_id) WHERE kind = 'subscription';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_commissions_activation ON commissions(reseller_id, tenant_id) WHERE kind = 'activation';
+CREATE INDEX IF NOT EXISTS idx_commissions_reseller ON commissions(reseller_id, earned_on DESC);
+CREATE INDEX IF NOT EXISTS idx_commissions_unsettled ON commissions(earned_on) WHERE payout_id IS NULL;
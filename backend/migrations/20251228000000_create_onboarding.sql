@@ -0,0 +1,53 @@
+-- Store onboarding. A new store is seeded from its business type template
+-- (categories, payment methods, roles, custom fields, optionally sample
+-- products) and its owner follows a setup checklist until done.
+CREATE TABLE IF NOT EXISTS product_categories (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (tenant_id, name)
+);
+
+-- Payment methods the store takes at the POS, by the code sales record in
+-- sales.payment_method. A store without any accepts every method.
+CREATE TABLE IF NOT EXISTS payment_methods (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    code VARCHAR(50) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    position INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (tenant_id, code)
+);
+
+CREATE TABLE IF NOT EXISTS onboarding (
+    tenant_id UUID PRIMARY KEY REFERENCES tenants(id) ON DELETE CASCADE,
+    template VARCHAR(50), -- business type last applied
+    seeded_at TIMESTAMP,
+    sample_products_at TIMESTAMP, -- when sample products were added, to tell them apart
+    completed_steps TEXT[] NOT NULL DEFAULT '{}', -- checked off by hand
+    dismissed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON product_categories, payment_methods TO app_tenant;
+GRANT SELECT, INSERT, UPDATE ON onboarding TO app_tenant;
+-- Applying a template sets the store's business type and custom fields
+GRANT UPDATE (business_type, custom_fields, updated_at) ON tenants TO app_tenant;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY['product_categories', 'payment_methods', 'onboarding'] LOOP
+        EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I', t);
+        EXECUTE format(
+            'CREATE POLICY tenant_isolation ON %I TO app_tenant USING (tenant_id = app_tenant_id()) WITH CHECK (tenant_id = app_tenant_id())',
+            t
+        );
+    END LOOP;
+END
+$$;
@@ -0,0 +1,3 @@
+-- Cancelling a sale writes 'reversal' rows to the gift card ledger. The
+-- original migration has shipped, so the kinds are documented here.
+COMMENT ON COLUMN gift_card_transactions.kind IS 'activation, redemption, store_credit, reversal';
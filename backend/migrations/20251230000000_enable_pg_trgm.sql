@@ -0,0 +1,3 @@
+-- Duplicate detection scores how alike the names of two customers are with
+-- pg_trgm's similarity().
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
@@ -0,0 +1,175 @@
+//! Append-only audit log of sensitive actions.
+//!
+//! Handlers take an [`AuditContext`] (who is acting and from which address)
+//! and record an [`Event`] once the action has succeeded, inside the action's
+//! transaction when it has one so the change and its entry commit together.
+//! Row snapshots for `before` / `after` come from [`snapshot`], which leaves
+//! secrets such as password hashes out.
+
+use crate::auth::Claims;
+use crate::handlers::auth::client_ip;
+use crate::models::User;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+/// Columns never copied into the log.
+const SECRET_COLUMNS: [&str; 6] = [
+    "password_hash",
+    "totp_secret",
+    "pin_hash",
+    "key_hash",
+    "refresh_token_hash",
+    "payment_method_token",
+];
+
+/// The actor and client address of the current request.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    actor_id: Option<String>,
+    actor_role: Option<String>,
+    tenant_id: Option<String>,
+    ip: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for AuditContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let claims = parts.extensions.get::<Claims>();
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| client_ip(&parts.headers, *addr));
+
+        Ok(AuditContext {
+            actor_id: claims.map(|c| c.sub.clone()),
+            actor_role: claims.map(|c| c.role.clone()),
+            tenant_id: claims.and_then(|c| c.tenant_id.clone()),
+            ip,
+        })
+    }
+}
+
+/// One entry. The entity type is the first part of the action, so
+/// `tenant.update` is about a `tenant`.
+pub struct Event {
+    action: &'static str,
+    entity_id: String,
+    tenant_id: Option<Option<String>>, // None: the actor's tenant
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl Event {
+    pub fn new(action: &'static str, entity_id: impl Into<String>) -> Self {
+        Event {
+            action,
+            entity_id: entity_id.into(),
+            tenant_id: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    /// Files the entry under another tenant than the actor's, e.g. a
+    /// platform admin changing a store's plan; `None` for platform-wide.
+    pub fn tenant(mut self, tenant_id: Option<&str>) -> Self {
+        self.tenant_id = Some(tenant_id.map(str::to_string));
+        self
+    }
+
+    pub fn before(mut self, before: Option<Value>) -> Self {
+        self.before = before;
+        self
+    }
+
+    pub fn after(mut self, after: Option<Value>) -> Self {
+        self.after = after;
+        self
+    }
+}
+
+impl AuditContext {
+    /// Scheduled jobs and other actions no user asked for.
+    pub fn system() -> Self {
+        AuditContext {
+            actor_id: None,
+            actor_role: Some("system".to_string()),
+            tenant_id: None,
+            ip: None,
+        }
+    }
+
+    /// Same address, acting as `user`: for logins and other flows where
+    /// the actor is only known once the handler has identified them.
+    pub fn as_user(&self, user: &User) -> Self {
+        AuditContext {
+            actor_id: Some(user.id.clone()),
+            actor_role: Some(user.role.clone()),
+            tenant_id: user.tenant_id.clone(),
+            ip: self.ip.clone(),
+        }
+    }
+
+    pub async fn record<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+        event: Event,
+    ) -> Result<(), sqlx::Error> {
+        let entity_type = event.action.split('.').next().unwrap_or(event.action);
+        let tenant_id = event.tenant_id.unwrap_or_else(|| self.tenant_id.clone());
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (id, tenant_id, actor_id, actor_role, ip, action, entity_type, entity_id, before, after)
+            VALUES ($1, $2::uuid, $3::uuid, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(tenant_id)
+        .bind(&self.actor_id)
+        .bind(&self.actor_role)
+        .bind(&self.ip)
+        .bind(event.action)
+        .bind(entity_type)
+        .bind(event.entity_id)
+        .bind(event.before)
+        .bind(event.after)
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    /// Records an action that has no transaction to join. The action already
+    /// happened, so a failed write is reported rather than returned.
+    pub async fn log(&self, pool: &PgPool, event: Event) {
+        let action = event.action;
+        if let Err(e) = self.record(pool, event).await {
+            eprintln!("Failed to write audit log entry {}: {}", action, e);
+        }
+    }
+}
+
+/// Current state of a row as JSON, without secret columns.
+pub async fn snapshot<'e>(
+    executor: impl PgExecutor<'e>,
+    table: &'static str,
+    id: &str,
+) -> Result<Option<Value>, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT to_jsonb(t) - $2::text[] FROM {} t WHERE t.id = $1::uuid",
+        table
+    ))
+    .bind(id)
+    .bind(&SECRET_COLUMNS[..])
+    .fetch_optional(executor)
+    .await
+}
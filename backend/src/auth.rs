@@ -14,6 +14,8 @@ pub struct Claims {
     pub sub: String,
     pub tenant_id: Option<String>,
     pub role: String,
+    #[serde(default)]
+    pub sid: Option<String>, // session the token was issued for
     pub exp: usize,
 }
 
@@ -32,19 +34,20 @@ pub fn verify_password(hash: &str, password: &str) -> bool {
     Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok()
 }
 
-pub fn create_jwt(user_id: &str, tenant_id: Option<&str>, role: &str, secret: &[u8]) -> Result<String, jsonwebtoken::errors::Error> {
+pub fn create_jwt(user_id: &str, tenant_id: Option<&str>, role: &str, session_id: &str, ttl: usize, header: &Header, key: &EncodingKey) -> Result<String, jsonwebtoken::errors::Error> {
     let expiration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs() as usize + 24 * 3600; // 24 hours
+        .as_secs() as usize + ttl;
 
     let claims = Claims {
         sub: user_id.to_owned(),
         tenant_id: tenant_id.map(|t| t.to_owned()),
         role: role.to_owned(),
+        sid: Some(session_id.to_owned()),
         exp: expiration,
     };
 
-    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret))
+    encode(header, &claims, key)
 }
 
@@ -0,0 +1,759 @@
+//! Subscription billing.
+//!
+//! A store's subscription bills its plan every month or year (a yearly
+//! period costs twelve months). New subscriptions start with a free trial
+//! of `BILLING_TRIAL_DAYS` (default 14). When a period ends,
+//! [`run_billing_cycle`] opens the invoice for the next one and charges the
+//! open invoices through the [`PaymentGateway`]:
+//! - paid: `tenants.paid_until` moves to the end of the invoice's period and
+//!   a store suspended for non-payment is reactivated
+//! - failed: the subscription is `past_due` and the charge is retried
+//!   [`RETRY_DAYS`] later; after the last retry the invoice waits for a
+//!   manual payment
+//!
+//! Once `paid_until` plus the grace period has passed, tenant status
+//! enforcement suspends the store and the subscription follows as
+//! `suspended`.
+//!
+//! Paid invoices earn the store's reseller their commission
+//! ([`commissions`]).
+//!
+//! Plan changes ([`change_plan`]) are prorated by day: the unused part of
+//! the old plan is credited against the rest of the period on the new one.
+//! An upgrade invoices the difference now; a downgrade leaves a credit
+//! taken off the next invoices.
+
+use crate::audit::{AuditContext, Event};
+use crate::commissions;
+use crate::payments::{Charge, ChargeOutcome, PaymentGateway};
+use crate::tenant_status::{self, TenantPolicy};
+use chrono::{Duration, Months, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::env;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub const MONTHLY: &str = "monthly";
+pub const YEARLY: &str = "yearly";
+pub const CYCLES: [&str; 2] = [MONTHLY, YEARLY];
+
+pub const TRIALING: &str = "trialing";
+pub const ACTIVE: &str = "active";
+pub const PAST_DUE: &str = "past_due";
+pub const SUSPENDED: &str = "suspended";
+pub const CANCELLED: &str = "cancelled";
+
+const DEFAULT_TRIAL_DAYS: i64 = 14;
+/// Days to wait before retrying a failed charge, one entry per retry.
+pub const RETRY_DAYS: [i64; 3] = [1, 3, 5];
+/// How often periods are renewed and invoices charged.
+const SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+
+pub fn trial_days() -> i64 {
+    env::var("BILLING_TRIAL_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(DEFAULT_TRIAL_DAYS)
+}
+
+fn cycle_months(cycle: &str) -> u32 {
+    if cycle == YEARLY { 12 } else { 1 }
+}
+
+/// Price of one period; plan prices are monthly.
+pub fn cycle_price(monthly_price: i32, cycle: &str) -> i64 {
+    monthly_price as i64 * cycle_months(cycle) as i64
+}
+
+/// Last day of the period starting on `start`.
+pub fn period_end(start: NaiveDate, cycle: &str) -> NaiveDate {
+    start
+        .checked_add_months(Months::new(cycle_months(cycle)))
+        .and_then(|next| next.pred_opt())
+        .unwrap_or(start)
+}
+
+/// Share of `amount` for `days` out of `total` days, rounded to the cent.
+fn prorate(amount: i64, days: i64, total: i64) -> i64 {
+    if total <= 0 {
+        return 0;
+    }
+    (amount * days + total / 2) / total
+}
+
+#[derive(FromRow)]
+struct SubscriptionState {
+    id: String,
+    tenant_id: String,
+    plan_id: String,
+    plan_name: String,
+    plan_price: i32,
+    billing_cycle: String,
+    status: String,
+    current_period_start: NaiveDate,
+    current_period_end: NaiveDate,
+    cancel_at_period_end: bool,
+    credit_balance: i64,
+}
+
+const STATE_COLUMNS: &str = "s.id::text AS id, s.tenant_id::text AS tenant_id, s.plan_id::text AS plan_id, p.name AS plan_name, p.price AS plan_price, s.billing_cycle, s.status, s.current_period_start, s.current_period_end, s.cancel_at_period_end, s.credit_balance";
+
+/// The tenant's subscription, locked until the transaction ends.
+async fn lock_subscription(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+) -> Result<Option<SubscriptionState>, sqlx::Error> {
+    sqlx::query_as::<_, SubscriptionState>(&format!(
+        "SELECT {} FROM subscriptions s JOIN plans p ON p.id = s.plan_id WHERE s.tenant_id = $1::uuid FOR UPDATE OF s",
+        STATE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Subscribes a store to `plan_id`, with a trial unless `trial` is false, in
+/// which case the first period is invoiced today. Returns the subscription id.
+pub async fn start_subscription(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    plan_id: &str,
+    cycle: &str,
+    payment_method_token: Option<&str>,
+    trial: bool,
+) -> Result<String, sqlx::Error> {
+    let today = Utc::now().date_naive();
+    let trial_days = if trial { trial_days() } else { 0 };
+    let id = Uuid::new_v4().to_string();
+
+    if trial_days > 0 {
+        let trial_end = today + Duration::days(trial_days - 1);
+        sqlx::query(
+            r#"
+            INSERT INTO subscriptions (id, tenant_id, plan_id, billing_cycle, status, trial_ends_at, current_period_start, current_period_end, payment_method_token)
+            VALUES ($1::uuid, $2::uuid, $3::uuid, $4, $5, $6, $7, $6, $8)
+            "#,
+        )
+        .bind(&id)
+        .bind(tenant_id)
+        .bind(plan_id)
+        .bind(cycle)
+        .bind(TRIALING)
+        .bind(trial_end)
+        .bind(today)
+        .bind(payment_method_token)
+        .execute(&mut *conn)
+        .await?;
+
+        // The trial counts as paid time
+        sqlx::query(
+            "UPDATE tenants SET paid_until = GREATEST(COALESCE(paid_until, $2), $2) WHERE id = $1::uuid",
+        )
+        .bind(tenant_id)
+        .bind(trial_end)
+        .execute(&mut *conn)
+        .await?;
+        return Ok(id);
+    }
+
+    let end = period_end(today, cycle);
+    sqlx::query(
+        r#"
+        INSERT INTO subscriptions (id, tenant_id, plan_id, billing_cycle, status, current_period_start, current_period_end, payment_method_token)
+        VALUES ($1::uuid, $2::uuid, $3::uuid, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(&id)
+    .bind(tenant_id)
+    .bind(plan_id)
+    .bind(cycle)
+    .bind(ACTIVE)
+    .bind(today)
+    .bind(end)
+    .bind(payment_method_token)
+    .execute(&mut *conn)
+    .await?;
+
+    let state = lock_subscription(&mut *conn, tenant_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    let lines = vec![(
+        period_description(&state.plan_name, cycle, today, end),
+        cycle_price(state.plan_price, cycle),
+    )];
+    create_invoice(&mut *conn, &state, lines, today, end, today).await?;
+    Ok(id)
+}
+
+fn period_description(plan: &str, cycle: &str, start: NaiveDate, end: NaiveDate) -> String {
+    format!("{} ({}), {} to {}", plan, cycle, start, end)
+}
+
+/// Opens an invoice for `lines` (description, cents), taking the store's
+/// credit off first. An invoice left at zero is paid on the spot.
+async fn create_invoice(
+    conn: &mut PgConnection,
+    subscription: &SubscriptionState,
+    mut lines: Vec<(String, i64)>,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    due_date: NaiveDate,
+) -> Result<String, sqlx::Error> {
+    let subtotal: i64 = lines.iter().map(|(_, amount)| amount).sum();
+    let credit = subscription.credit_balance.min(subtotal.max(0));
+    if credit > 0 {
+        lines.push(("Account credit".to_string(), -credit));
+        sqlx::query(
+            "UPDATE subscriptions SET credit_balance = credit_balance - $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2::uuid",
+        )
+        .bind(credit)
+        .bind(&subscription.id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    let amount = (subtotal - credit).max(0);
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO invoices (id, tenant_id, subscription_id, amount, period_start, period_end, due_date, next_attempt_at)
+        VALUES ($1::uuid, $2::uuid, $3::uuid, $4, $5, $6, $7, $7::timestamp)
+        "#,
+    )
+    .bind(&id)
+    .bind(&subscription.tenant_id)
+    .bind(&subscription.id)
+    .bind(amount)
+    .bind(period_start)
+    .bind(period_end)
+    .bind(due_date)
+    .execute(&mut *conn)
+    .await?;
+
+    for (description, amount) in &lines {
+        sqlx::query(
+            "INSERT INTO invoice_lines (id, invoice_id, description, amount) VALUES ($1, $2::uuid, $3, $4)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(&id)
+        .bind(description)
+        .bind(amount)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    if amount == 0 {
+        mark_paid(&mut *conn, &AuditContext::system(), &id).await?;
+    }
+    Ok(id)
+}
+
+/// What a plan change cost, in cents.
+#[derive(Debug, Serialize)]
+pub struct Proration {
+    pub credit: i64,
+    pub charge: i64,
+    pub invoice_id: Option<String>,
+}
+
+/// Moves the tenant's subscription to `plan_id`, prorating the rest of the
+/// current period. Nothing to prorate during a trial, for a cancelled
+/// subscription, or for a tenant without one. Call in the transaction that
+/// changes `tenants.plan_id`.
+pub async fn change_plan(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    plan_id: &str,
+) -> Result<Option<Proration>, sqlx::Error> {
+    let Some(state) = lock_subscription(&mut *conn, tenant_id).await? else {
+        return Ok(None);
+    };
+    if state.plan_id == plan_id || state.status == CANCELLED {
+        return Ok(None);
+    }
+
+    let new_plan: Option<(String, i32)> =
+        sqlx::query_as("SELECT name, price FROM plans WHERE id = $1::uuid")
+            .bind(plan_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let Some((new_name, new_price)) = new_plan else {
+        return Err(sqlx::Error::RowNotFound);
+    };
+
+    sqlx::query(
+        "UPDATE subscriptions SET plan_id = $1::uuid, updated_at = CURRENT_TIMESTAMP WHERE id = $2::uuid",
+    )
+    .bind(plan_id)
+    .bind(&state.id)
+    .execute(&mut *conn)
+    .await?;
+
+    if state.status == TRIALING {
+        return Ok(None);
+    }
+
+    let today = Utc::now().date_naive();
+    let total = (state.current_period_end - state.current_period_start).num_days() + 1;
+    let remaining = ((state.current_period_end - today).num_days() + 1).clamp(0, total);
+    let credit = prorate(
+        cycle_price(state.plan_price, &state.billing_cycle),
+        remaining,
+        total,
+    );
+    let charge = prorate(
+        cycle_price(new_price, &state.billing_cycle),
+        remaining,
+        total,
+    );
+
+    let mut invoice_id = None;
+    if charge > credit {
+        let lines = vec![
+            (format!("Unused time on {}", state.plan_name), -credit),
+            (format!("Remaining time on {}", new_name), charge),
+        ];
+        let start = today.max(state.current_period_start);
+        invoice_id = Some(
+            create_invoice(
+                &mut *conn,
+                &state,
+                lines,
+                start,
+                state.current_period_end,
+                today,
+            )
+            .await?,
+        );
+    } else if credit > charge {
+        sqlx::query(
+            "UPDATE subscriptions SET credit_balance = credit_balance + $1 WHERE id = $2::uuid",
+        )
+        .bind(credit - charge)
+        .bind(&state.id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(Some(Proration {
+        credit,
+        charge,
+        invoice_id,
+    }))
+}
+
+/// Settles an invoice: the store is paid up to the end of its period, its
+/// reseller earns their commission, the subscription is back in good
+/// standing once nothing overdue is left, and a store suspended for
+/// non-payment is reactivated.
+async fn mark_paid(
+    conn: &mut PgConnection,
+    audit: &AuditContext,
+    invoice_id: &str,
+) -> Result<(), sqlx::Error> {
+    let (tenant_id, subscription_id, period_end): (String, String, NaiveDate) = sqlx::query_as(
+        r#"
+        UPDATE invoices SET status = 'paid', paid_at = CURRENT_TIMESTAMP, next_attempt_at = NULL
+        WHERE id = $1::uuid
+        RETURNING tenant_id::text, subscription_id::text, period_end
+        "#,
+    )
+    .bind(invoice_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE tenants SET paid_until = GREATEST(COALESCE(paid_until, $2), $2) WHERE id = $1::uuid",
+    )
+    .bind(&tenant_id)
+    .bind(period_end)
+    .execute(&mut *conn)
+    .await?;
+    commissions::record(&mut *conn, invoice_id).await?;
+
+    sqlx::query(
+        r#"
+        UPDATE subscriptions SET status = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2::uuid AND status IN ($3, $4)
+        AND NOT EXISTS (
+            SELECT 1 FROM invoices WHERE subscription_id = $2::uuid AND status = 'open' AND due_date < CURRENT_DATE
+        )
+        "#,
+    )
+    .bind(ACTIVE)
+    .bind(&subscription_id)
+    .bind(PAST_DUE)
+    .bind(SUSPENDED)
+    .execute(&mut *conn)
+    .await?;
+
+    let reactivated = sqlx::query(
+        r#"
+        UPDATE tenants SET status = $1, suspended_reason = NULL, status_changed_at = CURRENT_TIMESTAMP
+        WHERE id = $2::uuid AND status = $3 AND suspended_reason = $4 AND paid_until >= CURRENT_DATE
+        "#,
+    )
+    .bind(tenant_status::ACTIVE)
+    .bind(&tenant_id)
+    .bind(tenant_status::SUSPENDED)
+    .bind(tenant_status::REASON_OVERDUE)
+    .execute(&mut *conn)
+    .await?;
+
+    if reactivated.rows_affected() > 0 {
+        let event = Event::new("tenant.reactivate", &tenant_id)
+            .tenant(Some(&tenant_id))
+            .before(Some(serde_json::json!({
+                "status": tenant_status::SUSPENDED,
+                "suspended_reason": tenant_status::REASON_OVERDUE,
+            })))
+            .after(Some(serde_json::json!({ "status": tenant_status::ACTIVE })));
+        audit.record(&mut *conn, event).await?;
+    }
+    Ok(())
+}
+
+/// Amount still owed on an open invoice, locked until the transaction ends;
+/// `None` when the invoice is not open.
+async fn lock_open_invoice(
+    conn: &mut PgConnection,
+    invoice_id: &str,
+) -> Result<Option<(String, i64, i32)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT i.tenant_id::text,
+               i.amount - COALESCE((SELECT SUM(p.amount) FROM payments p WHERE p.invoice_id = i.id AND p.status = 'succeeded'), 0)::BIGINT,
+               i.attempt_count
+        FROM invoices i WHERE i.id = $1::uuid AND i.status = 'open'
+        FOR UPDATE
+        "#,
+    )
+    .bind(invoice_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// A manual payment (bank transfer, bank slip, ...) against an open
+/// invoice, which is settled once fully paid. `Ok(None)` when the invoice
+/// is not open.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_payment(
+    conn: &mut PgConnection,
+    audit: &AuditContext,
+    invoice_id: &str,
+    amount: i64,
+    method: &str,
+    reference: Option<&str>,
+    recorded_by: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    let Some((tenant_id, due, _)) = lock_open_invoice(&mut *conn, invoice_id).await? else {
+        return Ok(None);
+    };
+
+    let payment_id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO payments (id, tenant_id, invoice_id, amount, status, method, reference, recorded_by)
+        VALUES ($1::uuid, $2::uuid, $3::uuid, $4, 'succeeded', $5, $6, $7::uuid)
+        "#,
+    )
+    .bind(&payment_id)
+    .bind(&tenant_id)
+    .bind(invoice_id)
+    .bind(amount)
+    .bind(method)
+    .bind(reference)
+    .bind(recorded_by)
+    .execute(&mut *conn)
+    .await?;
+
+    let event = Event::new("payment.record", &payment_id)
+        .tenant(Some(&tenant_id))
+        .after(Some(serde_json::json!({
+            "invoice_id": invoice_id,
+            "amount": amount,
+            "method": method,
+            "reference": reference,
+        })));
+    audit.record(&mut *conn, event).await?;
+
+    if amount >= due {
+        mark_paid(&mut *conn, audit, invoice_id).await?;
+    }
+    Ok(Some(payment_id))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ChargeAttempt {
+    Paid {
+        payment_id: String,
+    },
+    Failed {
+        reason: String,
+        next_attempt_at: Option<chrono::NaiveDateTime>,
+    },
+    /// Not open any more: paid or voided meanwhile.
+    NotOpen,
+}
+
+/// Charges what is owed on an open invoice to the store's card. The invoice
+/// stays locked during the call, so it is never charged twice at once.
+pub async fn charge_invoice(
+    pool: &PgPool,
+    gateway: &dyn PaymentGateway,
+    invoice_id: &str,
+) -> Result<ChargeAttempt, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let Some((tenant_id, due, attempts)) = lock_open_invoice(&mut tx, invoice_id).await? else {
+        return Ok(ChargeAttempt::NotOpen);
+    };
+    if due <= 0 {
+        mark_paid(&mut tx, &AuditContext::system(), invoice_id).await?;
+        tx.commit().await?;
+        return Ok(ChargeAttempt::NotOpen);
+    }
+
+    let token: Option<String> = sqlx::query_scalar(
+        "SELECT s.payment_method_token FROM subscriptions s JOIN invoices i ON i.subscription_id = s.id WHERE i.id = $1::uuid",
+    )
+    .bind(invoice_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let attempt = attempts + 1;
+    let outcome = gateway
+        .charge(Charge {
+            payment_method: token.as_deref(),
+            amount: due,
+            description: format!("Invoice {}", invoice_id),
+            idempotency_key: format!("{}-{}", invoice_id, attempt),
+        })
+        .await;
+
+    let payment_id = Uuid::new_v4().to_string();
+    let (reference, failure) = match outcome {
+        Ok(ChargeOutcome::Paid { reference }) => (Some(reference), None),
+        Ok(ChargeOutcome::Declined { reason }) => (None, Some(reason)),
+        Err(e) => (None, Some(e)),
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO payments (id, tenant_id, invoice_id, amount, status, method, reference, failure_reason)
+        VALUES ($1::uuid, $2::uuid, $3::uuid, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(&payment_id)
+    .bind(&tenant_id)
+    .bind(invoice_id)
+    .bind(due)
+    .bind(if failure.is_none() { "succeeded" } else { "failed" })
+    .bind(gateway.name())
+    .bind(&reference)
+    .bind(&failure)
+    .execute(&mut *tx)
+    .await?;
+
+    let result = match failure {
+        None => {
+            sqlx::query("UPDATE invoices SET attempt_count = $1 WHERE id = $2::uuid")
+                .bind(attempt)
+                .bind(invoice_id)
+                .execute(&mut *tx)
+                .await?;
+            mark_paid(&mut tx, &AuditContext::system(), invoice_id).await?;
+            ChargeAttempt::Paid { payment_id }
+        }
+        Some(reason) => {
+            let retry = RETRY_DAYS.get(attempts as usize).copied();
+            let next_attempt_at: Option<chrono::NaiveDateTime> = sqlx::query_scalar(
+                r#"
+                UPDATE invoices SET attempt_count = $1,
+                    next_attempt_at = CURRENT_TIMESTAMP + make_interval(days => $2)
+                WHERE id = $3::uuid
+                RETURNING next_attempt_at
+                "#,
+            )
+            .bind(attempt)
+            .bind(retry.map(|days| days as i32))
+            .bind(invoice_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "UPDATE subscriptions SET status = $1, updated_at = CURRENT_TIMESTAMP WHERE tenant_id = $2::uuid AND status IN ($3, $4)",
+            )
+            .bind(PAST_DUE)
+            .bind(&tenant_id)
+            .bind(ACTIVE)
+            .bind(TRIALING)
+            .execute(&mut *tx)
+            .await?;
+            ChargeAttempt::Failed {
+                reason,
+                next_attempt_at,
+            }
+        }
+    };
+
+    tx.commit().await?;
+    Ok(result)
+}
+
+/// Ends the current period of one subscription: a cancellation takes
+/// effect, anything else rolls over into a new period and its invoice.
+async fn renew(pool: &PgPool, tenant_id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let today = Utc::now().date_naive();
+    let Some(state) = lock_subscription(&mut tx, tenant_id).await? else {
+        return Ok(());
+    };
+    if state.status == CANCELLED || state.current_period_end >= today {
+        return Ok(());
+    }
+
+    let system = AuditContext::system();
+    if state.cancel_at_period_end {
+        sqlx::query(
+            "UPDATE subscriptions SET status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2::uuid",
+        )
+        .bind(CANCELLED)
+        .bind(&state.id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE tenants SET status = $1, suspended_reason = NULL, status_changed_at = CURRENT_TIMESTAMP WHERE id = $2::uuid",
+        )
+        .bind(tenant_status::INACTIVE)
+        .bind(tenant_id)
+        .execute(&mut *tx)
+        .await?;
+        let event = Event::new("subscription.cancel", &state.id)
+            .tenant(Some(tenant_id))
+            .before(Some(serde_json::json!({ "status": state.status })))
+            .after(Some(serde_json::json!({ "status": CANCELLED })));
+        system.record(&mut *tx, event).await?;
+        tx.commit().await?;
+        return Ok(());
+    }
+
+    let start = state.current_period_end + Duration::days(1);
+    let end = period_end(start, &state.billing_cycle);
+    let status = if state.status == TRIALING {
+        ACTIVE
+    } else {
+        state.status.as_str()
+    };
+    sqlx::query(
+        r#"
+        UPDATE subscriptions SET status = $1, current_period_start = $2, current_period_end = $3, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $4::uuid
+        "#,
+    )
+    .bind(status)
+    .bind(start)
+    .bind(end)
+    .bind(&state.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let lines = vec![(
+        period_description(&state.plan_name, &state.billing_cycle, start, end),
+        cycle_price(state.plan_price, &state.billing_cycle),
+    )];
+    create_invoice(&mut tx, &state, lines, start, end, start).await?;
+    tx.commit().await
+}
+
+/// What one billing run did.
+#[derive(Debug, Default, Serialize)]
+pub struct BillingRun {
+    pub renewed: usize,
+    pub paid: usize,
+    pub failed: usize,
+    pub suspended: Vec<String>,
+    /// Reseller payout statements created or topped up.
+    pub statements: usize,
+}
+
+/// Renews ended periods, charges the invoices due, suspends stores whose
+/// grace period is over and marks their subscriptions `suspended`, then
+/// closes reseller commissions of ended months.
+pub async fn run_billing_cycle(
+    pool: &PgPool,
+    gateway: &dyn PaymentGateway,
+    policy: &TenantPolicy,
+) -> Result<BillingRun, sqlx::Error> {
+    let mut run = BillingRun::default();
+
+    // A store down for a while catches up one period per pass
+    loop {
+        let ended: Vec<String> = sqlx::query_scalar(
+            "SELECT tenant_id::text FROM subscriptions WHERE status <> $1 AND current_period_end < CURRENT_DATE",
+        )
+        .bind(CANCELLED)
+        .fetch_all(pool)
+        .await?;
+        if ended.is_empty() {
+            break;
+        }
+        for tenant_id in &ended {
+            renew(pool, tenant_id).await?;
+        }
+        run.renewed += ended.len();
+    }
+
+    let due: Vec<String> = sqlx::query_scalar(
+        "SELECT id::text FROM invoices WHERE status = 'open' AND next_attempt_at <= CURRENT_TIMESTAMP ORDER BY due_date",
+    )
+    .fetch_all(pool)
+    .await?;
+    for invoice_id in &due {
+        match charge_invoice(pool, gateway, invoice_id).await? {
+            ChargeAttempt::Paid { .. } => run.paid += 1,
+            ChargeAttempt::Failed { .. } => run.failed += 1,
+            ChargeAttempt::NotOpen => {}
+        }
+    }
+
+    run.suspended = tenant_status::suspend_overdue(pool, policy).await?;
+    sqlx::query(
+        r#"
+        UPDATE subscriptions s SET status = $1, updated_at = CURRENT_TIMESTAMP
+        FROM tenants t
+        WHERE t.id = s.tenant_id AND t.status = $2 AND t.suspended_reason = $3 AND s.status IN ($4, $5)
+        "#,
+    )
+    .bind(SUSPENDED)
+    .bind(tenant_status::SUSPENDED)
+    .bind(tenant_status::REASON_OVERDUE)
+    .bind(ACTIVE)
+    .bind(PAST_DUE)
+    .execute(pool)
+    .await?;
+
+    run.statements = commissions::close_months(pool).await?;
+    Ok(run)
+}
+
+/// Background task running [`run_billing_cycle`] every hour.
+pub async fn run_billing(
+    pool: PgPool,
+    gateway: Arc<dyn PaymentGateway>,
+    policy: Arc<TenantPolicy>,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(SWEEP_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        match run_billing_cycle(&pool, gateway.as_ref(), &policy).await {
+            Ok(run) if run.renewed + run.paid + run.failed > 0 => println!(
+                "Billing: {} period(s) renewed, {} invoice(s) paid, {} charge(s) failed",
+                run.renewed, run.paid, run.failed
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("Billing run failed: {}", e),
+        }
+    }
+}
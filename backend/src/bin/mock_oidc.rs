@@ -0,0 +1,287 @@
+//! Minimal OpenID Connect issuer for testing single sign-on locally.
+//!
+//! Usage: `cargo run --bin mock_oidc`
+//!
+//! There is no login page: `/authorize` signs in whoever the query asks for
+//! and redirects straight back with a code. Besides the standard parameters
+//! it reads `email`, `sub`, `groups` (comma separated) and
+//! `email_verified` (default `true`). Codes are single use and checked
+//! against the PKCE verifier, client and redirect URI like a real provider.
+//!
+//! - `MOCK_OIDC_PORT`: listen port (default 9000)
+//! - `MOCK_OIDC_ISSUER`: issuer URL (default `http://127.0.0.1:<port>`)
+//! - `MOCK_OIDC_CLIENT_ID` / `MOCK_OIDC_CLIENT_SECRET`: accepted client
+//!   (default `pdv`, no secret)
+
+use axum::{
+    Form, Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rand_core::{OsRng, RngCore};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+const KEY_ID: &str = "mock";
+const TOKEN_TTL: i64 = 5 * 60;
+
+struct Grant {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: Option<String>,
+    claims: serde_json::Value,
+}
+
+struct Issuer {
+    url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    signing_key: EncodingKey,
+    jwk: Jwk,
+    grants: Mutex<HashMap<String, Grant>>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    email: Option<String>,
+    sub: Option<String>,
+    groups: Option<String>,
+    email_verified: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    code_verifier: Option<String>,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn oauth_error(status: StatusCode, error: &str) -> axum::response::Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+async fn discovery(State(issuer): State<Arc<Issuer>>) -> impl IntoResponse {
+    Json(json!({
+        "issuer": issuer.url,
+        "authorization_endpoint": format!("{}/authorize", issuer.url),
+        "token_endpoint": format!("{}/token", issuer.url),
+        "jwks_uri": format!("{}/jwks", issuer.url),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["EdDSA"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+async fn jwks(State(issuer): State<Arc<Issuer>>) -> impl IntoResponse {
+    Json(JwkSet {
+        keys: vec![issuer.jwk.clone()],
+    })
+}
+
+async fn authorize(
+    State(issuer): State<Arc<Issuer>>,
+    Query(query): Query<AuthorizeQuery>,
+) -> impl IntoResponse {
+    if query.response_type != "code" {
+        return oauth_error(StatusCode::BAD_REQUEST, "unsupported_response_type");
+    }
+    if query.client_id != issuer.client_id {
+        return oauth_error(StatusCode::BAD_REQUEST, "unauthorized_client");
+    }
+    if query.code_challenge.is_some() && query.code_challenge_method.as_deref() != Some("S256") {
+        return oauth_error(StatusCode::BAD_REQUEST, "invalid_request");
+    }
+    let Some(email) = query.email.map(|e| e.trim().to_lowercase()) else {
+        return oauth_error(StatusCode::BAD_REQUEST, "login_required");
+    };
+
+    let groups: Vec<&str> = query
+        .groups
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .collect();
+    let claims = json!({
+        "sub": query.sub.unwrap_or_else(|| format!("mock|{}", email)),
+        "email": email,
+        "email_verified": query.email_verified.unwrap_or(true),
+        "groups": groups,
+        "nonce": query.nonce,
+    });
+
+    let code = random_token();
+    issuer.grants.lock().unwrap().insert(
+        code.clone(),
+        Grant {
+            client_id: query.client_id,
+            redirect_uri: query.redirect_uri.clone(),
+            code_challenge: query.code_challenge,
+            claims,
+        },
+    );
+
+    let mut location = match reqwest::Url::parse(&query.redirect_uri) {
+        Ok(url) => url,
+        Err(_) => return oauth_error(StatusCode::BAD_REQUEST, "invalid_request"),
+    };
+    location.query_pairs_mut().append_pair("code", &code);
+    if let Some(state) = &query.state {
+        location.query_pairs_mut().append_pair("state", state);
+    }
+    Redirect::to(location.as_str()).into_response()
+}
+
+/// Client credentials from `Authorization: Basic` or the form body.
+fn client_credentials(headers: &HeaderMap, form: &TokenForm) -> (Option<String>, Option<String>) {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|v| STANDARD.decode(v).ok())
+        .and_then(|v| String::from_utf8(v).ok());
+    match basic.as_deref().and_then(|v| v.split_once(':')) {
+        Some((id, secret)) => (Some(id.to_string()), Some(secret.to_string())),
+        None => (form.client_id.clone(), form.client_secret.clone()),
+    }
+}
+
+async fn token(
+    State(issuer): State<Arc<Issuer>>,
+    headers: HeaderMap,
+    Form(form): Form<TokenForm>,
+) -> impl IntoResponse {
+    if form.grant_type != "authorization_code" {
+        return oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type");
+    }
+    let (client_id, client_secret) = client_credentials(&headers, &form);
+    if client_id.as_deref() != Some(issuer.client_id.as_str())
+        || (issuer.client_secret.is_some() && client_secret != issuer.client_secret)
+    {
+        return oauth_error(StatusCode::UNAUTHORIZED, "invalid_client");
+    }
+
+    // Removed on first use, so a replayed code fails
+    let Some(grant) = issuer.grants.lock().unwrap().remove(&form.code) else {
+        return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+    };
+    let verifier_matches = match (&grant.code_challenge, &form.code_verifier) {
+        (Some(challenge), Some(verifier)) => {
+            URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == *challenge
+        }
+        (None, _) => true,
+        (Some(_), None) => false,
+    };
+    if grant.client_id != issuer.client_id
+        || grant.redirect_uri != form.redirect_uri
+        || !verifier_matches
+    {
+        return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut claims = grant.claims;
+    claims["iss"] = json!(issuer.url);
+    claims["aud"] = json!(issuer.client_id);
+    claims["iat"] = json!(now);
+    claims["exp"] = json!(now + TOKEN_TTL);
+
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some(KEY_ID.to_string());
+    let id_token = match jsonwebtoken::encode(&header, &claims, &issuer.signing_key) {
+        Ok(token) => token,
+        Err(_) => return oauth_error(StatusCode::INTERNAL_SERVER_ERROR, "server_error"),
+    };
+
+    Json(json!({
+        "access_token": random_token(),
+        "token_type": "Bearer",
+        "expires_in": TOKEN_TTL,
+        "id_token": id_token,
+    }))
+    .into_response()
+}
+
+#[tokio::main]
+async fn main() {
+    let port = env::var("MOCK_OIDC_PORT")
+        .unwrap_or_else(|_| "9000".to_string())
+        .parse::<u16>()
+        .expect("MOCK_OIDC_PORT must be a valid number");
+    let url = env::var("MOCK_OIDC_ISSUER").unwrap_or_else(|_| format!("http://127.0.0.1:{}", port));
+
+    // A fresh key every run; the backend fetches it from /jwks
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+        .expect("Failed to generate key");
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).expect("Invalid generated key");
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(KeyAlgorithm::EdDSA),
+            key_id: Some(KEY_ID.to_string()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+        }),
+    };
+
+    let issuer = Arc::new(Issuer {
+        url: url.trim_end_matches('/').to_string(),
+        client_id: env::var("MOCK_OIDC_CLIENT_ID").unwrap_or_else(|_| "pdv".to_string()),
+        client_secret: env::var("MOCK_OIDC_CLIENT_SECRET").ok(),
+        signing_key: EncodingKey::from_ed_der(pkcs8.as_ref()),
+        jwk,
+        grants: Mutex::new(HashMap::new()),
+    });
+
+    let app = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/authorize", get(authorize))
+        .route("/token", post(token))
+        .with_state(Arc::clone(&issuer));
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    println!("🔑 Mock OIDC issuer {} listening on {}", issuer.url, addr);
+    let listener = TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
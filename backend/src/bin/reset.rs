@@ -1,27 +1,79 @@
+//! Emergency password reset, for when nobody can log in to use the
+//! forgot-password flow.
+//!
+//! Usage: `cargo run --bin reset -- <email> [new-password]`
+//! Without a password a random one is generated and printed.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use dotenvy::dotenv;
+use rand_core::{OsRng, RngCore};
 use sqlx::postgres::PgPoolOptions;
 use std::env;
+use std::process;
 
 #[path = "../auth.rs"]
 mod auth;
 
+const MIN_PASSWORD_LENGTH: usize = 8;
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+
+    let mut args = env::args().skip(1);
+    let Some(email) = args.next() else {
+        eprintln!("Usage: reset <email> [new-password]");
+        process::exit(2);
+    };
+    let (password, generated) = match args.next() {
+        Some(password) => (password, false),
+        None => {
+            let mut bytes = [0u8; 12];
+            OsRng.fill_bytes(&mut bytes);
+            (URL_SAFE_NO_PAD.encode(bytes), true)
+        }
+    };
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        eprintln!(
+            "Password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        );
+        process::exit(2);
+    }
+
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let pool = PgPoolOptions::new()
         .connect(&database_url)
         .await
         .expect("Failed to connect to DB");
 
-    let password = "123";
-    let hash = auth::hash_password(password).expect("Failed to hash");
+    let hash = auth::hash_password(&password).expect("Failed to hash");
 
-    sqlx::query("UPDATE users SET password_hash = $1 WHERE email = 'master@saas.com'")
-        .bind(&hash)
-        .execute(&pool)
-        .await
-        .expect("Failed to update password");
+    let user_id: Option<String> = sqlx::query_scalar(
+        "UPDATE users SET password_hash = $1 WHERE email = $2 RETURNING id::text",
+    )
+    .bind(&hash)
+    .bind(&email)
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to update password");
+
+    let Some(user_id) = user_id else {
+        eprintln!("No user with email {}", email);
+        process::exit(1);
+    };
+
+    // Whoever held the old password is logged out everywhere
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(&user_id)
+    .execute(&pool)
+    .await
+    .expect("Failed to revoke sessions");
 
-    println!("Password reset successfully for master@saas.com");
+    println!("Password reset successfully for {}", email);
+    if generated {
+        println!("New password: {}", password);
+    }
 }
@@ -0,0 +1,482 @@
+//! Business type templates for new stores.
+//!
+//! Each business type a store can pick (`tenants.business_type`) comes with
+//! the defaults onboarding seeds: product categories, payment methods on
+//! top of [`BASE_PAYMENT_METHODS`], staff roles, sample products and the
+//! custom fields the store profile asks for. Values and custom fields
+//! mirror `frontend/src/lib/businessTypes.ts`; seeded names are shown to
+//! the store, hence in Portuguese.
+
+use crate::permissions::Permission::{self, *};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Number,
+    Boolean,
+    Select,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomField {
+    pub name: &'static str,
+    pub label: &'static str,
+    #[serde(rename = "type")]
+    pub kind: FieldKind,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub options: &'static [&'static str],
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentMethod {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleTemplate {
+    pub name: &'static str,
+    pub permissions: &'static [Permission],
+}
+
+#[derive(Debug, Serialize)]
+pub struct SampleProduct {
+    pub name: &'static str,
+    pub category: &'static str,
+    pub price: i32, // cents
+    pub stock_quantity: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BusinessType {
+    pub value: &'static str,
+    pub label: &'static str,
+    pub custom_fields: &'static [CustomField],
+    pub categories: &'static [&'static str],
+    /// Taken on top of [`BASE_PAYMENT_METHODS`].
+    pub payment_methods: &'static [PaymentMethod],
+    pub roles: &'static [RoleTemplate],
+    pub sample_products: &'static [SampleProduct],
+}
+
+/// Payment methods every store starts with; the codes the POS sends.
+pub const BASE_PAYMENT_METHODS: &[PaymentMethod] = &[
+    PaymentMethod {
+        code: "cash",
+        name: "Dinheiro",
+    },
+    PaymentMethod {
+        code: "credit_card",
+        name: "Cartão de Crédito",
+    },
+    PaymentMethod {
+        code: "debit_card",
+        name: "Cartão de Débito",
+    },
+    PaymentMethod {
+        code: "pix",
+        name: "Pix",
+    },
+];
+
+const BANK_SLIP: PaymentMethod = PaymentMethod {
+    code: "bank_slip",
+    name: "Boleto",
+};
+const MEAL_VOUCHER: PaymentMethod = PaymentMethod {
+    code: "meal_voucher",
+    name: "Vale-Refeição",
+};
+
+const fn boolean(name: &'static str, label: &'static str) -> CustomField {
+    CustomField {
+        name,
+        label,
+        kind: FieldKind::Boolean,
+        options: &[],
+    }
+}
+
+const fn number(name: &'static str, label: &'static str) -> CustomField {
+    CustomField {
+        name,
+        label,
+        kind: FieldKind::Number,
+        options: &[],
+    }
+}
+
+const fn product(
+    name: &'static str,
+    category: &'static str,
+    price: i32,
+    stock_quantity: i32,
+) -> SampleProduct {
+    SampleProduct {
+        name,
+        category,
+        price,
+        stock_quantity,
+    }
+}
+
+/// Front desk staff who book and charge services.
+const RECEPTIONIST: RoleTemplate = RoleTemplate {
+    name: "receptionist",
+    permissions: &[
+        SalesCreate,
+        SalesView,
+        ProductsView,
+        CustomersView,
+        CustomersEdit,
+    ],
+};
+
+pub const ALL: &[BusinessType] = &[
+    BusinessType {
+        value: "retail",
+        label: "Varejo Geral",
+        custom_fields: &[],
+        categories: &["Alimentos", "Bebidas", "Higiene", "Limpeza", "Utilidades"],
+        payment_methods: &[],
+        roles: &[],
+        sample_products: &[
+            product("Arroz 5kg", "Alimentos", 2890, 20),
+            product("Refrigerante 2L", "Bebidas", 999, 30),
+            product("Sabonete", "Higiene", 350, 50),
+        ],
+    },
+    BusinessType {
+        value: "pharmacy",
+        label: "Farmácia/Drogaria",
+        custom_fields: &[
+            boolean("requires_prescription", "Controla Receitas"),
+            boolean("tracks_batch", "Controla Lotes"),
+            boolean("tracks_expiry", "Controla Validade"),
+        ],
+        categories: &[
+            "Medicamentos",
+            "Genéricos",
+            "Dermocosméticos",
+            "Higiene",
+            "Suplementos",
+        ],
+        payment_methods: &[],
+        roles: &[RoleTemplate {
+            name: "pharmacist",
+            permissions: &[
+                SalesCreate,
+                SalesView,
+                SalesCancel,
+                ProductsView,
+                ProductsEdit,
+                CustomersView,
+                CustomersEdit,
+            ],
+        }],
+        sample_products: &[
+            product("Dipirona 500mg", "Genéricos", 890, 40),
+            product("Protetor Solar FPS 50", "Dermocosméticos", 5990, 10),
+            product("Vitamina C 1g", "Suplementos", 2490, 15),
+        ],
+    },
+    BusinessType {
+        value: "beauty",
+        label: "Salão de Beleza/Estética",
+        custom_fields: &[
+            boolean("has_appointments", "Sistema de Agendamento"),
+            number("num_professionals", "Número de Profissionais"),
+        ],
+        categories: &["Cabelo", "Unhas", "Estética", "Produtos"],
+        payment_methods: &[],
+        roles: &[
+            RECEPTIONIST,
+            RoleTemplate {
+                name: "professional",
+                permissions: &[SalesCreate, ProductsView, CustomersView],
+            },
+        ],
+        sample_products: &[
+            product("Corte Feminino", "Cabelo", 8000, 999),
+            product("Manicure", "Unhas", 3500, 999),
+            product("Shampoo Profissional", "Produtos", 4590, 12),
+        ],
+    },
+    BusinessType {
+        value: "gym",
+        label: "Academia/Fitness",
+        custom_fields: &[
+            boolean("has_membership", "Controla Mensalidades"),
+            boolean("has_checkin", "Sistema de Check-in"),
+            number("max_members", "Capacidade Máxima"),
+        ],
+        categories: &["Planos", "Aulas", "Suplementos", "Acessórios"],
+        payment_methods: &[BANK_SLIP],
+        roles: &[
+            RECEPTIONIST,
+            RoleTemplate {
+                name: "instructor",
+                permissions: &[CustomersView],
+            },
+        ],
+        sample_products: &[
+            product("Mensalidade", "Planos", 9990, 999),
+            product("Aula Avulsa", "Aulas", 3000, 999),
+            product("Whey Protein 900g", "Suplementos", 12990, 8),
+        ],
+    },
+    BusinessType {
+        value: "food",
+        label: "Restaurante/Alimentação",
+        custom_fields: &[
+            boolean("has_tables", "Controla Mesas"),
+            number("num_tables", "Número de Mesas"),
+            boolean("has_delivery", "Faz Delivery"),
+        ],
+        categories: &["Entradas", "Pratos Principais", "Bebidas", "Sobremesas"],
+        payment_methods: &[MEAL_VOUCHER],
+        roles: &[
+            RoleTemplate {
+                name: "waiter",
+                permissions: &[SalesCreate, SalesView, ProductsView, CustomersView],
+            },
+            RoleTemplate {
+                name: "courier",
+                permissions: &[DeliveriesDispatch],
+            },
+        ],
+        sample_products: &[
+            product("Prato Executivo", "Pratos Principais", 3290, 999),
+            product("Suco Natural", "Bebidas", 900, 999),
+            product("Pudim", "Sobremesas", 1200, 20),
+        ],
+    },
+    BusinessType {
+        value: "automotive",
+        label: "Automotivo (Oficina/Autopeças)",
+        custom_fields: &[CustomField {
+            name: "service_type",
+            label: "Tipo de Serviço",
+            kind: FieldKind::Select,
+            options: &["Oficina", "Autopeças", "Ambos"],
+        }],
+        categories: &["Peças", "Óleos e Fluidos", "Serviços", "Acessórios"],
+        payment_methods: &[BANK_SLIP],
+        roles: &[RoleTemplate {
+            name: "mechanic",
+            permissions: &[SalesCreate, ProductsView, CustomersView],
+        }],
+        sample_products: &[
+            product("Óleo 5W30 1L", "Óleos e Fluidos", 4590, 24),
+            product("Troca de Óleo", "Serviços", 6000, 999),
+            product("Pastilha de Freio", "Peças", 11990, 6),
+        ],
+    },
+    BusinessType {
+        value: "health",
+        label: "Clínica/Consultório",
+        custom_fields: &[
+            boolean("has_appointments", "Sistema de Agendamento"),
+            number("num_doctors", "Número de Profissionais"),
+        ],
+        categories: &["Consultas", "Exames", "Procedimentos"],
+        payment_methods: &[],
+        roles: &[RECEPTIONIST],
+        sample_products: &[
+            product("Consulta", "Consultas", 25000, 999),
+            product("Retorno", "Consultas", 0, 999),
+        ],
+    },
+    BusinessType {
+        value: "education",
+        label: "Escola/Cursos",
+        custom_fields: &[
+            boolean("has_enrollment", "Controla Matrículas"),
+            number("num_classes", "Número de Turmas"),
+        ],
+        categories: &["Matrículas", "Mensalidades", "Material Didático"],
+        payment_methods: &[BANK_SLIP],
+        roles: &[RoleTemplate {
+            name: "secretary",
+            permissions: &[SalesCreate, SalesView, CustomersView, CustomersEdit],
+        }],
+        sample_products: &[
+            product("Matrícula", "Matrículas", 20000, 999),
+            product("Mensalidade", "Mensalidades", 45000, 999),
+            product("Apostila", "Material Didático", 8900, 30),
+        ],
+    },
+    BusinessType {
+        value: "pet",
+        label: "Pet Shop",
+        custom_fields: &[
+            boolean("has_grooming", "Oferece Banho e Tosa"),
+            boolean("has_vet", "Tem Veterinário"),
+        ],
+        categories: &[
+            "Rações",
+            "Petiscos",
+            "Acessórios",
+            "Higiene",
+            "Banho e Tosa",
+        ],
+        payment_methods: &[],
+        roles: &[RoleTemplate {
+            name: "groomer",
+            permissions: &[SalesCreate, ProductsView, CustomersView],
+        }],
+        sample_products: &[
+            product("Ração 15kg", "Rações", 18990, 10),
+            product("Banho Porte Médio", "Banho e Tosa", 6000, 999),
+            product("Coleira", "Acessórios", 2990, 15),
+        ],
+    },
+    BusinessType {
+        value: "clothing",
+        label: "Vestuário/Moda",
+        custom_fields: &[boolean("tracks_sizes", "Controla Tamanhos (P/M/G)")],
+        categories: &[
+            "Feminino",
+            "Masculino",
+            "Infantil",
+            "Calçados",
+            "Acessórios",
+        ],
+        payment_methods: &[],
+        roles: &[],
+        sample_products: &[
+            product("Camiseta Básica", "Masculino", 4990, 30),
+            product("Vestido Midi", "Feminino", 15990, 8),
+            product("Tênis Casual", "Calçados", 19990, 6),
+        ],
+    },
+    BusinessType {
+        value: "electronics",
+        label: "Eletrônicos",
+        custom_fields: &[
+            boolean("tracks_warranty", "Controla Garantias"),
+            boolean("has_repair", "Oferece Assistência Técnica"),
+        ],
+        categories: &[
+            "Celulares",
+            "Acessórios",
+            "Informática",
+            "Assistência Técnica",
+        ],
+        payment_methods: &[],
+        roles: &[RoleTemplate {
+            name: "technician",
+            permissions: &[SalesCreate, ProductsView, CustomersView],
+        }],
+        sample_products: &[
+            product("Carregador USB-C", "Acessórios", 7990, 25),
+            product("Película de Vidro", "Acessórios", 2990, 50),
+            product("Troca de Tela", "Assistência Técnica", 29990, 999),
+        ],
+    },
+    BusinessType {
+        value: "bookstore",
+        label: "Livraria/Papelaria",
+        custom_fields: &[boolean("tracks_isbn", "Controla ISBN")],
+        categories: &["Livros", "Cadernos", "Escrita", "Escritório"],
+        payment_methods: &[],
+        roles: &[],
+        sample_products: &[
+            product("Caderno 10 Matérias", "Cadernos", 2990, 40),
+            product("Caneta Esferográfica", "Escrita", 250, 200),
+            product("Resma A4", "Escritório", 3290, 20),
+        ],
+    },
+    BusinessType {
+        value: "construction",
+        label: "Materiais de Construção",
+        custom_fields: &[boolean("tracks_volume", "Controla Volumes/Medidas")],
+        categories: &["Básicos", "Hidráulica", "Elétrica", "Ferramentas", "Tintas"],
+        payment_methods: &[BANK_SLIP],
+        roles: &[],
+        sample_products: &[
+            product("Cimento 50kg", "Básicos", 3890, 40),
+            product("Tubo PVC 25mm 6m", "Hidráulica", 2490, 30),
+            product("Tinta Acrílica 18L", "Tintas", 32990, 5),
+        ],
+    },
+    BusinessType {
+        value: "wholesale",
+        label: "Atacado",
+        custom_fields: &[number("min_order", "Pedido Mínimo")],
+        categories: &["Alimentos", "Bebidas", "Limpeza", "Descartáveis"],
+        payment_methods: &[BANK_SLIP],
+        roles: &[RoleTemplate {
+            name: "sales_rep",
+            permissions: &[
+                SalesCreate,
+                SalesView,
+                ProductsView,
+                CustomersView,
+                CustomersEdit,
+            ],
+        }],
+        sample_products: &[
+            product("Fardo Água 500ml (12 un)", "Bebidas", 1590, 100),
+            product(
+                "Caixa Copos Descartáveis (2500 un)",
+                "Descartáveis",
+                11990,
+                20,
+            ),
+        ],
+    },
+    BusinessType {
+        value: "service",
+        label: "Serviços Gerais",
+        custom_fields: &[boolean("has_work_orders", "Usa Ordens de Serviço")],
+        categories: &["Serviços", "Materiais", "Visitas"],
+        payment_methods: &[BANK_SLIP],
+        roles: &[],
+        sample_products: &[
+            product("Visita Técnica", "Visitas", 8000, 999),
+            product("Hora Técnica", "Serviços", 12000, 999),
+        ],
+    },
+];
+
+/// The template for a business type, `retail` for unknown ones.
+pub fn get(value: &str) -> &'static BusinessType {
+    ALL.iter().find(|t| t.value == value).unwrap_or(&ALL[0])
+}
+
+pub fn exists(value: &str) -> bool {
+    ALL.iter().any(|t| t.value == value)
+}
+
+impl BusinessType {
+    /// Starting custom field values: every yes/no field off.
+    pub fn default_custom_fields(&self) -> Map<String, Value> {
+        self.custom_fields
+            .iter()
+            .filter(|f| f.kind == FieldKind::Boolean)
+            .map(|f| (f.name.to_string(), Value::Bool(false)))
+            .collect()
+    }
+
+    /// Checks values for this type's custom fields.
+    pub fn validate_custom_fields(&self, values: &Map<String, Value>) -> Result<(), String> {
+        for (name, value) in values {
+            let Some(field) = self.custom_fields.iter().find(|f| f.name == name) else {
+                return Err(format!("Unknown custom field for {}: {}", self.value, name));
+            };
+            let valid = match field.kind {
+                _ if value.is_null() => true,
+                FieldKind::Number => value.is_number(),
+                FieldKind::Boolean => value.is_boolean(),
+                FieldKind::Select => value.as_str().is_some_and(|v| field.options.contains(&v)),
+            };
+            if !valid {
+                return Err(format!("Invalid value for custom field {}", name));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! Reseller commissions.
+//!
+//! A reseller with a commission rule earns, for the stores they created:
+//! - `rate_bps` of every paid invoice (basis points: 1500 = 15%)
+//! - `activation_bonus` once, when a store pays its first invoice
+//!
+//! [`record`] adds the ledger entries when billing settles an invoice, so
+//! a rule only applies to invoices paid after it was set. [`close_months`]
+//! groups the entries of each ended month into a payout statement per
+//! reseller, which the platform marks paid once the money is sent.
+
+use sqlx::{PgConnection, PgPool};
+
+pub const SUBSCRIPTION: &str = "subscription";
+pub const ACTIVATION: &str = "activation";
+
+pub const PENDING: &str = "pending";
+pub const PAID: &str = "paid";
+
+/// Share of `amount` for a rate in basis points, rounded to the cent.
+pub fn commission(amount: i64, rate_bps: i32) -> i64 {
+    (amount * rate_bps as i64 + 5_000) / 10_000
+}
+
+/// Earns the reseller of the invoice's store their commission on it. Call
+/// in the transaction that marks the invoice paid; recording the same
+/// invoice twice earns nothing more.
+pub async fn record(conn: &mut PgConnection, invoice_id: &str) -> Result<(), sqlx::Error> {
+    let earning: Option<(String, String, String, i64, i32, i64)> = sqlx::query_as(
+        r#"
+        SELECT r.reseller_id::text, i.tenant_id::text, t.name, i.amount, r.rate_bps, r.activation_bonus
+        FROM invoices i
+        JOIN tenants t ON t.id = i.tenant_id
+        JOIN commission_rules r ON r.reseller_id = t.reseller_id
+        WHERE i.id = $1::uuid AND i.status = 'paid' AND i.amount > 0
+        "#,
+    )
+    .bind(invoice_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((reseller_id, tenant_id, tenant_name, amount, rate_bps, activation_bonus)) = earning
+    else {
+        return Ok(());
+    };
+
+    let share = commission(amount, rate_bps);
+    if share > 0 {
+        sqlx::query(
+            r#"
+            INSERT INTO commissions (reseller_id, tenant_id, invoice_id, kind, base_amount, amount, description)
+            VALUES ($1::uuid, $2::uuid, $3::uuid, $4, $5, $6, $7)
+            ON CONFLICT (invoice_id) WHERE kind = 'subscription' DO NOTHING
+            "#,
+        )
+        .bind(&reseller_id)
+        .bind(&tenant_id)
+        .bind(invoice_id)
+        .bind(SUBSCRIPTION)
+        .bind(amount)
+        .bind(share)
+        .bind(format!(
+            "{:.2}% of {} subscription",
+            rate_bps as f64 / 100.0,
+            tenant_name
+        ))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    // Only the store's first paid invoice activates it
+    if activation_bonus > 0 {
+        sqlx::query(
+            r#"
+            INSERT INTO commissions (reseller_id, tenant_id, invoice_id, kind, base_amount, amount, description)
+            SELECT $1::uuid, $2::uuid, $3::uuid, $4, $5, $6, $7
+            WHERE NOT EXISTS (
+                SELECT 1 FROM invoices
+                WHERE tenant_id = $2::uuid AND status = 'paid' AND amount > 0 AND id <> $3::uuid
+            )
+            ON CONFLICT (reseller_id, tenant_id) WHERE kind = 'activation' DO NOTHING
+            "#,
+        )
+        .bind(&reseller_id)
+        .bind(&tenant_id)
+        .bind(invoice_id)
+        .bind(ACTIVATION)
+        .bind(amount)
+        .bind(activation_bonus)
+        .bind(format!("Activation of {}", tenant_name))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Closes every ended month with unsettled commissions into one payout
+/// statement per reseller and month; entries arriving for a month already
+/// closed are added to its statement while it is unpaid. Returns how many
+/// statements were created or topped up.
+pub async fn close_months(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let closed = sqlx::query(
+        r#"
+        INSERT INTO payouts (reseller_id, period_start, period_end, amount)
+        SELECT reseller_id, month, (month + INTERVAL '1 month' - INTERVAL '1 day')::date, SUM(amount)::BIGINT
+        FROM (
+            SELECT reseller_id, date_trunc('month', earned_on)::date AS month, amount
+            FROM commissions
+            WHERE payout_id IS NULL AND earned_on < date_trunc('month', CURRENT_DATE)
+        ) unsettled
+        GROUP BY reseller_id, month
+        ON CONFLICT (reseller_id, period_start) DO UPDATE SET amount = payouts.amount + EXCLUDED.amount
+        WHERE payouts.status = $1
+        "#,
+    )
+    .bind(PENDING)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        r#"
+        UPDATE commissions c SET payout_id = p.id
+        FROM payouts p
+        WHERE c.payout_id IS NULL AND c.earned_on < date_trunc('month', CURRENT_DATE)
+        AND p.reseller_id = c.reseller_id AND p.period_start = date_trunc('month', c.earned_on)::date
+        AND p.status = $1
+        "#,
+    )
+    .bind(PENDING)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(closed as usize)
+}
@@ -0,0 +1,129 @@
+//! Brazilian taxpayer documents (CPF for people, CNPJ for companies).
+//! Documents are stored normalised: digits only, with the detected type.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    Cpf,
+    Cnpj,
+}
+
+impl DocumentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentType::Cpf => "cpf",
+            DocumentType::Cnpj => "cnpj",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub number: String,
+    pub kind: DocumentType,
+}
+
+/// Strips punctuation such as `123.456.789-09` or `12.345.678/0001-95`.
+pub fn normalize(input: &str) -> String {
+    input.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// Detects the document type by length and validates its check digits.
+pub fn parse(input: &str) -> Result<Document, String> {
+    let number = normalize(input);
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+
+    // Sequences like 000.000.000-00 pass the check digit math but are invalid
+    if digits.windows(2).all(|w| w[0] == w[1]) {
+        return Err(format!("Invalid document: {}", input));
+    }
+
+    let kind = match digits.len() {
+        11 if is_valid_cpf(&digits) => DocumentType::Cpf,
+        14 if is_valid_cnpj(&digits) => DocumentType::Cnpj,
+        11 => return Err(format!("Invalid CPF: {}", input)),
+        14 => return Err(format!("Invalid CNPJ: {}", input)),
+        _ => return Err(format!("Invalid document: {}", input)),
+    };
+
+    Ok(Document { number, kind })
+}
+
+fn is_valid_cpf(digits: &[u32]) -> bool {
+    let check = |len: usize| {
+        let sum: u32 = digits[..len]
+            .iter()
+            .enumerate()
+            .map(|(i, d)| d * (len as u32 + 1 - i as u32))
+            .sum();
+        (sum * 10) % 11 % 10
+    };
+
+    check(9) == digits[9] && check(10) == digits[10]
+}
+
+fn is_valid_cnpj(digits: &[u32]) -> bool {
+    const WEIGHTS: [u32; 13] = [6, 5, 4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2];
+
+    let check = |len: usize| {
+        let weights = &WEIGHTS[WEIGHTS.len() - len..];
+        let sum: u32 = digits[..len].iter().zip(weights).map(|(d, w)| d * w).sum();
+        match sum % 11 {
+            0 | 1 => 0,
+            r => 11 - r,
+        }
+    };
+
+    check(12) == digits[12] && check(13) == digits[13]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_valid_cpf_with_or_without_mask() {
+        for input in ["529.982.247-25", "52998224725", "111.444.777-35"] {
+            let document = parse(input).unwrap();
+            assert_eq!(document.kind, DocumentType::Cpf);
+            assert_eq!(document.number, normalize(input));
+        }
+    }
+
+    #[test]
+    fn parses_valid_cnpj_with_or_without_mask() {
+        for input in ["11.222.333/0001-81", "11222333000181", "11.444.777/0001-61"] {
+            let document = parse(input).unwrap();
+            assert_eq!(document.kind, DocumentType::Cnpj);
+            assert_eq!(document.number, normalize(input));
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_check_digits() {
+        for input in ["529.982.247-24", "529.982.247-15"] {
+            assert_eq!(parse(input), Err(format!("Invalid CPF: {}", input)));
+        }
+        for input in ["11.222.333/0001-80", "11.222.333/0001-91"] {
+            assert_eq!(parse(input), Err(format!("Invalid CNPJ: {}", input)));
+        }
+    }
+
+    #[test]
+    fn rejects_repeated_digits() {
+        // These pass the check digit math on their own
+        for digit in '0'..='9' {
+            assert!(parse(&digit.to_string().repeat(11)).is_err());
+            assert!(parse(&digit.to_string().repeat(14)).is_err());
+        }
+        assert!(parse("000.000.000-00").is_err());
+        assert!(parse("00.000.000/0000-00").is_err());
+    }
+
+    #[test]
+    fn rejects_other_lengths() {
+        assert!(parse("").is_err());
+        assert!(parse("529.982.247").is_err());
+        assert!(parse("11.222.333/0001").is_err());
+        assert!(parse("529982247250").is_err());
+    }
+}
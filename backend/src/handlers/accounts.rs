@@ -0,0 +1,400 @@
+//! Password reset and email verification through single-use emailed tokens.
+
+use crate::audit::{AuditContext, Event};
+use crate::auth;
+use crate::handlers::sessions::{self, generate_token, hash_token};
+use crate::lockout::{self, LoginThrottle};
+use crate::mailer::{Email, Mailer};
+use crate::models::{
+    ForgotPasswordRequest, ResendVerificationRequest, ResetPasswordRequest, VerifyEmailRequest,
+};
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const PASSWORD_RESET: &str = "password_reset";
+const EMAIL_VERIFICATION: &str = "email_verification";
+const PASSWORD_RESET_HOURS: i32 = 1;
+const EMAIL_VERIFICATION_HOURS: i32 = 48;
+/// Minimum time between two emails of the same kind to one user.
+const RESEND_COOLDOWN_SECS: i32 = 60;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Whether login is refused until the email address is confirmed
+/// (`REQUIRE_EMAIL_VERIFICATION`, default true).
+pub fn verification_required() -> bool {
+    env::var("REQUIRE_EMAIL_VERIFICATION").as_deref() != Ok("false")
+}
+
+fn frontend_link(path: &str, token: &str) -> String {
+    let base = env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:5173".to_string());
+    format!("{}/{}?token={}", base.trim_end_matches('/'), path, token)
+}
+
+/// Whether a token for `purpose` was sent too recently to send another.
+async fn recently_issued(pool: &PgPool, user_id: &str, purpose: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM user_tokens WHERE user_id = $1 AND purpose = $2 AND created_at > CURRENT_TIMESTAMP - make_interval(secs => $3))",
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .bind(RESEND_COOLDOWN_SECS)
+    .fetch_one(pool)
+    .await
+}
+
+/// Creates a single-use token for `purpose`, voiding the user's older ones.
+pub async fn issue_token(
+    pool: &PgPool,
+    user_id: &str,
+    purpose: &str,
+    minutes: i32,
+) -> Result<String, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE user_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .execute(&mut *tx)
+    .await?;
+
+    let token = generate_token();
+    sqlx::query(
+        "INSERT INTO user_tokens (id, user_id, purpose, token_hash, expires_at) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + make_interval(mins => $5))",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(user_id)
+    .bind(purpose)
+    .bind(hash_token(&token))
+    .bind(minutes)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(token)
+}
+
+/// User a still valid token belongs to, without using it up.
+pub async fn peek_token(
+    pool: &PgPool,
+    token: &str,
+    purpose: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT user_id::text FROM user_tokens WHERE token_hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP",
+    )
+    .bind(hash_token(token))
+    .bind(purpose)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Marks a token used and returns its user, if it is valid for `purpose`.
+/// A single UPDATE makes redeeming the same token twice impossible.
+pub async fn consume_token(
+    tx: &mut sqlx::PgConnection,
+    token: &str,
+    purpose: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "UPDATE user_tokens SET used_at = CURRENT_TIMESTAMP WHERE token_hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP RETURNING user_id::text",
+    )
+    .bind(hash_token(token))
+    .bind(purpose)
+    .fetch_optional(tx)
+    .await
+}
+
+/// Emails a verification link to a newly registered user.
+pub async fn send_verification_email(
+    pool: &PgPool,
+    mailer: &Arc<Mailer>,
+    user_id: &str,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    if recently_issued(pool, user_id, EMAIL_VERIFICATION).await? {
+        return Ok(());
+    }
+
+    let token = issue_token(
+        pool,
+        user_id,
+        EMAIL_VERIFICATION,
+        EMAIL_VERIFICATION_HOURS * 60,
+    )
+    .await?;
+    mailer.dispatch(Email {
+        to: email.to_string(),
+        subject: "Confirme seu email".to_string(),
+        body: format!(
+            "Olá!\n\nPara ativar sua conta, confirme seu email acessando o link abaixo:\n\n{}\n\nO link expira em {} horas. Se você não criou esta conta, ignore esta mensagem.\n",
+            frontend_link("verify-email", &token),
+            EMAIL_VERIFICATION_HOURS
+        ),
+    });
+    Ok(())
+}
+
+async fn send_reset_email(
+    pool: &PgPool,
+    mailer: &Arc<Mailer>,
+    user_id: &str,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    if recently_issued(pool, user_id, PASSWORD_RESET).await? {
+        return Ok(());
+    }
+
+    let token = issue_token(pool, user_id, PASSWORD_RESET, PASSWORD_RESET_HOURS * 60).await?;
+    mailer.dispatch(Email {
+        to: email.to_string(),
+        subject: "Redefinição de senha".to_string(),
+        body: format!(
+            "Olá!\n\nRecebemos um pedido para redefinir sua senha. Para escolher uma nova, acesse:\n\n{}\n\nO link expira em {} hora e só pode ser usado uma vez. Se você não fez este pedido, ignore esta mensagem.\n",
+            frontend_link("reset-password", &token),
+            PASSWORD_RESET_HOURS
+        ),
+    });
+    Ok(())
+}
+
+/// POST /auth/forgot-password
+/// Always answers the same way so it cannot be used to discover accounts
+pub async fn forgot_password(
+    State(pool): State<PgPool>,
+    Extension(mailer): Extension<Arc<Mailer>>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> impl IntoResponse {
+    let user: Option<(String, String)> =
+        match sqlx::query_as("SELECT id::text, email FROM users WHERE email = $1")
+            .bind(payload.email.trim())
+            .fetch_optional(&pool)
+            .await
+        {
+            Ok(user) => user,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Database error: {}", e),
+                )
+                    .into_response();
+            }
+        };
+
+    if let Some((user_id, email)) = user
+        && let Err(e) = send_reset_email(&pool, &mailer, &user_id, &email).await
+    {
+        eprintln!("Failed to issue reset token: {}", e);
+    }
+
+    (
+        StatusCode::OK,
+        "If the email is registered, a reset link has been sent",
+    )
+        .into_response()
+}
+
+/// POST /auth/reset-password
+/// Sets a new password and logs the user out of every device
+pub async fn reset_password(
+    State(pool): State<PgPool>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    audit: AuditContext,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> impl IntoResponse {
+    if payload.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Password must have at least {} characters",
+                MIN_PASSWORD_LENGTH
+            ),
+        )
+            .into_response();
+    }
+
+    let password_hash = match auth::hash_password(&payload.password) {
+        Ok(hash) => hash,
+        Err(_) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password").into_response();
+        }
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let user_id = match consume_token(&mut tx, &payload.token, PASSWORD_RESET).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    // The link was delivered to the mailbox, which also proves the address
+    let (email, tenant_id): (String, Option<String>) = match sqlx::query_as(
+        "UPDATE users SET password_hash = $1, email_verified_at = COALESCE(email_verified_at, CURRENT_TIMESTAMP) WHERE id = $2 RETURNING email, tenant_id::text",
+    )
+    .bind(&password_hash)
+    .bind(&user_id)
+    .fetch_one(&mut *tx)
+    .await
+    {
+        Ok(row) => row,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update password: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let event = Event::new("auth.password_reset", &user_id).tenant(tenant_id.as_deref());
+    if let Err(e) = audit.record(&mut *tx, event).await {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    if let Err(e) = tx.commit().await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response();
+    }
+
+    throttle.reset(&lockout::account_key(&email)).await;
+    match sessions::revoke_user_sessions(&pool, &user_id).await {
+        Ok(_) => (StatusCode::OK, "Password updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke sessions: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/verify-email
+pub async fn verify_email(
+    State(pool): State<PgPool>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> impl IntoResponse {
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let user_id = match consume_token(&mut tx, &payload.token, EMAIL_VERIFICATION).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let result = sqlx::query(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, CURRENT_TIMESTAMP) WHERE id = $1",
+    )
+    .bind(&user_id)
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to verify email: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Email verified").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/resend-verification
+/// Sends a fresh verification link; answers the same way for unknown emails
+pub async fn resend_verification(
+    State(pool): State<PgPool>,
+    Extension(mailer): Extension<Arc<Mailer>>,
+    Json(payload): Json<ResendVerificationRequest>,
+) -> impl IntoResponse {
+    let user: Option<(String, String)> = match sqlx::query_as(
+        "SELECT id::text, email FROM users WHERE email = $1 AND email_verified_at IS NULL",
+    )
+    .bind(payload.email.trim())
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if let Some((user_id, email)) = user
+        && let Err(e) = send_verification_email(&pool, &mailer, &user_id, &email).await
+    {
+        eprintln!("Failed to issue verification token: {}", e);
+    }
+
+    (
+        StatusCode::OK,
+        "If the email awaits verification, a new link has been sent",
+    )
+        .into_response()
+}
@@ -0,0 +1,281 @@
+use crate::auth::Claims;
+use crate::models::{CustomerAddress, CustomerAddressRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use uuid::Uuid;
+
+pub const ADDRESS_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, customer_id::text AS customer_id, label, street, number, complement, neighborhood, city, state, zip_code, reference, is_default, created_at";
+
+fn validate(payload: &CustomerAddressRequest) -> Result<String, &'static str> {
+    let zip_code: String = payload
+        .zip_code
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect();
+    if zip_code.len() != 8 {
+        return Err("CEP must have 8 digits");
+    }
+    if payload.state.trim().len() != 2 {
+        return Err("State must be a 2-letter UF");
+    }
+    if payload.street.trim().is_empty() || payload.city.trim().is_empty() {
+        return Err("Street and city are required");
+    }
+    Ok(zip_code)
+}
+
+pub async fn list_addresses(
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(customer_id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let addresses = sqlx::query_as::<_, CustomerAddress>(&format!(
+        "SELECT {} FROM customer_addresses WHERE customer_id = $1::uuid AND tenant_id = $2::uuid ORDER BY is_default DESC, created_at ASC",
+        ADDRESS_COLUMNS
+    ))
+    .bind(&customer_id)
+    .bind(&tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match addresses {
+        Ok(addresses) => Json(addresses).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list addresses: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list addresses",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn create_address(
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(customer_id): Path<String>,
+    Json(payload): Json<CustomerAddressRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let zip_code = match validate(&payload) {
+        Ok(zip_code) => zip_code,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let exists = sqlx::query("SELECT 1 FROM customers WHERE id = $1 AND tenant_id = $2")
+        .bind(&customer_id)
+        .bind(&tenant_id)
+        .fetch_optional(&mut *tx)
+        .await;
+
+    match exists {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    // First address becomes the default one
+    let has_addresses: Option<i32> =
+        sqlx::query_scalar("SELECT 1 FROM customer_addresses WHERE customer_id = $1 LIMIT 1")
+            .bind(&customer_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .unwrap_or(None);
+    let is_default = payload.is_default.unwrap_or(false) || has_addresses.is_none();
+
+    let id = Uuid::new_v4().to_string();
+    let result = async {
+        if is_default {
+            sqlx::query(
+                "UPDATE customer_addresses SET is_default = FALSE WHERE customer_id = $1",
+            )
+            .bind(&customer_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO customer_addresses (id, tenant_id, customer_id, label, street, number, complement, neighborhood, city, state, zip_code, reference, is_default) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+        )
+        .bind(&id)
+        .bind(&tenant_id)
+        .bind(&customer_id)
+        .bind(&payload.label)
+        .bind(payload.street.trim())
+        .bind(&payload.number)
+        .bind(&payload.complement)
+        .bind(&payload.neighborhood)
+        .bind(payload.city.trim())
+        .bind(payload.state.trim().to_uppercase())
+        .bind(&zip_code)
+        .bind(&payload.reference)
+        .bind(is_default)
+        .execute(&mut *tx)
+        .await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        eprintln!("Failed to create address: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create address",
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn update_address(
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path((customer_id, address_id)): Path<(String, String)>,
+    Json(payload): Json<CustomerAddressRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let zip_code = match validate(&payload) {
+        Ok(zip_code) => zip_code,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let is_default = payload.is_default.unwrap_or(false);
+    let result = async {
+        if is_default {
+            sqlx::query(
+                "UPDATE customer_addresses SET is_default = FALSE WHERE customer_id = $1 AND tenant_id = $2",
+            )
+            .bind(&customer_id)
+            .bind(&tenant_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE customer_addresses SET
+                label = $1, street = $2, number = $3, complement = $4, neighborhood = $5,
+                city = $6, state = $7, zip_code = $8, reference = $9,
+                is_default = is_default OR $10
+            WHERE id = $11 AND customer_id = $12 AND tenant_id = $13
+            "#,
+        )
+        .bind(&payload.label)
+        .bind(payload.street.trim())
+        .bind(&payload.number)
+        .bind(&payload.complement)
+        .bind(&payload.neighborhood)
+        .bind(payload.city.trim())
+        .bind(payload.state.trim().to_uppercase())
+        .bind(&zip_code)
+        .bind(&payload.reference)
+        .bind(is_default)
+        .bind(&address_id)
+        .bind(&customer_id)
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await
+    }
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            let _ = tx.rollback().await;
+            return (StatusCode::NOT_FOUND, "Address not found").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let _ = tx.rollback().await;
+            eprintln!("Failed to update address: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update address",
+            )
+                .into_response();
+        }
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Address updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn delete_address(
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path((customer_id, address_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    // Past deliveries keep their address snapshot
+    let result = sqlx::query(
+        "DELETE FROM customer_addresses WHERE id = $1 AND customer_id = $2 AND tenant_id = $3",
+    )
+    .bind(&address_id)
+    .bind(&customer_id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Address not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Address deleted").into_response(),
+        Err(e) => {
+            eprintln!("Failed to delete address: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete address",
+            )
+                .into_response()
+        }
+    }
+}
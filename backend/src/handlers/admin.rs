@@ -1,32 +1,53 @@
 use axum::{
-    extract::{State, Json, Extension, Path},
+    extract::{State, Json, Extension, Path, Query},
     response::IntoResponse,
     http::StatusCode,
 };
 use sqlx::PgPool;
 use uuid::Uuid;
 use crate::models::{Plan, CreatePlanRequest, Tenant, CreateTenantRequest};
+use crate::audit::{self, AuditContext, Event};
 use crate::auth::Claims;
+use crate::billing;
+use crate::business_types;
+use crate::documents;
+use crate::lockout::{self, LoginThrottle};
+use crate::onboarding;
+use std::sync::Arc;
+use crate::permissions::{Require, perm};
+use crate::plans::{self, Feature};
+use crate::tenant_status::{self, TenantPolicy};
 
 pub async fn create_plan(
+    _: Require<perm::PlansManage>,
     State(pool): State<PgPool>,
+    audit: AuditContext,
     Json(payload): Json<CreatePlanRequest>,
 ) -> impl IntoResponse {
     let id = Uuid::new_v4().to_string();
 
+    if let Some(unknown) = payload.features.iter().flatten().find(|f| Feature::parse(f).is_none()) {
+        return (StatusCode::BAD_REQUEST, format!("Unknown feature: {}", unknown)).into_response();
+    }
+    let features = payload.features.as_ref().map(|f| serde_json::json!(f));
+
     let result = sqlx::query(
-        "INSERT INTO plans (id, name, price, max_users, features) VALUES ($1, $2, $3, $4, $5)",
+        "INSERT INTO plans (id, name, price, max_users, features) VALUES ($1::uuid, $2, $3, $4, $5)",
     )
     .bind(&id)
     .bind(&payload.name)
     .bind(&payload.price)
     .bind(&payload.max_users)
-    .bind(&payload.features)
+    .bind(features)
     .execute(&pool)
     .await;
 
     match result {
-        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Ok(_) => {
+            let after = audit::snapshot(&pool, "plans", &id).await.unwrap_or(None);
+            audit.log(&pool, Event::new("plan.create", &id).tenant(None).after(after)).await;
+            (StatusCode::CREATED, Json(id)).into_response()
+        }
         Err(e) => {
             eprintln!("Failed to create plan: {}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create plan").into_response()
@@ -35,9 +56,10 @@ pub async fn create_plan(
 }
 
 pub async fn list_plans(
+    _: Require<perm::PlansView>,
     State(pool): State<PgPool>,
 ) -> impl IntoResponse {
-    let plans = sqlx::query_as::<_, Plan>("SELECT * FROM plans")
+    let plans = sqlx::query_as::<_, Plan>("SELECT id::text AS id, name, price, max_users, features, created_at FROM plans ORDER BY price, name")
         .fetch_all(&pool)
         .await;
 
@@ -51,10 +73,22 @@ pub async fn list_plans(
 }
 
 pub async fn create_tenant(
+    _: Require<perm::TenantsManage>,
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    audit: AuditContext,
     Json(payload): Json<CreateTenantRequest>,
 ) -> impl IntoResponse {
+    let document = match payload.document.as_deref().map(documents::parse).transpose() {
+        Ok(document) => document,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    if let Some(business_type) = &payload.business_type
+        && !business_types::exists(business_type)
+    {
+        return (StatusCode::BAD_REQUEST, format!("Unknown business type: {}", business_type)).into_response();
+    }
+
     let id = Uuid::new_v4().to_string();
     
     // Determine reseller_id based on who is creating the tenant
@@ -70,13 +104,15 @@ pub async fn create_tenant(
     };
 
     let result = sqlx::query(
-        "INSERT INTO tenants (id, name, plan_id, business_type, reseller_id) VALUES ($1, $2, $3, $4, $5)",
+        "INSERT INTO tenants (id, name, plan_id, business_type, reseller_id, document, document_type) VALUES ($1, $2, $3, $4, $5, $6, $7)",
     )
     .bind(&id)
     .bind(&payload.name)
     .bind(&payload.plan_id)
     .bind(payload.business_type.as_deref().unwrap_or("retail"))
     .bind(reseller_id)
+    .bind(document.as_ref().map(|d| &d.number))
+    .bind(document.as_ref().map(|d| d.kind.as_str()))
     .execute(&mut *transaction)
     .await;
 
@@ -84,8 +120,20 @@ pub async fn create_tenant(
         return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create tenant: {}", e)).into_response();
     }
 
+    // Seed the store's categories, payment methods, roles and custom fields from its business type
+    let template = business_types::get(payload.business_type.as_deref().unwrap_or("retail"));
+    if let Err(e) = onboarding::apply(&mut transaction, &id, template, &serde_json::Map::new(), false).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to seed store: {}", e)).into_response();
+    }
+
     // If Owner info provided, create the user
     if let (Some(email), Some(password)) = (&payload.owner_email, &payload.owner_password) {
+        match plans::check_user_limit(&mut transaction, &pool, &id, 1).await {
+            Ok(None) => {}
+            Ok(Some(limit)) => return limit.into_response(),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response(),
+        }
+
         let user_id = Uuid::new_v4().to_string();
         let password_hash = match crate::auth::hash_password(password) {
             Ok(hash) => hash,
@@ -104,6 +152,23 @@ pub async fn create_tenant(
         if let Err(e) = user_result {
             return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create owner user: {}", e)).into_response();
         }
+
+        let after = audit::snapshot(&mut *transaction, "users", &user_id).await.unwrap_or(None);
+        if let Err(e) = audit.record(&mut *transaction, Event::new("user.create", &user_id).tenant(Some(&id)).after(after)).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
+        }
+    }
+
+    // New stores start on a trial of their plan
+    if let Some(plan_id) = &payload.plan_id
+        && let Err(e) = billing::start_subscription(&mut transaction, &id, plan_id, billing::MONTHLY, None, true).await
+    {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start subscription: {}", e)).into_response();
+    }
+
+    let after = audit::snapshot(&mut *transaction, "tenants", &id).await.unwrap_or(None);
+    if let Err(e) = audit.record(&mut *transaction, Event::new("tenant.create", &id).tenant(Some(&id)).after(after)).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
     }
 
     match transaction.commit().await {
@@ -113,6 +178,7 @@ pub async fn create_tenant(
 }
 
 pub async fn list_tenants(
+    _: Require<perm::TenantsManage>,
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
@@ -136,14 +202,13 @@ pub async fn list_tenants(
 }
 
 pub async fn update_tenant(
+    _: Require<perm::TenantsManage>,
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    audit: AuditContext,
     Path(id): Path<String>,
     Json(payload): Json<crate::models::UpdateTenantRequest>,
 ) -> impl IntoResponse {
-    if claims.role != "admin" && claims.role != "reseller" {
-        return (StatusCode::FORBIDDEN, "Not authorized").into_response();
-    }
     // Verify ownership if reseller
     if claims.role == "reseller" {
          let exists = sqlx::query("SELECT 1 FROM tenants WHERE id = $1 AND reseller_id = $2")
@@ -157,6 +222,17 @@ pub async fn update_tenant(
          }
     }
 
+    let document = match payload.document.as_deref().map(documents::parse).transpose() {
+        Ok(document) => document,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    if let Some(status) = &payload.status
+        && !tenant_status::STATUSES.contains(&status.as_str())
+    {
+        return (StatusCode::BAD_REQUEST, "Status must be active, inactive or suspended").into_response();
+    }
+
     let mut builder = sqlx::QueryBuilder::new("UPDATE tenants SET updated_at = CURRENT_TIMESTAMP");
     
     if let Some(name) = &payload.name {
@@ -168,8 +244,20 @@ pub async fn update_tenant(
         builder.push_bind(plan_id);
     }
     if let Some(status) = &payload.status {
-        builder.push(", status = ");
+        // An unchanged status keeps when and why it was set
+        let reason = (status == tenant_status::SUSPENDED).then_some(tenant_status::REASON_MANUAL);
+        builder.push(", status_changed_at = CASE WHEN status = ");
+        builder.push_bind(status);
+        builder.push(" THEN status_changed_at ELSE CURRENT_TIMESTAMP END, suspended_reason = CASE WHEN status = ");
         builder.push_bind(status);
+        builder.push(" THEN suspended_reason ELSE ");
+        builder.push_bind(reason);
+        builder.push(" END, status = ");
+        builder.push_bind(status);
+    }
+    if let Some(paid_until) = &payload.paid_until {
+        builder.push(", paid_until = ");
+        builder.push_bind(paid_until);
     }
     if let Some(business_type) = &payload.business_type {
         builder.push(", business_type = ");
@@ -179,62 +267,131 @@ pub async fn update_tenant(
         builder.push(", custom_fields = ");
         builder.push_bind(custom_fields);
     }
+    if let Some(document) = &document {
+        builder.push(", document = ");
+        builder.push_bind(&document.number);
+        builder.push(", document_type = ");
+        builder.push_bind(document.kind.as_str());
+    }
 
     builder.push(" WHERE id = ");
-    builder.push_bind(id);
+    builder.push_bind(&id);
 
-    let result = builder.build().execute(&pool).await;
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start transaction: {}", e)).into_response(),
+    };
 
-    match result {
+    let before = match audit::snapshot(&mut *transaction, "tenants", &id).await {
+        Ok(Some(before)) => before,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update: {}", e)).into_response(),
+    };
+
+    if let Err(e) = builder.build().execute(&mut *transaction).await {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update: {}", e)).into_response();
+    }
+
+    // A plan change is prorated over the rest of the billing period
+    if let Some(plan_id) = &payload.plan_id {
+        match billing::change_plan(&mut transaction, &id, plan_id).await {
+            Ok(Some(proration)) => {
+                let event = Event::new("subscription.change_plan", &id).tenant(Some(&id)).after(serde_json::to_value(&proration).ok());
+                if let Err(e) = audit.record(&mut *transaction, event).await {
+                    let _ = transaction.rollback().await;
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                let _ = transaction.rollback().await;
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to change plan: {}", e)).into_response();
+            }
+        }
+    }
+
+    let after = audit::snapshot(&mut *transaction, "tenants", &id).await.unwrap_or(None);
+    if let Err(e) = audit.record(&mut *transaction, Event::new("tenant.update", &id).tenant(Some(&id)).before(Some(before)).after(after)).await {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
+    }
+
+    match transaction.commit().await {
         Ok(_) => (StatusCode::OK, "Tenant updated").into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update: {}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit transaction: {}", e)).into_response(),
     }
 }
 
 pub async fn delete_tenant(
+    _: Require<perm::TenantsManage>,
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    audit: AuditContext,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    if claims.role != "admin" { // Only admin can delete for now? Or reseller too? Let's allow reseller to delete THEIR tenants
-        if claims.role == "reseller" {
-             let exists = sqlx::query("SELECT 1 FROM tenants WHERE id = $1 AND reseller_id = $2")
-                 .bind(&id)
-                 .bind(&claims.sub)
-                 .fetch_optional(&pool)
-                 .await
-                 .unwrap_or(None);
-             if exists.is_none() {
-                 return (StatusCode::FORBIDDEN, "Not owner").into_response();
-             }
-        } else {
-             return (StatusCode::FORBIDDEN, "Not authorized").into_response();
-        }
+    // Resellers can only delete THEIR tenants
+    if claims.role == "reseller" {
+         let exists = sqlx::query("SELECT 1 FROM tenants WHERE id = $1 AND reseller_id = $2")
+             .bind(&id)
+             .bind(&claims.sub)
+             .fetch_optional(&pool)
+             .await
+             .unwrap_or(None);
+         if exists.is_none() {
+             return (StatusCode::FORBIDDEN, "Not owner").into_response();
+         }
     }
 
     // Cascade delete users? Generally handled by DB FKs if configured, but here we might need manual
     // Let's just delete tenant and assume DB is robust or we don't care about orphans for prototype
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start transaction: {}", e)).into_response(),
+    };
+
+    let before = audit::snapshot(&mut *transaction, "tenants", &id).await.unwrap_or(None);
+
     let result = sqlx::query("DELETE FROM tenants WHERE id = $1")
         .bind(&id)
-        .execute(&pool)
+        .execute(&mut *transaction)
         .await;
 
-    match result {
+    if let Err(e) = result {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete: {}", e)).into_response();
+    }
+
+    if let Err(e) = audit.record(&mut *transaction, Event::new("tenant.delete", &id).tenant(Some(&id)).before(before)).await {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
+    }
+
+    match transaction.commit().await {
         Ok(_) => (StatusCode::OK, "Tenant deleted").into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete: {}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit transaction: {}", e)).into_response(),
     }
 }
 
-pub async fn create_reseller(
+/// POST /admin/tenants/suspend-overdue
+/// Runs the overdue suspension now instead of waiting for the hourly sweep
+pub async fn suspend_overdue_tenants(
+    _: Require<perm::BillingManage>,
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
-    Json(payload): Json<crate::models::CreateUserRequest>,
+    Extension(policy): Extension<Arc<TenantPolicy>>,
 ) -> impl IntoResponse {
-    // Only admin can create resellers
-    if claims.role != "admin" {
-         return (StatusCode::FORBIDDEN, "Only admins can create resellers").into_response();
+    match tenant_status::suspend_overdue(&pool, &policy).await {
+        Ok(suspended) => Json(suspended).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to suspend overdue tenants: {}", e)).into_response(),
     }
+}
 
+pub async fn create_reseller(
+    _: Require<perm::ResellersManage>,
+    State(pool): State<PgPool>,
+    audit: AuditContext,
+    Json(payload): Json<crate::models::CreateUserRequest>,
+) -> impl IntoResponse {
     let user_id = Uuid::new_v4().to_string();
     let tenant_id = Uuid::new_v4().to_string(); // Reseller has their own tenant/workspace
 
@@ -253,7 +410,11 @@ pub async fn create_reseller(
         .await;
 
     match result {
-        Ok(_) => (StatusCode::CREATED, "Reseller created successfully").into_response(),
+        Ok(_) => {
+            let after = audit::snapshot(&pool, "users", &user_id).await.unwrap_or(None);
+            audit.log(&pool, Event::new("user.create", &user_id).tenant(None).after(after)).await;
+            (StatusCode::CREATED, "Reseller created successfully").into_response()
+        }
         Err(e) => {
              eprintln!("Failed to create reseller: {}", e);
              (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response()
@@ -261,13 +422,9 @@ pub async fn create_reseller(
     }
 }
 pub async fn list_resellers(
+    _: Require<perm::ResellersManage>,
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
-    if claims.role != "admin" {
-        return (StatusCode::FORBIDDEN, "Only admins can list resellers").into_response();
-    }
-    
     // We reuse User struct but we should filter only role='reseller'
     // Since User hash is hidden by serde logic (maybe?), we can return list of users.
     // User struct has skip_serializing for password_hash.
@@ -286,20 +443,15 @@ pub async fn list_resellers(
 }
 
 pub async fn create_user_admin(
+    _: Require<perm::UsersManage>,
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
     Json(payload): Json<crate::models::CreateUserRequest>,
 ) -> impl IntoResponse {
-    if claims.role != "admin" {
-         return (StatusCode::FORBIDDEN, "Only admins can create users").into_response();
-    }
-
     let user_id = Uuid::new_v4().to_string();
-    let tenant_id = if payload.role.as_deref() == Some("admin") {
-         Uuid::new_v4().to_string()
-    } else {
-         Uuid::new_v4().to_string()
-    };
+    // Joining an existing store counts against its plan; otherwise the user gets a store of their own
+    let joins_tenant = payload.tenant_id.is_some();
+    let tenant_id = payload.tenant_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
 
     let password_hash = match crate::auth::hash_password(&payload.password) {
         Ok(hash) => hash,
@@ -308,17 +460,51 @@ pub async fn create_user_admin(
 
     let role = payload.role.unwrap_or_else(|| "user".to_string());
 
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start transaction: {}", e)).into_response(),
+    };
+
+    if joins_tenant {
+        let exists = sqlx::query("SELECT 1 FROM tenants WHERE id = $1::uuid")
+            .bind(&tenant_id)
+            .fetch_optional(&mut *transaction)
+            .await;
+        match exists {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+            Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid tenant: {}", e)).into_response(),
+        }
+        match plans::check_user_limit(&mut transaction, &pool, &tenant_id, 1).await {
+            Ok(None) => {}
+            Ok(Some(limit)) => return limit.into_response(),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response(),
+        }
+    }
+
     let result = sqlx::query("INSERT INTO users (id, email, password_hash, role, tenant_id) VALUES ($1, $2, $3, $4, $5)")
         .bind(&user_id)
         .bind(&payload.email)
         .bind(&password_hash)
         .bind(&role)
         .bind(&tenant_id)
-        .execute(&pool)
+        .execute(&mut *transaction)
         .await;
 
+    let result = match result {
+        Ok(_) => {
+            let after = audit::snapshot(&mut *transaction, "users", &user_id).await.unwrap_or(None);
+            let tenant = joins_tenant.then_some(tenant_id.as_str());
+            audit.record(&mut *transaction, Event::new("user.create", &user_id).tenant(tenant).after(after)).await
+        }
+        Err(e) => Err(e),
+    };
+
     match result {
-        Ok(_) => (StatusCode::CREATED, "User created successfully").into_response(),
+        Ok(_) => match transaction.commit().await {
+            Ok(_) => (StatusCode::CREATED, "User created successfully").into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit transaction: {}", e)).into_response(),
+        },
         Err(e) => {
              // Check for unique constraint violation (sqlite code 2067)
              let msg = e.to_string();
@@ -333,13 +519,9 @@ pub async fn create_user_admin(
 }
 
 pub async fn list_users(
+    _: Require<perm::UsersManage>,
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
-    if claims.role != "admin" {
-        return (StatusCode::FORBIDDEN, "Only admins can list users").into_response();
-    }
-    
     let users = sqlx::query_as::<_, crate::models::User>("SELECT * FROM users")
         .fetch_all(&pool)
         .await;
@@ -354,15 +536,12 @@ pub async fn list_users(
 }
 
 pub async fn update_user(
+    _: Require<perm::UsersManage>,
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
     Path(id): Path<String>,
     Json(payload): Json<crate::models::UpdateUserRequest>,
 ) -> impl IntoResponse {
-    if claims.role != "admin" {
-        return (StatusCode::FORBIDDEN, "Only admins can update users").into_response();
-    }
-
     let mut builder = sqlx::QueryBuilder::new("UPDATE users SET id = id"); // Dummy start
 
     if let Some(email) = &payload.email {
@@ -381,108 +560,143 @@ pub async fn update_user(
     }
 
     builder.push(" WHERE id = ");
-    builder.push_bind(id);
+    builder.push_bind(&id);
 
-    let result = builder.build().execute(&pool).await;
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start transaction: {}", e)).into_response(),
+    };
 
-    match result {
-        Ok(_) => (StatusCode::OK, "User updated").into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update: {}", e)).into_response(),
+    let before = match audit::snapshot(&mut *transaction, "users", &id).await {
+        Ok(Some(before)) => before,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update: {}", e)).into_response(),
+    };
+
+    if let Err(e) = builder.build().execute(&mut *transaction).await {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update: {}", e)).into_response();
+    }
+
+    let after = audit::snapshot(&mut *transaction, "users", &id).await.unwrap_or(None);
+    let tenant_id = before["tenant_id"].as_str().map(str::to_string);
+    let mut events = vec![Event::new("user.update", &id).tenant(tenant_id.as_deref()).before(Some(before)).after(after)];
+    if payload.password.is_some() {
+        events.push(Event::new("user.password_reset", &id).tenant(tenant_id.as_deref()));
     }
+    for event in events {
+        if let Err(e) = audit.record(&mut *transaction, event).await {
+            let _ = transaction.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
+        }
+    }
+
+    if let Err(e) = transaction.commit().await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit transaction: {}", e)).into_response();
+    }
+
+    // A new role or password must not ride on tokens issued before the change
+    if (payload.role.is_some() || payload.password.is_some())
+        && let Err(e) = crate::handlers::sessions::revoke_user_sessions(&pool, &id).await
+    {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to revoke sessions: {}", e)).into_response();
+    }
+
+    (StatusCode::OK, "User updated").into_response()
 }
 
 pub async fn delete_user(
+    _: Require<perm::UsersManage>,
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    audit: AuditContext,
     Path(id): Path<String>,
-    Json(payload): Json<crate::models::UpdateUserRequest>, // Warning: this was unused in previous file content
 ) -> impl IntoResponse {
-    // Note: signature in file was `delete_user` without Json payload in Step 147. Let me check.
-    // Step 147 shows `delete_user` has `State`, `Extension`, `Path`. NO Json.
-    // Check Step 147 Line 394.
-    // Wait, my replacement content has `Json(payload)`? NO.
-    // I need to match original function signature or reuse it.
-    // Let me check my replacement content above... I used `Json(payload)` in `delete_user` at the bottom?
-    // NO. Line 394 in my replacement matches Line 394 in file?
-    // Wait, my replacement chunk stops after `delete_user`.
-    // I need to be careful not to introduce syntax errors.
-    
-    // Actually, I can replace the chunk from 115 to 413, covering `list_tenants`, `update_tenant`, `delete_tenant`, `create_reseller`, `delete_user`.
-    // The usage of ? is spanning multiple functions.
-    // list_tenants (119): ?
-    // update_tenant (149): ?
-    // delete_tenant (201): ?
-    // delete_user (409): ?
-    
-    // I will use `replace_file_content` for the whole block from 115 to 413.
-    // Wait, `delete_user` is near end.
-    
-    // My previous replacement content was huge. I will verify matching context.
-    // It seems safe.
-    
-    // Let's re-verify delete_user signature.
-    // In file (Step 147):
-    /*
-    pub async fn delete_user(
-        State(pool): State<PgPool>,
-        Extension(claims): Extension<Claims>,
-        Path(id): Path<String>,
-    ) -> impl IntoResponse {
-    */
-    // In my replacement content:
-    /*
-    pub async fn delete_user(
-        State(pool): State<PgPool>,
-        Extension(claims): Extension<Claims>,
-        Path(id): Path<String>,
-    // ) -> impl IntoResponse { // I pasted Json(payload) by mistake in thought block?
-    // No, I'll check what I actually put in `ReplacementContent` arg.
-    
-    // I will double check the ReplacementContent I prepared.
-    */
-    // Implementation: I haven't submitted the tool call yet.
-
-    // I will target line 119 to 422 to cover all usages.
-    // That's a lot of lines.
-    // Maybe smaller chunks?
-    // list_tenants: 119-125
-    // update_tenant (reseller check): 149
-    // delete_tenant (reseller check): 201
-    // delete_user (tenant check): 409
-    
-    // I'll do 4 separate edits or 2.
-    // 1. list_tenants + update_tenant + delete_tenant (they are sequential).
-    // 2. delete_user (far below).
-    
-    // Chunk 1: Lines 119-219.
-    // Chunk 2: Line 409.
-    
-    // Wait, `list_tenants` is lines 115-136.
-    // `update_tenant` is 138-192.
-    // `delete_tenant` is 194-226.
-    
-    // I can do one `replace_file_content` for lines 119 to 219.
-    // And another for 409.
-    
-    // Or just use `multi_replace_file_content`.
-    
-    /*
-    Chunk 1:
-    StartLine: 119
-    EndLine: 125
-    
-    Chunk 2:
-    StartLine: 149
-    EndLine: 154
-    
-    Chunk 3:
-    StartLine: 201
-    EndLine: 206
-    
-    Chunk 4:
-    StartLine: 409
-    EndLine: 413
-    */
-    
-    // This is cleaner.
+    if claims.sub == id {
+        return (StatusCode::BAD_REQUEST, "Cannot delete your own user").into_response();
+    }
+
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start transaction: {}", e)).into_response(),
+    };
+
+    let before = match audit::snapshot(&mut *transaction, "users", &id).await {
+        Ok(Some(before)) => before,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete: {}", e)).into_response(),
+    };
+
+    let result = sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(&id)
+        .execute(&mut *transaction)
+        .await;
+
+    if let Err(e) = result {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete: {}", e)).into_response();
+    }
+
+    let tenant_id = before["tenant_id"].as_str().map(str::to_string);
+    if let Err(e) = audit.record(&mut *transaction, Event::new("user.delete", &id).tenant(tenant_id.as_deref()).before(Some(before))).await {
+        let _ = transaction.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)).into_response();
+    }
+
+    match transaction.commit().await {
+        Ok(_) => (StatusCode::OK, "User deleted").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit transaction: {}", e)).into_response(),
+    }
+}
+
+/// GET /admin/login-events
+/// Failed and blocked login attempts, newest first
+pub async fn list_login_events(
+    _: Require<perm::UsersManage>,
+    State(pool): State<PgPool>,
+    Query(params): Query<crate::models::LoginEventQuery>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let events = sqlx::query_as::<_, crate::models::LoginEvent>(
+        "SELECT id::text AS id, email, ip, user_id::text AS user_id, reason, created_at FROM login_events WHERE ($1::text IS NULL OR email = LOWER($1)) ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(&params.email)
+    .bind(limit)
+    .fetch_all(&pool)
+    .await;
+
+    match events {
+        Ok(events) => Json(events).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list login events: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list login events").into_response()
+        }
+    }
+}
+
+/// POST /admin/lockouts/unlock
+/// Lifts the login backoff of an account and/or an IP address
+pub async fn unlock_login(
+    _: Require<perm::UsersManage>,
+    State(pool): State<PgPool>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    audit: AuditContext,
+    Json(payload): Json<crate::models::UnlockRequest>,
+) -> impl IntoResponse {
+    if payload.email.is_none() && payload.ip.is_none() {
+        return (StatusCode::BAD_REQUEST, "Provide an email or an ip").into_response();
+    }
 
+    if let Some(email) = &payload.email {
+        throttle.reset(&lockout::account_key(email)).await;
+    }
+    if let Some(ip) = &payload.ip {
+        throttle.reset(&lockout::ip_key(ip.trim())).await;
+    }
+
+    let target = payload.email.as_deref().or(payload.ip.as_deref()).unwrap_or_default();
+    let after = serde_json::json!({ "email": payload.email, "ip": payload.ip });
+    audit.log(&pool, Event::new("auth.unlock", target).tenant(None).after(Some(after))).await;
+
+    (StatusCode::OK, "Unlocked").into_response()
+}
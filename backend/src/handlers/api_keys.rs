@@ -0,0 +1,248 @@
+//! API keys for integrations (e-commerce, ERP) that call the API without a
+//! human login.
+//!
+//! Keys are sent as `Authorization: Bearer pdv_...` or `X-API-Key: pdv_...`.
+//! A request made with a key acts as the user who created it, limited to
+//! the key's scopes; if that user loses a permission, their keys lose it too.
+
+use crate::auth::Claims;
+use crate::handlers::sessions::{generate_token, hash_token};
+use crate::models::{ApiKey, ApiKeyCreated, CreateApiKeyRequest};
+use crate::permissions::{Permission, Permissions, Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// Marks API keys apart from JWTs in the `Authorization` header.
+pub const KEY_PREFIX: &str = "pdv_";
+const MAX_EXPIRY_DAYS: i32 = 365 * 5;
+
+#[derive(FromRow)]
+struct KeyRow {
+    id: String,
+    tenant_id: String,
+    created_by: String,
+    role: String,
+    scopes: Vec<String>,
+}
+
+/// Resolves a key to the claims and permissions it acts with, or `None`
+/// when it is unknown, revoked or expired.
+pub async fn authenticate(pool: &PgPool, key: &str) -> Option<(Claims, Permissions)> {
+    let row = sqlx::query_as::<_, KeyRow>(
+        r#"
+        SELECT k.id::text AS id, k.tenant_id::text AS tenant_id, k.created_by::text AS created_by,
+               u.role, k.scopes
+        FROM api_keys k
+        JOIN users u ON u.id = k.created_by
+        WHERE k.key_hash = $1
+          AND k.revoked_at IS NULL
+          AND (k.expires_at IS NULL OR k.expires_at > CURRENT_TIMESTAMP)
+        "#,
+    )
+    .bind(hash_token(key))
+    .fetch_optional(pool)
+    .await
+    .ok()??;
+
+    // Written at most once a minute so busy integrations do not hammer the row
+    let _ = sqlx::query(
+        "UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < CURRENT_TIMESTAMP - INTERVAL '1 minute')",
+    )
+    .bind(&row.id)
+    .execute(pool)
+    .await;
+
+    let claims = Claims {
+        sub: row.created_by,
+        tenant_id: Some(row.tenant_id),
+        role: row.role,
+        sid: None,
+        exp: 0,
+    };
+    let scopes: Vec<Permission> = row
+        .scopes
+        .iter()
+        .filter_map(|s| Permission::parse(s))
+        .collect();
+    let permissions = Permissions::resolve(pool, &claims).await.restrict(&scopes);
+
+    Some((claims, permissions))
+}
+
+/// GET /api-keys/scopes
+pub async fn list_scopes(_: Require<perm::ApiKeysManage>) -> impl IntoResponse {
+    Json(
+        Permission::api_key_scopes()
+            .map(Permission::as_str)
+            .collect::<Vec<_>>(),
+    )
+}
+
+pub async fn list_api_keys(
+    _: Require<perm::ApiKeysManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let keys = sqlx::query_as::<_, ApiKey>(
+        "SELECT id::text, name, prefix, scopes, created_by::text, expires_at, last_used_at, revoked_at, created_at FROM api_keys WHERE tenant_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match keys {
+        Ok(keys) => Json(keys).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list API keys: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list API keys").into_response()
+        }
+    }
+}
+
+/// POST /api-keys
+/// The key is in the response only; it cannot be retrieved later
+pub async fn create_api_key(
+    _: Require<perm::ApiKeysManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Extension(permissions): Extension<Permissions>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let name = payload.name.trim().to_string();
+    if name.is_empty() || name.len() > 100 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Name must have 1 to 100 characters",
+        )
+            .into_response();
+    }
+
+    let mut scopes: Vec<String> = Vec::new();
+    for value in &payload.scopes {
+        let scope = match Permission::parse(value) {
+            Some(p) if Permission::api_key_scopes().any(|s| s == p) => p,
+            _ => {
+                return (StatusCode::BAD_REQUEST, format!("Invalid scope: {}", value))
+                    .into_response();
+            }
+        };
+        // A key can never do more than the person creating it
+        if !permissions.has(scope) {
+            return (
+                StatusCode::FORBIDDEN,
+                format!("Missing permission: {}", scope.as_str()),
+            )
+                .into_response();
+        }
+        if !scopes.iter().any(|s| s == scope.as_str()) {
+            scopes.push(scope.as_str().to_string());
+        }
+    }
+    if scopes.is_empty() {
+        return (StatusCode::BAD_REQUEST, "At least one scope is required").into_response();
+    }
+
+    if let Some(days) = payload.expires_in_days
+        && !(1..=MAX_EXPIRY_DAYS).contains(&days)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Expiry must be between 1 and {} days", MAX_EXPIRY_DAYS),
+        )
+            .into_response();
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let key = format!("{}{}", KEY_PREFIX, generate_token());
+    let prefix: String = key.chars().take(12).collect();
+
+    let expires_at: Result<Option<chrono::NaiveDateTime>, sqlx::Error> = sqlx::query_scalar(
+        r#"
+        INSERT INTO api_keys (id, tenant_id, created_by, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, CURRENT_TIMESTAMP + make_interval(days => $8))
+        RETURNING expires_at
+        "#,
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .bind(&claims.sub)
+    .bind(&name)
+    .bind(&prefix)
+    .bind(hash_token(&key))
+    .bind(&scopes)
+    .bind(payload.expires_in_days)
+    .fetch_one(&mut *tx)
+    .await;
+    let expires_at = tx.commit_if_ok(expires_at).await;
+
+    match expires_at {
+        Ok(expires_at) => (
+            StatusCode::CREATED,
+            Json(ApiKeyCreated {
+                id,
+                name,
+                key,
+                scopes,
+                expires_at,
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create API key: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// DELETE /api-keys/{id}
+/// Revoked keys stop working on their next request
+pub async fn revoke_api_key(
+    _: Require<perm::ApiKeysManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query(
+        "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND tenant_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "API key not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "API key revoked").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,134 @@
+use crate::auth::Claims;
+use crate::models::{AuditEntry, AuditLogQuery};
+use crate::permissions::{Permission, Permissions, Require, perm};
+use crate::tenant_db;
+use axum::{
+    Json,
+    extract::{Extension, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct AuditLogPage {
+    pub items: Vec<AuditEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+fn parse_id(value: Option<&str>, name: &str) -> Result<Option<Uuid>, String> {
+    value
+        .map(|v| Uuid::parse_str(v.trim()).map_err(|_| format!("Invalid {}", name)))
+        .transpose()
+}
+
+/// GET /audit-log?action=&entity_type=&entity_id=&actor_id=&from=&to=&page=1&per_page=50
+/// Newest first. Platform admins (`users.manage`) see every tenant (or one,
+/// with `tenant_id`); everyone else sees their own tenant only
+pub async fn list_audit_log(
+    _: Require<perm::AuditView>,
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    Extension(permissions): Extension<Permissions>,
+    Query(params): Query<AuditLogQuery>,
+) -> impl IntoResponse {
+    // Tenants read through their row-level security policies
+    let (tenant_id, tx) = if permissions.has(Permission::UsersManage) {
+        (params.tenant_id.as_deref(), pool.begin().await)
+    } else {
+        match claims.tenant_id.as_deref() {
+            Some(id) => (Some(id), tenant_db::begin(&pool, id).await),
+            None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+        }
+    };
+    let mut tx = match tx {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+    let tenant_id = match parse_id(tenant_id, "tenant_id") {
+        Ok(id) => id,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    let actor_id = match parse_id(params.actor_id.as_deref(), "actor_id") {
+        Ok(id) => id,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(50).clamp(1, 200);
+
+    let filter = r#"
+        WHERE ($1::uuid IS NULL OR a.tenant_id = $1)
+          AND ($2::uuid IS NULL OR a.actor_id = $2)
+          AND ($3::text IS NULL OR a.action = $3)
+          AND ($4::text IS NULL OR a.entity_type = $4)
+          AND ($5::text IS NULL OR a.entity_id = $5)
+          AND ($6::date IS NULL OR a.created_at >= $6)
+          AND ($7::date IS NULL OR a.created_at < $7 + 1)
+    "#;
+
+    let total: Result<i64, _> =
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM audit_log a {}", filter))
+            .bind(tenant_id)
+            .bind(actor_id)
+            .bind(&params.action)
+            .bind(&params.entity_type)
+            .bind(&params.entity_id)
+            .bind(params.from)
+            .bind(params.to)
+            .fetch_one(&mut *tx)
+            .await;
+
+    let entries = sqlx::query_as::<_, AuditEntry>(&format!(
+        r#"
+        SELECT a.id::text AS id, a.tenant_id::text AS tenant_id, a.actor_id::text AS actor_id,
+               u.email AS actor_email, a.actor_role, a.ip, a.action, a.entity_type, a.entity_id,
+               a.before, a.after, a.created_at
+        FROM audit_log a
+        LEFT JOIN users u ON u.id = a.actor_id
+        {}
+        ORDER BY a.created_at DESC
+        LIMIT $8 OFFSET $9
+        "#,
+        filter
+    ))
+    .bind(tenant_id)
+    .bind(actor_id)
+    .bind(&params.action)
+    .bind(&params.entity_type)
+    .bind(&params.entity_id)
+    .bind(params.from)
+    .bind(params.to)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match (total, entries) {
+        (Ok(total), Ok(items)) => Json(AuditLogPage {
+            items,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Failed to list audit log: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list audit log",
+            )
+                .into_response()
+        }
+    }
+}
@@ -1,13 +1,165 @@
+use crate::audit::{AuditContext, Event};
 use crate::auth;
+use crate::handlers::accounts::{self, MIN_PASSWORD_LENGTH};
+use crate::handlers::{sessions, two_factor};
+use crate::jwt::{ACCESS_TOKEN_TTL, JwtKeys};
+use crate::lockout::{self, FREE_ATTEMPTS_PER_ACCOUNT, FREE_ATTEMPTS_PER_IP, LoginThrottle};
+use crate::mailer::Mailer;
 use crate::models::{AuthResponse, CreateUserRequest, LoginRequest, User};
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    extract::{ConnectInfo, Extension, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
 use sqlx::PgPool;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use uuid::Uuid;
 
+pub const USER_COLUMNS: &str = "id::text AS id, email, password_hash, role, tenant_id::text AS tenant_id, email_verified_at, created_at";
+
+/// Client address, taken from `X-Forwarded-For` only when running behind a
+/// trusted proxy (`TRUST_PROXY_HEADERS=true`), since clients can forge it.
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
+    if env::var("TRUST_PROXY_HEADERS").as_deref() == Ok("true")
+        && let Some(ip) = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    {
+        return ip.to_string();
+    }
+    addr.ip().to_string()
+}
+
+pub async fn record_failed_login(
+    pool: &PgPool,
+    email: &str,
+    ip: &str,
+    user_id: Option<&str>,
+    reason: &str,
+) {
+    let result = sqlx::query(
+        "INSERT INTO login_events (id, email, ip, user_id, reason) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(email.trim().to_lowercase())
+    .bind(ip)
+    .bind(user_id)
+    .bind(reason)
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        eprintln!("Failed to record login event: {}", e);
+    }
+}
+
+/// Opens a session for an authenticated user and builds the login response.
+/// `method` (password, two_factor, sso, pin) goes into the audit log.
+#[allow(clippy::too_many_arguments)]
+pub async fn complete_login(
+    pool: &PgPool,
+    keys: &JwtKeys,
+    user: User,
+    audit: &AuditContext,
+    method: &str,
+    user_agent: Option<&str>,
+    terminal_id: Option<&str>,
+    recovery_codes: Option<Vec<String>>,
+) -> Response {
+    // Fetch tenant info to get business_type
+    let business_type = if let Some(ref tenant_id_str) = user.tenant_id {
+        let tenant_uuid = match Uuid::parse_str(tenant_id_str) {
+            Ok(uuid) => uuid,
+            Err(_) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Invalid tenant ID").into_response();
+            }
+        };
+
+        let tenant: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT business_type FROM tenants WHERE id = $1")
+                .bind(tenant_uuid)
+                .fetch_optional(pool)
+                .await
+                .unwrap_or(None);
+
+        tenant.and_then(|(bt,)| bt)
+    } else {
+        None
+    };
+
+    let (session_id, refresh_token) =
+        match sessions::create_session(pool, &user.id, user_agent, terminal_id).await {
+            Ok(session) => session,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to create session: {}", e),
+                )
+                    .into_response();
+            }
+        };
+
+    let after = serde_json::json!({
+        "method": method,
+        "session_id": session_id,
+        "terminal_id": terminal_id,
+        "user_agent": user_agent,
+    });
+    audit
+        .as_user(&user)
+        .log(pool, Event::new("auth.login", &user.id).after(Some(after)))
+        .await;
+
+    let token =
+        match keys.create_token(&user.id, user.tenant_id.as_deref(), &user.role, &session_id) {
+            Ok(t) => t,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to generate token",
+                )
+                    .into_response();
+            }
+        };
+
+    (
+        StatusCode::OK,
+        Json(AuthResponse {
+            token,
+            refresh_token,
+            expires_in: ACCESS_TOKEN_TTL,
+            role: user.role,
+            business_type,
+            email: user.email,
+            name: None,
+            recovery_codes,
+        }),
+    )
+        .into_response()
+}
+
 pub async fn register(
     State(pool): State<PgPool>,
+    Extension(mailer): Extension<Arc<Mailer>>,
     Json(payload): Json<CreateUserRequest>,
 ) -> impl IntoResponse {
+    if payload.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Password must have at least {} characters",
+                MIN_PASSWORD_LENGTH
+            ),
+        )
+            .into_response();
+    }
+
     let password_hash = match auth::hash_password(&payload.password) {
         Ok(hash) => hash,
         Err(_) => {
@@ -19,18 +171,31 @@ pub async fn register(
     let tenant_id = Uuid::new_v4();
 
     let result = sqlx::query(
-        "INSERT INTO users (id, email, password_hash, role, tenant_id) VALUES ($1, $2, $3, $4, $5)",
+        "INSERT INTO users (id, email, password_hash, role, tenant_id, email_verified_at) VALUES ($1, $2, $3, $4, $5, NULL)",
     )
     .bind(user_id)
     .bind(&payload.email)
     .bind(&password_hash)
-    .bind(payload.role.unwrap_or_else(|| "user".to_string()))
+    .bind("user") // Self sign-up always creates a shop owner, never a platform role
     .bind(tenant_id)
     .execute(&pool)
     .await;
 
     match result {
-        Ok(_) => (StatusCode::CREATED, "User created successfully").into_response(),
+        Ok(_) => {
+            // The account exists either way; the user can ask for a new link
+            if let Err(e) = accounts::send_verification_email(
+                &pool,
+                &mailer,
+                &user_id.to_string(),
+                &payload.email,
+            )
+            .await
+            {
+                eprintln!("Failed to issue verification token: {}", e);
+            }
+            (StatusCode::CREATED, "User created successfully").into_response()
+        }
         Err(e) => {
             // PostgreSQL unique constraint error code is 23505
             if e.to_string().contains("23505") || e.to_string().contains("duplicate key") {
@@ -48,11 +213,36 @@ pub async fn register(
 
 pub async fn login(
     State(pool): State<PgPool>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    audit: AuditContext,
     Json(payload): Json<LoginRequest>,
 ) -> impl IntoResponse {
-    let user = sqlx::query_as::<_, User>(
-        "SELECT id::text, email, password_hash, role, tenant_id::text as tenant_id, created_at FROM users WHERE email = $1"
-    )
+    let ip = client_ip(&headers, addr);
+    let account_key = lockout::account_key(&payload.email);
+    let ip_key = lockout::ip_key(&ip);
+
+    // Blocked keys are rejected before the password is even checked
+    let wait = throttle
+        .retry_after(&account_key)
+        .await
+        .max(throttle.retry_after(&ip_key).await);
+    if let Some(wait) = wait {
+        record_failed_login(&pool, &payload.email, &ip, None, "locked").await;
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, wait.to_string())],
+            "Too many failed login attempts, try again later",
+        )
+            .into_response();
+    }
+
+    let user = sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM users WHERE email = $1",
+        USER_COLUMNS
+    ))
     .bind(&payload.email)
     .fetch_optional(&pool)
     .await;
@@ -60,60 +250,50 @@ pub async fn login(
     match user {
         Ok(Some(user)) => {
             if auth::verify_password(&user.password_hash, &payload.password) {
-                // Fetch tenant info to get business_type
-                let business_type = if let Some(ref tenant_id_str) = user.tenant_id {
-                    let tenant_uuid = match Uuid::parse_str(tenant_id_str) {
-                        Ok(uuid) => uuid,
-                        Err(_) => {
-                            return (StatusCode::INTERNAL_SERVER_ERROR, "Invalid tenant ID")
-                                .into_response();
-                        }
-                    };
-
-                    let tenant: Option<(Option<String>,)> =
-                        sqlx::query_as("SELECT business_type FROM tenants WHERE id = $1")
-                            .bind(tenant_uuid)
-                            .fetch_optional(&pool)
-                            .await
-                            .unwrap_or(None);
-
-                    tenant.and_then(|(bt,)| bt)
-                } else {
-                    None
-                };
-
-                let token = match auth::create_jwt(
-                    &user.id,
-                    user.tenant_id.as_deref(),
-                    &user.role,
-                    b"secret",
-                ) {
-                    Ok(t) => t,
-                    Err(_) => {
+                throttle.reset(&account_key).await;
+
+                if user.email_verified_at.is_none() && accounts::verification_required() {
+                    return (StatusCode::FORBIDDEN, "Email not verified").into_response();
+                }
+
+                match two_factor::login_challenge(&pool, &user).await {
+                    Ok(Some(challenge)) => return Json(challenge).into_response(),
+                    Ok(None) => {}
+                    Err(e) => {
                         return (
                             StatusCode::INTERNAL_SERVER_ERROR,
-                            "Failed to generate token",
+                            format!("Database error: {}", e),
                         )
                             .into_response();
                     }
-                };
+                }
 
-                (
-                    StatusCode::OK,
-                    Json(AuthResponse {
-                        token,
-                        role: user.role,
-                        business_type,
-                        email: user.email,
-                        name: None,
-                    }),
+                let user_agent = headers
+                    .get(header::USER_AGENT)
+                    .and_then(|v| v.to_str().ok());
+                complete_login(
+                    &pool, &keys, user, &audit, "password", user_agent, None, None,
                 )
-                    .into_response()
+                .await
             } else {
+                throttle
+                    .record_failure(&account_key, FREE_ATTEMPTS_PER_ACCOUNT)
+                    .await;
+                throttle.record_failure(&ip_key, FREE_ATTEMPTS_PER_IP).await;
+                record_failed_login(&pool, &payload.email, &ip, Some(&user.id), "bad_password")
+                    .await;
                 (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response()
             }
         }
-        Ok(None) => (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response(),
+        Ok(None) => {
+            // Unknown emails count too, so lockouts do not reveal which accounts exist
+            throttle
+                .record_failure(&account_key, FREE_ATTEMPTS_PER_ACCOUNT)
+                .await;
+            throttle.record_failure(&ip_key, FREE_ATTEMPTS_PER_IP).await;
+            record_failed_login(&pool, &payload.email, &ip, None, "unknown_user").await;
+            (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response()
+        }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Database error: {}", e),
@@ -121,3 +301,9 @@ pub async fn login(
             .into_response(),
     }
 }
+
+/// GET /.well-known/jwks.json
+/// Public keys other services can use to verify our tokens
+pub async fn jwks(Extension(keys): Extension<Arc<JwtKeys>>) -> impl IntoResponse {
+    Json(keys.jwks())
+}
@@ -0,0 +1,415 @@
+use crate::audit::{self, AuditContext, Event};
+use crate::auth::Claims;
+use crate::billing::{self, ChargeAttempt};
+use crate::models::{
+    BillingOverview, Invoice, InvoiceDetail, InvoiceLine, Payment, RecordPaymentRequest,
+    Subscription, UpdateSubscriptionRequest,
+};
+use crate::payments::PaymentGateway;
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use crate::tenant_status::TenantPolicy;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+
+const INVOICE_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, status, amount, period_start, period_end, due_date, attempt_count, next_attempt_at, paid_at, created_at";
+
+async fn overview(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+) -> Result<BillingOverview, sqlx::Error> {
+    let subscription = sqlx::query_as::<_, Subscription>(
+        r#"
+        SELECT s.id::text AS id, s.tenant_id::text AS tenant_id, s.plan_id::text AS plan_id, p.name AS plan_name,
+               s.billing_cycle, s.status, s.trial_ends_at, s.current_period_start, s.current_period_end,
+               s.cancel_at_period_end, s.payment_method_token IS NOT NULL AS has_payment_method,
+               s.credit_balance, s.created_at
+        FROM subscriptions s JOIN plans p ON p.id = s.plan_id
+        WHERE s.tenant_id = $1::uuid
+        "#,
+    )
+    .bind(tenant_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let invoices = sqlx::query_as::<_, Invoice>(&format!(
+        "SELECT {} FROM invoices WHERE tenant_id = $1::uuid ORDER BY created_at DESC",
+        INVOICE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(BillingOverview {
+        subscription,
+        invoices,
+    })
+}
+
+/// `tenant_id` limits the lookup to one tenant's invoices.
+async fn invoice_detail(
+    conn: &mut PgConnection,
+    id: &str,
+    tenant_id: Option<&str>,
+) -> Result<Option<InvoiceDetail>, sqlx::Error> {
+    let invoice = sqlx::query_as::<_, Invoice>(&format!(
+        "SELECT {} FROM invoices WHERE id = $1::uuid AND ($2::uuid IS NULL OR tenant_id = $2::uuid)",
+        INVOICE_COLUMNS
+    ))
+    .bind(id)
+    .bind(tenant_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(invoice) = invoice else {
+        return Ok(None);
+    };
+
+    let lines = sqlx::query_as::<_, InvoiceLine>(
+        "SELECT description, amount FROM invoice_lines WHERE invoice_id = $1::uuid ORDER BY created_at, amount",
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let payments = sqlx::query_as::<_, Payment>(
+        r#"
+        SELECT id::text AS id, amount, status, method, reference, failure_reason, created_at
+        FROM payments WHERE invoice_id = $1::uuid ORDER BY created_at
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(Some(InvoiceDetail {
+        invoice,
+        lines,
+        payments,
+    }))
+}
+
+fn database_error(e: sqlx::Error) -> axum::response::Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+        .into_response()
+}
+
+fn invoice_response(
+    result: Result<Option<InvoiceDetail>, sqlx::Error>,
+) -> axum::response::Response {
+    match result {
+        Ok(Some(detail)) => Json(detail).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Invoice not found").into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /billing
+/// The store's subscription and its invoices, newest first
+pub async fn get_billing(
+    _: Require<perm::BillingView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    match overview(&mut tx, &tenant_id).await {
+        Ok(overview) => Json(overview).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /billing/invoices/{id}
+pub async fn get_invoice(
+    _: Require<perm::BillingView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    invoice_response(invoice_detail(&mut tx, &id, Some(&tenant_id)).await)
+}
+
+/// GET /admin/tenants/{id}/billing
+pub async fn get_tenant_billing(
+    _: Require<perm::BillingManage>,
+    State(pool): State<PgPool>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let mut conn = match pool.acquire().await {
+        Ok(conn) => conn,
+        Err(e) => return database_error(e),
+    };
+
+    match overview(&mut conn, &id).await {
+        Ok(overview) => Json(overview).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// PUT /admin/tenants/{id}/subscription
+/// Starts the tenant's subscription (on `plan_id` or the tenant's plan, with
+/// a trial unless `trial` is false) or updates its billing cycle, card and
+/// cancellation. A new cycle applies from the next period; plan changes go
+/// through PUT /admin/tenants/{id}, which prorates them
+pub async fn update_subscription(
+    _: Require<perm::BillingManage>,
+    State(pool): State<PgPool>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateSubscriptionRequest>,
+) -> impl IntoResponse {
+    if let Some(cycle) = payload.billing_cycle.as_deref()
+        && !billing::CYCLES.contains(&cycle)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            "billing_cycle must be monthly or yearly",
+        )
+            .into_response();
+    }
+
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return database_error(e),
+    };
+
+    let tenant: Option<(Option<String>, Option<String>)> = match sqlx::query_as(
+        r#"
+        SELECT t.plan_id::text, s.id::text
+        FROM tenants t LEFT JOIN subscriptions s ON s.tenant_id = t.id
+        WHERE t.id = $1::uuid
+        FOR UPDATE OF t
+        "#,
+    )
+    .bind(&id)
+    .fetch_optional(&mut *transaction)
+    .await
+    {
+        Ok(tenant) => tenant,
+        Err(e) => return database_error(e),
+    };
+    let Some((tenant_plan, subscription_id)) = tenant else {
+        return (StatusCode::NOT_FOUND, "Tenant not found").into_response();
+    };
+
+    let (subscription_id, before) = match subscription_id {
+        Some(subscription_id) => {
+            if payload.plan_id.is_some() {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "Change the plan through the tenant",
+                )
+                    .into_response();
+            }
+            let before = audit::snapshot(&mut *transaction, "subscriptions", &subscription_id)
+                .await
+                .unwrap_or(None);
+            let updated = sqlx::query(
+                r#"
+                UPDATE subscriptions SET
+                    billing_cycle = COALESCE($2, billing_cycle),
+                    payment_method_token = COALESCE($3, payment_method_token),
+                    cancel_at_period_end = COALESCE($4, cancel_at_period_end),
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = $1::uuid
+                "#,
+            )
+            .bind(&subscription_id)
+            .bind(&payload.billing_cycle)
+            .bind(&payload.payment_method_token)
+            .bind(payload.cancel_at_period_end)
+            .execute(&mut *transaction)
+            .await;
+            if let Err(e) = updated {
+                return database_error(e);
+            }
+            (subscription_id, before)
+        }
+        None => {
+            let Some(plan_id) = payload.plan_id.or(tenant_plan) else {
+                return (StatusCode::BAD_REQUEST, "The tenant has no plan").into_response();
+            };
+            // The tenant follows the plan it is billed for
+            if let Err(e) = sqlx::query("UPDATE tenants SET plan_id = $1::uuid WHERE id = $2::uuid")
+                .bind(&plan_id)
+                .bind(&id)
+                .execute(&mut *transaction)
+                .await
+            {
+                return (StatusCode::BAD_REQUEST, format!("Invalid plan: {}", e)).into_response();
+            }
+            let started = billing::start_subscription(
+                &mut transaction,
+                &id,
+                &plan_id,
+                payload.billing_cycle.as_deref().unwrap_or(billing::MONTHLY),
+                payload.payment_method_token.as_deref(),
+                payload.trial.unwrap_or(true),
+            )
+            .await;
+            let subscription_id = match started {
+                Ok(subscription_id) => subscription_id,
+                Err(e) => return database_error(e),
+            };
+            if payload.cancel_at_period_end == Some(true)
+                && let Err(e) = sqlx::query(
+                    "UPDATE subscriptions SET cancel_at_period_end = TRUE WHERE id = $1::uuid",
+                )
+                .bind(&subscription_id)
+                .execute(&mut *transaction)
+                .await
+            {
+                return database_error(e);
+            }
+            (subscription_id, None)
+        }
+    };
+
+    let after = audit::snapshot(&mut *transaction, "subscriptions", &subscription_id)
+        .await
+        .unwrap_or(None);
+    let action = if before.is_some() {
+        "subscription.update"
+    } else {
+        "subscription.create"
+    };
+    let event = Event::new(action, &subscription_id)
+        .tenant(Some(&id))
+        .before(before)
+        .after(after);
+    if let Err(e) = audit.record(&mut *transaction, event).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    let overview = match overview(&mut transaction, &id).await {
+        Ok(overview) => overview,
+        Err(e) => return database_error(e),
+    };
+    match transaction.commit().await {
+        Ok(_) => Json(overview).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /admin/invoices/{id}
+pub async fn get_any_invoice(
+    _: Require<perm::BillingManage>,
+    State(pool): State<PgPool>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match pool.acquire().await {
+        Ok(mut conn) => invoice_response(invoice_detail(&mut conn, &id, None).await),
+        Err(e) => database_error(e),
+    }
+}
+
+/// POST /admin/invoices/{id}/payments
+/// Records a payment made outside the gateway (bank transfer, bank slip,
+/// ...). The invoice is paid once payments cover its amount
+pub async fn record_payment(
+    _: Require<perm::BillingManage>,
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+    Json(payload): Json<RecordPaymentRequest>,
+) -> impl IntoResponse {
+    let method = payload.method.trim();
+    if payload.amount <= 0 || method.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "amount must be positive and method is required",
+        )
+            .into_response();
+    }
+
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return database_error(e),
+    };
+    let recorded = billing::record_payment(
+        &mut transaction,
+        &audit,
+        &id,
+        payload.amount,
+        method,
+        payload.reference.as_deref(),
+        &claims.sub,
+    )
+    .await;
+
+    match recorded {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::CONFLICT, "Invoice not found or not open").into_response(),
+        Err(e) => return database_error(e),
+    }
+    if let Err(e) = transaction.commit().await {
+        return database_error(e);
+    }
+
+    match pool.acquire().await {
+        Ok(mut conn) => {
+            let detail = invoice_detail(&mut conn, &id, None).await;
+            match detail {
+                Ok(Some(detail)) => (StatusCode::CREATED, Json(detail)).into_response(),
+                other => invoice_response(other),
+            }
+        }
+        Err(e) => database_error(e),
+    }
+}
+
+/// POST /admin/invoices/{id}/charge
+/// Charges the invoice to the tenant's card now, outside the retry schedule
+pub async fn charge_invoice(
+    _: Require<perm::BillingManage>,
+    State(pool): State<PgPool>,
+    Extension(gateway): Extension<Arc<dyn PaymentGateway>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match billing::charge_invoice(&pool, gateway.as_ref(), &id).await {
+        Ok(ChargeAttempt::NotOpen) => {
+            (StatusCode::CONFLICT, "Invoice not found or not open").into_response()
+        }
+        Ok(attempt) => Json(attempt).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// POST /admin/billing/run
+/// Runs the billing cycle now instead of waiting for the hourly sweep
+pub async fn run_billing(
+    _: Require<perm::BillingManage>,
+    State(pool): State<PgPool>,
+    Extension(gateway): Extension<Arc<dyn PaymentGateway>>,
+    Extension(policy): Extension<Arc<TenantPolicy>>,
+) -> impl IntoResponse {
+    match billing::run_billing_cycle(&pool, gateway.as_ref(), &policy).await {
+        Ok(run) => Json(run).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Billing run failed: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,359 @@
+use crate::audit::{self, AuditContext, Event};
+use crate::auth::Claims;
+use crate::commissions;
+use crate::models::{
+    CommissionEntry, CommissionRule, EarningsTotals, MonthlyEarnings, PayPayoutRequest, Payout,
+    PayoutDetail, PayoutQuery, ResellerEarnings, UpdateCommissionRuleRequest,
+};
+use crate::permissions::{Require, perm};
+use axum::{
+    Json,
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sqlx::PgPool;
+
+const RECENT_ENTRIES: i64 = 50;
+
+const ENTRY_COLUMNS: &str = "c.id::text AS id, c.tenant_id::text AS tenant_id, t.name AS tenant_name, c.invoice_id::text AS invoice_id, c.kind, c.base_amount, c.amount, c.description, c.earned_on, c.payout_id::text AS payout_id";
+
+const PAYOUT_COLUMNS: &str = "p.id::text AS id, p.reseller_id::text AS reseller_id, u.email AS reseller_email, p.period_start, p.period_end, p.amount, p.status, p.reference, p.paid_at, p.created_at";
+
+async fn earnings(pool: &PgPool, reseller_id: &str) -> Result<ResellerEarnings, sqlx::Error> {
+    let rule = sqlx::query_as::<_, CommissionRule>(
+        "SELECT rate_bps, activation_bonus, updated_at FROM commission_rules WHERE reseller_id = $1::uuid",
+    )
+    .bind(reseller_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let totals = sqlx::query_as::<_, EarningsTotals>(
+        r#"
+        SELECT
+            COALESCE(SUM(c.amount), 0)::BIGINT AS earned,
+            COALESCE(SUM(c.amount) FILTER (WHERE c.payout_id IS NULL), 0)::BIGINT AS unsettled,
+            COALESCE(SUM(c.amount) FILTER (WHERE p.status = $2), 0)::BIGINT AS pending,
+            COALESCE(SUM(c.amount) FILTER (WHERE p.status = $3), 0)::BIGINT AS paid,
+            (SELECT COUNT(*) FROM tenants WHERE reseller_id = $1::uuid) AS stores,
+            (SELECT COUNT(*) FROM tenants t JOIN subscriptions s ON s.tenant_id = t.id
+             WHERE t.reseller_id = $1::uuid AND s.status = 'active') AS paying_stores
+        FROM commissions c
+        LEFT JOIN payouts p ON p.id = c.payout_id
+        WHERE c.reseller_id = $1::uuid
+        "#,
+    )
+    .bind(reseller_id)
+    .bind(commissions::PENDING)
+    .bind(commissions::PAID)
+    .fetch_one(pool)
+    .await?;
+
+    let months = sqlx::query_as::<_, MonthlyEarnings>(
+        r#"
+        SELECT date_trunc('month', earned_on)::date AS month,
+               COALESCE(SUM(amount) FILTER (WHERE kind = $2), 0)::BIGINT AS subscription,
+               COALESCE(SUM(amount) FILTER (WHERE kind = $3), 0)::BIGINT AS activation,
+               SUM(amount)::BIGINT AS total
+        FROM commissions
+        WHERE reseller_id = $1::uuid AND earned_on >= date_trunc('month', CURRENT_DATE) - INTERVAL '11 months'
+        GROUP BY 1
+        ORDER BY 1 DESC
+        "#,
+    )
+    .bind(reseller_id)
+    .bind(commissions::SUBSCRIPTION)
+    .bind(commissions::ACTIVATION)
+    .fetch_all(pool)
+    .await?;
+
+    let recent = sqlx::query_as::<_, CommissionEntry>(&format!(
+        r#"
+        SELECT {} FROM commissions c LEFT JOIN tenants t ON t.id = c.tenant_id
+        WHERE c.reseller_id = $1::uuid
+        ORDER BY c.earned_on DESC, c.created_at DESC
+        LIMIT $2
+        "#,
+        ENTRY_COLUMNS
+    ))
+    .bind(reseller_id)
+    .bind(RECENT_ENTRIES)
+    .fetch_all(pool)
+    .await?;
+
+    let payouts = sqlx::query_as::<_, Payout>(&format!(
+        "SELECT {} FROM payouts p JOIN users u ON u.id = p.reseller_id WHERE p.reseller_id = $1::uuid ORDER BY p.period_start DESC",
+        PAYOUT_COLUMNS
+    ))
+    .bind(reseller_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ResellerEarnings {
+        rule,
+        totals,
+        months,
+        recent,
+        payouts,
+    })
+}
+
+/// `reseller_id` limits the lookup to one reseller's statements.
+async fn payout_detail(
+    pool: &PgPool,
+    id: &str,
+    reseller_id: Option<&str>,
+) -> Result<Option<PayoutDetail>, sqlx::Error> {
+    let payout = sqlx::query_as::<_, Payout>(&format!(
+        "SELECT {} FROM payouts p JOIN users u ON u.id = p.reseller_id WHERE p.id = $1::uuid AND ($2::uuid IS NULL OR p.reseller_id = $2::uuid)",
+        PAYOUT_COLUMNS
+    ))
+    .bind(id)
+    .bind(reseller_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some(payout) = payout else {
+        return Ok(None);
+    };
+
+    let entries = sqlx::query_as::<_, CommissionEntry>(&format!(
+        "SELECT {} FROM commissions c LEFT JOIN tenants t ON t.id = c.tenant_id WHERE c.payout_id = $1::uuid ORDER BY c.earned_on, c.created_at",
+        ENTRY_COLUMNS
+    ))
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(PayoutDetail { payout, entries }))
+}
+
+fn database_error(e: sqlx::Error) -> axum::response::Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+        .into_response()
+}
+
+fn earnings_response(result: Result<ResellerEarnings, sqlx::Error>) -> axum::response::Response {
+    match result {
+        Ok(earnings) => Json(earnings).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+fn payout_response(result: Result<Option<PayoutDetail>, sqlx::Error>) -> axum::response::Response {
+    match result {
+        Ok(Some(detail)) => Json(detail).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Payout not found").into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /reseller/earnings
+/// The caller's commission rule, totals, last 12 months, latest ledger
+/// entries and payout statements
+pub async fn get_earnings(
+    _: Require<perm::EarningsView>,
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    earnings_response(earnings(&pool, &claims.sub).await)
+}
+
+/// GET /reseller/payouts/{id}
+/// A payout statement with the commissions it settles
+pub async fn get_payout(
+    _: Require<perm::EarningsView>,
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    payout_response(payout_detail(&pool, &id, Some(&claims.sub)).await)
+}
+
+/// GET /admin/resellers/{id}/earnings
+pub async fn get_reseller_earnings(
+    _: Require<perm::ResellersManage>,
+    State(pool): State<PgPool>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    earnings_response(earnings(&pool, &id).await)
+}
+
+/// PUT /admin/resellers/{id}/commission
+/// Sets the reseller's rate (basis points: 1500 = 15%) and activation
+/// bonus (cents). Applies to invoices paid from now on
+pub async fn update_commission_rule(
+    _: Require<perm::ResellersManage>,
+    State(pool): State<PgPool>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateCommissionRuleRequest>,
+) -> impl IntoResponse {
+    if !(0..=10_000).contains(&payload.rate_bps) || payload.activation_bonus < 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "rate_bps must be between 0 and 10000 and activation_bonus not negative",
+        )
+            .into_response();
+    }
+
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return database_error(e),
+    };
+
+    let reseller: Option<Option<String>> = match sqlx::query_scalar(
+        r#"
+        SELECT r.id::text FROM users u LEFT JOIN commission_rules r ON r.reseller_id = u.id
+        WHERE u.id = $1::uuid AND u.role = 'reseller'
+        "#,
+    )
+    .bind(&id)
+    .fetch_optional(&mut *transaction)
+    .await
+    {
+        Ok(reseller) => reseller,
+        Err(e) => return database_error(e),
+    };
+    let Some(rule_id) = reseller else {
+        return (StatusCode::NOT_FOUND, "Reseller not found").into_response();
+    };
+    let before = match &rule_id {
+        Some(rule_id) => audit::snapshot(&mut *transaction, "commission_rules", rule_id)
+            .await
+            .unwrap_or(None),
+        None => None,
+    };
+
+    let rule_id: String = match sqlx::query_scalar(
+        r#"
+        INSERT INTO commission_rules (reseller_id, rate_bps, activation_bonus)
+        VALUES ($1::uuid, $2, $3)
+        ON CONFLICT (reseller_id) DO UPDATE
+        SET rate_bps = EXCLUDED.rate_bps, activation_bonus = EXCLUDED.activation_bonus, updated_at = CURRENT_TIMESTAMP
+        RETURNING id::text
+        "#,
+    )
+    .bind(&id)
+    .bind(payload.rate_bps)
+    .bind(payload.activation_bonus)
+    .fetch_one(&mut *transaction)
+    .await
+    {
+        Ok(rule_id) => rule_id,
+        Err(e) => return database_error(e),
+    };
+
+    let after = audit::snapshot(&mut *transaction, "commission_rules", &rule_id)
+        .await
+        .unwrap_or(None);
+    let event = Event::new("reseller.commission", &id)
+        .tenant(None)
+        .before(before)
+        .after(after);
+    if let Err(e) = audit.record(&mut *transaction, event).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    match transaction.commit().await {
+        Ok(_) => (StatusCode::OK, "Commission rule updated").into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /admin/payouts?reseller_id=&status=
+/// Payout statements, newest month first
+pub async fn list_payouts(
+    _: Require<perm::ResellersManage>,
+    State(pool): State<PgPool>,
+    Query(params): Query<PayoutQuery>,
+) -> impl IntoResponse {
+    let payouts = sqlx::query_as::<_, Payout>(&format!(
+        r#"
+        SELECT {} FROM payouts p JOIN users u ON u.id = p.reseller_id
+        WHERE ($1::uuid IS NULL OR p.reseller_id = $1::uuid) AND ($2::text IS NULL OR p.status = $2)
+        ORDER BY p.period_start DESC, u.email
+        "#,
+        PAYOUT_COLUMNS
+    ))
+    .bind(&params.reseller_id)
+    .bind(&params.status)
+    .fetch_all(&pool)
+    .await;
+
+    match payouts {
+        Ok(payouts) => Json(payouts).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /admin/payouts/{id}
+pub async fn get_any_payout(
+    _: Require<perm::ResellersManage>,
+    State(pool): State<PgPool>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    payout_response(payout_detail(&pool, &id, None).await)
+}
+
+/// POST /admin/payouts/{id}/pay
+/// Records that the statement's amount was sent to the reseller
+pub async fn pay_payout(
+    _: Require<perm::ResellersManage>,
+    State(pool): State<PgPool>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+    Json(payload): Json<PayPayoutRequest>,
+) -> impl IntoResponse {
+    let mut transaction = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return database_error(e),
+    };
+
+    let paid: Option<(i64, String)> = match sqlx::query_as(
+        r#"
+        UPDATE payouts SET status = $1, reference = $2, paid_at = CURRENT_TIMESTAMP
+        WHERE id = $3::uuid AND status = $4
+        RETURNING amount, reseller_id::text
+        "#,
+    )
+    .bind(commissions::PAID)
+    .bind(&payload.reference)
+    .bind(&id)
+    .bind(commissions::PENDING)
+    .fetch_optional(&mut *transaction)
+    .await
+    {
+        Ok(paid) => paid,
+        Err(e) => return database_error(e),
+    };
+    let Some((amount, reseller_id)) = paid else {
+        return (StatusCode::CONFLICT, "Payout not found or already paid").into_response();
+    };
+
+    let event = Event::new("payout.pay", &id)
+        .tenant(None)
+        .after(Some(serde_json::json!({
+            "reseller_id": reseller_id,
+            "amount": amount,
+            "reference": payload.reference,
+        })));
+    if let Err(e) = audit.record(&mut *transaction, event).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    if let Err(e) = transaction.commit().await {
+        return database_error(e);
+    }
+    payout_response(payout_detail(&pool, &id, None).await)
+}
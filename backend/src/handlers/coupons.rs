@@ -0,0 +1,439 @@
+use crate::auth::Claims;
+use crate::models::{Coupon, CouponRedemption, CreateCouponRequest, UpdateCouponRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+const COUPON_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, code, discount_type, discount_value, valid_from, valid_until, min_basket, max_uses, max_uses_per_customer, times_used, active, created_at";
+pub const REDEMPTION_COLUMNS: &str = "id::text AS id, coupon_id::text AS coupon_id, sale_id::text AS sale_id, customer_id::text AS customer_id, discount_amount, created_at";
+
+#[derive(Debug, Serialize)]
+pub struct CouponReport {
+    pub coupon: Coupon,
+    pub redemptions_count: i64,
+    pub total_discount: i64,
+    pub redemptions: Vec<CouponRedemption>,
+}
+
+/// Checks a coupon's validity window and limits.
+fn validate_limits(
+    valid_from: Option<chrono::NaiveDateTime>,
+    valid_until: Option<chrono::NaiveDateTime>,
+    min_basket: i32,
+    max_uses: Option<i32>,
+    max_uses_per_customer: Option<i32>,
+) -> Result<(), &'static str> {
+    if let (Some(from), Some(until)) = (valid_from, valid_until)
+        && from > until
+    {
+        return Err("valid_from must not be after valid_until");
+    }
+    if min_basket < 0 {
+        return Err("min_basket must not be negative");
+    }
+    if max_uses.is_some_and(|max| max < 1) {
+        return Err("max_uses must be at least 1");
+    }
+    if max_uses_per_customer.is_some_and(|max| max < 1) {
+        return Err("max_uses_per_customer must be at least 1");
+    }
+    Ok(())
+}
+
+/// Coupon validated and reserved inside a sale transaction.
+pub struct AppliedCoupon {
+    pub coupon_id: String,
+    pub discount_amount: i64,
+}
+
+pub async fn list_coupons(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let coupons = sqlx::query_as::<_, Coupon>(&format!(
+        "SELECT {} FROM coupons WHERE tenant_id = $1::uuid ORDER BY created_at DESC",
+        COUPON_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match coupons {
+        Ok(coupons) => Json(coupons).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list coupons: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list coupons").into_response()
+        }
+    }
+}
+
+pub async fn create_coupon(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateCouponRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    match payload.discount_type.as_str() {
+        "percentage" if (1..=100).contains(&payload.discount_value) => {}
+        "fixed" if payload.discount_value > 0 => {}
+        "percentage" | "fixed" => {
+            return (StatusCode::BAD_REQUEST, "Invalid discount value").into_response();
+        }
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "discount_type must be 'percentage' or 'fixed'",
+            )
+                .into_response();
+        }
+    }
+
+    let code = payload.code.trim().to_uppercase();
+    if code.is_empty() || code.chars().count() > 50 {
+        return (StatusCode::BAD_REQUEST, "Code must have 1 to 50 characters").into_response();
+    }
+    let min_basket = payload.min_basket.unwrap_or(0);
+    if let Err(msg) = validate_limits(
+        payload.valid_from,
+        payload.valid_until,
+        min_basket,
+        payload.max_uses,
+        payload.max_uses_per_customer,
+    ) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let id = Uuid::new_v4().to_string();
+
+    let result = sqlx::query(
+        "INSERT INTO coupons (id, tenant_id, code, discount_type, discount_value, valid_from, valid_until, min_basket, max_uses, max_uses_per_customer) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .bind(&code)
+    .bind(&payload.discount_type)
+    .bind(payload.discount_value)
+    .bind(payload.valid_from)
+    .bind(payload.valid_until)
+    .bind(min_basket)
+    .bind(payload.max_uses)
+    .bind(payload.max_uses_per_customer)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(e) => {
+            if e.to_string().contains("23505") || e.to_string().contains("duplicate key") {
+                (StatusCode::CONFLICT, "Coupon code already exists").into_response()
+            } else {
+                eprintln!("Failed to create coupon: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create coupon").into_response()
+            }
+        }
+    }
+}
+
+/// PUT /coupons/{id}
+/// Changes a coupon's validity and limits (`null` removes a limit), or
+/// deactivates it (`active: false`)
+pub async fn update_coupon(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateCouponRequest>,
+) -> impl IntoResponse {
+    let coupon = sqlx::query_as::<_, Coupon>(&format!(
+        "SELECT {} FROM coupons WHERE id = $1::uuid AND tenant_id = $2::uuid FOR UPDATE",
+        COUPON_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&claims.tenant_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let coupon = match coupon {
+        Ok(Some(coupon)) => coupon,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Coupon not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let valid_from = payload.valid_from.unwrap_or(coupon.valid_from);
+    let valid_until = payload.valid_until.unwrap_or(coupon.valid_until);
+    let min_basket = payload.min_basket.unwrap_or(coupon.min_basket);
+    let max_uses = payload.max_uses.unwrap_or(coupon.max_uses);
+    let max_uses_per_customer = payload
+        .max_uses_per_customer
+        .unwrap_or(coupon.max_uses_per_customer);
+    if let Err(msg) = validate_limits(
+        valid_from,
+        valid_until,
+        min_basket,
+        max_uses,
+        max_uses_per_customer,
+    ) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let updated = sqlx::query_as::<_, Coupon>(&format!(
+        r#"
+        UPDATE coupons SET active = $2, valid_from = $3, valid_until = $4, min_basket = $5,
+            max_uses = $6, max_uses_per_customer = $7
+        WHERE id = $1::uuid
+        RETURNING {}
+        "#,
+        COUPON_COLUMNS
+    ))
+    .bind(&id)
+    .bind(payload.active.unwrap_or(coupon.active))
+    .bind(valid_from)
+    .bind(valid_until)
+    .bind(min_basket)
+    .bind(max_uses)
+    .bind(max_uses_per_customer)
+    .fetch_one(&mut *tx)
+    .await;
+
+    match tx.commit_if_ok(updated).await {
+        Ok(coupon) => Json(coupon).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /coupons/{id}/redemptions
+/// Redemption report for a single coupon
+pub async fn get_coupon_report(
+    _: Require<perm::ReportsView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let coupon = sqlx::query_as::<_, Coupon>(&format!(
+        "SELECT {} FROM coupons WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        COUPON_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let coupon = match coupon {
+        Ok(Some(coupon)) => coupon,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Coupon not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let redemptions = sqlx::query_as::<_, CouponRedemption>(&format!(
+        "SELECT {} FROM coupon_redemptions WHERE coupon_id = $1::uuid ORDER BY created_at DESC",
+        REDEMPTION_COLUMNS
+    ))
+    .bind(&id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match redemptions {
+        Ok(redemptions) => {
+            let total_discount = redemptions.iter().map(|r| r.discount_amount).sum();
+            Json(CouponReport {
+                coupon,
+                redemptions_count: redemptions.len() as i64,
+                total_discount,
+                redemptions,
+            })
+            .into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Validates a coupon code against the basket and reserves one use.
+/// Must run inside the sale transaction: the coupon row is locked until commit.
+pub async fn apply_coupon(
+    conn: &mut PgConnection,
+    tenant_id: Option<&str>,
+    code: &str,
+    customer_id: Option<&str>,
+    subtotal: i64,
+) -> Result<AppliedCoupon, (StatusCode, String)> {
+    let coupon = sqlx::query_as::<_, Coupon>(&format!(
+        "SELECT {} FROM coupons WHERE tenant_id = $1::uuid AND code = $2 FOR UPDATE",
+        COUPON_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(code.trim().to_uppercase())
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error fetching coupon: {}", e),
+        )
+    })?
+    .ok_or((
+        StatusCode::BAD_REQUEST,
+        format!("Coupon {} not found", code),
+    ))?;
+
+    let now = chrono::Utc::now().naive_utc();
+    if !coupon.active
+        || coupon.valid_from.is_some_and(|from| now < from)
+        || coupon.valid_until.is_some_and(|until| now > until)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Coupon {} is not valid", code),
+        ));
+    }
+
+    if subtotal < coupon.min_basket as i64 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Coupon {} requires a minimum basket of {}",
+                code, coupon.min_basket
+            ),
+        ));
+    }
+
+    if coupon.max_uses.is_some_and(|max| coupon.times_used >= max) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Coupon {} is exhausted", code),
+        ));
+    }
+
+    if let Some(max_per_customer) = coupon.max_uses_per_customer {
+        let customer_id = customer_id.ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("Coupon {} requires a customer", code),
+        ))?;
+
+        let used: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM coupon_redemptions WHERE coupon_id = $1 AND customer_id = $2",
+        )
+        .bind(&coupon.id)
+        .bind(customer_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+        })?;
+
+        if used >= max_per_customer as i64 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Coupon {} already used by this customer", code),
+            ));
+        }
+    }
+
+    let discount_amount = match coupon.discount_type.as_str() {
+        "percentage" => subtotal * coupon.discount_value as i64 / 100,
+        _ => (coupon.discount_value as i64).min(subtotal),
+    };
+
+    sqlx::query("UPDATE coupons SET times_used = times_used + 1 WHERE id = $1")
+        .bind(&coupon.id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update coupon: {}", e),
+            )
+        })?;
+
+    Ok(AppliedCoupon {
+        coupon_id: coupon.id,
+        discount_amount,
+    })
+}
+
+/// Records the redemption once the sale row exists.
+pub async fn record_redemption(
+    conn: &mut PgConnection,
+    applied: &AppliedCoupon,
+    sale_id: &str,
+    customer_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO coupon_redemptions (id, coupon_id, sale_id, customer_id, discount_amount) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&applied.coupon_id)
+    .bind(sale_id)
+    .bind(customer_id)
+    .bind(applied.discount_amount)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Gives back the coupon use of a cancelled sale.
+pub async fn release_sale(conn: &mut PgConnection, sale_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        WITH released AS (
+            DELETE FROM coupon_redemptions WHERE sale_id = $1 RETURNING coupon_id
+        )
+        UPDATE coupons c SET times_used = c.times_used - 1
+        FROM released r
+        WHERE c.id = r.coupon_id
+        "#,
+    )
+    .bind(sale_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -1,15 +1,50 @@
 use crate::auth::Claims;
-use crate::models::{CreateCustomerRequest, Customer};
+use crate::documents::{self, Document};
+use crate::handlers::sales;
+use crate::models::{
+    CreateCustomerRequest, Customer, CustomerSearchQuery, Sale, UpdateCustomerRequest,
+};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
 use axum::{
-    extract::{Extension, Json, State},
+    extract::{Extension, Json, Path, Query},
     http::StatusCode,
     response::IntoResponse,
 };
-use sqlx::PgPool;
+use serde::Serialize;
+use sqlx::FromRow;
 use uuid::Uuid;
 
+pub const CUSTOMER_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, name, email, phone, notes, document, document_type, marketing_consent, consent_updated_at, anonymized_at, created_at, updated_at";
+
+#[derive(Debug, Serialize)]
+pub struct CustomerPage {
+    pub items: Vec<Customer>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FavouriteProduct {
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomerProfile {
+    pub customer: Customer,
+    pub lifetime_value: i64,
+    pub visit_count: i64,
+    pub last_purchase: Option<chrono::NaiveDateTime>,
+    pub favourite_products: Vec<FavouriteProduct>,
+    pub sales: Vec<Sale>,
+}
+
 pub async fn list_customers(
-    State(pool): State<PgPool>,
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
     // If tenant_id is in claims, filter by it.
@@ -23,10 +58,13 @@ pub async fn list_customers(
         None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
     };
 
-    let customers = sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE tenant_id = $1")
-        .bind(tenant_id)
-        .fetch_all(&pool)
-        .await;
+    let customers = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE tenant_id = $1::uuid",
+        CUSTOMER_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
 
     match customers {
         Ok(customers) => Json(customers).into_response(),
@@ -42,7 +80,8 @@ pub async fn list_customers(
 }
 
 pub async fn create_customer(
-    State(pool): State<PgPool>,
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
     Json(payload): Json<CreateCustomerRequest>,
 ) -> impl IntoResponse {
@@ -51,10 +90,20 @@ pub async fn create_customer(
         None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
     };
 
+    let document = match payload
+        .document
+        .as_deref()
+        .map(documents::parse)
+        .transpose()
+    {
+        Ok(document) => document,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
     let id = Uuid::new_v4().to_string();
 
     let result = sqlx::query(
-        "INSERT INTO customers (id, tenant_id, name, email, phone, notes) VALUES ($1, $2, $3, $4, $5, $6)",
+        "INSERT INTO customers (id, tenant_id, name, email, phone, notes, document, document_type, marketing_consent, consent_updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, CASE WHEN $9 THEN CURRENT_TIMESTAMP END)",
     )
     .bind(&id)
     .bind(&tenant_id)
@@ -62,11 +111,18 @@ pub async fn create_customer(
     .bind(&payload.email)
     .bind(&payload.phone)
     .bind(&payload.notes)
-    .execute(&pool)
+    .bind(document.as_ref().map(|d| &d.number))
+    .bind(document.as_ref().map(|d| d.kind.as_str()))
+    .bind(payload.marketing_consent.unwrap_or(false))
+    .execute(&mut *tx)
     .await;
+    let result = tx.commit_if_ok(result).await;
 
     match result {
         Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(e) if is_duplicate_document(&e) => {
+            (StatusCode::CONFLICT, "Document already registered").into_response()
+        }
         Err(e) => {
             eprintln!("Failed to create customer: {}", e);
             (
@@ -77,3 +133,378 @@ pub async fn create_customer(
         }
     }
 }
+
+/// Escapes `\`, `%` and `_` so a search term only matches itself inside
+/// a LIKE pattern
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// GET /customers/search?q=&page=1&per_page=20
+/// Searches customers by name, email, phone or CPF/CNPJ
+pub async fn search_customers(
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<CustomerSearchQuery>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(20).clamp(1, 100);
+
+    let term = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let pattern = term.map(|q| format!("%{}%", escape_like(q)));
+    // Phone numbers and documents are matched on digits only, ignoring masks like (11) 9999-9999
+    let digits = term
+        .map(|q| q.chars().filter(char::is_ascii_digit).collect::<String>())
+        .filter(|d| !d.is_empty())
+        .map(|d| format!("%{}%", d));
+
+    let filter = r#"
+        WHERE tenant_id = $1
+        AND (
+            $2::text IS NULL
+            OR name ILIKE $2
+            OR email ILIKE $2
+            OR ($3::text IS NOT NULL AND regexp_replace(COALESCE(phone, ''), '\D', '', 'g') LIKE $3)
+            OR ($3::text IS NOT NULL AND document LIKE $3)
+        )
+    "#;
+
+    let total: Result<i64, _> =
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM customers {}", filter))
+            .bind(&tenant_id)
+            .bind(&pattern)
+            .bind(&digits)
+            .fetch_one(&mut *tx)
+            .await;
+
+    let customers = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers {} ORDER BY name ASC LIMIT $4 OFFSET $5",
+        CUSTOMER_COLUMNS, filter
+    ))
+    .bind(&tenant_id)
+    .bind(&pattern)
+    .bind(&digits)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match (total, customers) {
+        (Ok(total), Ok(items)) => Json(CustomerPage {
+            items,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Failed to search customers: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to search customers",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn get_customer(
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let customer = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        CUSTOMER_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    match customer {
+        Ok(Some(customer)) => Json(customer).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => {
+            eprintln!("Failed to fetch customer: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch customer",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /customers/by-document/{document}
+/// POS lookup by CPF/CNPJ, with or without punctuation
+pub async fn get_customer_by_document(
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(document): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let Document { number, .. } = match documents::parse(&document) {
+        Ok(document) => document,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let customer = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE tenant_id = $1::uuid AND document = $2",
+        CUSTOMER_COLUMNS
+    ))
+    .bind(&tenant_id)
+    .bind(&number)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    match customer {
+        Ok(Some(customer)) => Json(customer).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => {
+            eprintln!("Failed to fetch customer: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch customer",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn update_customer(
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateCustomerRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    // Empty string clears the document
+    let document = match payload.document.as_deref().map(str::trim) {
+        None => None,
+        Some("") => Some(None),
+        Some(input) => match documents::parse(input) {
+            Ok(document) => Some(Some(document)),
+            Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+        },
+    };
+
+    let mut builder =
+        sqlx::QueryBuilder::new("UPDATE customers SET updated_at = CURRENT_TIMESTAMP");
+
+    if let Some(name) = &payload.name {
+        builder.push(", name = ");
+        builder.push_bind(name);
+    }
+    if let Some(email) = &payload.email {
+        builder.push(", email = ");
+        builder.push_bind(email);
+    }
+    if let Some(phone) = &payload.phone {
+        builder.push(", phone = ");
+        builder.push_bind(phone);
+    }
+    if let Some(notes) = &payload.notes {
+        builder.push(", notes = ");
+        builder.push_bind(notes);
+    }
+    if let Some(document) = &document {
+        builder.push(", document = ");
+        builder.push_bind(document.as_ref().map(|d| d.number.clone()));
+        builder.push(", document_type = ");
+        builder.push_bind(document.as_ref().map(|d| d.kind.as_str()));
+    }
+
+    builder.push(" WHERE id = ");
+    builder.push_bind(id);
+    builder.push(" AND tenant_id = ");
+    builder.push_bind(tenant_id);
+
+    let result = builder.build().execute(&mut *tx).await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Customer not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Customer updated").into_response(),
+        Err(e) if is_duplicate_document(&e) => {
+            (StatusCode::CONFLICT, "Document already registered").into_response()
+        }
+        Err(e) => {
+            eprintln!("Failed to update customer: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update customer",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn delete_customer(
+    _: Require<perm::CustomersDelete>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    // Sales keep their history: sales.customer_id is set to NULL by the FK
+    let result = sqlx::query("DELETE FROM customers WHERE id = $1 AND tenant_id = $2")
+        .bind(&id)
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Customer not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Customer deleted").into_response(),
+        Err(e) => {
+            eprintln!("Failed to delete customer: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete customer",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /customers/{id}/profile
+/// Lifetime value, visits, favourite products and sales of a customer
+pub async fn get_customer_profile(
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let customer = match sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        CUSTOMER_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(customer)) => customer,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => {
+            eprintln!("Failed to fetch customer: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch customer",
+            )
+                .into_response();
+        }
+    };
+
+    let profile = async {
+        let (lifetime_value, visit_count, last_purchase): (
+            i64,
+            i64,
+            Option<chrono::NaiveDateTime>,
+        ) = sqlx::query_as(
+            r#"
+            SELECT COALESCE(SUM(total_amount), 0)::BIGINT, COUNT(*), MAX(created_at)
+            FROM sales
+            WHERE customer_id = $1 AND tenant_id = $2 AND status <> 'cancelled'
+            "#,
+        )
+        .bind(&id)
+        .bind(&tenant_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let favourite_products = sqlx::query_as::<_, FavouriteProduct>(
+            r#"
+            SELECT si.product_id::text AS product_id, p.name AS product_name, SUM(si.quantity)::BIGINT AS quantity
+            FROM sale_items si
+            JOIN sales s ON si.sale_id = s.id
+            JOIN products p ON si.product_id = p.id
+            WHERE s.customer_id = $1 AND s.tenant_id = $2 AND s.status <> 'cancelled'
+            GROUP BY si.product_id, p.name
+            ORDER BY quantity DESC
+            LIMIT 5
+            "#,
+        )
+        .bind(&id)
+        .bind(&tenant_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let sales = sqlx::query_as::<_, Sale>(&format!(
+            "SELECT {} FROM sales WHERE customer_id = $1::uuid AND tenant_id = $2::uuid ORDER BY created_at DESC",
+            sales::SALE_COLUMNS
+        ))
+        .bind(&id)
+        .bind(&tenant_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        Ok::<_, sqlx::Error>((lifetime_value, visit_count, last_purchase, favourite_products, sales))
+    }
+    .await;
+
+    match profile {
+        Ok((lifetime_value, visit_count, last_purchase, favourite_products, sales)) => {
+            Json(CustomerProfile {
+                customer,
+                lifetime_value,
+                visit_count,
+                last_purchase,
+                favourite_products,
+                sales,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            eprintln!("Failed to build customer profile: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to build customer profile",
+            )
+                .into_response()
+        }
+    }
+}
+
+fn is_duplicate_document(e: &sqlx::Error) -> bool {
+    e.to_string().contains("idx_customers_tenant_document")
+}
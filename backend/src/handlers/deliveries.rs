@@ -0,0 +1,414 @@
+use crate::auth::Claims;
+use crate::handlers::addresses::ADDRESS_COLUMNS;
+use crate::models::{
+    CreateDeliveryZoneRequest, CustomerAddress, Delivery, DeliveryZone, UpdateDeliveryStatusRequest,
+};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+pub const DELIVERY_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, sale_id::text AS sale_id, customer_id::text AS customer_id, address_id::text AS address_id, address_text, zone_id::text AS zone_id, fee, courier_name, status, dispatched_at, delivered_at, created_at, updated_at";
+const ZONE_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, name, zip_code_start, zip_code_end, fee, estimated_minutes, created_at";
+
+/// Delivery status pipeline, in order. `cancelled` can be reached from any
+/// status before `delivered`.
+const PIPELINE: [&str; 4] = ["received", "preparing", "out_for_delivery", "delivered"];
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DispatchItem {
+    pub id: String,
+    pub sale_id: String,
+    pub customer_name: Option<String>,
+    pub customer_phone: Option<String>,
+    pub address_text: String,
+    pub fee: i64,
+    pub sale_total: i64,
+    pub payment_method: String,
+    pub courier_name: Option<String>,
+    pub status: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub dispatched_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DispatchBoard {
+    pub received: Vec<DispatchItem>,
+    pub preparing: Vec<DispatchItem>,
+    pub out_for_delivery: Vec<DispatchItem>,
+    pub delivered_today: Vec<DispatchItem>,
+}
+
+/// Address and fee resolved for a delivery before the sale is inserted.
+pub struct DeliveryQuote {
+    pub address_id: String,
+    pub address_text: String,
+    pub zone_id: String,
+    pub fee: i64,
+}
+
+pub async fn list_zones(
+    _: Require<perm::DeliveriesDispatch>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let zones = sqlx::query_as::<_, DeliveryZone>(&format!(
+        "SELECT {} FROM delivery_zones WHERE tenant_id = $1::uuid ORDER BY zip_code_start ASC",
+        ZONE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match zones {
+        Ok(zones) => Json(zones).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list delivery zones: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list delivery zones",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn create_zone(
+    _: Require<perm::DeliveriesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateDeliveryZoneRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let digits = |cep: &str| cep.chars().filter(char::is_ascii_digit).collect::<String>();
+    let (start, end) = (
+        digits(&payload.zip_code_start),
+        digits(&payload.zip_code_end),
+    );
+    if start.len() != 8 || end.len() != 8 || start > end || payload.fee < 0 {
+        return (StatusCode::BAD_REQUEST, "Invalid delivery zone").into_response();
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let result = sqlx::query(
+        "INSERT INTO delivery_zones (id, tenant_id, name, zip_code_start, zip_code_end, fee, estimated_minutes) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .bind(&payload.name)
+    .bind(&start)
+    .bind(&end)
+    .bind(payload.fee)
+    .bind(payload.estimated_minutes)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(e) => {
+            eprintln!("Failed to create delivery zone: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create delivery zone",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn delete_zone(
+    _: Require<perm::DeliveriesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query("DELETE FROM delivery_zones WHERE id = $1 AND tenant_id = $2")
+        .bind(&id)
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Delivery zone not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Delivery zone deleted").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to delete: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /deliveries/board
+/// Open deliveries grouped by status, plus the ones delivered today
+pub async fn get_dispatch_board(
+    _: Require<perm::DeliveriesDispatch>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let items = sqlx::query_as::<_, DispatchItem>(
+        r#"
+        SELECT
+            d.id::text AS id, d.sale_id::text AS sale_id,
+            c.name AS customer_name, c.phone AS customer_phone,
+            d.address_text, d.fee, s.total_amount AS sale_total, s.payment_method,
+            d.courier_name, d.status, d.created_at, d.dispatched_at
+        FROM deliveries d
+        JOIN sales s ON d.sale_id = s.id
+        LEFT JOIN customers c ON d.customer_id = c.id
+        WHERE d.tenant_id = $1
+        AND (
+            d.status IN ('received', 'preparing', 'out_for_delivery')
+            OR (d.status = 'delivered' AND d.delivered_at >= CURRENT_DATE)
+        )
+        ORDER BY d.created_at ASC
+        "#,
+    )
+    .bind(&tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    let items = match items {
+        Ok(items) => items,
+        Err(e) => {
+            eprintln!("Failed to load dispatch board: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load dispatch board",
+            )
+                .into_response();
+        }
+    };
+
+    let mut board = DispatchBoard::default();
+    for item in items {
+        match item.status.as_str() {
+            "received" => board.received.push(item),
+            "preparing" => board.preparing.push(item),
+            "out_for_delivery" => board.out_for_delivery.push(item),
+            _ => board.delivered_today.push(item),
+        }
+    }
+
+    Json(board).into_response()
+}
+
+/// PUT /deliveries/{id}/status
+/// Moves a delivery forward in the pipeline (or cancels it)
+pub async fn update_status(
+    _: Require<perm::DeliveriesDispatch>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateDeliveryStatusRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let delivery = sqlx::query_as::<_, Delivery>(&format!(
+        "SELECT {} FROM deliveries WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        DELIVERY_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let delivery = match delivery {
+        Ok(Some(delivery)) => delivery,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Delivery not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let current = PIPELINE.iter().position(|s| *s == delivery.status);
+    let allowed = match (current, payload.status.as_str()) {
+        // Finished deliveries are immutable
+        (None, _) | (Some(3), _) => false,
+        (Some(_), "cancelled") => true,
+        (Some(from), to) => PIPELINE.iter().position(|s| *s == to) == Some(from + 1),
+    };
+
+    if !allowed {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Cannot move delivery from {} to {}",
+                delivery.status, payload.status
+            ),
+        )
+            .into_response();
+    }
+
+    if payload.status == "out_for_delivery"
+        && payload.courier_name.is_none()
+        && delivery.courier_name.is_none()
+    {
+        return (StatusCode::BAD_REQUEST, "A courier is required to dispatch").into_response();
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE deliveries SET
+            status = $1,
+            courier_name = COALESCE($2, courier_name),
+            dispatched_at = CASE WHEN $1 = 'out_for_delivery' THEN CURRENT_TIMESTAMP ELSE dispatched_at END,
+            delivered_at = CASE WHEN $1 = 'delivered' THEN CURRENT_TIMESTAMP ELSE delivered_at END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $3 AND status = $4
+        "#,
+    )
+    .bind(&payload.status)
+    .bind(&payload.courier_name)
+    .bind(&id)
+    .bind(&delivery.status)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::CONFLICT, "Delivery was updated concurrently").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Delivery updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to update: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Resolves the customer's address and the fee of the zone it falls in.
+pub async fn quote_delivery(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    customer_id: Option<&str>,
+    address_id: &str,
+) -> Result<DeliveryQuote, (StatusCode, String)> {
+    let customer_id = customer_id.ok_or((
+        StatusCode::BAD_REQUEST,
+        "Delivery requires a customer".to_string(),
+    ))?;
+
+    let address = sqlx::query_as::<_, CustomerAddress>(&format!(
+        "SELECT {} FROM customer_addresses WHERE id = $1::uuid AND customer_id = $2::uuid AND tenant_id = $3::uuid",
+        ADDRESS_COLUMNS
+    ))
+    .bind(address_id)
+    .bind(customer_id)
+    .bind(tenant_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error fetching address: {}", e),
+        )
+    })?
+    .ok_or((StatusCode::BAD_REQUEST, "Address not found".to_string()))?;
+
+    let zone = sqlx::query_as::<_, DeliveryZone>(&format!(
+        "SELECT {} FROM delivery_zones WHERE tenant_id = $1::uuid AND $2 BETWEEN zip_code_start AND zip_code_end ORDER BY fee ASC LIMIT 1",
+        ZONE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(&address.zip_code)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error fetching delivery zone: {}", e),
+        )
+    })?
+    .ok_or((
+        StatusCode::BAD_REQUEST,
+        format!("CEP {} is outside the delivery zones", address.zip_code),
+    ))?;
+
+    let address_text = [
+        Some(address.street.as_str()),
+        address.number.as_deref(),
+        address.complement.as_deref(),
+        address.neighborhood.as_deref(),
+        Some(address.city.as_str()),
+        Some(address.state.as_str()),
+        Some(address.zip_code.as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    Ok(DeliveryQuote {
+        address_id: address.id,
+        address_text,
+        zone_id: zone.id,
+        fee: zone.fee as i64,
+    })
+}
+
+/// Creates the delivery order for a sale. Call after the sale row exists.
+pub async fn create_delivery(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    sale_id: &str,
+    customer_id: Option<&str>,
+    quote: &DeliveryQuote,
+    courier_name: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO deliveries (id, tenant_id, sale_id, customer_id, address_id, address_text, zone_id, fee, courier_name) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(tenant_id)
+    .bind(sale_id)
+    .bind(customer_id)
+    .bind(&quote.address_id)
+    .bind(&quote.address_text)
+    .bind(&quote.zone_id)
+    .bind(quote.fee)
+    .bind(courier_name)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
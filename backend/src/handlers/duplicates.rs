@@ -0,0 +1,347 @@
+//! Customer deduplication: candidate detection and merge into a surviving record.
+
+use crate::auth::Claims;
+use crate::handlers::customers::CUSTOMER_COLUMNS;
+use crate::models::{Customer, DuplicatesQuery, MergeCustomersRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::FromRow;
+use std::collections::HashMap;
+
+/// Candidate pairs, older customer first, with the keys they share. Each key
+/// is an equality join, so the search stays linear in the number of
+/// customers. Names match once case, accents and punctuation are folded;
+/// `name_similarity` (pg_trgm) scores how alike a pair's names are.
+const PAIRS: &str = r#"
+    WITH keyed AS (
+        SELECT id, created_at, NULLIF(lower(trim(email)), '') AS email, document,
+            -- Drop the Brazilian country code so +55 (11) 99999-9999 matches 11999999999
+            CASE
+                WHEN digits LIKE '55%' AND length(digits) >= 12 THEN substr(digits, 3)
+                WHEN length(digits) >= 8 THEN digits
+            END AS phone,
+            NULLIF(trim(regexp_replace(
+                lower(translate(
+                    name,
+                    'ÁÀÂÃÄÉÈÊËÍÌÎÏÓÒÔÕÖÚÙÛÜÇáàâãäéèêëíìîïóòôõöúùûüç',
+                    'AAAAAEEEEIIIIOOOOOUUUUCaaaaaeeeeiiiiooooouuuuc'
+                )),
+                '[^a-z0-9]+', ' ', 'g'
+            )), '') AS name
+        FROM (
+            SELECT *, regexp_replace(COALESCE(phone, ''), '\D', '', 'g') AS digits
+            FROM customers WHERE tenant_id = $1::uuid AND anonymized_at IS NULL
+        ) c
+    ),
+    matches AS (
+        SELECT a.id AS a_id, b.id AS b_id, 'phone' AS reason FROM keyed a
+        JOIN keyed b ON a.phone = b.phone AND (a.created_at, a.id) < (b.created_at, b.id)
+        UNION ALL
+        SELECT a.id, b.id, 'email' FROM keyed a
+        JOIN keyed b ON a.email = b.email AND (a.created_at, a.id) < (b.created_at, b.id)
+        UNION ALL
+        SELECT a.id, b.id, 'document' FROM keyed a
+        JOIN keyed b ON a.document = b.document AND (a.created_at, a.id) < (b.created_at, b.id)
+        UNION ALL
+        SELECT a.id, b.id, 'name' FROM keyed a
+        JOIN keyed b ON a.name = b.name AND (a.created_at, a.id) < (b.created_at, b.id)
+    ),
+    pairs AS (
+        SELECT m.a_id, m.b_id, array_agg(m.reason ORDER BY m.reason) AS reasons,
+            COALESCE(similarity(a.name, b.name), 0)::float8 AS name_similarity
+        FROM matches m
+        JOIN keyed a ON a.id = m.a_id
+        JOIN keyed b ON b.id = m.b_id
+        GROUP BY m.a_id, m.b_id, a.name, b.name
+    )
+"#;
+
+#[derive(Debug, FromRow)]
+struct Pair {
+    a_id: String,
+    b_id: String,
+    reasons: Vec<String>, // document, email, name, phone
+    name_similarity: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateCandidate<'a> {
+    pub customers: [&'a Customer; 2],
+    pub reasons: Vec<String>,
+    pub name_similarity: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicatePage<'a> {
+    pub items: Vec<DuplicateCandidate<'a>>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+/// GET /customers/duplicates?page=1&per_page=20
+/// Pairs of customers that look like the same person, strongest matches first
+pub async fn find_duplicates(
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<DuplicatesQuery>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(20).clamp(1, 100);
+
+    let result = async {
+        let total: i64 = sqlx::query_scalar(&format!("{} SELECT COUNT(*) FROM pairs", PAIRS))
+            .bind(&tenant_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let pairs = sqlx::query_as::<_, Pair>(&format!(
+            r#"
+            {}
+            SELECT a_id::text AS a_id, b_id::text AS b_id, reasons, name_similarity FROM pairs
+            ORDER BY cardinality(reasons) DESC, name_similarity DESC, a_id, b_id
+            LIMIT $2 OFFSET $3
+            "#,
+            PAIRS
+        ))
+        .bind(&tenant_id)
+        .bind(per_page)
+        .bind((page - 1) * per_page)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let ids: Vec<&str> = pairs
+            .iter()
+            .flat_map(|p| [p.a_id.as_str(), p.b_id.as_str()])
+            .collect();
+        let customers = sqlx::query_as::<_, Customer>(&format!(
+            "SELECT {} FROM customers WHERE id::text = ANY($1)",
+            CUSTOMER_COLUMNS
+        ))
+        .bind(&ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        Ok::<_, sqlx::Error>((total, pairs, customers))
+    }
+    .await;
+
+    let (total, pairs, customers) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Failed to find duplicates: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to find duplicates",
+            )
+                .into_response();
+        }
+    };
+
+    let customers: HashMap<&str, &Customer> =
+        customers.iter().map(|c| (c.id.as_str(), c)).collect();
+    let items = pairs
+        .into_iter()
+        .filter_map(|pair| {
+            Some(DuplicateCandidate {
+                customers: [
+                    *customers.get(pair.a_id.as_str())?,
+                    *customers.get(pair.b_id.as_str())?,
+                ],
+                reasons: pair.reasons,
+                name_similarity: pair.name_similarity,
+            })
+        })
+        .collect();
+
+    Json(DuplicatePage {
+        items,
+        total,
+        page,
+        per_page,
+    })
+    .into_response()
+}
+
+/// POST /customers/{id}/merge
+/// Merges the given duplicates into customer `{id}` and deletes them
+pub async fn merge_customers(
+    _: Require<perm::CustomersDelete>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<MergeCustomersRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    if payload.duplicate_ids.is_empty() || payload.duplicate_ids.contains(&id) {
+        return (StatusCode::BAD_REQUEST, "Invalid duplicate_ids").into_response();
+    }
+
+    let survivor = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE id = $1::uuid AND tenant_id = $2::uuid FOR UPDATE",
+        CUSTOMER_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let survivor = match survivor {
+        Ok(Some(customer)) => customer,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let duplicates = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE id::text = ANY($1) AND tenant_id = $2::uuid ORDER BY created_at ASC FOR UPDATE",
+        CUSTOMER_COLUMNS
+    ))
+    .bind(&payload.duplicate_ids)
+    .bind(&tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    let duplicates = match duplicates {
+        Ok(duplicates) if duplicates.len() == payload.duplicate_ids.len() => duplicates,
+        Ok(_) => {
+            return (StatusCode::NOT_FOUND, "Duplicate customer not found").into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    // Fill gaps on the surviving record with the duplicates' data
+    let pick = |own: &Option<String>, field: fn(&Customer) -> &Option<String>| {
+        own.clone()
+            .or_else(|| duplicates.iter().find_map(|d| field(d).clone()))
+    };
+    let email = pick(&survivor.email, |c| &c.email);
+    let phone = pick(&survivor.phone, |c| &c.phone);
+    let document = pick(&survivor.document, |c| &c.document);
+    let document_type = if survivor.document.is_some() {
+        survivor.document_type.clone()
+    } else {
+        duplicates
+            .iter()
+            .find(|d| d.document.is_some())
+            .and_then(|d| d.document_type.clone())
+    };
+    let notes = std::iter::once(&survivor.notes)
+        .chain(duplicates.iter().map(|d| &d.notes))
+        .filter_map(|n| n.as_deref())
+        .filter(|n| !n.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let marketing_consent =
+        survivor.marketing_consent && duplicates.iter().all(|d| d.marketing_consent);
+
+    let result = async {
+        // Moved addresses keep one default: the survivor's, else the oldest
+        // default among the duplicates
+        sqlx::query(
+            r#"
+            UPDATE customer_addresses SET is_default = FALSE
+            WHERE customer_id::text = ANY($2) AND id <> (
+                SELECT id FROM customer_addresses
+                WHERE customer_id = $1::uuid OR customer_id::text = ANY($2)
+                ORDER BY customer_id = $1::uuid DESC, is_default DESC, created_at ASC
+                LIMIT 1
+            )
+            "#,
+        )
+        .bind(&id)
+        .bind(&payload.duplicate_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        for table in [
+            "sales",
+            "loyalty_transactions",
+            "gift_cards",
+            "coupon_redemptions",
+            "data_subject_requests",
+            "customer_addresses",
+            "deliveries",
+        ] {
+            sqlx::query(&format!(
+                "UPDATE {} SET customer_id = $1 WHERE customer_id::text = ANY($2)",
+                table
+            ))
+            .bind(&id)
+            .bind(&payload.duplicate_ids)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("DELETE FROM customers WHERE id::text = ANY($1) AND tenant_id = $2")
+            .bind(&payload.duplicate_ids)
+            .bind(&tenant_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE customers SET
+                email = $1, phone = $2, document = $3, document_type = $4,
+                notes = NULLIF($5, ''), marketing_consent = $6, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $7
+            "#,
+        )
+        .bind(&email)
+        .bind(&phone)
+        .bind(&document)
+        .bind(&document_type)
+        .bind(&notes)
+        .bind(marketing_consent)
+        .bind(&id)
+        .execute(&mut *tx)
+        .await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to merge customers: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Customers merged").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
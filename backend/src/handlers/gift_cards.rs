@@ -0,0 +1,461 @@
+use crate::auth::Claims;
+use crate::models::{GiftCard, GiftCardTransaction, IssueStoreCreditRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Gift cards sold at the POS are valid for one year after activation.
+const GIFT_CARD_VALIDITY_DAYS: i64 = 365;
+
+pub const CARD_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, code, kind, initial_balance, balance, customer_id::text AS customer_id, expires_at, status, created_at";
+const TRANSACTION_COLUMNS: &str = "id::text AS id, gift_card_id::text AS gift_card_id, sale_id::text AS sale_id, kind, amount, balance_after, created_at";
+
+#[derive(Debug, Serialize)]
+pub struct GiftCardBalance {
+    pub code: String,
+    pub kind: String,
+    pub balance: i64,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub status: String,
+    pub transactions: Vec<GiftCardTransaction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssuedStoreCredit {
+    pub id: String,
+    pub code: String,
+    pub balance: i64,
+}
+
+pub async fn list_gift_cards(
+    _: Require<perm::GiftCardsManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let cards = sqlx::query_as::<_, GiftCard>(&format!(
+        "SELECT {} FROM gift_cards WHERE tenant_id = $1::uuid ORDER BY created_at DESC",
+        CARD_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match cards {
+        Ok(cards) => Json(cards).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list gift cards: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list gift cards",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /gift-cards/{code}/balance
+/// Balance lookup by card code, with the card's ledger
+pub async fn get_balance(
+    _: Require<perm::SalesCreate>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(code): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let card = sqlx::query_as::<_, GiftCard>(&format!(
+        "SELECT {} FROM gift_cards WHERE tenant_id = $1::uuid AND code = $2",
+        CARD_COLUMNS
+    ))
+    .bind(&tenant_id)
+    .bind(code.trim().to_uppercase())
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let card = match card {
+        Ok(Some(card)) => card,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Gift card not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let transactions = sqlx::query_as::<_, GiftCardTransaction>(&format!(
+        "SELECT {} FROM gift_card_transactions WHERE gift_card_id = $1::uuid ORDER BY created_at ASC",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(&card.id)
+    .fetch_all(&mut *tx)
+    .await
+    .unwrap_or_default();
+
+    Json(GiftCardBalance {
+        code: card.code,
+        kind: card.kind,
+        balance: card.balance,
+        expires_at: card.expires_at,
+        status: card.status,
+        transactions,
+    })
+    .into_response()
+}
+
+/// POST /gift-cards/store-credit
+/// Issues a store credit voucher (e.g. for a refund) on the gift card ledger
+pub async fn issue_store_credit(
+    _: Require<perm::GiftCardsManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<IssueStoreCreditRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    if payload.amount <= 0 {
+        return (StatusCode::BAD_REQUEST, "Amount must be positive").into_response();
+    }
+
+    let code = generate_code();
+    let result = create_card(
+        &mut tx,
+        &tenant_id,
+        &code,
+        "store_credit",
+        payload.amount,
+        payload.customer_id.as_deref(),
+        payload.expires_at,
+        payload.sale_id.as_deref(),
+    )
+    .await;
+
+    let id = match result {
+        Ok(id) => id,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to issue store credit: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match tx.commit().await {
+        Ok(_) => (
+            StatusCode::CREATED,
+            Json(IssuedStoreCredit {
+                id,
+                code,
+                balance: payload.amount,
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+fn generate_code() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_uppercase()
+}
+
+/// Inserts a card and its opening ledger entry.
+#[allow(clippy::too_many_arguments)]
+async fn create_card(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    code: &str,
+    kind: &str,
+    amount: i64,
+    customer_id: Option<&str>,
+    expires_at: Option<chrono::NaiveDateTime>,
+    sale_id: Option<&str>,
+) -> Result<String, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+
+    sqlx::query(
+        "INSERT INTO gift_cards (id, tenant_id, code, kind, initial_balance, balance, customer_id, expires_at) VALUES ($1, $2, $3, $4, $5, $5, $6, $7)",
+    )
+    .bind(&id)
+    .bind(tenant_id)
+    .bind(code)
+    .bind(kind)
+    .bind(amount)
+    .bind(customer_id)
+    .bind(expires_at)
+    .execute(&mut *conn)
+    .await?;
+
+    let entry_kind = if kind == "store_credit" {
+        "store_credit"
+    } else {
+        "activation"
+    };
+    record_transaction(conn, &id, sale_id, entry_kind, amount, amount).await?;
+
+    Ok(id)
+}
+
+async fn record_transaction(
+    conn: &mut PgConnection,
+    gift_card_id: &str,
+    sale_id: Option<&str>,
+    kind: &str,
+    amount: i64,
+    balance_after: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO gift_card_transactions (id, gift_card_id, sale_id, kind, amount, balance_after) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(gift_card_id)
+    .bind(sale_id)
+    .bind(kind)
+    .bind(amount)
+    .bind(balance_after)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Activates a gift card sold as a sale item. Runs inside the sale transaction.
+pub async fn activate_card(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    code: &str,
+    amount: i64,
+    customer_id: Option<&str>,
+    sale_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    let expires_at =
+        chrono::Utc::now().naive_utc() + chrono::Duration::days(GIFT_CARD_VALIDITY_DAYS);
+
+    create_card(
+        conn,
+        tenant_id,
+        &code.trim().to_uppercase(),
+        "gift_card",
+        amount,
+        customer_id,
+        Some(expires_at),
+        Some(sale_id),
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+        if e.to_string().contains("23505") || e.to_string().contains("duplicate key") {
+            (
+                StatusCode::CONFLICT,
+                format!("Gift card {} already activated", code),
+            )
+        } else {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to activate gift card: {}", e),
+            )
+        }
+    })
+}
+
+/// Debits `amount` from a card as payment for a sale. Partial redemption is
+/// allowed; the card row is locked until the sale transaction commits.
+pub async fn redeem_card(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    code: &str,
+    amount: i64,
+    sale_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    if amount <= 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid amount for gift card {}", code),
+        ));
+    }
+
+    let card = sqlx::query_as::<_, GiftCard>(&format!(
+        "SELECT {} FROM gift_cards WHERE tenant_id = $1::uuid AND code = $2 FOR UPDATE",
+        CARD_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(code.trim().to_uppercase())
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error fetching gift card: {}", e),
+        )
+    })?
+    .ok_or((
+        StatusCode::BAD_REQUEST,
+        format!("Gift card {} not found", code),
+    ))?;
+
+    let now = chrono::Utc::now().naive_utc();
+    if card.status != "active" || card.expires_at.is_some_and(|exp| now > exp) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Gift card {} is not valid", code),
+        ));
+    }
+
+    if card.balance < amount {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Insufficient balance on gift card {}", code),
+        ));
+    }
+
+    let balance_after = card.balance - amount;
+
+    sqlx::query("UPDATE gift_cards SET balance = $1 WHERE id = $2")
+        .bind(balance_after)
+        .bind(&card.id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update gift card: {}", e),
+            )
+        })?;
+
+    record_transaction(
+        conn,
+        &card.id,
+        Some(sale_id),
+        "redemption",
+        -amount,
+        balance_after,
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to record gift card transaction: {}", e),
+        )
+    })
+}
+
+/// Credits back every gift card redemption of a cancelled sale.
+pub async fn refund_sale(conn: &mut PgConnection, sale_id: &str) -> Result<(), sqlx::Error> {
+    // Cards the sale activated are voided instead, see `void_sale_cards`
+    let redemptions = sqlx::query_as::<_, GiftCardTransaction>(&format!(
+        r#"
+        SELECT {} FROM gift_card_transactions
+        WHERE sale_id = $1::uuid AND kind = 'redemption'
+            AND gift_card_id NOT IN (
+                SELECT gift_card_id FROM gift_card_transactions
+                WHERE sale_id = $1::uuid AND kind = 'activation'
+            )
+        "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(sale_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for redemption in redemptions {
+        let balance_after: i64 = sqlx::query_scalar(
+            "UPDATE gift_cards SET balance = balance + $1 WHERE id = $2 RETURNING balance",
+        )
+        .bind(-redemption.amount)
+        .bind(&redemption.gift_card_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        record_transaction(
+            conn,
+            &redemption.gift_card_id,
+            Some(sale_id),
+            "reversal",
+            -redemption.amount,
+            balance_after,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Voids the gift cards a cancelled sale activated, so a refunded buyer does
+/// not keep a spendable card. Refuses if a card has been spent elsewhere;
+/// what the sale itself paid with the card does not count.
+pub async fn void_sale_cards(
+    conn: &mut PgConnection,
+    sale_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to void gift cards: {}", e),
+        )
+    };
+
+    let cards: Vec<(String, String, i64, i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT c.id::text, c.code, c.initial_balance, c.balance,
+            COALESCE((
+                SELECT -SUM(t.amount)::bigint FROM gift_card_transactions t
+                WHERE t.gift_card_id = c.id AND t.sale_id = $1::uuid AND t.kind = 'redemption'
+            ), 0)
+        FROM gift_cards c
+        WHERE c.id IN (
+            SELECT gift_card_id FROM gift_card_transactions
+            WHERE sale_id = $1::uuid AND kind = 'activation'
+        )
+        FOR UPDATE OF c
+        "#,
+    )
+    .bind(sale_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    for (id, code, initial_balance, balance, spent_on_sale) in cards {
+        if balance + spent_on_sale < initial_balance {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Gift card {} has already been used", code),
+            ));
+        }
+
+        sqlx::query("UPDATE gift_cards SET balance = 0, status = 'cancelled' WHERE id = $1::uuid")
+            .bind(&id)
+            .execute(&mut *conn)
+            .await
+            .map_err(db_error)?;
+        record_transaction(conn, &id, Some(sale_id), "reversal", -balance, 0)
+            .await
+            .map_err(db_error)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,490 @@
+use crate::auth::Claims;
+use crate::models::{LoyaltyProgram, LoyaltyTier, LoyaltyTransaction, UpdateLoyaltyProgramRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+const PROGRAM_COLUMNS: &str = "tenant_id::text AS tenant_id, points_per_real, point_value, expiry_days, active, created_at, updated_at";
+const TIER_COLUMNS: &str =
+    "id::text AS id, tenant_id::text AS tenant_id, name, min_points, multiplier";
+pub const TRANSACTION_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, customer_id::text AS customer_id, sale_id::text AS sale_id, kind, points, remaining, expires_at, created_at";
+
+#[derive(Debug, Serialize)]
+pub struct LoyaltyProgramResponse {
+    pub program: Option<LoyaltyProgram>,
+    pub tiers: Vec<LoyaltyTier>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomerLoyalty {
+    pub customer_id: String,
+    pub balance: i64,
+    pub lifetime_points: i64,
+    pub tier: Option<LoyaltyTier>,
+    pub history: Vec<LoyaltyTransaction>,
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+pub async fn get_program(
+    _: Require<perm::SalesCreate>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let program = match load_program(&mut tx, &tenant_id).await {
+        Ok(program) => program,
+        Err(e) => return db_error(e).into_response(),
+    };
+
+    let tiers = sqlx::query_as::<_, LoyaltyTier>(&format!(
+        "SELECT {} FROM loyalty_tiers WHERE tenant_id = $1::uuid ORDER BY min_points ASC",
+        TIER_COLUMNS
+    ))
+    .bind(&tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match tiers {
+        Ok(tiers) => Json(LoyaltyProgramResponse { program, tiers }).into_response(),
+        Err(e) => db_error(e).into_response(),
+    }
+}
+
+/// PUT /loyalty/program
+/// Creates or replaces the tenant's loyalty program and its tiers
+pub async fn update_program(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<UpdateLoyaltyProgramRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    if payload.points_per_real < 0
+        || payload.point_value < 0
+        || payload.expiry_days.is_some_and(|d| d <= 0)
+        || payload
+            .tiers
+            .iter()
+            .any(|t| t.min_points < 0 || t.multiplier < 0)
+    {
+        return (StatusCode::BAD_REQUEST, "Invalid loyalty program settings").into_response();
+    }
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO loyalty_programs (tenant_id, points_per_real, point_value, expiry_days, active)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (tenant_id) DO UPDATE SET
+            points_per_real = excluded.points_per_real,
+            point_value = excluded.point_value,
+            expiry_days = excluded.expiry_days,
+            active = excluded.active,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&tenant_id)
+    .bind(payload.points_per_real)
+    .bind(payload.point_value)
+    .bind(payload.expiry_days)
+    .bind(payload.active)
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return db_error(e).into_response();
+    }
+
+    if let Err(e) = sqlx::query("DELETE FROM loyalty_tiers WHERE tenant_id = $1")
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await
+    {
+        let _ = tx.rollback().await;
+        return db_error(e).into_response();
+    }
+
+    for tier in &payload.tiers {
+        let result = sqlx::query(
+            "INSERT INTO loyalty_tiers (id, tenant_id, name, min_points, multiplier) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&tenant_id)
+        .bind(&tier.name)
+        .bind(tier.min_points)
+        .bind(tier.multiplier)
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = result {
+            let _ = tx.rollback().await;
+            return db_error(e).into_response();
+        }
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Loyalty program updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /loyalty/customers/{id}
+/// Points balance, tier and ledger history of a customer
+pub async fn get_customer_loyalty(
+    _: Require<perm::CustomersView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(customer_id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let exists = sqlx::query("SELECT 1 FROM customers WHERE id = $1 AND tenant_id = $2")
+        .bind(&customer_id)
+        .bind(&tenant_id)
+        .fetch_optional(&mut *tx)
+        .await;
+
+    match exists {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => return db_error(e).into_response(),
+    }
+
+    let summary = async {
+        expire_points(&mut tx, &customer_id).await?;
+        let balance = balance(&mut tx, &customer_id).await?;
+        let lifetime_points = lifetime_points(&mut tx, &customer_id).await?;
+        let tier = current_tier(&mut tx, &tenant_id, lifetime_points).await?;
+        let history = sqlx::query_as::<_, LoyaltyTransaction>(&format!(
+            "SELECT {} FROM loyalty_transactions WHERE customer_id = $1::uuid ORDER BY created_at DESC",
+            TRANSACTION_COLUMNS
+        ))
+        .bind(&customer_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        Ok::<_, sqlx::Error>(CustomerLoyalty {
+            customer_id: customer_id.clone(),
+            balance,
+            lifetime_points,
+            tier,
+            history,
+        })
+    }
+    .await;
+
+    match summary {
+        Ok(summary) => match tx.commit().await {
+            Ok(_) => Json(summary).into_response(),
+            Err(e) => db_error(e).into_response(),
+        },
+        Err(e) => {
+            let _ = tx.rollback().await;
+            db_error(e).into_response()
+        }
+    }
+}
+
+async fn load_program(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+) -> Result<Option<LoyaltyProgram>, sqlx::Error> {
+    sqlx::query_as::<_, LoyaltyProgram>(&format!(
+        "SELECT {} FROM loyalty_programs WHERE tenant_id = $1::uuid",
+        PROGRAM_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_optional(conn)
+    .await
+}
+
+fn expiry_for(program: &LoyaltyProgram) -> Option<chrono::NaiveDateTime> {
+    program
+        .expiry_days
+        .map(|days| chrono::Utc::now().naive_utc() + chrono::Duration::days(days as i64))
+}
+
+/// Moves unspent points past their expiry date to `expire` ledger rows.
+async fn expire_points(conn: &mut PgConnection, customer_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO loyalty_transactions (id, tenant_id, customer_id, kind, points)
+        SELECT gen_random_uuid(), tenant_id, customer_id, 'expire', -remaining
+        FROM loyalty_transactions
+        WHERE customer_id = $1 AND remaining > 0 AND expires_at <= CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(customer_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE loyalty_transactions SET remaining = 0 WHERE customer_id = $1 AND remaining > 0 AND expires_at <= CURRENT_TIMESTAMP",
+    )
+    .bind(customer_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn balance(conn: &mut PgConnection, customer_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COALESCE(SUM(remaining), 0)::BIGINT FROM loyalty_transactions WHERE customer_id = $1 AND remaining > 0",
+    )
+    .bind(customer_id)
+    .fetch_one(conn)
+    .await
+}
+
+/// Points earned on sales that still stand; a cancelled sale takes back
+/// everything it earned, even points already spent.
+async fn lifetime_points(conn: &mut PgConnection, customer_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(SUM(t.points), 0)::BIGINT FROM loyalty_transactions t
+        WHERE t.customer_id = $1::uuid AND t.kind = 'earn'
+            AND NOT EXISTS (
+                SELECT 1 FROM sales s WHERE s.id = t.sale_id AND s.status = 'cancelled'
+            )
+        "#,
+    )
+    .bind(customer_id)
+    .fetch_one(conn)
+    .await
+}
+
+async fn current_tier(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    lifetime_points: i64,
+) -> Result<Option<LoyaltyTier>, sqlx::Error> {
+    sqlx::query_as::<_, LoyaltyTier>(&format!(
+        "SELECT {} FROM loyalty_tiers WHERE tenant_id = $1::uuid AND min_points <= $2 ORDER BY min_points DESC LIMIT 1",
+        TIER_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(lifetime_points)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Checks that the customer can redeem `points` and returns the discount in cents.
+/// Locks the customer's credit rows so concurrent sales cannot spend the same points.
+pub async fn quote_redemption(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    customer_id: Option<&str>,
+    points: i64,
+) -> Result<i64, (StatusCode, String)> {
+    let customer_id = customer_id.ok_or((
+        StatusCode::BAD_REQUEST,
+        "Redeeming loyalty points requires a customer".to_string(),
+    ))?;
+
+    if points <= 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Invalid loyalty points".to_string(),
+        ));
+    }
+
+    let program = match load_program(&mut *conn, tenant_id)
+        .await
+        .map_err(db_error)?
+    {
+        Some(program) if program.active => program,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Loyalty program is not active".to_string(),
+            ));
+        }
+    };
+
+    sqlx::query(
+        "SELECT id FROM loyalty_transactions WHERE customer_id = $1 AND remaining > 0 FOR UPDATE",
+    )
+    .bind(customer_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    expire_points(&mut *conn, customer_id)
+        .await
+        .map_err(db_error)?;
+
+    if balance(&mut *conn, customer_id).await.map_err(db_error)? < points {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Insufficient loyalty points".to_string(),
+        ));
+    }
+
+    Ok(points * program.point_value as i64)
+}
+
+/// Spends `points` from the oldest credit rows first. Call after `quote_redemption`.
+pub async fn redeem_points(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    customer_id: &str,
+    sale_id: &str,
+    points: i64,
+) -> Result<(), sqlx::Error> {
+    let credits: Vec<(String, i64)> = sqlx::query_as(
+        r#"
+        SELECT id::text, remaining FROM loyalty_transactions
+        WHERE customer_id = $1 AND remaining > 0
+        ORDER BY expires_at ASC NULLS LAST, created_at ASC
+        FOR UPDATE
+        "#,
+    )
+    .bind(customer_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut left = points;
+    for (id, remaining) in credits {
+        if left == 0 {
+            break;
+        }
+        let used = remaining.min(left);
+        sqlx::query("UPDATE loyalty_transactions SET remaining = remaining - $1 WHERE id = $2")
+            .bind(used)
+            .bind(&id)
+            .execute(&mut *conn)
+            .await?;
+        left -= used;
+    }
+
+    sqlx::query(
+        "INSERT INTO loyalty_transactions (id, tenant_id, customer_id, sale_id, kind, points) VALUES ($1, $2, $3, $4, 'redeem', $5)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(tenant_id)
+    .bind(customer_id)
+    .bind(sale_id)
+    .bind(-points)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Credits points for a completed sale. `amount` is what the customer actually paid, in cents.
+pub async fn earn_points(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    customer_id: &str,
+    sale_id: &str,
+    amount: i64,
+) -> Result<(), sqlx::Error> {
+    let program = match load_program(&mut *conn, tenant_id).await? {
+        Some(program) if program.active => program,
+        _ => return Ok(()),
+    };
+
+    let lifetime = lifetime_points(&mut *conn, customer_id).await?;
+    let multiplier = current_tier(&mut *conn, tenant_id, lifetime)
+        .await?
+        .map_or(100, |tier| tier.multiplier);
+
+    let points = amount / 100 * program.points_per_real as i64 * multiplier as i64 / 100;
+    if points <= 0 {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO loyalty_transactions (id, tenant_id, customer_id, sale_id, kind, points, remaining, expires_at) VALUES ($1, $2, $3, $4, 'earn', $5, $5, $6)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(tenant_id)
+    .bind(customer_id)
+    .bind(sale_id)
+    .bind(points)
+    .bind(expiry_for(&program))
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Undoes the loyalty effects of a cancelled sale: unspent earned points are
+/// removed and redeemed points are given back.
+pub async fn reverse_sale(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    sale_id: &str,
+) -> Result<(), sqlx::Error> {
+    let program = load_program(&mut *conn, tenant_id).await?;
+    let expires_at = program.as_ref().and_then(expiry_for);
+
+    let entries = sqlx::query_as::<_, LoyaltyTransaction>(&format!(
+        "SELECT {} FROM loyalty_transactions WHERE sale_id = $1::uuid AND kind IN ('earn', 'redeem') FOR UPDATE",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(sale_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for entry in entries {
+        let (points, remaining) = if entry.kind == "earn" {
+            sqlx::query("UPDATE loyalty_transactions SET remaining = 0 WHERE id = $1")
+                .bind(&entry.id)
+                .execute(&mut *conn)
+                .await?;
+            (-entry.remaining, 0)
+        } else {
+            (-entry.points, -entry.points)
+        };
+
+        if points == 0 {
+            continue;
+        }
+
+        sqlx::query(
+            "INSERT INTO loyalty_transactions (id, tenant_id, customer_id, sale_id, kind, points, remaining, expires_at) VALUES ($1, $2, $3, $4, 'reversal', $5, $6, $7)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(tenant_id)
+        .bind(&entry.customer_id)
+        .bind(sale_id)
+        .bind(points)
+        .bind(remaining)
+        .bind(if remaining > 0 { expires_at } else { None })
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -1,8 +1,9 @@
-use axum::{Extension, extract::State, http::StatusCode, response::Json};
+use axum::{Extension, http::StatusCode, response::Json};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
 
 use crate::auth::Claims;
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
 
 #[derive(Debug, Serialize)]
 pub struct MetricsOverview {
@@ -39,25 +40,30 @@ pub struct InventoryAlert {
 /// GET /api/metrics/overview
 /// Retorna métricas gerais do negócio
 pub async fn get_overview(
-    State(pool): State<PgPool>,
+    _: Require<perm::ReportsView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> Result<Json<MetricsOverview>, StatusCode> {
     let tenant_id = &claims.tenant_id;
 
     // Total de receita
-    let total_revenue: i64 =
-        sqlx::query_scalar("SELECT COALESCE(SUM(total_amount), 0) FROM sales WHERE tenant_id = $1")
-            .bind(tenant_id)
-            .fetch_one(&pool)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Vendas canceladas não contam como receita
+    let total_revenue: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(total_amount), 0)::BIGINT FROM sales WHERE tenant_id = $1 AND status <> 'cancelled'",
+    )
+    .bind(tenant_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Número de vendas
-    let sales_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sales WHERE tenant_id = $1")
-        .bind(tenant_id)
-        .fetch_one(&pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let sales_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sales WHERE tenant_id = $1 AND status <> 'cancelled'",
+    )
+    .bind(tenant_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Ticket médio
     let average_ticket = if sales_count > 0 {
@@ -70,7 +76,7 @@ pub async fn get_overview(
     let products_count: i64 =
         sqlx::query_scalar("SELECT COUNT(*) FROM products WHERE tenant_id = $1")
             .bind(tenant_id)
-            .fetch_one(&pool)
+            .fetch_one(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -78,7 +84,7 @@ pub async fn get_overview(
     let customers_count: i64 =
         sqlx::query_scalar("SELECT COUNT(*) FROM customers WHERE tenant_id = $1")
             .bind(tenant_id)
-            .fetch_one(&pool)
+            .fetch_one(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -94,7 +100,8 @@ pub async fn get_overview(
 /// GET /api/metrics/sales-trend?days=7
 /// Retorna tendência de vendas dos últimos N dias
 pub async fn get_sales_trend(
-    State(pool): State<PgPool>,
+    _: Require<perm::ReportsView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> Result<Json<Vec<SalesTrendPoint>>, StatusCode> {
     let tenant_id = &claims.tenant_id;
@@ -104,17 +111,18 @@ pub async fn get_sales_trend(
         r#"
         SELECT 
             TO_CHAR(created_at, 'YYYY-MM-DD') as date,
-            COALESCE(SUM(total_amount), 0) as revenue,
+            COALESCE(SUM(total_amount), 0)::BIGINT as revenue,
             COUNT(*) as sales_count
         FROM sales 
         WHERE tenant_id = $1 
+        AND status <> 'cancelled'
         AND created_at >= NOW() - INTERVAL '7 days'
         GROUP BY TO_CHAR(created_at, 'YYYY-MM-DD')
         ORDER BY date ASC
         "#,
     )
     .bind(tenant_id)
-    .fetch_all(&pool)
+    .fetch_all(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -133,7 +141,8 @@ pub async fn get_sales_trend(
 /// GET /api/metrics/top-products?limit=5
 /// Retorna os produtos mais vendidos
 pub async fn get_top_products(
-    State(pool): State<PgPool>,
+    _: Require<perm::ReportsView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> Result<Json<Vec<TopProduct>>, StatusCode> {
     let tenant_id = &claims.tenant_id;
@@ -141,7 +150,7 @@ pub async fn get_top_products(
     let top_products = sqlx::query_as::<_, (String, String, i64, i64)>(
         r#"
         SELECT 
-            si.product_id,
+            si.product_id::text as product_id,
             p.name as product_name,
             SUM(si.quantity) as quantity_sold,
             SUM(si.subtotal) as revenue
@@ -149,13 +158,14 @@ pub async fn get_top_products(
         JOIN sales s ON si.sale_id = s.id
         JOIN products p ON si.product_id = p.id
         WHERE s.tenant_id = $1
+        AND s.status <> 'cancelled'
         GROUP BY si.product_id, p.name
         ORDER BY quantity_sold DESC
         LIMIT 5
         "#,
     )
     .bind(tenant_id)
-    .fetch_all(&pool)
+    .fetch_all(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -177,7 +187,8 @@ pub async fn get_top_products(
 /// GET /api/metrics/inventory-alerts
 /// Retorna produtos com estoque baixo
 pub async fn get_inventory_alerts(
-    State(pool): State<PgPool>,
+    _: Require<perm::ReportsView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> Result<Json<Vec<InventoryAlert>>, StatusCode> {
     let tenant_id = &claims.tenant_id;
@@ -196,7 +207,7 @@ pub async fn get_inventory_alerts(
         "#,
     )
     .bind(tenant_id)
-    .fetch_all(&pool)
+    .fetch_all(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
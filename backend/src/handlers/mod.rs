@@ -4,5 +4,24 @@ pub mod sales;
 pub mod admin;
 pub mod customers;
 pub mod metrics;
-
-
+pub mod coupons;
+pub mod gift_cards;
+pub mod loyalty;
+pub mod privacy;
+pub mod duplicates;
+pub mod addresses;
+pub mod deliveries;
+pub mod segments;
+pub mod sessions;
+pub mod roles;
+pub mod accounts;
+pub mod two_factor;
+pub mod terminals;
+pub mod api_keys;
+pub mod sso;
+pub mod audit_log;
+pub mod plan;
+pub mod billing;
+pub mod commissions;
+pub mod onboarding;
+pub mod payment_methods;
@@ -0,0 +1,217 @@
+//! Store setup wizard: business type templates and the onboarding checklist.
+
+use crate::audit::{AuditContext, Event};
+use crate::auth::Claims;
+use crate::business_types::{self, BusinessType};
+use crate::models::{ApplyTemplateRequest, CheckOffStepRequest};
+use crate::onboarding::{self, Checklist, Seeded};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct TemplateCatalog {
+    pub base_payment_methods: &'static [business_types::PaymentMethod],
+    pub templates: &'static [BusinessType],
+}
+
+#[derive(Debug, Serialize)]
+pub struct Applied {
+    pub seeded: Seeded,
+    pub checklist: Checklist,
+}
+
+fn checklist_response(result: Result<Checklist, sqlx::Error>) -> axum::response::Response {
+    match result {
+        Ok(checklist) => Json(checklist).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /onboarding/templates
+/// Every business type with what picking it seeds
+pub async fn list_templates(_: Require<perm::SetupManage>) -> impl IntoResponse {
+    Json(TemplateCatalog {
+        base_payment_methods: business_types::BASE_PAYMENT_METHODS,
+        templates: business_types::ALL,
+    })
+}
+
+/// GET /onboarding
+/// The store's setup checklist
+pub async fn get_onboarding(
+    _: Require<perm::SetupManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    checklist_response(onboarding::checklist(&mut tx, &tenant_id).await)
+}
+
+/// POST /onboarding/apply
+/// Sets the store's business type (its current one by default) and seeds
+/// what the template adds, plus sample products into an empty catalog when
+/// `sample_products` is true. Never removes or renames what the store has
+pub async fn apply_template(
+    _: Require<perm::SetupManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Json(payload): Json<ApplyTemplateRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let business_type = match payload.business_type {
+        Some(value) if !business_types::exists(&value) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Unknown business type: {}", value),
+            )
+                .into_response();
+        }
+        Some(value) => value,
+        None => {
+            let current: Result<Option<String>, sqlx::Error> =
+                sqlx::query_scalar("SELECT business_type FROM tenants WHERE id = $1::uuid")
+                    .bind(&tenant_id)
+                    .fetch_one(&mut *tx)
+                    .await;
+            match current {
+                Ok(current) => current.unwrap_or_default(),
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Database error: {}", e),
+                    )
+                        .into_response();
+                }
+            }
+        }
+    };
+    let template = business_types::get(&business_type);
+
+    let custom_fields = payload.custom_fields.unwrap_or_default();
+    if let Err(msg) = template.validate_custom_fields(&custom_fields) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let seeded = match onboarding::apply(
+        &mut tx,
+        &tenant_id,
+        template,
+        &custom_fields,
+        payload.sample_products.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(seeded) => seeded,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to apply template: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let event = Event::new("onboarding.apply", &tenant_id).after(Some(serde_json::json!({
+        "business_type": template.value,
+        "custom_fields": custom_fields,
+        "seeded": seeded,
+    })));
+    if let Err(e) = audit.record(&mut *tx, event).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    let checklist = onboarding::checklist(&mut tx, &tenant_id).await;
+    match tx.commit_if_ok(checklist).await {
+        Ok(checklist) => Json(Applied { seeded, checklist }).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// PUT /onboarding/steps/{step}
+/// Checks a step off by hand (`done: false` takes the check back)
+pub async fn check_off_step(
+    _: Require<perm::SetupManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(step): Path<String>,
+    Json(payload): Json<CheckOffStepRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+    if !onboarding::STEPS.contains(&step.as_str()) {
+        return (StatusCode::NOT_FOUND, "Unknown step").into_response();
+    }
+
+    if let Err(e) = onboarding::check_off(&mut tx, &tenant_id, &step, payload.done).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response();
+    }
+    let checklist = onboarding::checklist(&mut tx, &tenant_id).await;
+    checklist_response(tx.commit_if_ok(checklist).await)
+}
+
+/// POST /onboarding/dismiss
+/// Hides the checklist for good, finished or not
+pub async fn dismiss(
+    _: Require<perm::SetupManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO onboarding (tenant_id, dismissed_at) VALUES ($1::uuid, CURRENT_TIMESTAMP)
+        ON CONFLICT (tenant_id) DO UPDATE
+        SET dismissed_at = COALESCE(onboarding.dismissed_at, EXCLUDED.dismissed_at)
+        "#,
+    )
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+    if let Err(e) = result {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response();
+    }
+    let checklist = onboarding::checklist(&mut tx, &tenant_id).await;
+    checklist_response(tx.commit_if_ok(checklist).await)
+}
@@ -0,0 +1,140 @@
+//! Payment methods a store takes at the POS.
+
+use crate::auth::Claims;
+use crate::models::{PaymentMethod, UpdatePaymentMethodRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sqlx::PgConnection;
+
+/// Whether the store takes `code`. A store that never set up its payment
+/// methods takes any.
+pub async fn accepts(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) = 0 OR COALESCE(BOOL_OR(code = $2 AND enabled), false)
+        FROM payment_methods WHERE tenant_id = $1::uuid
+        "#,
+    )
+    .bind(tenant_id)
+    .bind(code)
+    .fetch_one(conn)
+    .await
+}
+
+/// GET /payment-methods
+pub async fn list_payment_methods(
+    _: Require<perm::SalesCreate>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let methods = sqlx::query_as::<_, PaymentMethod>(
+        "SELECT code, name, enabled, position FROM payment_methods WHERE tenant_id = $1::uuid ORDER BY position, name",
+    )
+    .bind(&claims.tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match methods {
+        Ok(methods) => Json(methods).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// PUT /payment-methods/{code}
+/// Renames, enables or disables a method, or adds a new one (`name`
+/// required)
+pub async fn update_payment_method(
+    _: Require<perm::SetupManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(code): Path<String>,
+    Json(payload): Json<UpdatePaymentMethodRequest>,
+) -> impl IntoResponse {
+    let valid_code = !code.is_empty()
+        && code.len() <= 50
+        && code
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_code {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Code must be 1 to 50 lowercase letters, digits or underscores",
+        )
+            .into_response();
+    }
+    let name = payload.name.as_deref().map(str::trim);
+    if name.is_some_and(|n| n.is_empty() || n.chars().count() > 100) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Name must have 1 to 100 characters",
+        )
+            .into_response();
+    }
+
+    let updated = sqlx::query_as::<_, PaymentMethod>(
+        r#"
+        UPDATE payment_methods SET
+            name = COALESCE($3, name),
+            enabled = COALESCE($4, enabled),
+            position = COALESCE($5, position)
+        WHERE tenant_id = $1::uuid AND code = $2
+        RETURNING code, name, enabled, position
+        "#,
+    )
+    .bind(&claims.tenant_id)
+    .bind(&code)
+    .bind(name)
+    .bind(payload.enabled)
+    .bind(payload.position)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let result = match updated {
+        Ok(Some(method)) => Ok(method),
+        Ok(None) => {
+            let Some(name) = name else {
+                return (StatusCode::BAD_REQUEST, "A new payment method needs a name")
+                    .into_response();
+            };
+            sqlx::query_as::<_, PaymentMethod>(
+                r#"
+                INSERT INTO payment_methods (tenant_id, code, name, enabled, position)
+                VALUES ($1::uuid, $2, $3, COALESCE($4, TRUE),
+                    COALESCE($5, (SELECT COALESCE(MAX(position) + 1, 0) FROM payment_methods WHERE tenant_id = $1::uuid)))
+                RETURNING code, name, enabled, position
+                "#,
+            )
+            .bind(&claims.tenant_id)
+            .bind(&code)
+            .bind(name)
+            .bind(payload.enabled)
+            .bind(payload.position)
+            .fetch_one(&mut *tx)
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match tx.commit_if_ok(result).await {
+        Ok(method) => Json(method).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
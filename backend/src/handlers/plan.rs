@@ -0,0 +1,97 @@
+use crate::auth::Claims;
+use crate::models::{FeatureStatus, PlanSummary, PlanUsage, UsageCount};
+use crate::permissions::{Require, perm};
+use crate::plans::{self, Entitlements, Feature};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sqlx::{PgConnection, PgPool};
+
+async fn usage(conn: &mut PgConnection, tenant_id: &str) -> Result<PlanUsage, sqlx::Error> {
+    let entitlements = Entitlements::load(&mut *conn, tenant_id, false).await?;
+    let users = plans::count_users(&mut *conn, tenant_id).await?;
+    let terminals = plans::count_terminals(&mut *conn, tenant_id).await?;
+
+    Ok(PlanUsage {
+        plan: entitlements
+            .plan_id
+            .clone()
+            .zip(entitlements.plan_name.clone())
+            .map(|(id, name)| PlanSummary { id, name }),
+        users: UsageCount {
+            used: users,
+            limit: entitlements.max_users.map(i64::from),
+        },
+        terminals: UsageCount {
+            used: terminals,
+            limit: entitlements.max_terminals(),
+        },
+        features: Feature::ALL
+            .iter()
+            .map(|&feature| FeatureStatus {
+                feature,
+                enabled: entitlements.allows(feature),
+            })
+            .collect(),
+    })
+}
+
+fn usage_response(result: Result<PlanUsage, sqlx::Error>) -> axum::response::Response {
+    match result {
+        Ok(usage) => Json(usage).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /plan/usage
+/// The caller's plan, what the tenant uses against its limits, and which
+/// features the plan includes
+pub async fn get_usage(
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    usage_response(usage(&mut tx, &tenant_id).await)
+}
+
+/// GET /admin/tenants/{id}/usage
+pub async fn get_tenant_usage(
+    _: Require<perm::TenantsManage>,
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let owned: Result<Option<bool>, sqlx::Error> = sqlx::query_scalar(
+        "SELECT $2::text IS NULL OR COALESCE(reseller_id = $2::uuid, false) FROM tenants WHERE id = $1::uuid",
+    )
+    .bind(&id)
+    .bind((claims.role == "reseller").then_some(&claims.sub))
+    .fetch_optional(&pool)
+    .await;
+
+    match owned {
+        Ok(Some(true)) => match pool.acquire().await {
+            Ok(mut conn) => usage_response(usage(&mut conn, &id).await),
+            Err(e) => usage_response(Err(e)),
+        },
+        Ok(Some(false)) => (StatusCode::FORBIDDEN, "Not owner of this tenant").into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,459 @@
+//! LGPD data subject tools: export, anonymisation and marketing consent.
+
+use crate::auth::Claims;
+use crate::handlers::{addresses, coupons, customers, deliveries, gift_cards, loyalty, sales};
+use crate::models::{
+    CouponRedemption, Customer, CustomerAddress, DataSubjectRequest, Delivery, GiftCard,
+    LoyaltyTransaction, Sale, UpdateConsentRequest,
+};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+const REQUEST_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, customer_id::text AS customer_id, request_type, requested_by::text AS requested_by, details, created_at";
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ExportedSaleItem {
+    pub sale_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: i32,
+    pub unit_price: i32,
+    pub subtotal: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomerExport {
+    pub generated_at: chrono::NaiveDateTime,
+    pub customer: Customer,
+    pub addresses: Vec<CustomerAddress>,
+    pub sales: Vec<Sale>,
+    pub sale_items: Vec<ExportedSaleItem>,
+    pub loyalty_transactions: Vec<LoyaltyTransaction>,
+    pub gift_cards: Vec<GiftCard>,
+    pub coupon_redemptions: Vec<CouponRedemption>,
+    pub deliveries: Vec<Delivery>,
+    pub requests: Vec<DataSubjectRequest>,
+}
+
+async fn record_request(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    customer_id: &str,
+    request_type: &str,
+    requested_by: &str,
+    details: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO data_subject_requests (id, tenant_id, customer_id, request_type, requested_by, details) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(tenant_id)
+    .bind(customer_id)
+    .bind(request_type)
+    .bind(requested_by)
+    .bind(details)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes the customer's saved addresses and scrubs the address snapshots
+/// kept on their deliveries.
+async fn scrub_addresses(conn: &mut PgConnection, customer_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM customer_addresses WHERE customer_id = $1::uuid")
+        .bind(customer_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "UPDATE deliveries SET address_text = 'Anonymized address', updated_at = CURRENT_TIMESTAMP WHERE customer_id = $1::uuid",
+    )
+    .bind(customer_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn build_export(
+    conn: &mut PgConnection,
+    customer: Customer,
+) -> Result<CustomerExport, sqlx::Error> {
+    let addresses = sqlx::query_as::<_, CustomerAddress>(&format!(
+        "SELECT {} FROM customer_addresses WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        addresses::ADDRESS_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let sales = sqlx::query_as::<_, Sale>(&format!(
+        "SELECT {} FROM sales WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        sales::SALE_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let sale_items = sqlx::query_as::<_, ExportedSaleItem>(
+        r#"
+        SELECT si.sale_id::text AS sale_id, si.product_id::text AS product_id, p.name AS product_name,
+               si.quantity, si.unit_price, si.subtotal
+        FROM sale_items si
+        JOIN sales s ON si.sale_id = s.id
+        JOIN products p ON si.product_id = p.id
+        WHERE s.customer_id = $1
+        ORDER BY s.created_at ASC
+        "#,
+    )
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let loyalty_transactions = sqlx::query_as::<_, LoyaltyTransaction>(&format!(
+        "SELECT {} FROM loyalty_transactions WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        loyalty::TRANSACTION_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let gift_cards = sqlx::query_as::<_, GiftCard>(&format!(
+        "SELECT {} FROM gift_cards WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        gift_cards::CARD_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let coupon_redemptions = sqlx::query_as::<_, CouponRedemption>(&format!(
+        "SELECT {} FROM coupon_redemptions WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        coupons::REDEMPTION_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let deliveries = sqlx::query_as::<_, Delivery>(&format!(
+        "SELECT {} FROM deliveries WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        deliveries::DELIVERY_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let requests = sqlx::query_as::<_, DataSubjectRequest>(&format!(
+        "SELECT {} FROM data_subject_requests WHERE customer_id = $1::uuid ORDER BY created_at ASC",
+        REQUEST_COLUMNS
+    ))
+    .bind(&customer.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(CustomerExport {
+        generated_at: chrono::Utc::now().naive_utc(),
+        customer,
+        addresses,
+        sales,
+        sale_items,
+        loyalty_transactions,
+        gift_cards,
+        coupon_redemptions,
+        deliveries,
+        requests,
+    })
+}
+
+/// GET /customers/{id}/export
+/// Everything held about a customer, as a downloadable JSON archive
+pub async fn export_customer(
+    _: Require<perm::CustomersPrivacy>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let customer = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT {} FROM customers WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        customers::CUSTOMER_COLUMNS
+    ))
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let customer = match customer {
+        Ok(Some(customer)) => customer,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Customer not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if let Err(e) = record_request(&mut tx, &tenant_id, &id, "export", &claims.sub, None).await {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to record request: {}", e),
+        )
+            .into_response();
+    }
+
+    let export = match build_export(&mut tx, customer).await {
+        Ok(export) => export,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to export customer: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if let Err(e) = tx.commit().await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response();
+    }
+
+    let disposition = format!("attachment; filename=\"customer-{}.json\"", id);
+    (
+        StatusCode::OK,
+        [(header::CONTENT_DISPOSITION, disposition)],
+        Json(export),
+    )
+        .into_response()
+}
+
+/// POST /customers/{id}/anonymize
+/// Scrubs personal fields and addresses but keeps the customer's sales for
+/// accounting. Refused while one of their deliveries is still on its way
+pub async fn anonymize_customer(
+    _: Require<perm::CustomersPrivacy>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let open_deliveries: Result<bool, sqlx::Error> = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM deliveries WHERE customer_id = $1::uuid AND status NOT IN ('delivered', 'cancelled'))",
+    )
+    .bind(&id)
+    .fetch_one(&mut *tx)
+    .await;
+
+    match open_deliveries {
+        Ok(true) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::CONFLICT, "Customer has deliveries in progress").into_response();
+        }
+        Ok(false) => {}
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE customers SET
+            name = 'Anonymized customer',
+            email = NULL,
+            phone = NULL,
+            notes = NULL,
+            document = NULL,
+            document_type = NULL,
+            marketing_consent = FALSE,
+            consent_updated_at = CURRENT_TIMESTAMP,
+            anonymized_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND tenant_id = $2 AND anonymized_at IS NULL
+        "#,
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::NOT_FOUND,
+                "Customer not found or already anonymized",
+            )
+                .into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to anonymize customer: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    if let Err(e) = scrub_addresses(&mut tx, &id).await {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to anonymize addresses: {}", e),
+        )
+            .into_response();
+    }
+
+    if let Err(e) = record_request(&mut tx, &tenant_id, &id, "anonymize", &claims.sub, None).await {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to record request: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Customer anonymized").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// PUT /customers/{id}/consent
+/// Grants or withdraws consent for marketing communications
+pub async fn update_consent(
+    _: Require<perm::CustomersEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateConsentRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query(
+        "UPDATE customers SET marketing_consent = $1, consent_updated_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $2 AND tenant_id = $3 AND anonymized_at IS NULL",
+    )
+    .bind(payload.marketing_consent)
+    .bind(&id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            let _ = tx.rollback().await;
+            return (StatusCode::NOT_FOUND, "Customer not found").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update consent: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let details = if payload.marketing_consent {
+        "marketing_consent=granted"
+    } else {
+        "marketing_consent=withdrawn"
+    };
+
+    if let Err(e) = record_request(
+        &mut tx,
+        &tenant_id,
+        &id,
+        "consent",
+        &claims.sub,
+        Some(details),
+    )
+    .await
+    {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to record request: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Consent updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /customers/privacy-requests
+/// Audit trail of data subject requests handled by the store
+pub async fn list_requests(
+    _: Require<perm::CustomersPrivacy>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let requests = sqlx::query_as::<_, DataSubjectRequest>(&format!(
+        "SELECT {} FROM data_subject_requests WHERE tenant_id = $1::uuid ORDER BY created_at DESC",
+        REQUEST_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match requests {
+        Ok(requests) => Json(requests).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list privacy requests: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list privacy requests",
+            )
+                .into_response()
+        }
+    }
+}
@@ -1,22 +1,25 @@
+use crate::audit::{AuditContext, Event};
 use crate::auth::Claims;
-use crate::models::{CreateProductRequest, Product};
+use crate::models::{CreateCategoryRequest, CreateProductRequest, Product, UpdateProductRequest};
+use crate::permissions::{Permission, Permissions, Require, perm};
+use crate::tenant_db::TenantTx;
 use axum::{
     Json,
-    extract::{Extension, State},
+    extract::{Extension, Path},
     http::StatusCode,
     response::IntoResponse,
 };
-use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 pub async fn list_products(
-    State(pool): State<PgPool>,
+    _: Require<perm::ProductsView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
     // Filter by tenant_id from claims
     let products = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE tenant_id = $1")
         .bind(&claims.tenant_id)
-        .fetch_all(&pool)
+        .fetch_all(&mut *tx)
         .await;
 
     match products {
@@ -30,13 +33,14 @@ pub async fn list_products(
 }
 
 pub async fn create_product(
-    State(pool): State<PgPool>,
+    _: Require<perm::ProductsEdit>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
     Json(payload): Json<CreateProductRequest>,
 ) -> impl IntoResponse {
     let product_id = Uuid::new_v4().to_string();
 
-    let result = sqlx::query("INSERT INTO products (id, tenant_id, name, description, price, stock_quantity, sku) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+    let result = sqlx::query("INSERT INTO products (id, tenant_id, name, description, price, stock_quantity, sku, is_gift_card, category) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)")
         .bind(&product_id)
         .bind(&claims.tenant_id)
         .bind(&payload.name)
@@ -44,9 +48,13 @@ pub async fn create_product(
         .bind(payload.price)
         .bind(payload.stock_quantity)
         .bind(&payload.sku)
-        .execute(&pool)
+        .bind(payload.is_gift_card.unwrap_or(false))
+        .bind(&payload.category)
+        .execute(&mut *tx)
         .await;
 
+    let result = tx.commit_if_ok(result).await;
+
     match result {
         Ok(_) => (StatusCode::CREATED, Json(product_id)).into_response(),
         Err(e) => (
@@ -56,3 +64,189 @@ pub async fn create_product(
             .into_response(),
     }
 }
+
+pub async fn update_product(
+    _: Require<perm::ProductsEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Extension(permissions): Extension<Permissions>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateProductRequest>,
+) -> impl IntoResponse {
+    if payload.price.is_some() && !permissions.has(Permission::ProductsEditPrice) {
+        return (
+            StatusCode::FORBIDDEN,
+            "Missing permission: products.edit_price",
+        )
+            .into_response();
+    }
+
+    // Price changes are audited, so the old price is read under the row lock
+    let old_price: Option<i32> = if payload.price.is_some() {
+        match sqlx::query_scalar(
+            "SELECT price FROM products WHERE id = $1::uuid AND tenant_id = $2::uuid FOR UPDATE",
+        )
+        .bind(&id)
+        .bind(&claims.tenant_id)
+        .fetch_optional(&mut *tx)
+        .await
+        {
+            Ok(Some(price)) => Some(price),
+            Ok(None) => {
+                let _ = tx.rollback().await;
+                return (StatusCode::NOT_FOUND, "Product not found").into_response();
+            }
+            Err(e) => {
+                let _ = tx.rollback().await;
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Database error: {}", e),
+                )
+                    .into_response();
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut builder = sqlx::QueryBuilder::new("UPDATE products SET updated_at = CURRENT_TIMESTAMP");
+
+    if let Some(name) = &payload.name {
+        builder.push(", name = ");
+        builder.push_bind(name);
+    }
+    if let Some(description) = &payload.description {
+        builder.push(", description = ");
+        builder.push_bind(description);
+    }
+    if let Some(price) = payload.price {
+        builder.push(", price = ");
+        builder.push_bind(price);
+    }
+    if let Some(stock_quantity) = payload.stock_quantity {
+        builder.push(", stock_quantity = ");
+        builder.push_bind(stock_quantity);
+    }
+    if let Some(sku) = &payload.sku {
+        builder.push(", sku = ");
+        builder.push_bind(sku);
+    }
+    if let Some(category) = &payload.category {
+        builder.push(", category = ");
+        builder.push_bind(category);
+    }
+
+    builder.push(" WHERE id = ");
+    builder.push_bind(&id);
+    builder.push(" AND tenant_id = ");
+    builder.push_bind(&claims.tenant_id);
+
+    match builder.build().execute(&mut *tx).await {
+        Ok(r) if r.rows_affected() == 0 => {
+            let _ = tx.rollback().await;
+            return (StatusCode::NOT_FOUND, "Product not found").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    if let (Some(old), Some(new)) = (old_price, payload.price)
+        && old != new
+    {
+        let event = Event::new("product.price_change", &id)
+            .before(Some(serde_json::json!({ "price": old })))
+            .after(Some(serde_json::json!({ "price": new })));
+        if let Err(e) = audit.record(&mut *tx, event).await {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to write audit log: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Product updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /products/categories
+/// The store's categories, plus any its products use that were never
+/// added to the list
+pub async fn list_categories(
+    _: Require<perm::ProductsView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let categories: Result<Vec<String>, sqlx::Error> = sqlx::query_scalar(
+        r#"
+        SELECT name FROM product_categories WHERE tenant_id = $1::uuid
+        UNION
+        SELECT category FROM products WHERE tenant_id = $1::uuid AND category IS NOT NULL
+        ORDER BY 1
+        "#,
+    )
+    .bind(&claims.tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match categories {
+        Ok(categories) => Json(categories).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /products/categories
+pub async fn create_category(
+    _: Require<perm::ProductsEdit>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateCategoryRequest>,
+) -> impl IntoResponse {
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Category name must have 1 to 100 characters",
+        )
+            .into_response();
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO product_categories (tenant_id, name) VALUES ($1::uuid, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(&claims.tenant_id)
+    .bind(name)
+    .execute(&mut *tx)
+    .await;
+
+    match tx.commit_if_ok(result).await {
+        Ok(done) if done.rows_affected() == 0 => {
+            (StatusCode::CONFLICT, "Category already exists").into_response()
+        }
+        Ok(_) => (StatusCode::CREATED, Json(name)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,375 @@
+//! Tenant-defined roles and staff role assignment.
+
+use crate::auth::Claims;
+use crate::handlers::auth::USER_COLUMNS;
+use crate::handlers::sessions;
+use crate::models::{AssignRoleRequest, TenantRole, TenantRoleRequest, User};
+use crate::permissions::{Permission, RESERVED_ROLES, Require, perm, role_template};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+const TEMPLATES: [&str; 3] = ["manager", "cashier", "stockist"];
+const ROLE_COLUMNS: &str =
+    "id::text AS id, tenant_id::text AS tenant_id, name, permissions, created_at, updated_at";
+
+#[derive(Debug, Serialize)]
+pub struct PermissionCatalog {
+    pub permissions: Vec<&'static str>,
+    pub templates: BTreeMap<&'static str, Vec<&'static str>>,
+}
+
+/// Normalised role name and permission list, or why they are invalid.
+fn validate(payload: &TenantRoleRequest) -> Result<(String, Vec<String>), String> {
+    let name = payload.name.trim().to_lowercase();
+    if name.is_empty() || name.len() > 50 {
+        return Err("Role name must have 1 to 50 characters".to_string());
+    }
+    if RESERVED_ROLES.contains(&name.as_str()) {
+        return Err(format!("'{}' is a built-in role", name));
+    }
+
+    let mut permissions = Vec::new();
+    for value in &payload.permissions {
+        match Permission::parse(value) {
+            Some(p) if !p.is_platform() => {
+                if !permissions.contains(&p) {
+                    permissions.push(p);
+                }
+            }
+            _ => return Err(format!("Unknown permission: {}", value)),
+        }
+    }
+
+    Ok((
+        name,
+        permissions.iter().map(|p| p.as_str().to_string()).collect(),
+    ))
+}
+
+/// GET /roles/permissions
+/// Permissions a tenant can grant, plus the default role templates
+pub async fn list_permissions(_: Require<perm::RolesManage>) -> impl IntoResponse {
+    let templates = TEMPLATES
+        .iter()
+        .map(|name| {
+            let permissions = role_template(name)
+                .unwrap_or_default()
+                .into_iter()
+                .map(Permission::as_str)
+                .collect();
+            (*name, permissions)
+        })
+        .collect();
+
+    Json(PermissionCatalog {
+        permissions: Permission::tenant_permissions()
+            .map(Permission::as_str)
+            .collect(),
+        templates,
+    })
+}
+
+pub async fn list_roles(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let roles = sqlx::query_as::<_, TenantRole>(&format!(
+        "SELECT {} FROM tenant_roles WHERE tenant_id = $1::uuid ORDER BY name ASC",
+        ROLE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match roles {
+        Ok(roles) => Json(roles).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list roles: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list roles").into_response()
+        }
+    }
+}
+
+pub async fn create_role(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<TenantRoleRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let (name, permissions) = match validate(&payload) {
+        Ok(role) => role,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let result = sqlx::query(
+        "INSERT INTO tenant_roles (id, tenant_id, name, permissions) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .bind(&name)
+    .bind(&permissions)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(e) => {
+            if e.to_string().contains("23505") || e.to_string().contains("duplicate key") {
+                (StatusCode::CONFLICT, "Role already exists").into_response()
+            } else {
+                eprintln!("Failed to create role: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create role").into_response()
+            }
+        }
+    }
+}
+
+/// PUT /roles/{id}
+/// Changes apply on the next request of every user holding the role
+pub async fn update_role(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<TenantRoleRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let (name, permissions) = match validate(&payload) {
+        Ok(role) => role,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let old_name: Option<String> = match sqlx::query_scalar(
+        "SELECT name FROM tenant_roles WHERE id = $1 AND tenant_id = $2 FOR UPDATE",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(name) => name,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let Some(old_name) = old_name else {
+        return (StatusCode::NOT_FOUND, "Role not found").into_response();
+    };
+
+    let result = async {
+        sqlx::query(
+            "UPDATE tenant_roles SET name = $1, permissions = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $3",
+        )
+        .bind(&name)
+        .bind(&permissions)
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Renaming keeps the role's users attached to it
+        sqlx::query("UPDATE users SET role = $1 WHERE tenant_id = $2 AND role = $3")
+            .bind(&name)
+            .bind(&tenant_id)
+            .bind(&old_name)
+            .execute(&mut *tx)
+            .await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        if e.to_string().contains("23505") || e.to_string().contains("duplicate key") {
+            return (StatusCode::CONFLICT, "Role already exists").into_response();
+        }
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to update role: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Role updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn delete_role(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let in_use: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM users u JOIN tenant_roles r ON u.role = r.name AND u.tenant_id = r.tenant_id WHERE r.id = $1 AND r.tenant_id = $2)",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap_or(false);
+
+    if in_use {
+        return (StatusCode::CONFLICT, "Role is assigned to users").into_response();
+    }
+
+    let result = sqlx::query("DELETE FROM tenant_roles WHERE id = $1 AND tenant_id = $2")
+        .bind(&id)
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Role not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Role deleted").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to delete: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /roles/users
+/// Staff of the current tenant with their roles
+pub async fn list_staff(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let users = sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM users WHERE tenant_id = $1::uuid ORDER BY created_at ASC",
+        USER_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match users {
+        Ok(users) => Json(users).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list users: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list users").into_response()
+        }
+    }
+}
+
+/// PUT /roles/users/{user_id}
+/// Gives a staff member a template, custom or owner (`user`) role
+pub async fn assign_role(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<AssignRoleRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    if user_id == claims.sub {
+        return (StatusCode::BAD_REQUEST, "Cannot change your own role").into_response();
+    }
+
+    let role = payload.role.trim().to_lowercase();
+    let known = if role == "user" || TEMPLATES.contains(&role.as_str()) {
+        true
+    } else if RESERVED_ROLES.contains(&role.as_str()) {
+        false
+    } else {
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM tenant_roles WHERE tenant_id = $1 AND name = $2)",
+        )
+        .bind(&tenant_id)
+        .bind(&role)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap_or(false)
+    };
+
+    if !known {
+        return (StatusCode::BAD_REQUEST, "Unknown role").into_response();
+    }
+
+    let result = sqlx::query(
+        "UPDATE users SET role = $1 WHERE id = $2 AND tenant_id = $3 AND role NOT IN ('admin', 'reseller')",
+    )
+    .bind(&role)
+    .bind(&user_id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            return (StatusCode::NOT_FOUND, "User not found").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to assign role: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    // The old role is baked into issued tokens
+    let revoked = sessions::revoke_user_sessions(&mut *tx, &user_id).await;
+    match tx.commit_if_ok(revoked).await {
+        Ok(_) => (StatusCode::OK, "Role assigned").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke sessions: {}", e),
+        )
+            .into_response(),
+    }
+}
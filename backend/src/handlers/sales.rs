@@ -1,24 +1,54 @@
+use crate::audit::{AuditContext, Event};
 use crate::auth::Claims;
+use crate::handlers::{coupons, deliveries, gift_cards, loyalty, payment_methods};
 use crate::models::{CreateSaleRequest, Sale};
+use crate::permissions::{Require, perm};
+use crate::plans::{self, Entitlements, Feature};
+use crate::tenant_db::TenantTx;
 use axum::{
     Json,
-    extract::{Extension, State},
+    extract::{Extension, Path},
     http::StatusCode,
     response::IntoResponse,
 };
 use serde::Serialize;
-use sqlx::{FromRow, PgPool, Row};
+use sqlx::{FromRow, Row};
 use uuid::Uuid;
 
+pub const SALE_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, user_id::text AS user_id, customer_id::text AS customer_id, total_amount, discount_amount, coupon_id::text AS coupon_id, loyalty_discount, delivery_fee, payment_method, status, terminal_id::text AS terminal_id, created_at";
+
+/// The first paid feature the sale uses that the tenant's plan lacks.
+fn missing_feature(
+    payload: &CreateSaleRequest,
+    entitlements: &Entitlements,
+    sells_gift_cards: bool,
+) -> Option<Feature> {
+    let pays_with_gift_cards = payload
+        .gift_card_payments
+        .as_ref()
+        .is_some_and(|p| !p.is_empty());
+    [
+        (payload.coupon_code.is_some(), Feature::Coupons),
+        (payload.loyalty_points.unwrap_or(0) != 0, Feature::Loyalty),
+        (sells_gift_cards || pays_with_gift_cards, Feature::GiftCards),
+        (payload.delivery.is_some(), Feature::Deliveries),
+    ]
+    .into_iter()
+    .find(|(used, feature)| *used && !entitlements.allows(*feature))
+    .map(|(_, feature)| feature)
+}
+
 pub async fn list_sales(
-    State(pool): State<PgPool>,
+    _: Require<perm::SalesView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
-    let sales = sqlx::query_as::<_, Sale>(
-        "SELECT * FROM sales WHERE tenant_id = $1 ORDER BY created_at DESC",
-    )
+    let sales = sqlx::query_as::<_, Sale>(&format!(
+        "SELECT {} FROM sales WHERE tenant_id = $1::uuid ORDER BY created_at DESC",
+        SALE_COLUMNS
+    ))
     .bind(&claims.tenant_id)
-    .fetch_all(&pool)
+    .fetch_all(&mut *tx)
     .await;
 
     match sales {
@@ -32,29 +62,43 @@ pub async fn list_sales(
 }
 
 pub async fn create_sale(
-    State(pool): State<PgPool>,
+    _: Require<perm::SalesCreate>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
     Json(payload): Json<CreateSaleRequest>,
 ) -> impl IntoResponse {
-    let mut tx = match pool.begin().await {
-        Ok(tx) => tx,
+    let sale_id = Uuid::new_v4().to_string();
+    let tenant_id = claims.tenant_id.clone().unwrap_or_default();
+    let mut total_amount = 0;
+    let mut gift_card_activations = Vec::new();
+    let mut sale_items = Vec::new();
+
+    // Only the store's enabled payment methods, once it has set them up
+    match payment_methods::accepts(&mut tx, &tenant_id, &payload.payment_method).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Payment method not accepted: {}", payload.payment_method),
+            )
+                .into_response();
+        }
         Err(e) => {
+            let _ = tx.rollback().await;
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to start transaction: {}", e),
+                format!("Database error: {}", e),
             )
                 .into_response();
         }
-    };
-
-    let sale_id = Uuid::new_v4().to_string();
-    let mut total_amount = 0;
+    }
 
     // Validate items and calculate total
     for item in &payload.items {
         // Fetch product to get price and check stock
         let product = sqlx::query(
-            "SELECT price, stock_quantity FROM products WHERE id = $1 AND tenant_id = $2",
+            "SELECT price, stock_quantity, is_gift_card FROM products WHERE id = $1 AND tenant_id = $2",
         )
         .bind(&item.product_id)
         .bind(&claims.tenant_id)
@@ -65,6 +109,7 @@ pub async fn create_sale(
             Ok(Some(row)) => {
                 let price: i32 = row.get("price");
                 let stock: i32 = row.get("stock_quantity");
+                let is_gift_card: bool = row.get("is_gift_card");
 
                 if stock < item.quantity {
                     let _ = tx.rollback().await;
@@ -78,6 +123,23 @@ pub async fn create_sale(
                 let subtotal = price * item.quantity;
                 total_amount += subtotal;
 
+                if is_gift_card {
+                    match (&item.gift_card_code, item.quantity) {
+                        (Some(code), 1) => gift_card_activations.push((code, subtotal as i64)),
+                        _ => {
+                            let _ = tx.rollback().await;
+                            return (
+                                StatusCode::BAD_REQUEST,
+                                format!(
+                                    "Gift card product {} must be sold one at a time with a gift_card_code",
+                                    item.product_id
+                                ),
+                            )
+                                .into_response();
+                        }
+                    }
+                }
+
                 // Update stock
                 let update_stock = sqlx::query(
                     "UPDATE products SET stock_quantity = stock_quantity - $1 WHERE id = $2",
@@ -96,26 +158,8 @@ pub async fn create_sale(
                         .into_response();
                 }
 
-                // Insert Sale Item
-                let item_id = Uuid::new_v4().to_string();
-                let insert_item = sqlx::query("INSERT INTO sale_items (id, sale_id, product_id, quantity, unit_price, subtotal) VALUES ($1, $2, $3, $4, $5, $6)")
-                    .bind(&item_id)
-                    .bind(&sale_id)
-                    .bind(&item.product_id)
-                    .bind(item.quantity)
-                    .bind(price)
-                    .bind(subtotal)
-                    .execute(&mut *tx)
-                    .await;
-
-                if let Err(e) = insert_item {
-                    let _ = tx.rollback().await;
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Failed to insert sale item: {}", e),
-                    )
-                        .into_response();
-                }
+                // Items go in once their sale exists
+                sale_items.push((&item.product_id, item.quantity, price, subtotal));
             }
             Ok(None) => {
                 let _ = tx.rollback().await;
@@ -136,15 +180,130 @@ pub async fn create_sale(
         }
     }
 
+    // A downgraded plan no longer unlocks its paid features at checkout
+    let entitlements = match Entitlements::load(&mut *tx, &tenant_id, false).await {
+        Ok(entitlements) => entitlements,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+    if let Some(feature) =
+        missing_feature(&payload, &entitlements, !gift_card_activations.is_empty())
+    {
+        let limit = plans::feature_limit(&mut *tx, &entitlements, feature).await;
+        let _ = tx.rollback().await;
+        return match limit {
+            Ok(limit) => limit.into_response(),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response(),
+        };
+    }
+
+    // Apply coupon (locks the coupon row until commit)
+    let applied_coupon = match &payload.coupon_code {
+        Some(code) => match coupons::apply_coupon(
+            &mut tx,
+            claims.tenant_id.as_deref(),
+            code,
+            payload.customer_id.as_deref(),
+            total_amount as i64,
+        )
+        .await
+        {
+            Ok(applied) => Some(applied),
+            Err((status, message)) => {
+                let _ = tx.rollback().await;
+                return (status, message).into_response();
+            }
+        },
+        None => None,
+    };
+    let discount_amount = applied_coupon.as_ref().map_or(0, |c| c.discount_amount);
+
+    // Loyalty points redeemed as a discount
+    let loyalty_points = payload.loyalty_points.unwrap_or(0);
+    let loyalty_discount = if loyalty_points != 0 {
+        match loyalty::quote_redemption(
+            &mut tx,
+            &tenant_id,
+            payload.customer_id.as_deref(),
+            loyalty_points,
+        )
+        .await
+        {
+            Ok(discount) if discount <= total_amount as i64 - discount_amount => discount,
+            Ok(_) => {
+                let _ = tx.rollback().await;
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "Loyalty points exceed the sale total",
+                )
+                    .into_response();
+            }
+            Err((status, message)) => {
+                let _ = tx.rollback().await;
+                return (status, message).into_response();
+            }
+        }
+    } else {
+        0
+    };
+    let sale_total = total_amount as i64 - discount_amount - loyalty_discount;
+
+    // Delivery fee from the zone of the customer's address
+    let delivery_quote = match &payload.delivery {
+        Some(delivery) => match deliveries::quote_delivery(
+            &mut tx,
+            &tenant_id,
+            payload.customer_id.as_deref(),
+            &delivery.address_id,
+        )
+        .await
+        {
+            Ok(quote) => Some(quote),
+            Err((status, message)) => {
+                let _ = tx.rollback().await;
+                return (status, message).into_response();
+            }
+        },
+        None => None,
+    };
+    let delivery_fee = delivery_quote.as_ref().map_or(0, |q| q.fee);
+
+    let gift_card_payments = payload.gift_card_payments.as_deref().unwrap_or_default();
+    let gift_card_total: i64 = gift_card_payments.iter().map(|p| p.amount).sum();
+    if gift_card_total > sale_total + delivery_fee {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::BAD_REQUEST,
+            "Gift card payments exceed the sale total",
+        )
+            .into_response();
+    }
+
     // Insert Sale
-    let insert_sale = sqlx::query("INSERT INTO sales (id, tenant_id, user_id, customer_id, total_amount, payment_method, status) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+    // Sales rung up on a shared terminal also record the device
+    let insert_sale = sqlx::query("INSERT INTO sales (id, tenant_id, user_id, customer_id, total_amount, discount_amount, coupon_id, loyalty_discount, delivery_fee, payment_method, status, terminal_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, (SELECT terminal_id FROM sessions WHERE id = $12))")
         .bind(&sale_id)
         .bind(&claims.tenant_id)
         .bind(&claims.sub) // user_id from token sub
         .bind(&payload.customer_id)
-        .bind(total_amount)
+        .bind(sale_total + delivery_fee)
+        .bind(discount_amount)
+        .bind(applied_coupon.as_ref().map(|c| &c.coupon_id))
+        .bind(loyalty_discount)
+        .bind(delivery_fee)
         .bind(&payload.payment_method)
         .bind("completed")
+        .bind(&claims.sid)
         .execute(&mut *tx)
         .await;
 
@@ -157,6 +316,118 @@ pub async fn create_sale(
             .into_response();
     }
 
+    for (product_id, quantity, price, subtotal) in sale_items {
+        let item_id = Uuid::new_v4().to_string();
+        let insert_item = sqlx::query("INSERT INTO sale_items (id, sale_id, product_id, quantity, unit_price, subtotal) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(&item_id)
+            .bind(&sale_id)
+            .bind(product_id)
+            .bind(quantity)
+            .bind(price)
+            .bind(subtotal)
+            .execute(&mut *tx)
+            .await;
+
+        if let Err(e) = insert_item {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to insert sale item: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    if let Some(applied) = &applied_coupon
+        && let Err(e) =
+            coupons::record_redemption(&mut tx, applied, &sale_id, payload.customer_id.as_deref())
+                .await
+    {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to record coupon redemption: {}", e),
+        )
+            .into_response();
+    }
+
+    // Gift cards: activate the ones sold, debit the ones used as payment
+    for (code, amount) in &gift_card_activations {
+        if let Err((status, message)) = gift_cards::activate_card(
+            &mut tx,
+            &tenant_id,
+            code,
+            *amount,
+            payload.customer_id.as_deref(),
+            &sale_id,
+        )
+        .await
+        {
+            let _ = tx.rollback().await;
+            return (status, message).into_response();
+        }
+    }
+
+    for payment in gift_card_payments {
+        if let Err((status, message)) =
+            gift_cards::redeem_card(&mut tx, &tenant_id, &payment.code, payment.amount, &sale_id)
+                .await
+        {
+            let _ = tx.rollback().await;
+            return (status, message).into_response();
+        }
+    }
+
+    // Loyalty: spend redeemed points, then earn on what was actually paid
+    if let Some(customer_id) = &payload.customer_id {
+        let mut result = Ok(());
+        if loyalty_points != 0 {
+            result =
+                loyalty::redeem_points(&mut tx, &tenant_id, customer_id, &sale_id, loyalty_points)
+                    .await;
+        }
+        if result.is_ok() && entitlements.allows(Feature::Loyalty) {
+            result = loyalty::earn_points(
+                &mut tx,
+                &tenant_id,
+                customer_id,
+                &sale_id,
+                (sale_total - gift_card_total).max(0),
+            )
+            .await;
+        }
+        if let Err(e) = result {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update loyalty points: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    if let Some(quote) = &delivery_quote
+        && let Err(e) = deliveries::create_delivery(
+            &mut tx,
+            &tenant_id,
+            &sale_id,
+            payload.customer_id.as_deref(),
+            quote,
+            payload
+                .delivery
+                .as_ref()
+                .and_then(|d| d.courier_name.as_deref()),
+        )
+        .await
+    {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create delivery: {}", e),
+        )
+            .into_response();
+    }
+
     if let Err(e) = tx.commit().await {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -168,6 +439,105 @@ pub async fn create_sale(
     (StatusCode::CREATED, Json(sale_id)).into_response()
 }
 
+/// POST /sales/{id}/cancel
+/// Cancels a completed sale, restoring stock, loyalty points and gift card balances
+pub async fn cancel_sale(
+    _: Require<perm::SalesCancel>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let status: Option<String> = match sqlx::query_scalar(
+        "SELECT status FROM sales WHERE id = $1 AND tenant_id = $2 FOR UPDATE",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(status) => status,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match status.as_deref() {
+        None => return (StatusCode::NOT_FOUND, "Sale not found").into_response(),
+        Some("cancelled") => {
+            return (StatusCode::BAD_REQUEST, "Sale already cancelled").into_response();
+        }
+        Some(_) => {}
+    }
+
+    if let Err((status, message)) = gift_cards::void_sale_cards(&mut tx, &id).await {
+        let _ = tx.rollback().await;
+        return (status, message).into_response();
+    }
+
+    let result = async {
+        sqlx::query("UPDATE sales SET status = 'cancelled' WHERE id = $1")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE products p SET stock_quantity = p.stock_quantity + si.quantity
+            FROM sale_items si
+            WHERE si.sale_id = $1 AND p.id = si.product_id
+            "#,
+        )
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE deliveries SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP WHERE sale_id = $1 AND status <> 'delivered'",
+        )
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+
+        coupons::release_sale(&mut tx, &id).await?;
+        loyalty::reverse_sale(&mut tx, &tenant_id, &id).await?;
+        gift_cards::refund_sale(&mut tx, &id).await?;
+
+        let event = Event::new("sale.cancel", &id)
+            .before(status.map(|status| serde_json::json!({ "status": status })))
+            .after(Some(serde_json::json!({ "status": "cancelled" })));
+        audit.record(&mut *tx, event).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to cancel sale: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Sale cancelled").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
 #[derive(Debug, Serialize, FromRow)]
 pub struct DashboardStats {
     pub total_revenue: i32, /* Changed to i32 to match DB type usually, or i64 for safety */
@@ -176,7 +546,8 @@ pub struct DashboardStats {
 }
 
 pub async fn get_dashboard_stats(
-    State(pool): State<PgPool>,
+    _: Require<perm::SalesView>,
+    mut tx: TenantTx,
     Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
     let tenant_id = match claims.tenant_id {
@@ -184,31 +555,35 @@ pub async fn get_dashboard_stats(
         None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
     };
 
-    // Calculate total revenue
-    let revenue_row: (i32,) =
-        sqlx::query_as("SELECT COALESCE(SUM(total_amount), 0) FROM sales WHERE tenant_id = $1")
-            .bind(&tenant_id)
-            .fetch_one(&pool)
-            .await
-            .unwrap_or((0,));
+    // Calculate total revenue; cancelled sales are not revenue
+    let revenue_row: (i32,) = sqlx::query_as(
+        "SELECT COALESCE(SUM(total_amount), 0)::INT FROM sales WHERE tenant_id = $1 AND status <> 'cancelled'",
+    )
+    .bind(&tenant_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap_or((0,));
 
     let total_revenue = revenue_row.0;
 
     // Calculate sales count
-    let count_row: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM sales WHERE tenant_id = $1")
-        .bind(&tenant_id)
-        .fetch_one(&pool)
-        .await
-        .unwrap_or((0,));
+    let count_row: (i32,) = sqlx::query_as(
+        "SELECT COUNT(*)::INT FROM sales WHERE tenant_id = $1 AND status <> 'cancelled'",
+    )
+    .bind(&tenant_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap_or((0,));
 
     let sales_count = count_row.0;
 
     // Fetch recent sales (last 5)
-    let recent_sales = sqlx::query_as::<_, Sale>(
-        "SELECT * FROM sales WHERE tenant_id = $1 ORDER BY created_at DESC LIMIT 5",
-    )
+    let recent_sales = sqlx::query_as::<_, Sale>(&format!(
+        "SELECT {} FROM sales WHERE tenant_id = $1::uuid ORDER BY created_at DESC LIMIT 5",
+        SALE_COLUMNS
+    ))
     .bind(&tenant_id)
-    .fetch_all(&pool)
+    .fetch_all(&mut *tx)
     .await
     .unwrap_or_default();
 
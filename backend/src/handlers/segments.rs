@@ -0,0 +1,489 @@
+//! Customer analytics: RFM scoring and saved segments with CSV export.
+
+use crate::auth::Claims;
+use crate::models::{CustomerSegment, CustomerSegmentRequest};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{Extension, Path, Query},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+const SEGMENT_COLUMNS: &str = "id::text AS id, tenant_id::text AS tenant_id, name, description, min_spent, inactive_days, category, created_at, updated_at";
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CustomerRfm {
+    pub customer_id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub last_purchase_at: chrono::NaiveDateTime,
+    pub recency_days: i32,
+    pub frequency: i64,
+    pub monetary: i64, // in cents
+    pub r_score: i32,  // 1 - 5, 5 = bought most recently
+    pub f_score: i32,
+    pub m_score: i32,
+    #[sqlx(skip)]
+    pub segment: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RfmQuery {
+    pub segment: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SegmentMember {
+    pub customer_id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub marketing_consent: bool,
+    pub total_spent: i64, // in cents
+    pub orders: i64,
+    pub last_purchase_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Classic RFM grid collapsed into a handful of actionable groups.
+fn rfm_segment(r: i32, f: i32, m: i32) -> &'static str {
+    match (r, f, m) {
+        (4..=5, 4..=5, _) => "champions",
+        (3..=5, 3..=5, _) => "loyal",
+        (4..=5, _, _) => "new",
+        (1..=2, _, 4..=5) | (1..=2, 4..=5, _) => "cant_lose",
+        (1..=2, 3, _) => "at_risk",
+        (1..=2, _, _) => "lapsed",
+        _ => "needs_attention",
+    }
+}
+
+fn validate(payload: &CustomerSegmentRequest) -> Result<(), &'static str> {
+    if payload.name.trim().is_empty() {
+        return Err("Segment name is required");
+    }
+    if payload.min_spent.is_some_and(|v| v < 0) {
+        return Err("min_spent must not be negative");
+    }
+    if payload.inactive_days.is_some_and(|v| v <= 0) {
+        return Err("inactive_days must be positive");
+    }
+    Ok(())
+}
+
+fn category_filter(category: &Option<String>) -> Option<String> {
+    category
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+}
+
+async fn fetch_segment(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<CustomerSegment, (StatusCode, String)> {
+    let segment = sqlx::query_as::<_, CustomerSegment>(&format!(
+        "SELECT {} FROM customer_segments WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        SEGMENT_COLUMNS
+    ))
+    .bind(id)
+    .bind(tenant_id)
+    .fetch_optional(conn)
+    .await;
+
+    match segment {
+        Ok(Some(segment)) => Ok(segment),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Segment not found".to_string())),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )),
+    }
+}
+
+/// Customers matching every filter set on the segment.
+async fn fetch_members(
+    conn: &mut PgConnection,
+    segment: &CustomerSegment,
+) -> Result<Vec<SegmentMember>, sqlx::Error> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"
+        SELECT c.id::text AS customer_id, c.name, c.email, c.phone, c.marketing_consent,
+               COALESCE(s.total_spent, 0)::BIGINT AS total_spent,
+               COALESCE(s.orders, 0) AS orders,
+               s.last_purchase_at
+        FROM customers c
+        LEFT JOIN (
+            SELECT customer_id, SUM(total_amount) AS total_spent, COUNT(*) AS orders,
+                   MAX(created_at) AS last_purchase_at
+            FROM sales
+            WHERE status <> 'cancelled' AND tenant_id = "#,
+    );
+    builder.push_bind(&segment.tenant_id);
+    builder.push(
+        r#"
+            GROUP BY customer_id
+        ) s ON s.customer_id = c.id
+        WHERE c.anonymized_at IS NULL AND c.tenant_id = "#,
+    );
+    builder.push_bind(&segment.tenant_id);
+
+    if let Some(min_spent) = segment.min_spent {
+        builder.push(" AND COALESCE(s.total_spent, 0) > ");
+        builder.push_bind(min_spent);
+    }
+    if let Some(days) = segment.inactive_days {
+        builder.push(
+            " AND (s.last_purchase_at IS NULL OR s.last_purchase_at < CURRENT_TIMESTAMP - make_interval(days => ",
+        );
+        builder.push_bind(days);
+        builder.push("))");
+    }
+    if let Some(category) = &segment.category {
+        builder.push(
+            r#" AND EXISTS (
+                SELECT 1 FROM sale_items si
+                JOIN sales cs ON si.sale_id = cs.id
+                JOIN products p ON si.product_id = p.id
+                WHERE cs.customer_id = c.id AND cs.status <> 'cancelled'
+                AND LOWER(p.category) = LOWER("#,
+        );
+        builder.push_bind(category);
+        builder.push("))");
+    }
+
+    builder.push(" ORDER BY total_spent DESC, c.name ASC");
+
+    builder
+        .build_query_as::<SegmentMember>()
+        .fetch_all(conn)
+        .await
+}
+
+fn csv_field(value: &str) -> String {
+    // Keep spreadsheet apps from evaluating cells as formulas
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn members_to_csv(members: &[SegmentMember]) -> String {
+    let mut csv = String::from(
+        "customer_id,name,email,phone,marketing_consent,total_spent_cents,orders,last_purchase_at\n",
+    );
+    for m in members {
+        let row = [
+            csv_field(&m.customer_id),
+            csv_field(&m.name),
+            csv_field(m.email.as_deref().unwrap_or("")),
+            csv_field(m.phone.as_deref().unwrap_or("")),
+            m.marketing_consent.to_string(),
+            m.total_spent.to_string(),
+            m.orders.to_string(),
+            m.last_purchase_at
+                .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// GET /segments/rfm
+/// Recency, frequency and monetary quintile scores per purchasing customer
+pub async fn get_rfm(
+    _: Require<perm::ReportsView>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<RfmQuery>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let rows = sqlx::query_as::<_, CustomerRfm>(
+        r#"
+        WITH stats AS (
+            SELECT c.id::text AS customer_id, c.name, c.email, c.phone,
+                   MAX(s.created_at) AS last_purchase_at,
+                   COUNT(s.id) AS frequency,
+                   SUM(s.total_amount)::BIGINT AS monetary
+            FROM customers c
+            JOIN sales s ON s.customer_id = c.id AND s.status <> 'cancelled'
+            WHERE c.tenant_id = $1 AND c.anonymized_at IS NULL
+            GROUP BY c.id
+        )
+        SELECT customer_id, name, email, phone, last_purchase_at, frequency, monetary,
+               EXTRACT(DAY FROM CURRENT_TIMESTAMP - last_purchase_at)::INT AS recency_days,
+               NTILE(5) OVER (ORDER BY last_purchase_at ASC) AS r_score,
+               NTILE(5) OVER (ORDER BY frequency ASC) AS f_score,
+               NTILE(5) OVER (ORDER BY monetary ASC) AS m_score
+        FROM stats
+        ORDER BY monetary DESC
+        "#,
+    )
+    .bind(&tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    let mut rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Failed to compute RFM scores: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to compute RFM scores",
+            )
+                .into_response();
+        }
+    };
+
+    for row in rows.iter_mut() {
+        row.segment = rfm_segment(row.r_score, row.f_score, row.m_score);
+    }
+    if let Some(segment) = params.segment.as_deref() {
+        rows.retain(|row| row.segment == segment);
+    }
+
+    Json(rows).into_response()
+}
+
+pub async fn list_segments(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let segments = sqlx::query_as::<_, CustomerSegment>(&format!(
+        "SELECT {} FROM customer_segments WHERE tenant_id = $1::uuid ORDER BY name ASC",
+        SEGMENT_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match segments {
+        Ok(segments) => Json(segments).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list segments: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list segments").into_response()
+        }
+    }
+}
+
+pub async fn create_segment(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CustomerSegmentRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    if let Err(msg) = validate(&payload) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let result = sqlx::query(
+        "INSERT INTO customer_segments (id, tenant_id, name, description, min_spent, inactive_days, category) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .bind(payload.name.trim())
+    .bind(&payload.description)
+    .bind(payload.min_spent)
+    .bind(payload.inactive_days)
+    .bind(category_filter(&payload.category))
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(_) => (StatusCode::CREATED, Json(id)).into_response(),
+        Err(e) => {
+            eprintln!("Failed to create segment: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create segment",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn update_segment(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<CustomerSegmentRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    if let Err(msg) = validate(&payload) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE customer_segments SET
+            name = $1, description = $2, min_spent = $3, inactive_days = $4, category = $5,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $6 AND tenant_id = $7
+        "#,
+    )
+    .bind(payload.name.trim())
+    .bind(&payload.description)
+    .bind(payload.min_spent)
+    .bind(payload.inactive_days)
+    .bind(category_filter(&payload.category))
+    .bind(&id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Segment not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Segment updated").into_response(),
+        Err(e) => {
+            eprintln!("Failed to update segment: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update segment",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn delete_segment(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query("DELETE FROM customer_segments WHERE id = $1 AND tenant_id = $2")
+        .bind(&id)
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Segment not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Segment deleted").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to delete: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /segments/{id}/customers
+pub async fn get_segment_customers(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let segment = match fetch_segment(&mut tx, &tenant_id, &id).await {
+        Ok(segment) => segment,
+        Err(e) => return e.into_response(),
+    };
+
+    match fetch_members(&mut tx, &segment).await {
+        Ok(members) => Json(members).into_response(),
+        Err(e) => {
+            eprintln!("Failed to load segment customers: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load segment customers",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /segments/{id}/export
+/// Segment members as a CSV download
+pub async fn export_segment(
+    _: Require<perm::MarketingManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let segment = match fetch_segment(&mut tx, &tenant_id, &id).await {
+        Ok(segment) => segment,
+        Err(e) => return e.into_response(),
+    };
+
+    let members = match fetch_members(&mut tx, &segment).await {
+        Ok(members) => members,
+        Err(e) => {
+            eprintln!("Failed to export segment: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to export segment",
+            )
+                .into_response();
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"segment-{}.csv\"", id);
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        members_to_csv(&members),
+    )
+        .into_response()
+}
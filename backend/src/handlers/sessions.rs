@@ -0,0 +1,349 @@
+//! Login sessions: rotating refresh tokens, logout and revocation.
+
+use crate::auth::Claims;
+use crate::handlers::auth::USER_COLUMNS;
+use crate::jwt::{ACCESS_TOKEN_TTL, JwtKeys};
+use crate::models::{RefreshRequest, Session, TokenResponse, User};
+use crate::permissions::{Require, perm};
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Refresh tokens (and so sessions) expire after this many days without use.
+const REFRESH_TOKEN_DAYS: i32 = 30;
+
+#[derive(FromRow)]
+struct SessionRow {
+    id: String,
+    user_id: String,
+    expires_at: chrono::NaiveDateTime,
+    revoked_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Random URL-safe token, for refresh tokens and emailed links.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Opens a session for a freshly authenticated user, bound to a terminal
+/// for PIN logins. Returns the session id and the refresh token.
+pub async fn create_session(
+    pool: &PgPool,
+    user_id: &str,
+    user_agent: Option<&str>,
+    terminal_id: Option<&str>,
+) -> Result<(String, String), sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let refresh_token = generate_token();
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, refresh_token_hash, user_agent, terminal_id, expires_at) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP + make_interval(days => $6))",
+    )
+    .bind(&id)
+    .bind(user_id)
+    .bind(hash_token(&refresh_token))
+    .bind(user_agent)
+    .bind(terminal_id)
+    .bind(REFRESH_TOKEN_DAYS)
+    .execute(pool)
+    .await?;
+
+    Ok((id, refresh_token))
+}
+
+/// Whether an access token's session is still valid; checked on every request.
+pub async fn is_active(pool: &PgPool, session_id: &str, user_id: &str) -> bool {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM sessions WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP)",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .unwrap_or(false)
+}
+
+/// Revokes every open session of a user, logging them out everywhere.
+pub async fn revoke_user_sessions<'e>(
+    executor: impl PgExecutor<'e>,
+    user_id: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// POST /auth/refresh
+/// Trades a refresh token for a new access token and a new refresh token
+pub async fn refresh(
+    State(pool): State<PgPool>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    Json(payload): Json<RefreshRequest>,
+) -> impl IntoResponse {
+    let token_hash = hash_token(&payload.refresh_token);
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let session = sqlx::query_as::<_, SessionRow>(
+        "SELECT id::text AS id, user_id::text AS user_id, expires_at, revoked_at FROM sessions WHERE refresh_token_hash = $1 FOR UPDATE",
+    )
+    .bind(&token_hash)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let session = match session {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            // A rotated token being replayed means it leaked: kill the session
+            let _ = sqlx::query(
+                "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE previous_token_hash = $1 AND revoked_at IS NULL",
+            )
+            .bind(&token_hash)
+            .execute(&mut *tx)
+            .await;
+            let _ = tx.commit().await;
+            return (StatusCode::UNAUTHORIZED, "Invalid refresh token").into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if session.revoked_at.is_some() || session.expires_at <= chrono::Utc::now().naive_utc() {
+        let _ = tx.rollback().await;
+        return (StatusCode::UNAUTHORIZED, "Session expired").into_response();
+    }
+
+    // Role and tenant are re-read so a demotion applies on the next refresh
+    let user = sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM users WHERE id = $1::uuid",
+        USER_COLUMNS
+    ))
+    .bind(&session.user_id)
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let user = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::UNAUTHORIZED, "Session expired").into_response();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let refresh_token = generate_token();
+    let result = sqlx::query(
+        r#"
+        UPDATE sessions SET
+            previous_token_hash = refresh_token_hash,
+            refresh_token_hash = $1,
+            last_used_at = CURRENT_TIMESTAMP,
+            expires_at = CURRENT_TIMESTAMP + make_interval(days => $2)
+        WHERE id = $3
+        "#,
+    )
+    .bind(hash_token(&refresh_token))
+    .bind(REFRESH_TOKEN_DAYS)
+    .bind(&session.id)
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to rotate refresh token: {}", e),
+        )
+            .into_response();
+    }
+
+    let token =
+        match keys.create_token(&user.id, user.tenant_id.as_deref(), &user.role, &session.id) {
+            Ok(t) => t,
+            Err(_) => {
+                let _ = tx.rollback().await;
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to generate token",
+                )
+                    .into_response();
+            }
+        };
+
+    match tx.commit().await {
+        Ok(_) => Json(TokenResponse {
+            token,
+            refresh_token,
+            expires_in: ACCESS_TOKEN_TTL,
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/logout
+/// Revokes the session the access token belongs to
+pub async fn logout(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(&claims.sid)
+    .bind(&claims.sub)
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(_) => (StatusCode::OK, "Logged out").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to logout: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn active_sessions(
+    pool: &PgPool,
+    user_id: &str,
+    current: Option<&str>,
+) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        r#"
+        SELECT id::text AS id, user_agent, created_at, last_used_at, expires_at,
+               COALESCE(id::text = $2, FALSE) AS current
+        FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY last_used_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(current)
+    .fetch_all(pool)
+    .await
+}
+
+/// GET /auth/sessions
+/// Devices the current user is logged in on
+pub async fn list_sessions(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    match active_sessions(&pool, &claims.sub, claims.sid.as_deref()).await {
+        Ok(sessions) => Json(sessions).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list sessions: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list sessions").into_response()
+        }
+    }
+}
+
+/// DELETE /auth/sessions/{id}
+/// Logs one of the current user's devices out
+pub async fn revoke_session(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(&id)
+    .bind(&claims.sub)
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "Session not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "Session revoked").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/users/{id}/sessions
+pub async fn list_user_sessions(
+    _: Require<perm::UsersManage>,
+    State(pool): State<PgPool>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match active_sessions(&pool, &id, None).await {
+        Ok(sessions) => Json(sessions).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list sessions: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list sessions").into_response()
+        }
+    }
+}
+
+/// DELETE /admin/users/{id}/sessions
+/// Logs a user out of every device
+pub async fn revoke_all_user_sessions(
+    _: Require<perm::UsersManage>,
+    State(pool): State<PgPool>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match revoke_user_sessions(&pool, &id).await {
+        Ok(count) => (StatusCode::OK, format!("{} sessions revoked", count)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke: {}", e),
+        )
+            .into_response(),
+    }
+}
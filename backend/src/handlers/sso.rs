@@ -0,0 +1,348 @@
+//! Single sign-on for admin and reseller users through OpenID Connect
+//! (authorization code flow with PKCE).
+//!
+//! 1. The login page lists providers from `GET /auth/sso/providers`
+//! 2. `POST /auth/sso/{provider}/authorize` returns the provider's login URL
+//! 3. The provider sends the browser back to the frontend, which posts the
+//!    `code` and `state` to `POST /auth/sso/{provider}/callback` and gets the
+//!    same response as password login (tokens or a 2FA challenge)
+//!
+//! Identities are matched by the provider's subject once linked, and by
+//! verified email the first time. Unknown users are created when the
+//! provider maps them to a role.
+
+use crate::audit::AuditContext;
+use crate::handlers::auth::{client_ip, complete_login, record_failed_login};
+use crate::handlers::sessions::{generate_token, hash_token};
+use crate::handlers::two_factor;
+use crate::jwt::JwtKeys;
+use crate::models::{SsoAuthorization, SsoCallbackRequest, SsoProvider, User};
+use crate::oidc::{Identity, Oidc, Provider, SSO_ROLES};
+use axum::{
+    Json,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Time to complete the sign-in at the provider.
+const STATE_MINUTES: i32 = 10;
+/// Never a valid hash: users created through SSO have no password until
+/// they reset it.
+const NO_PASSWORD: &str = "!";
+
+const USER_COLUMNS: &str = "u.id::text AS id, u.email, u.password_hash, u.role, u.tenant_id::text AS tenant_id, u.email_verified_at, u.created_at";
+
+enum SsoError {
+    /// Refused sign-in, with the reason recorded in the login events.
+    Denied {
+        reason: &'static str,
+        message: &'static str,
+        user_id: Option<String>,
+    },
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for SsoError {
+    fn from(e: sqlx::Error) -> Self {
+        SsoError::Database(e)
+    }
+}
+
+fn denied(reason: &'static str, message: &'static str, user_id: Option<String>) -> SsoError {
+    SsoError::Denied {
+        reason,
+        message,
+        user_id,
+    }
+}
+
+/// GET /auth/sso/providers
+pub async fn list_providers(Extension(oidc): Extension<Arc<Oidc>>) -> impl IntoResponse {
+    Json(
+        oidc.providers()
+            .iter()
+            .map(|p| SsoProvider {
+                name: p.name.clone(),
+                display_name: p.display_name.clone(),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// POST /auth/sso/{provider}/authorize
+pub async fn authorize(
+    State(pool): State<PgPool>,
+    Extension(oidc): Extension<Arc<Oidc>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let Some(provider) = oidc.provider(&name) else {
+        return (StatusCode::NOT_FOUND, "Unknown provider").into_response();
+    };
+
+    let state = generate_token();
+    let nonce = generate_token();
+    let code_verifier = generate_token();
+
+    let authorization_url = match oidc
+        .authorization_url(provider, &state, &nonce, &code_verifier)
+        .await
+    {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("SSO provider {} unavailable: {}", name, e);
+            return (StatusCode::BAD_GATEWAY, "Identity provider unavailable").into_response();
+        }
+    };
+
+    // Abandoned attempts are cleared as new ones start
+    let _ = sqlx::query("DELETE FROM sso_login_states WHERE expires_at < CURRENT_TIMESTAMP")
+        .execute(&pool)
+        .await;
+
+    let result = sqlx::query(
+        "INSERT INTO sso_login_states (id, provider, state_hash, nonce, code_verifier, expires_at) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP + make_interval(mins => $6))",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&provider.name)
+    .bind(hash_token(&state))
+    .bind(&nonce)
+    .bind(&code_verifier)
+    .bind(STATE_MINUTES)
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(_) => Json(SsoAuthorization { authorization_url }).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Finds, links or creates the local user for a verified identity.
+async fn resolve_user(
+    pool: &PgPool,
+    provider: &Provider,
+    identity: &Identity,
+) -> Result<User, SsoError> {
+    if !provider.allows_email(identity.email.as_deref()) {
+        return Err(denied(
+            "sso_domain",
+            "Email domain not allowed for this provider",
+            None,
+        ));
+    }
+    if provider.maps_roles() && identity.role.is_none() {
+        return Err(denied(
+            "sso_no_role",
+            "No role assigned by the identity provider",
+            None,
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let linked = sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM user_identities i JOIN users u ON u.id = i.user_id WHERE i.provider = $1 AND i.subject = $2",
+        USER_COLUMNS
+    ))
+    .bind(&provider.name)
+    .bind(&identity.subject)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let mut user = match linked {
+        Some(user) => user,
+        None => {
+            // An unverified address could belong to someone else
+            let Some(email) = identity
+                .email
+                .as_deref()
+                .filter(|_| identity.email_verified)
+            else {
+                let _ = tx.rollback().await;
+                return Err(denied(
+                    "sso_unverified_email",
+                    "The identity provider did not verify this email",
+                    None,
+                ));
+            };
+
+            let existing = sqlx::query_as::<_, User>(&format!(
+                "SELECT {} FROM users u WHERE LOWER(u.email) = $1",
+                USER_COLUMNS
+            ))
+            .bind(email)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let user = match (existing, &identity.role) {
+                (Some(user), _) => user,
+                (None, Some(role)) => {
+                    sqlx::query_as::<_, User>(
+                        "INSERT INTO users (id, email, password_hash, role) VALUES ($1, $2, $3, $4) RETURNING id::text, email, password_hash, role, tenant_id::text AS tenant_id, email_verified_at, created_at",
+                    )
+                    .bind(Uuid::new_v4().to_string())
+                    .bind(email)
+                    .bind(NO_PASSWORD)
+                    .bind(role)
+                    .fetch_one(&mut *tx)
+                    .await?
+                }
+                (None, None) => {
+                    let _ = tx.rollback().await;
+                    return Err(denied(
+                        "sso_no_account",
+                        "No account for this identity",
+                        None,
+                    ));
+                }
+            };
+
+            sqlx::query(
+                "INSERT INTO user_identities (id, user_id, provider, subject) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&user.id)
+            .bind(&provider.name)
+            .bind(&identity.subject)
+            .execute(&mut *tx)
+            .await?;
+
+            user
+        }
+    };
+
+    if !SSO_ROLES.contains(&user.role.as_str()) {
+        let _ = tx.rollback().await;
+        return Err(denied(
+            "sso_role_not_allowed",
+            "Single sign-on is only available to admin and reseller accounts",
+            Some(user.id),
+        ));
+    }
+
+    // The provider is the source of truth for the roles it maps
+    if let Some(role) = &identity.role
+        && *role != user.role
+    {
+        sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
+            .bind(role)
+            .bind(&user.id)
+            .execute(&mut *tx)
+            .await?;
+        user.role = role.clone();
+    }
+
+    sqlx::query(
+        "UPDATE user_identities SET email = $1, last_login_at = CURRENT_TIMESTAMP WHERE provider = $2 AND subject = $3",
+    )
+    .bind(&identity.email)
+    .bind(&provider.name)
+    .bind(&identity.subject)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(user)
+}
+
+/// POST /auth/sso/{provider}/callback
+#[allow(clippy::too_many_arguments)]
+pub async fn callback(
+    State(pool): State<PgPool>,
+    Extension(oidc): Extension<Arc<Oidc>>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    audit: AuditContext,
+    Path(name): Path<String>,
+    Json(payload): Json<SsoCallbackRequest>,
+) -> impl IntoResponse {
+    let Some(provider) = oidc.provider(&name) else {
+        return (StatusCode::NOT_FOUND, "Unknown provider").into_response();
+    };
+
+    // Each state is good for one callback, and only for the provider it was issued for
+    let attempt: Result<Option<(String, String)>, sqlx::Error> = sqlx::query_as(
+        "DELETE FROM sso_login_states WHERE state_hash = $1 AND provider = $2 AND expires_at > CURRENT_TIMESTAMP RETURNING nonce, code_verifier",
+    )
+    .bind(hash_token(&payload.state))
+    .bind(&provider.name)
+    .fetch_optional(&pool)
+    .await;
+
+    let (nonce, code_verifier) = match attempt {
+        Ok(Some(attempt)) => attempt,
+        Ok(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Invalid or expired sign-in attempt",
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let identity = match oidc
+        .exchange(provider, &payload.code, &code_verifier, &nonce)
+        .await
+    {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("SSO with {} failed: {}", name, e);
+            return (StatusCode::UNAUTHORIZED, "Single sign-on failed").into_response();
+        }
+    };
+
+    let user = match resolve_user(&pool, provider, &identity).await {
+        Ok(user) => user,
+        Err(SsoError::Denied {
+            reason,
+            message,
+            user_id,
+        }) => {
+            let ip = client_ip(&headers, addr);
+            let login = identity.email.as_deref().unwrap_or(&identity.subject);
+            record_failed_login(&pool, login, &ip, user_id.as_deref(), reason).await;
+            return (StatusCode::FORBIDDEN, message).into_response();
+        }
+        Err(SsoError::Database(e)) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match two_factor::login_challenge(&pool, &user).await {
+        Ok(Some(challenge)) => return Json(challenge).into_response(),
+        Ok(None) => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    complete_login(&pool, &keys, user, &audit, "sso", user_agent, None, None).await
+}
@@ -0,0 +1,546 @@
+//! Shared POS terminals and cashier PIN quick-switch.
+//!
+//! A manager registers a device once and gets a device token to store on it.
+//! Staff then log in on that device with a short PIN; the registered device
+//! stands in for the password, so a PIN alone is useless anywhere else.
+//!
+//! PIN login is only for staff outside two-factor authentication. Users who
+//! turned 2FA on, or whose role the platform or store policy requires it
+//! for, are refused and log in with their password and code instead. An
+//! unverified email is refused the same way as at password login.
+
+use crate::audit::AuditContext;
+use crate::auth;
+use crate::handlers::accounts;
+use crate::handlers::auth::{USER_COLUMNS, client_ip, complete_login, record_failed_login};
+use crate::handlers::sessions::{generate_token, hash_token};
+use crate::handlers::two_factor;
+use crate::jwt::JwtKeys;
+use crate::lockout::{self, FREE_ATTEMPTS_PER_TERMINAL, LoginThrottle};
+use crate::models::{
+    CreateTerminalRequest, PinLoginRequest, SetPinRequest, Terminal, TerminalCreated,
+    TerminalRequest, TerminalUser, User,
+};
+use crate::permissions::{Require, perm};
+use crate::plans::{self, Entitlements, Feature};
+use crate::tenant_db::TenantTx;
+use axum::{
+    Json,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use sqlx::{FromRow, PgPool};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Wrong PINs in a row before the PIN is cleared and the user has to log in
+/// with their password to set a new one.
+const MAX_PIN_ATTEMPTS: i32 = 5;
+
+#[derive(FromRow)]
+struct TerminalRow {
+    id: String,
+    tenant_id: String,
+    name: String,
+}
+
+fn valid_pin(pin: &str) -> bool {
+    (4..=8).contains(&pin.len()) && pin.chars().all(|c| c.is_ascii_digit())
+}
+
+async fn find_terminal(
+    pool: &PgPool,
+    device_token: &str,
+) -> Result<Option<TerminalRow>, sqlx::Error> {
+    sqlx::query_as::<_, TerminalRow>(
+        "SELECT id::text, tenant_id::text, name FROM terminals WHERE device_token_hash = $1 AND revoked_at IS NULL",
+    )
+    .bind(hash_token(device_token))
+    .fetch_optional(pool)
+    .await
+}
+
+/// POST /terminals
+/// Registers a device; the token in the response must be stored on it
+pub async fn register_terminal(
+    _: Require<perm::TerminalsManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<auth::Claims>,
+    Json(payload): Json<CreateTerminalRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let name = payload.name.trim().to_string();
+    if name.is_empty() || name.len() > 100 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Name must have 1 to 100 characters",
+        )
+            .into_response();
+    }
+
+    // A second register needs a plan with multiple registers
+    let limit = async {
+        let entitlements = Entitlements::load(&mut *tx, &tenant_id, true).await?;
+        match entitlements.max_terminals() {
+            Some(max) if plans::count_terminals(&mut *tx, &tenant_id).await? >= max => {
+                plans::feature_limit(&mut *tx, &entitlements, Feature::MultipleRegisters)
+                    .await
+                    .map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+    .await;
+    match limit {
+        Ok(None) => {}
+        Ok(Some(limit)) => {
+            let _ = tx.rollback().await;
+            return limit.into_response();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let device_token = generate_token();
+    let result = sqlx::query(
+        "INSERT INTO terminals (id, tenant_id, name, device_token_hash, created_by) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .bind(&name)
+    .bind(hash_token(&device_token))
+    .bind(&claims.sub)
+    .execute(&mut *tx)
+    .await;
+
+    let result = match result {
+        Ok(_) => tx.commit().await,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
+    match result {
+        Ok(_) => (
+            StatusCode::CREATED,
+            Json(TerminalCreated {
+                id,
+                name,
+                device_token,
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to register terminal: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_terminals(
+    _: Require<perm::TerminalsManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<auth::Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let terminals = sqlx::query_as::<_, Terminal>(
+        "SELECT id::text, name, created_at, last_used_at, revoked_at FROM terminals WHERE tenant_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(tenant_id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    match terminals {
+        Ok(terminals) => Json(terminals).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list terminals: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list terminals",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// DELETE /terminals/{id}
+/// Revokes the device and logs out whoever is using it
+pub async fn revoke_terminal(
+    _: Require<perm::TerminalsManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<auth::Claims>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query(
+        "UPDATE terminals SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND tenant_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(&id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            let _ = tx.rollback().await;
+            return (StatusCode::NOT_FOUND, "Terminal not found").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to revoke terminal: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE terminal_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(&id)
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke sessions: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => (StatusCode::OK, "Terminal revoked").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// DELETE /terminals/pins/{user_id}
+/// Clears a staff member's PIN, e.g. when it was shared
+pub async fn clear_pin(
+    _: Require<perm::TerminalsManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<auth::Claims>,
+    Path(user_id): Path<String>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let result = sqlx::query(
+        "UPDATE users SET pin_hash = NULL, pin_failed_attempts = 0 WHERE id = $1 AND tenant_id = $2",
+    )
+    .bind(&user_id)
+    .bind(&tenant_id)
+    .execute(&mut *tx)
+    .await;
+    let result = tx.commit_if_ok(result).await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            (StatusCode::NOT_FOUND, "User not found").into_response()
+        }
+        Ok(_) => (StatusCode::OK, "PIN cleared").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to clear PIN: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// PUT /auth/pin
+/// Sets the current user's PIN; needs the password
+pub async fn set_pin(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<auth::Claims>,
+    Json(payload): Json<SetPinRequest>,
+) -> impl IntoResponse {
+    if claims.tenant_id.is_none() {
+        return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response();
+    }
+    if !valid_pin(&payload.pin) {
+        return (StatusCode::BAD_REQUEST, "PIN must have 4 to 8 digits").into_response();
+    }
+
+    let password_hash: Option<String> =
+        match sqlx::query_scalar("SELECT password_hash FROM users WHERE id = $1")
+            .bind(&claims.sub)
+            .fetch_optional(&pool)
+            .await
+        {
+            Ok(hash) => hash,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Database error: {}", e),
+                )
+                    .into_response();
+            }
+        };
+
+    match password_hash {
+        Some(hash) if auth::verify_password(&hash, &payload.password) => {}
+        Some(_) => return (StatusCode::UNAUTHORIZED, "Invalid password").into_response(),
+        None => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+    }
+
+    let pin_hash = match auth::hash_password(&payload.pin) {
+        Ok(hash) => hash,
+        Err(_) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash PIN").into_response();
+        }
+    };
+
+    let result =
+        sqlx::query("UPDATE users SET pin_hash = $1, pin_failed_attempts = 0 WHERE id = $2")
+            .bind(&pin_hash)
+            .bind(&claims.sub)
+            .execute(&pool)
+            .await;
+
+    match result {
+        Ok(_) => (StatusCode::OK, "PIN updated").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to update PIN: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/terminal/users
+/// Staff who can log in on this terminal, for the user picker
+pub async fn terminal_users(
+    State(pool): State<PgPool>,
+    Json(payload): Json<TerminalRequest>,
+) -> impl IntoResponse {
+    let terminal = match find_terminal(&pool, &payload.device_token).await {
+        Ok(Some(terminal)) => terminal,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Unknown terminal").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let users = sqlx::query_as::<_, TerminalUser>(
+        "SELECT id::text, email, role FROM users WHERE tenant_id = $1 AND pin_hash IS NOT NULL ORDER BY email ASC",
+    )
+    .bind(&terminal.tenant_id)
+    .fetch_all(&pool)
+    .await;
+
+    match users {
+        Ok(users) => Json(users).into_response(),
+        Err(e) => {
+            eprintln!("Failed to list terminal users: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list users").into_response()
+        }
+    }
+}
+
+/// POST /auth/pin-login
+/// Logs a staff member in on a registered terminal, ending the previous
+/// user's session on that terminal. Refused for users under 2FA
+pub async fn pin_login(
+    State(pool): State<PgPool>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    audit: AuditContext,
+    Json(payload): Json<PinLoginRequest>,
+) -> impl IntoResponse {
+    let terminal = match find_terminal(&pool, &payload.device_token).await {
+        Ok(Some(terminal)) => terminal,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Unknown terminal").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    // Slows down guessing across all the terminal's users
+    let terminal_key = lockout::terminal_key(&terminal.id);
+    if let Some(wait) = throttle.retry_after(&terminal_key).await {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, wait.to_string())],
+            "Too many failed attempts, try again later",
+        )
+            .into_response();
+    }
+
+    let user = match sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM users WHERE id = $1::uuid AND tenant_id = $2::uuid",
+        USER_COLUMNS
+    ))
+    .bind(&payload.user_id)
+    .bind(&terminal.tenant_id)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Invalid PIN").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let pin_hash: Option<String> = sqlx::query_scalar("SELECT pin_hash FROM users WHERE id = $1")
+        .bind(&user.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap_or(None);
+
+    let Some(pin_hash) = pin_hash else {
+        return (
+            StatusCode::FORBIDDEN,
+            "No PIN set, log in with your password to create one",
+        )
+            .into_response();
+    };
+
+    if !auth::verify_password(&pin_hash, &payload.pin) {
+        throttle
+            .record_failure(&terminal_key, FREE_ATTEMPTS_PER_TERMINAL)
+            .await;
+        let ip = client_ip(&headers, addr);
+        record_failed_login(&pool, &user.email, &ip, Some(&user.id), "bad_pin").await;
+
+        let locked: bool = sqlx::query_scalar(
+            r#"
+            UPDATE users SET
+                pin_failed_attempts = pin_failed_attempts + 1,
+                pin_hash = CASE WHEN pin_failed_attempts + 1 >= $2 THEN NULL ELSE pin_hash END
+            WHERE id = $1
+            RETURNING pin_hash IS NULL
+            "#,
+        )
+        .bind(&user.id)
+        .bind(MAX_PIN_ATTEMPTS)
+        .fetch_one(&pool)
+        .await
+        .unwrap_or(false);
+
+        if locked {
+            return (
+                StatusCode::FORBIDDEN,
+                "PIN locked after too many attempts, log in with your password to set a new one",
+            )
+                .into_response();
+        }
+        return (StatusCode::UNAUTHORIZED, "Invalid PIN").into_response();
+    }
+
+    if user.email_verified_at.is_none() && accounts::verification_required() {
+        return (StatusCode::FORBIDDEN, "Email not verified").into_response();
+    }
+
+    let two_factor = async {
+        Ok::<_, sqlx::Error>(
+            two_factor::is_enabled(&pool, &user.id).await?
+                || two_factor::is_required(&pool, &user.role, user.tenant_id.as_deref()).await?,
+        )
+    }
+    .await;
+    match two_factor {
+        Ok(false) => {}
+        Ok(true) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Two-factor authentication is on for this account, log in with your password",
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let result = async {
+        sqlx::query("UPDATE users SET pin_failed_attempts = 0 WHERE id = $1")
+            .bind(&user.id)
+            .execute(&pool)
+            .await?;
+        sqlx::query("UPDATE terminals SET last_used_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(&terminal.id)
+            .execute(&pool)
+            .await?;
+        // One cashier at a time: the previous one is logged out
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE terminal_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(&terminal.id)
+        .execute(&pool)
+        .await
+    }
+    .await;
+
+    if let Err(e) = result {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response();
+    }
+
+    let user_agent = format!("terminal: {}", terminal.name);
+    complete_login(
+        &pool,
+        &keys,
+        user,
+        &audit,
+        "pin",
+        Some(&user_agent),
+        Some(&terminal.id),
+        None,
+    )
+    .await
+}
@@ -0,0 +1,898 @@
+//! TOTP two-factor authentication: enrolment, recovery codes, the second
+//! login step and the policies that make it mandatory.
+//!
+//! Roles listed in `TWO_FACTOR_REQUIRED_ROLES` (default `admin,reseller`)
+//! must use 2FA everywhere; tenants can require it for their own roles.
+//! A user who must use 2FA but has not enrolled yet is walked through
+//! enrolment as part of login.
+
+use crate::audit::{AuditContext, Event};
+use crate::auth::Claims;
+use crate::handlers::accounts;
+use crate::handlers::auth::{USER_COLUMNS, client_ip, complete_login, record_failed_login};
+use crate::handlers::sessions::hash_token;
+use crate::jwt::JwtKeys;
+use crate::lockout::{self, FREE_ATTEMPTS_PER_ACCOUNT, FREE_ATTEMPTS_PER_IP, LoginThrottle};
+use crate::models::{
+    RecoveryCodes, TotpSetup, TwoFactorChallenge, TwoFactorChallengeRequest, TwoFactorCodeRequest,
+    TwoFactorLoginRequest, TwoFactorPolicy, TwoFactorStatus, User,
+};
+use crate::permissions::{Require, perm};
+use crate::tenant_db::TenantTx;
+use crate::totp;
+use axum::{
+    Json,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use data_encoding::BASE32_NOPAD;
+use rand_core::{OsRng, RngCore};
+use sqlx::{FromRow, PgPool};
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const CHALLENGE: &str = "two_factor_login";
+/// Time to type the code after the password was accepted.
+const CHALLENGE_MINUTES: i32 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(FromRow)]
+struct TotpState {
+    email: String,
+    role: String,
+    tenant_id: Option<String>,
+    totp_secret: Option<String>,
+    totp_enabled_at: Option<chrono::NaiveDateTime>,
+    totp_last_step: Option<i64>,
+}
+
+async fn load_state(pool: &PgPool, user_id: &str) -> Result<Option<TotpState>, sqlx::Error> {
+    sqlx::query_as::<_, TotpState>(
+        "SELECT email, role, tenant_id::text AS tenant_id, totp_secret, totp_enabled_at, totp_last_step FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Whether the platform or the user's tenant requires 2FA for this role.
+pub async fn is_required(
+    pool: &PgPool,
+    role: &str,
+    tenant_id: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let platform_roles =
+        env::var("TWO_FACTOR_REQUIRED_ROLES").unwrap_or_else(|_| "admin,reseller".to_string());
+    if platform_roles.split(',').map(str::trim).any(|r| r == role) {
+        return Ok(true);
+    }
+
+    let Some(tenant_id) = tenant_id else {
+        return Ok(false);
+    };
+    let required: Option<bool> =
+        sqlx::query_scalar("SELECT $2 = ANY(two_factor_roles) FROM tenants WHERE id = $1")
+            .bind(tenant_id)
+            .bind(role)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(required.unwrap_or(false))
+}
+
+/// Whether the user has turned 2FA on.
+pub async fn is_enabled(pool: &PgPool, user_id: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT totp_enabled_at IS NOT NULL FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Called by login once the password is accepted. Returns a challenge when
+/// the user has 2FA enabled or must enrol before getting tokens.
+pub async fn login_challenge(
+    pool: &PgPool,
+    user: &User,
+) -> Result<Option<TwoFactorChallenge>, sqlx::Error> {
+    let enabled = is_enabled(pool, &user.id).await?;
+    if !enabled && !is_required(pool, &user.role, user.tenant_id.as_deref()).await? {
+        return Ok(None);
+    }
+
+    let challenge_token =
+        accounts::issue_token(pool, &user.id, CHALLENGE, CHALLENGE_MINUTES).await?;
+    Ok(Some(TwoFactorChallenge {
+        challenge_token,
+        two_factor_required: true,
+        setup_required: !enabled,
+    }))
+}
+
+/// Recovery codes look like `k3vq-9mzt`; dashes and case are ignored.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn new_recovery_code() -> String {
+    let mut bytes = [0u8; 5];
+    OsRng.fill_bytes(&mut bytes);
+    let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+/// Replaces the user's recovery codes, returning the new ones in clear
+/// text; only their hashes are stored.
+async fn replace_recovery_codes(
+    tx: &mut sqlx::PgConnection,
+    user_id: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| new_recovery_code())
+        .collect();
+    for code in &codes {
+        sqlx::query("INSERT INTO recovery_codes (id, user_id, code_hash) VALUES ($1, $2, $3)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(hash_token(&normalize_recovery_code(code)))
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    Ok(codes)
+}
+
+/// Accepts a TOTP code from the enrolled secret, or an unused recovery code.
+/// Both are single-use.
+async fn check_code(
+    pool: &PgPool,
+    user_id: &str,
+    state: &TotpState,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    let (Some(secret), Some(_)) = (&state.totp_secret, state.totp_enabled_at) else {
+        return Ok(false);
+    };
+
+    if let Some(step) = totp::verify(secret, code, state.totp_last_step) {
+        // Guarded so two requests racing with the same code cannot both pass
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_step = $1 WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        return Ok(result.rows_affected() == 1);
+    }
+
+    let result = sqlx::query(
+        "UPDATE recovery_codes SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(hash_token(&normalize_recovery_code(code)))
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Generates a pending secret; 2FA is only enabled once a code from it is
+/// confirmed, so a half-finished enrolment never locks the user out.
+async fn start_setup(pool: &PgPool, user_id: &str, email: &str) -> Result<TotpSetup, sqlx::Error> {
+    let secret = totp::generate_secret();
+    sqlx::query(
+        "UPDATE users SET totp_secret = $1, totp_last_step = NULL WHERE id = $2 AND totp_enabled_at IS NULL",
+    )
+    .bind(&secret)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(TotpSetup {
+        otpauth_uri: totp::otpauth_uri(&secret, email),
+        secret,
+    })
+}
+
+/// Confirms the pending secret with a code and enables 2FA.
+/// Returns the recovery codes, or `None` when the code is wrong.
+async fn confirm_setup(
+    tx: &mut sqlx::PgConnection,
+    user_id: &str,
+    state: &TotpState,
+    code: &str,
+) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let Some(secret) = &state.totp_secret else {
+        return Ok(None);
+    };
+    let Some(step) = totp::verify(secret, code, state.totp_last_step) else {
+        return Ok(None);
+    };
+
+    let result = sqlx::query(
+        "UPDATE users SET totp_enabled_at = CURRENT_TIMESTAMP, totp_last_step = $1 WHERE id = $2 AND totp_enabled_at IS NULL",
+    )
+    .bind(step)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    replace_recovery_codes(tx, user_id).await.map(Some)
+}
+
+fn invalid_challenge() -> axum::response::Response {
+    (StatusCode::UNAUTHORIZED, "Invalid or expired challenge").into_response()
+}
+
+/// POST /auth/2fa/login/setup
+/// Enrolment during login, for users a policy forces to use 2FA
+pub async fn login_setup(
+    State(pool): State<PgPool>,
+    Json(payload): Json<TwoFactorChallengeRequest>,
+) -> impl IntoResponse {
+    let user_id = match accounts::peek_token(&pool, &payload.challenge_token, CHALLENGE).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return invalid_challenge(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let state = match load_state(&pool, &user_id).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return invalid_challenge(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if state.totp_enabled_at.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication already enabled",
+        )
+            .into_response();
+    }
+
+    match start_setup(&pool, &user_id, &state.email).await {
+        Ok(setup) => Json(setup).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to start setup: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/2fa/login
+/// Second login step: trades the challenge and a code for tokens. Users
+/// enrolling during login get their recovery codes in the response.
+pub async fn login(
+    State(pool): State<PgPool>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    audit: AuditContext,
+    Json(payload): Json<TwoFactorLoginRequest>,
+) -> impl IntoResponse {
+    let user_id = match accounts::peek_token(&pool, &payload.challenge_token, CHALLENGE).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return invalid_challenge(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let state = match load_state(&pool, &user_id).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return invalid_challenge(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    // Codes share the password's backoff, so six digits cannot be brute-forced
+    let ip = client_ip(&headers, addr);
+    let account_key = lockout::account_key(&state.email);
+    let ip_key = lockout::ip_key(&ip);
+    let wait = throttle
+        .retry_after(&account_key)
+        .await
+        .max(throttle.retry_after(&ip_key).await);
+    if let Some(wait) = wait {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, wait.to_string())],
+            "Too many failed login attempts, try again later",
+        )
+            .into_response();
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let checked = if state.totp_enabled_at.is_some() {
+        check_code(&pool, &user_id, &state, &payload.code)
+            .await
+            .map(|ok| ok.then_some(None))
+    } else {
+        confirm_setup(&mut tx, &user_id, &state, &payload.code)
+            .await
+            .map(|codes| codes.map(Some))
+    };
+
+    let recovery_codes = match checked {
+        Ok(Some(codes)) => codes,
+        Ok(None) => {
+            let _ = tx.rollback().await;
+            throttle
+                .record_failure(&account_key, FREE_ATTEMPTS_PER_ACCOUNT)
+                .await;
+            throttle.record_failure(&ip_key, FREE_ATTEMPTS_PER_IP).await;
+            record_failed_login(&pool, &state.email, &ip, Some(&user_id), "bad_2fa_code").await;
+            return (StatusCode::UNAUTHORIZED, "Invalid code").into_response();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match accounts::consume_token(&mut tx, &payload.challenge_token, CHALLENGE).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let _ = tx.rollback().await;
+            return invalid_challenge();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    if let Err(e) = tx.commit().await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response();
+    }
+    throttle.reset(&account_key).await;
+
+    let user = match sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM users WHERE id = $1::uuid",
+        USER_COLUMNS
+    ))
+    .bind(&user_id)
+    .fetch_one(&pool)
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    // Enrolled as part of this login
+    if recovery_codes.is_some() {
+        audit
+            .as_user(&user)
+            .log(&pool, Event::new("auth.two_factor_enable", &user.id))
+            .await;
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    complete_login(
+        &pool,
+        &keys,
+        user,
+        &audit,
+        "two_factor",
+        user_agent,
+        None,
+        recovery_codes,
+    )
+    .await
+}
+
+/// GET /auth/2fa
+pub async fn status(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let state = match load_state(&pool, &claims.sub).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let required = match is_required(&pool, &state.role, state.tenant_id.as_deref()).await {
+        Ok(required) => required,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let recovery_codes_left: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+    )
+    .bind(&claims.sub)
+    .fetch_one(&pool)
+    .await
+    .unwrap_or(0);
+
+    Json(TwoFactorStatus {
+        enabled: state.totp_enabled_at.is_some(),
+        required,
+        recovery_codes_left,
+    })
+    .into_response()
+}
+
+/// POST /auth/2fa/setup
+/// Starts enrolment; confirm with POST /auth/2fa/enable
+pub async fn setup(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let state = match load_state(&pool, &claims.sub).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if state.totp_enabled_at.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication already enabled",
+        )
+            .into_response();
+    }
+
+    match start_setup(&pool, &claims.sub, &state.email).await {
+        Ok(setup) => Json(setup).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to start setup: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/2fa/enable
+pub async fn enable(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    let state = match load_state(&pool, &claims.sub).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if state.totp_enabled_at.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication already enabled",
+        )
+            .into_response();
+    }
+    if state.totp_secret.is_none() {
+        return (StatusCode::BAD_REQUEST, "Start the setup first").into_response();
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let recovery_codes = match confirm_setup(&mut tx, &claims.sub, &state, &payload.code).await {
+        Ok(Some(codes)) => codes,
+        Ok(None) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::BAD_REQUEST, "Invalid code").into_response();
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to enable: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if let Err(e) = audit
+        .record(&mut *tx, Event::new("auth.two_factor_enable", &claims.sub))
+        .await
+    {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(RecoveryCodes { recovery_codes }).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/2fa/disable
+/// Needs a current code; refused while a policy requires 2FA
+pub async fn disable(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    let state = match load_state(&pool, &claims.sub).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match is_required(&pool, &state.role, state.tenant_id.as_deref()).await {
+        Ok(true) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Two-factor authentication is required for your role",
+            )
+                .into_response();
+        }
+        Ok(false) => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    match check_code(&pool, &claims.sub, &state, &payload.code).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::BAD_REQUEST, "Invalid code").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    match clear_two_factor(&pool, &claims.sub).await {
+        Ok(_) => {
+            audit
+                .log(&pool, Event::new("auth.two_factor_disable", &claims.sub))
+                .await;
+            (StatusCode::OK, "Two-factor authentication disabled").into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to disable: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /auth/2fa/recovery-codes
+/// Replaces all recovery codes; needs a current code
+pub async fn regenerate_recovery_codes(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    let state = match load_state(&pool, &claims.sub).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match check_code(&pool, &claims.sub, &state, &payload.code).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::BAD_REQUEST, "Invalid code").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let recovery_codes = match replace_recovery_codes(&mut tx, &claims.sub).await {
+        Ok(codes) => codes,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to generate recovery codes: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    if let Err(e) = audit
+        .record(
+            &mut *tx,
+            Event::new("auth.recovery_codes_regenerate", &claims.sub),
+        )
+        .await
+    {
+        let _ = tx.rollback().await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to write audit log: {}", e),
+        )
+            .into_response();
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(RecoveryCodes { recovery_codes }).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to commit transaction: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn clear_two_factor(pool: &PgPool, user_id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL WHERE id = $1",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// DELETE /admin/users/{id}/2fa
+/// For users who lost both their device and their recovery codes. If a
+/// policy applies they enrol again at their next login.
+pub async fn reset_user_two_factor(
+    _: Require<perm::UsersManage>,
+    State(pool): State<PgPool>,
+    audit: AuditContext,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match clear_two_factor(&pool, &id).await {
+        Ok(_) => {
+            let tenant_id = load_state(&pool, &id)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|state| state.tenant_id);
+            audit
+                .log(
+                    &pool,
+                    Event::new("user.two_factor_reset", &id).tenant(tenant_id.as_deref()),
+                )
+                .await;
+            (StatusCode::OK, "Two-factor authentication reset").into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to reset: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /roles/two-factor-policy
+pub async fn get_policy(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let roles: Option<Vec<String>> =
+        match sqlx::query_scalar("SELECT two_factor_roles FROM tenants WHERE id = $1")
+            .bind(&tenant_id)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(roles) => roles,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Database error: {}", e),
+                )
+                    .into_response();
+            }
+        };
+
+    match roles {
+        Some(roles) => Json(TwoFactorPolicy { roles }).into_response(),
+        None => (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+    }
+}
+
+/// PUT /roles/two-factor-policy
+/// Roles of the tenant (`user` for the owner) that must use 2FA
+pub async fn update_policy(
+    _: Require<perm::RolesManage>,
+    mut tx: TenantTx,
+    Extension(claims): Extension<Claims>,
+    audit: AuditContext,
+    Json(payload): Json<TwoFactorPolicy>,
+) -> impl IntoResponse {
+    let tenant_id = match claims.tenant_id {
+        Some(id) => id,
+        None => return (StatusCode::FORBIDDEN, "Tenant ID missing").into_response(),
+    };
+
+    let mut roles: Vec<String> = Vec::new();
+    for role in &payload.roles {
+        let role = role.trim().to_lowercase();
+        if role.is_empty() || role.len() > 50 {
+            return (StatusCode::BAD_REQUEST, "Invalid role name").into_response();
+        }
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+
+    let before: Option<Vec<String>> =
+        sqlx::query_scalar("SELECT two_factor_roles FROM tenants WHERE id = $1")
+            .bind(&tenant_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .unwrap_or(None);
+
+    let result = sqlx::query("UPDATE tenants SET two_factor_roles = $1 WHERE id = $2")
+        .bind(&roles)
+        .bind(&tenant_id)
+        .execute(&mut *tx)
+        .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            return (StatusCode::NOT_FOUND, "Tenant not found").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update policy: {}", e),
+            )
+                .into_response();
+        }
+    }
+
+    let event = Event::new("tenant.two_factor_policy", &tenant_id)
+        .before(before.map(|roles| serde_json::json!({ "roles": roles })))
+        .after(Some(serde_json::json!({ "roles": roles })));
+    let recorded = audit.record(&mut *tx, event).await;
+    match tx.commit_if_ok(recorded).await {
+        Ok(_) => Json(TwoFactorPolicy { roles }).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to update policy: {}", e),
+        )
+            .into_response(),
+    }
+}
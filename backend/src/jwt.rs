@@ -0,0 +1,240 @@
+//! JWT signing and verification keys, loaded from the environment.
+//!
+//! - `JWT_ALGORITHM`: `HS256` (default), `RS256` or `EdDSA`
+//! - `JWT_KEY_ID`: `kid` of the current signing key (default `default`)
+//! - `JWT_SECRET`: shared secret, for `HS256`
+//! - `JWT_PRIVATE_KEY` / `JWT_PUBLIC_KEY`: PEM file paths, for `RS256` and `EdDSA`
+//! - `JWT_PREVIOUS_KEYS`: retired keys still accepted for verification, as a
+//!   comma separated list of `kid:ALG:value` where value is the secret (HS256)
+//!   or the public key PEM path (RS256/EdDSA)
+
+use crate::auth::{self, Claims, DecodingKey, EncodingKey, Header, Validation};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use rsa::{RsaPublicKey, pkcs8::DecodePublicKey, traits::PublicKeyParts};
+use std::env;
+use std::fs;
+
+/// Access tokens are short-lived; clients renew them with a refresh token.
+pub const ACCESS_TOKEN_TTL: usize = 15 * 60;
+
+struct VerificationKey {
+    kid: String,
+    algorithm: Algorithm,
+    key: DecodingKey,
+    jwk: Option<Jwk>, // public keys only, secrets are never published
+}
+
+pub struct JwtKeys {
+    kid: String,
+    algorithm: Algorithm,
+    signing_key: EncodingKey,
+    verification_keys: Vec<VerificationKey>,
+}
+
+fn parse_algorithm(value: &str) -> Result<Algorithm, String> {
+    match value {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        other => Err(format!("Unsupported JWT algorithm: {}", other)),
+    }
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read key {}: {}", path, e))
+}
+
+/// Builds the JWKS entry for a public key PEM.
+fn public_jwk(kid: &str, algorithm: Algorithm, pem: &[u8]) -> Result<Jwk, String> {
+    let pem = std::str::from_utf8(pem).map_err(|_| "Public key is not valid PEM".to_string())?;
+    let (key_algorithm, algorithm_params) = match algorithm {
+        Algorithm::RS256 => {
+            let key = RsaPublicKey::from_public_key_pem(pem)
+                .map_err(|e| format!("Invalid RSA public key: {}", e))?;
+            (
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+                }),
+            )
+        }
+        Algorithm::EdDSA => {
+            let der = pem::parse(pem).map_err(|e| format!("Invalid Ed25519 public key: {}", e))?;
+            // SubjectPublicKeyInfo for Ed25519 is a fixed 12 byte prefix plus the raw key
+            let raw = match der.contents() {
+                contents if contents.len() == 44 => &contents[12..],
+                _ => return Err("Invalid Ed25519 public key".to_string()),
+            };
+            (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(raw),
+                }),
+            )
+        }
+        _ => return Err("Only asymmetric keys can be published".to_string()),
+    };
+
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: algorithm_params,
+    })
+}
+
+fn verification_key(
+    kid: &str,
+    algorithm: Algorithm,
+    value: &str,
+) -> Result<VerificationKey, String> {
+    let (key, jwk) = match algorithm {
+        Algorithm::HS256 => (DecodingKey::from_secret(value.as_bytes()), None),
+        Algorithm::RS256 => {
+            let pem = read_pem(value)?;
+            let key = DecodingKey::from_rsa_pem(&pem).map_err(|e| e.to_string())?;
+            (key, Some(public_jwk(kid, algorithm, &pem)?))
+        }
+        _ => {
+            let pem = read_pem(value)?;
+            let key = DecodingKey::from_ed_pem(&pem).map_err(|e| e.to_string())?;
+            (key, Some(public_jwk(kid, algorithm, &pem)?))
+        }
+    };
+
+    Ok(VerificationKey {
+        kid: kid.to_string(),
+        algorithm,
+        key,
+        jwk,
+    })
+}
+
+impl JwtKeys {
+    pub fn from_env() -> Result<Self, String> {
+        let algorithm =
+            parse_algorithm(&env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string()))?;
+        let kid = env::var("JWT_KEY_ID").unwrap_or_else(|_| "default".to_string());
+
+        let (signing_key, current) = match algorithm {
+            Algorithm::HS256 => {
+                let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET must be set")?;
+                if secret.len() < 32 {
+                    return Err("JWT_SECRET must be at least 32 characters".to_string());
+                }
+                (
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    verification_key(&kid, algorithm, &secret)?,
+                )
+            }
+            _ => {
+                let private_path =
+                    env::var("JWT_PRIVATE_KEY").map_err(|_| "JWT_PRIVATE_KEY must be set")?;
+                let public_path =
+                    env::var("JWT_PUBLIC_KEY").map_err(|_| "JWT_PUBLIC_KEY must be set")?;
+                let pem = read_pem(&private_path)?;
+                let signing_key = if algorithm == Algorithm::RS256 {
+                    EncodingKey::from_rsa_pem(&pem)
+                } else {
+                    EncodingKey::from_ed_pem(&pem)
+                }
+                .map_err(|e| format!("Invalid JWT private key: {}", e))?;
+                (
+                    signing_key,
+                    verification_key(&kid, algorithm, &public_path)?,
+                )
+            }
+        };
+
+        let mut verification_keys = vec![current];
+        for entry in env::var("JWT_PREVIOUS_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let mut parts = entry.splitn(3, ':');
+            let (Some(old_kid), Some(old_algorithm), Some(value)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(format!("Invalid JWT_PREVIOUS_KEYS entry: {}", entry));
+            };
+            if verification_keys.iter().any(|k| k.kid == old_kid) {
+                return Err(format!("Duplicate JWT key id: {}", old_kid));
+            }
+            verification_keys.push(verification_key(
+                old_kid,
+                parse_algorithm(old_algorithm)?,
+                value,
+            )?);
+        }
+
+        Ok(JwtKeys {
+            kid,
+            algorithm,
+            signing_key,
+            verification_keys,
+        })
+    }
+
+    /// Signs a token with the current key, tagging it with its `kid`.
+    pub fn create_token(
+        &self,
+        user_id: &str,
+        tenant_id: Option<&str>,
+        role: &str,
+        session_id: &str,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        auth::create_jwt(
+            user_id,
+            tenant_id,
+            role,
+            session_id,
+            ACCESS_TOKEN_TTL,
+            &header,
+            &self.signing_key,
+        )
+    }
+
+    /// Verifies a token against the key named by its `kid`. Tokens issued
+    /// before key ids existed carry none and are checked against the current key.
+    pub fn verify(&self, token: &str) -> Option<Claims> {
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        let key = match header.kid.as_deref() {
+            Some(kid) => self.verification_keys.iter().find(|k| k.kid == kid)?,
+            None => &self.verification_keys[0],
+        };
+        if header.alg != key.algorithm {
+            return None;
+        }
+
+        auth::decode::<Claims>(token, &key.key, &Validation::new(key.algorithm))
+            .ok()
+            .map(|data| data.claims)
+    }
+
+    /// Public keys for `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .verification_keys
+                .iter()
+                .filter_map(|k| k.jwk.clone())
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Failed login tracking with exponential backoff.
+//!
+//! Failures are counted per account (email) and per client IP. After a few
+//! free attempts every further failure blocks the key for twice as long as the
+//! previous one, up to `MAX_LOCKOUT_SECS`. Counters live in process memory by
+//! default; set `LOGIN_THROTTLE_STORE=postgres` to share them between
+//! instances through the `login_throttle` table.
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+/// Failures are forgotten after this long without a new one.
+const WINDOW_SECS: i64 = 15 * 60;
+const MAX_LOCKOUT_SECS: i64 = 15 * 60;
+/// Failures allowed before backoff kicks in.
+pub const FREE_ATTEMPTS_PER_ACCOUNT: i32 = 3;
+pub const FREE_ATTEMPTS_PER_IP: i32 = 10;
+pub const FREE_ATTEMPTS_PER_TERMINAL: i32 = 10;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    failures: i32,
+    last_failure_at: NaiveDateTime,
+    locked_until: Option<NaiveDateTime>,
+}
+
+enum Store {
+    Memory(Mutex<HashMap<String, Entry>>),
+    Postgres(PgPool),
+}
+
+pub struct LoginThrottle {
+    store: Store,
+}
+
+pub fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+pub fn terminal_key(terminal_id: &str) -> String {
+    format!("terminal:{}", terminal_id)
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+/// Lock duration after `failures` failures: 1s, 2s, 4s... capped.
+fn backoff(failures: i32, free_attempts: i32) -> Option<Duration> {
+    let over = failures - free_attempts;
+    if over <= 0 {
+        return None;
+    }
+    let secs = 1i64
+        .checked_shl((over - 1).min(30) as u32)
+        .unwrap_or(MAX_LOCKOUT_SECS);
+    Some(Duration::seconds(secs.min(MAX_LOCKOUT_SECS)))
+}
+
+/// Counter after one more failure, starting over if the window elapsed.
+fn next_entry(previous: Option<Entry>, free_attempts: i32) -> Entry {
+    let now = now();
+    let failures = match previous {
+        Some(e) if now - e.last_failure_at < Duration::seconds(WINDOW_SECS) => e.failures + 1,
+        _ => 1,
+    };
+    Entry {
+        failures,
+        last_failure_at: now,
+        locked_until: backoff(failures, free_attempts).map(|d| now + d),
+    }
+}
+
+impl LoginThrottle {
+    pub fn from_env(pool: &PgPool) -> Self {
+        let store = match env::var("LOGIN_THROTTLE_STORE").as_deref() {
+            Ok("postgres") => Store::Postgres(pool.clone()),
+            _ => Store::Memory(Mutex::new(HashMap::new())),
+        };
+        LoginThrottle { store }
+    }
+
+    async fn load(&self, key: &str) -> Option<Entry> {
+        match &self.store {
+            Store::Memory(map) => map.lock().unwrap().get(key).cloned(),
+            Store::Postgres(pool) => {
+                let row: Option<(i32, NaiveDateTime, Option<NaiveDateTime>)> = sqlx::query_as(
+                    "SELECT failures, last_failure_at, locked_until FROM login_throttle WHERE key = $1",
+                )
+                .bind(key)
+                .fetch_optional(pool)
+                .await
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to read login throttle: {}", e);
+                    None
+                });
+                row.map(|(failures, last_failure_at, locked_until)| Entry {
+                    failures,
+                    last_failure_at,
+                    locked_until,
+                })
+            }
+        }
+    }
+
+    /// Seconds until the key may try again, if it is currently blocked.
+    pub async fn retry_after(&self, key: &str) -> Option<i64> {
+        let locked_until = self.load(key).await?.locked_until?;
+        // Round up, or the last second of every block would let attempts through
+        let remaining = (locked_until - now()).num_milliseconds();
+        (remaining > 0).then_some((remaining + 999) / 1000)
+    }
+
+    pub async fn record_failure(&self, key: &str, free_attempts: i32) {
+        match &self.store {
+            Store::Memory(map) => {
+                let mut map = map.lock().unwrap();
+                let entry = next_entry(map.get(key).cloned(), free_attempts);
+                map.insert(key.to_string(), entry);
+
+                // Keep memory bounded under a spray of distinct emails or IPs
+                if map.len() > 10_000 {
+                    let cutoff = now() - Duration::seconds(WINDOW_SECS);
+                    map.retain(|_, e| e.last_failure_at > cutoff);
+                }
+            }
+            Store::Postgres(pool) => {
+                let entry = next_entry(self.load(key).await, free_attempts);
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO login_throttle (key, failures, last_failure_at, locked_until)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (key) DO UPDATE SET
+                        failures = EXCLUDED.failures,
+                        last_failure_at = EXCLUDED.last_failure_at,
+                        locked_until = EXCLUDED.locked_until
+                    "#,
+                )
+                .bind(key)
+                .bind(entry.failures)
+                .bind(entry.last_failure_at)
+                .bind(entry.locked_until)
+                .execute(pool)
+                .await;
+                if let Err(e) = result {
+                    eprintln!("Failed to record login failure: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Clears a key, after a successful login or an admin unlock.
+    pub async fn reset(&self, key: &str) {
+        match &self.store {
+            Store::Memory(map) => {
+                map.lock().unwrap().remove(key);
+            }
+            Store::Postgres(pool) => {
+                if let Err(e) = sqlx::query("DELETE FROM login_throttle WHERE key = $1")
+                    .bind(key)
+                    .execute(pool)
+                    .await
+                {
+                    eprintln!("Failed to reset login throttle: {}", e);
+                }
+            }
+        }
+    }
+}
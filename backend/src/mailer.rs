@@ -0,0 +1,241 @@
+//! Outbound email.
+//!
+//! `MAILER` selects the transport:
+//! - `log` (default): prints messages to stdout
+//! - `file`: writes one `.eml` file per message to `MAIL_DIR` (default `./mail`)
+//! - `smtp`: delivers through `SMTP_HOST`:`SMTP_PORT` (default 25), with
+//!   `AUTH PLAIN` when `SMTP_USERNAME` / `SMTP_PASSWORD` are set. The client
+//!   speaks plain SMTP without TLS, so point it at a local relay or a capture
+//!   server such as Mailpit, not directly at a public provider.
+//!
+//! `MAIL_FROM` sets the sender address for every transport.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use uuid::Uuid;
+
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+struct SmtpConfig {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+}
+
+enum Transport {
+    Log,
+    File(PathBuf),
+    Smtp(SmtpConfig),
+}
+
+pub struct Mailer {
+    from: String,
+    transport: Transport,
+}
+
+/// RFC 2047 encoded-word, so non-ASCII subjects survive 7-bit relays.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+/// Rejects addresses that could inject headers or SMTP commands.
+fn valid_address(address: &str) -> bool {
+    !address.is_empty()
+        && address.contains('@')
+        && !address
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || c == '<' || c == '>')
+}
+
+impl Mailer {
+    pub fn from_env() -> Result<Self, String> {
+        let from = env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string());
+        if !valid_address(&from) {
+            return Err(format!("Invalid MAIL_FROM: {}", from));
+        }
+
+        let transport = match env::var("MAILER").as_deref() {
+            Ok("smtp") => {
+                let host = env::var("SMTP_HOST").map_err(|_| "SMTP_HOST must be set")?;
+                let port = match env::var("SMTP_PORT") {
+                    Ok(port) => port
+                        .parse()
+                        .map_err(|_| format!("Invalid SMTP_PORT: {}", port))?,
+                    Err(_) => 25,
+                };
+                let credentials = match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+                    (Ok(user), Ok(password)) => Some((user, password)),
+                    _ => None,
+                };
+                Transport::Smtp(SmtpConfig {
+                    host,
+                    port,
+                    credentials,
+                })
+            }
+            Ok("file") => {
+                let dir = PathBuf::from(env::var("MAIL_DIR").unwrap_or_else(|_| "./mail".into()));
+                std::fs::create_dir_all(&dir)
+                    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+                Transport::File(dir)
+            }
+            Ok("log") | Err(_) => Transport::Log,
+            Ok(other) => return Err(format!("Unknown MAILER: {}", other)),
+        };
+
+        Ok(Mailer { from, transport })
+    }
+
+    /// Full RFC 5322 message with CRLF line endings and a base64 body.
+    fn render(&self, id: &Uuid, email: &Email) -> String {
+        let domain = self.from.rsplit('@').next().unwrap_or("localhost");
+        let body = STANDARD.encode(&email.body);
+        let wrapped: Vec<&str> = body
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap_or_default())
+            .collect();
+
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+            self.from,
+            email.to,
+            encode_header(&email.subject),
+            chrono::Utc::now().to_rfc2822(),
+            id,
+            domain,
+            wrapped.join("\r\n"),
+        )
+    }
+
+    pub async fn send(&self, email: &Email) -> Result<(), String> {
+        if !valid_address(&email.to) {
+            return Err(format!("Invalid recipient: {}", email.to));
+        }
+        let id = Uuid::new_v4();
+
+        match &self.transport {
+            Transport::Log => {
+                println!(
+                    "📧 Mail to {} | {}\n{}",
+                    email.to, email.subject, email.body
+                );
+                Ok(())
+            }
+            Transport::File(dir) => {
+                let path = dir.join(format!("{}.eml", id));
+                tokio::fs::write(&path, self.render(&id, email))
+                    .await
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+            }
+            Transport::Smtp(config) => {
+                smtp_send(config, &self.from, &email.to, &self.render(&id, email)).await
+            }
+        }
+    }
+
+    /// Sends in the background so a slow relay never delays the response,
+    /// and response times do not reveal whether a mail was sent.
+    pub fn dispatch(self: &Arc<Self>, email: Email) {
+        let mailer = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(e) = mailer.send(&email).await {
+                eprintln!("Failed to send mail to {}: {}", email.to, e);
+            }
+        });
+    }
+}
+
+/// Reads a (possibly multi-line) reply and checks its status code.
+async fn expect_reply(reader: &mut BufReader<OwnedReadHalf>, code: u16) -> Result<(), String> {
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("SMTP read failed: {}", e))?;
+        if read == 0 {
+            return Err("SMTP server closed the connection".to_string());
+        }
+        // "250-..." continues, "250 ..." is the last line
+        if line.len() < 4 || line.as_bytes()[3] != b'-' {
+            return match line.get(..3).and_then(|c| c.parse::<u16>().ok()) {
+                Some(c) if c == code => Ok(()),
+                _ => Err(format!("Unexpected SMTP reply: {}", line.trim_end())),
+            };
+        }
+    }
+}
+
+async fn command(
+    writer: &mut OwnedWriteHalf,
+    reader: &mut BufReader<OwnedReadHalf>,
+    line: &str,
+    code: u16,
+) -> Result<(), String> {
+    writer
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .await
+        .map_err(|e| format!("SMTP write failed: {}", e))?;
+    expect_reply(reader, code).await
+}
+
+async fn smtp_send(config: &SmtpConfig, from: &str, to: &str, message: &str) -> Result<(), String> {
+    let stream = TcpStream::connect((config.host.as_str(), config.port))
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to connect to {}:{}: {}",
+                config.host, config.port, e
+            )
+        })?;
+    let (read, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(read);
+
+    expect_reply(&mut reader, 220).await?;
+    command(&mut writer, &mut reader, "EHLO localhost", 250).await?;
+    if let Some((user, password)) = &config.credentials {
+        let token = STANDARD.encode(format!("\0{}\0{}", user, password));
+        command(
+            &mut writer,
+            &mut reader,
+            &format!("AUTH PLAIN {}", token),
+            235,
+        )
+        .await?;
+    }
+    command(
+        &mut writer,
+        &mut reader,
+        &format!("MAIL FROM:<{}>", from),
+        250,
+    )
+    .await?;
+    command(&mut writer, &mut reader, &format!("RCPT TO:<{}>", to), 250).await?;
+    command(&mut writer, &mut reader, "DATA", 354).await?;
+
+    // Dot-stuffing, so a line starting with "." cannot end the message early
+    let data = message.replace("\r\n.", "\r\n..");
+    command(
+        &mut writer,
+        &mut reader,
+        &format!("{}\r\n.", data.trim_end()),
+        250,
+    )
+    .await?;
+    let _ = command(&mut writer, &mut reader, "QUIT", 221).await;
+    Ok(())
+}
@@ -1,16 +1,33 @@
 use axum::{
     Router, middleware as axum_middleware,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
 };
 use dotenvy::dotenv;
 use sqlx::postgres::PgPoolOptions;
 use std::env;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
+mod audit;
 mod auth;
+mod billing;
+mod business_types;
+mod commissions;
+mod documents;
 mod handlers;
+mod jwt;
+mod lockout;
+mod mailer;
 mod middleware;
+mod oidc;
+mod onboarding;
+mod payments;
+mod permissions;
+mod plans;
+mod tenant_db;
+mod tenant_status;
+mod totp;
 mod models;
 
 use tower_http::cors::CorsLayer;
@@ -33,10 +50,79 @@ async fn main() {
 
     println!("✅ Connected to database");
 
+    let jwt_keys = match jwt::JwtKeys::from_env() {
+        Ok(keys) => Arc::new(keys),
+        Err(e) => panic!("Invalid JWT configuration: {}", e),
+    };
+    let login_throttle = Arc::new(lockout::LoginThrottle::from_env(&pool));
+    let mailer = match mailer::Mailer::from_env() {
+        Ok(mailer) => Arc::new(mailer),
+        Err(e) => panic!("Invalid mail configuration: {}", e),
+    };
+    let oidc = match oidc::Oidc::from_env() {
+        Ok(oidc) => Arc::new(oidc),
+        Err(e) => panic!("Invalid OIDC configuration: {}", e),
+    };
+    let tenant_policy = match tenant_status::TenantPolicy::from_env() {
+        Ok(policy) => Arc::new(policy),
+        Err(e) => panic!("Invalid tenant status configuration: {}", e),
+    };
+    tokio::spawn(tenant_status::run_overdue_suspensions(pool.clone(), tenant_policy.clone()));
+    let payment_gateway = match payments::from_env() {
+        Ok(gateway) => gateway,
+        Err(e) => panic!("Invalid payment gateway configuration: {}", e),
+    };
+    tokio::spawn(billing::run_billing(pool.clone(), payment_gateway.clone(), tenant_policy.clone()));
+
     // Auth Routes (Public)
     let auth_routes = Router::new()
         .route("/register", post(handlers::auth::register))
-        .route("/login", post(handlers::auth::login));
+        .route("/login", post(handlers::auth::login))
+        .route("/refresh", post(handlers::sessions::refresh))
+        .route("/forgot-password", post(handlers::accounts::forgot_password))
+        .route("/reset-password", post(handlers::accounts::reset_password))
+        .route("/verify-email", post(handlers::accounts::verify_email))
+        .route("/resend-verification", post(handlers::accounts::resend_verification))
+        .route("/2fa/login", post(handlers::two_factor::login))
+        .route("/2fa/login/setup", post(handlers::two_factor::login_setup))
+        .route("/terminal/users", post(handlers::terminals::terminal_users))
+        .route("/pin-login", post(handlers::terminals::pin_login))
+        .route("/sso/providers", get(handlers::sso::list_providers))
+        .route("/sso/{provider}/authorize", post(handlers::sso::authorize))
+        .route("/sso/{provider}/callback", post(handlers::sso::callback))
+        .merge(
+            Router::new()
+                .route("/logout", post(handlers::sessions::logout))
+                .route("/sessions", get(handlers::sessions::list_sessions))
+                .route("/sessions/{id}", delete(handlers::sessions::revoke_session))
+                .route("/2fa", get(handlers::two_factor::status))
+                .route("/2fa/setup", post(handlers::two_factor::setup))
+                .route("/2fa/enable", post(handlers::two_factor::enable))
+                .route("/2fa/disable", post(handlers::two_factor::disable))
+                .route("/2fa/recovery-codes", post(handlers::two_factor::regenerate_recovery_codes))
+                .route("/pin", put(handlers::terminals::set_pin))
+                .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::session_auth_middleware)),
+        );
+
+    // Terminal Routes (Protected)
+    let terminal_routes = Router::new()
+        .route(
+            "/",
+            post(handlers::terminals::register_terminal).get(handlers::terminals::list_terminals),
+        )
+        .route("/{id}", delete(handlers::terminals::revoke_terminal))
+        .route("/pins/{user_id}", delete(handlers::terminals::clear_pin))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // API Key Routes (Protected, people only)
+    let api_key_routes = Router::new()
+        .route(
+            "/",
+            post(handlers::api_keys::create_api_key).get(handlers::api_keys::list_api_keys),
+        )
+        .route("/scopes", get(handlers::api_keys::list_scopes))
+        .route("/{id}", delete(handlers::api_keys::revoke_api_key))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::session_auth_middleware));
 
     // Admin Routes (Protected)
     let admin_routes = Router::new()
@@ -60,11 +146,32 @@ async fn main() {
             "/tenants/{id}",
             put(handlers::admin::update_tenant).delete(handlers::admin::delete_tenant),
         )
+        .route("/tenants/{id}/usage", get(handlers::plan::get_tenant_usage))
+        .route("/tenants/suspend-overdue", post(handlers::admin::suspend_overdue_tenants))
+        .route("/tenants/{id}/billing", get(handlers::billing::get_tenant_billing))
+        .route("/tenants/{id}/subscription", put(handlers::billing::update_subscription))
+        .route("/invoices/{id}", get(handlers::billing::get_any_invoice))
+        .route("/invoices/{id}/payments", post(handlers::billing::record_payment))
+        .route("/invoices/{id}/charge", post(handlers::billing::charge_invoice))
+        .route("/billing/run", post(handlers::billing::run_billing))
+        .route("/resellers/{id}/earnings", get(handlers::commissions::get_reseller_earnings))
+        .route("/resellers/{id}/commission", put(handlers::commissions::update_commission_rule))
+        .route("/payouts", get(handlers::commissions::list_payouts))
+        .route("/payouts/{id}", get(handlers::commissions::get_any_payout))
+        .route("/payouts/{id}/pay", post(handlers::commissions::pay_payout))
         .route(
             "/users/{id}",
             put(handlers::admin::update_user).delete(handlers::admin::delete_user),
         )
-        .route_layer(axum_middleware::from_fn(middleware::auth_middleware));
+        .route("/login-events", get(handlers::admin::list_login_events))
+        .route("/lockouts/unlock", post(handlers::admin::unlock_login))
+        .route(
+            "/users/{id}/sessions",
+            get(handlers::sessions::list_user_sessions)
+                .delete(handlers::sessions::revoke_all_user_sessions),
+        )
+        .route("/users/{id}/2fa", delete(handlers::two_factor::reset_user_two_factor))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
 
     // Product Routes (Protected)
     let product_routes = Router::new()
@@ -72,7 +179,12 @@ async fn main() {
             "/",
             get(handlers::products::list_products).post(handlers::products::create_product),
         )
-        .route_layer(axum_middleware::from_fn(middleware::auth_middleware));
+        .route(
+            "/categories",
+            get(handlers::products::list_categories).post(handlers::products::create_category),
+        )
+        .route("/{id}", put(handlers::products::update_product))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
 
     // Sales Routes (Protected)
     let sales_routes = Router::new()
@@ -81,7 +193,8 @@ async fn main() {
             get(handlers::sales::list_sales).post(handlers::sales::create_sale),
         )
         .route("/stats", get(handlers::sales::get_dashboard_stats))
-        .route_layer(axum_middleware::from_fn(middleware::auth_middleware));
+        .route("/{id}/cancel", post(handlers::sales::cancel_sale))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
 
     // Customer Routes (Protected)
     let customer_routes = Router::new()
@@ -89,7 +202,176 @@ async fn main() {
             "/",
             get(handlers::customers::list_customers).post(handlers::customers::create_customer),
         )
-        .route_layer(axum_middleware::from_fn(middleware::auth_middleware));
+        .route("/search", get(handlers::customers::search_customers))
+        .route(
+            "/by-document/{document}",
+            get(handlers::customers::get_customer_by_document),
+        )
+        .route(
+            "/{id}",
+            get(handlers::customers::get_customer)
+                .put(handlers::customers::update_customer)
+                .delete(handlers::customers::delete_customer),
+        )
+        .route(
+            "/{id}/profile",
+            get(handlers::customers::get_customer_profile),
+        )
+        .route(
+            "/privacy-requests",
+            get(handlers::privacy::list_requests),
+        )
+        .route("/{id}/export", get(handlers::privacy::export_customer))
+        .route(
+            "/{id}/anonymize",
+            post(handlers::privacy::anonymize_customer),
+        )
+        .route("/{id}/consent", put(handlers::privacy::update_consent))
+        .route(
+            "/duplicates",
+            get(handlers::duplicates::find_duplicates),
+        )
+        .route(
+            "/{id}/merge",
+            post(handlers::duplicates::merge_customers),
+        )
+        .route(
+            "/{id}/addresses",
+            get(handlers::addresses::list_addresses).post(handlers::addresses::create_address),
+        )
+        .route(
+            "/{id}/addresses/{address_id}",
+            put(handlers::addresses::update_address).delete(handlers::addresses::delete_address),
+        )
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Coupon Routes (Protected)
+    let coupon_routes = Router::new()
+        .route(
+            "/",
+            get(handlers::coupons::list_coupons).post(handlers::coupons::create_coupon),
+        )
+        .route("/{id}", put(handlers::coupons::update_coupon))
+        .route(
+            "/{id}/redemptions",
+            get(handlers::coupons::get_coupon_report),
+        )
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), plans::require_feature::<plans::feature::Coupons>))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Gift Card Routes (Protected)
+    let gift_card_routes = Router::new()
+        .route("/", get(handlers::gift_cards::list_gift_cards))
+        .route(
+            "/store-credit",
+            post(handlers::gift_cards::issue_store_credit),
+        )
+        .route("/{code}/balance", get(handlers::gift_cards::get_balance))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), plans::require_feature::<plans::feature::GiftCards>))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Loyalty Routes (Protected)
+    let loyalty_routes = Router::new()
+        .route(
+            "/program",
+            get(handlers::loyalty::get_program).put(handlers::loyalty::update_program),
+        )
+        .route(
+            "/customers/{id}",
+            get(handlers::loyalty::get_customer_loyalty),
+        )
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), plans::require_feature::<plans::feature::Loyalty>))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Delivery Routes (Protected)
+    let delivery_routes = Router::new()
+        .route("/board", get(handlers::deliveries::get_dispatch_board))
+        .route(
+            "/zones",
+            get(handlers::deliveries::list_zones).post(handlers::deliveries::create_zone),
+        )
+        .route(
+            "/zones/{id}",
+            delete(handlers::deliveries::delete_zone),
+        )
+        .route("/{id}/status", put(handlers::deliveries::update_status))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), plans::require_feature::<plans::feature::Deliveries>))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Role Routes (Protected)
+    let role_routes = Router::new()
+        .route(
+            "/",
+            get(handlers::roles::list_roles).post(handlers::roles::create_role),
+        )
+        .route("/permissions", get(handlers::roles::list_permissions))
+        .route("/users", get(handlers::roles::list_staff))
+        .route("/users/{user_id}", put(handlers::roles::assign_role))
+        .route(
+            "/two-factor-policy",
+            get(handlers::two_factor::get_policy).put(handlers::two_factor::update_policy),
+        )
+        .route(
+            "/{id}",
+            put(handlers::roles::update_role).delete(handlers::roles::delete_role),
+        )
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Segment Routes (Protected)
+    let segment_routes = Router::new()
+        .route(
+            "/",
+            get(handlers::segments::list_segments).post(handlers::segments::create_segment),
+        )
+        .route("/rfm", get(handlers::segments::get_rfm))
+        .route(
+            "/{id}",
+            put(handlers::segments::update_segment).delete(handlers::segments::delete_segment),
+        )
+        .route(
+            "/{id}/customers",
+            get(handlers::segments::get_segment_customers),
+        )
+        .route("/{id}/export", get(handlers::segments::export_segment))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), plans::require_feature::<plans::feature::Segments>))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Plan Routes (Protected)
+    let plan_routes = Router::new()
+        .route("/usage", get(handlers::plan::get_usage))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Billing Routes (Protected)
+    let billing_routes = Router::new()
+        .route("/", get(handlers::billing::get_billing))
+        .route("/invoices/{id}", get(handlers::billing::get_invoice))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Onboarding Routes (Protected)
+    let onboarding_routes = Router::new()
+        .route("/", get(handlers::onboarding::get_onboarding))
+        .route("/templates", get(handlers::onboarding::list_templates))
+        .route("/apply", post(handlers::onboarding::apply_template))
+        .route("/steps/{step}", put(handlers::onboarding::check_off_step))
+        .route("/dismiss", post(handlers::onboarding::dismiss))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Payment Method Routes (Protected)
+    let payment_method_routes = Router::new()
+        .route("/", get(handlers::payment_methods::list_payment_methods))
+        .route("/{code}", put(handlers::payment_methods::update_payment_method))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Reseller Routes (Protected)
+    let reseller_routes = Router::new()
+        .route("/earnings", get(handlers::commissions::get_earnings))
+        .route("/payouts/{id}", get(handlers::commissions::get_payout))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Audit Log Routes (Protected)
+    let audit_log_routes = Router::new()
+        .route("/", get(handlers::audit_log::list_audit_log))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
 
     // Metrics Routes (Protected)
     let metrics_routes = Router::new()
@@ -100,17 +382,39 @@ async fn main() {
             "/inventory-alerts",
             get(handlers::metrics::get_inventory_alerts),
         )
-        .route_layer(axum_middleware::from_fn(middleware::auth_middleware));
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), plans::require_feature::<plans::feature::Metrics>))
+        .route_layer(axum_middleware::from_fn_with_state(pool.clone(), middleware::auth_middleware));
 
     // Combine routes
     let app = Router::new()
         .route("/", get(root))
+        .route("/.well-known/jwks.json", get(handlers::auth::jwks))
         .nest("/auth", auth_routes)
         .nest("/admin", admin_routes)
         .nest("/products", product_routes)
         .nest("/sales", sales_routes)
         .nest("/customers", customer_routes)
+        .nest("/coupons", coupon_routes)
+        .nest("/gift-cards", gift_card_routes)
+        .nest("/loyalty", loyalty_routes)
+        .nest("/deliveries", delivery_routes)
+        .nest("/segments", segment_routes)
+        .nest("/roles", role_routes)
+        .nest("/terminals", terminal_routes)
+        .nest("/api-keys", api_key_routes)
+        .nest("/audit-log", audit_log_routes)
+        .nest("/plan", plan_routes)
+        .nest("/billing", billing_routes)
+        .nest("/reseller", reseller_routes)
+        .nest("/onboarding", onboarding_routes)
+        .nest("/payment-methods", payment_method_routes)
         .nest("/metrics", metrics_routes)
+        .layer(axum::Extension(jwt_keys))
+        .layer(axum::Extension(login_throttle))
+        .layer(axum::Extension(mailer))
+        .layer(axum::Extension(oidc))
+        .layer(axum::Extension(tenant_policy))
+        .layer(axum::Extension(payment_gateway))
         .layer(CorsLayer::permissive())
         .with_state(pool);
 
@@ -124,5 +428,8 @@ async fn main() {
     println!("🚀 Server listening on {}", addr);
 
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Connection info gives the login throttle the client address
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 }
@@ -1,15 +1,101 @@
 use axum::{
+    Extension,
     extract::{Request, State},
     http::{StatusCode, header},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
-use crate::auth::{decode, DecodingKey, Validation, Claims};
+use crate::handlers::{api_keys, sessions};
+use crate::jwt::JwtKeys;
+use crate::permissions::Permissions;
+use crate::tenant_status::{self, Access, TenantPolicy};
+use sqlx::PgPool;
+use std::sync::Arc;
 
+/// Accepts a user's Bearer JWT or an integration's API key, for stores
+/// whose status allows the request.
 pub async fn auth_middleware(
+    State(pool): State<PgPool>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    Extension(policy): Extension<Arc<TenantPolicy>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    authenticate(&pool, &keys, Some(&policy), req, next, true).await
+}
+
+/// JWT only, for account endpoints (sessions, 2FA, API key management)
+/// that an integration must never reach. These stay open to suspended
+/// stores so their users can still manage their own accounts.
+pub async fn session_auth_middleware(
+    State(pool): State<PgPool>,
+    Extension(keys): Extension<Arc<JwtKeys>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    authenticate(&pool, &keys, None, req, next, false).await
+}
+
+/// Runs the request if the caller's store may make it.
+async fn run_for_tenant(
+    pool: &PgPool,
+    policy: Option<&TenantPolicy>,
+    tenant_id: Option<&str>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let (Some(policy), Some(tenant_id)) = (policy, tenant_id) else {
+        return Ok(next.run(req).await);
+    };
+
+    let access = tenant_status::check(pool, policy, tenant_id, req.method())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to read tenant status: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Access::Refused(blocked) = access {
+        return Ok(blocked.into_response());
+    }
+
+    let mut response = next.run(req).await;
+    access.mark(&mut response);
+    Ok(response)
+}
+
+async fn authenticate(
+    pool: &PgPool,
+    keys: &JwtKeys,
+    policy: Option<&TenantPolicy>,
     mut req: Request,
     next: Next,
+    allow_api_keys: bool,
 ) -> Result<Response, StatusCode> {
+    let api_key = req.headers()
+        .get("x-api-key")
+        .and_then(|header| header.to_str().ok())
+        .or_else(|| {
+            req.headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|header| header.to_str().ok())
+                .and_then(|header| header.strip_prefix("Bearer "))
+                .filter(|token| token.starts_with(api_keys::KEY_PREFIX))
+        })
+        .map(str::to_owned);
+
+    if let Some(api_key) = api_key {
+        if !allow_api_keys {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        let (claims, permissions) = api_keys::authenticate(pool, &api_key)
+            .await
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let tenant_id = claims.tenant_id.clone();
+        req.extensions_mut().insert(permissions);
+        req.extensions_mut().insert(claims);
+        return run_for_tenant(pool, policy, tenant_id.as_deref(), req, next).await;
+    }
+
     let auth_header = req.headers()
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok());
@@ -26,14 +112,18 @@ pub async fn auth_middleware(
 
     let token = &auth_header[7..];
 
-    // TODO: Use secret from env
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(b"secret"),
-        &Validation::default(),
-    ).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let claims = keys.verify(token).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Revoked sessions (logout, deleted or demoted user) lock the token out immediately
+    let session_id = claims.sid.as_deref().ok_or(StatusCode::UNAUTHORIZED)?;
+    if !sessions::is_active(pool, session_id, &claims.sub).await {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
 
-    req.extensions_mut().insert(token_data.claims);
+    let permissions = Permissions::resolve(pool, &claims).await;
+    let tenant_id = claims.tenant_id.clone();
+    req.extensions_mut().insert(permissions);
+    req.extensions_mut().insert(claims);
 
-    Ok(next.run(req).await)
+    run_for_tenant(pool, policy, tenant_id.as_deref(), req, next).await
 }
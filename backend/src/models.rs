@@ -1,6 +1,17 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 
+/// For nullable fields of partial updates: a field sent as `null` comes
+/// out as `Some(None)` (clear it), one left out as `None` (keep it).
+/// Use with `#[serde(default, deserialize_with = "double_option")]`.
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: String,
@@ -9,6 +20,7 @@ pub struct User {
     pub password_hash: String,
     pub role: String,
     pub tenant_id: Option<String>,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
     pub created_at: chrono::NaiveDateTime,
 }
 
@@ -17,6 +29,7 @@ pub struct CreateUserRequest {
     pub email: String,
     pub password: String,
     pub role: Option<String>,
+    pub tenant_id: Option<String>, // admin only: adds the user to an existing store
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,10 +41,14 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    pub refresh_token: String,
+    pub expires_in: usize, // access token lifetime, in seconds
     pub role: String,
     pub business_type: Option<String>,
     pub name: Option<String>, // if we had name column, but we have email.
     pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>, // only right after enrolling in 2FA
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -43,6 +60,8 @@ pub struct Product {
     pub price: i32,
     pub stock_quantity: i32,
     pub sku: Option<String>,
+    pub is_gift_card: bool,
+    pub category: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -54,6 +73,8 @@ pub struct CreateProductRequest {
     pub price: i32,
     pub stock_quantity: i32,
     pub sku: Option<String>,
+    pub is_gift_card: Option<bool>,
+    pub category: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -63,6 +84,7 @@ pub struct UpdateProductRequest {
     pub price: Option<i32>,
     pub stock_quantity: Option<i32>,
     pub sku: Option<String>,
+    pub category: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -72,8 +94,13 @@ pub struct Sale {
     pub user_id: String,
     pub customer_id: Option<String>,
     pub total_amount: i64,
+    pub discount_amount: i64,
+    pub coupon_id: Option<String>,
+    pub loyalty_discount: i64,
+    pub delivery_fee: i64,
     pub payment_method: String,
     pub status: String,
+    pub terminal_id: Option<String>,
     pub created_at: chrono::NaiveDateTime,
 }
 
@@ -82,12 +109,23 @@ pub struct CreateSaleRequest {
     pub items: Vec<CreateSaleItemRequest>,
     pub payment_method: String,
     pub customer_id: Option<String>,
+    pub coupon_code: Option<String>,
+    pub gift_card_payments: Option<Vec<GiftCardPaymentRequest>>,
+    pub loyalty_points: Option<i64>, // points to redeem as a discount
+    pub delivery: Option<CreateDeliveryRequest>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateSaleItemRequest {
     pub product_id: String,
     pub quantity: i32,
+    pub gift_card_code: Option<String>, // required when the product is a gift card
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GiftCardPaymentRequest {
+    pub code: String,
+    pub amount: i64, /* in cents */
 }
 
 
@@ -98,7 +136,7 @@ pub struct Plan {
     pub name: String,
     pub price: i32, /* in cents */
     pub max_users: i32,
-    pub features: Option<String>,
+    pub features: Option<serde_json::Value>, // feature names, see plans::Feature; null: everything
     pub created_at: chrono::NaiveDateTime,
 }
 
@@ -107,7 +145,7 @@ pub struct CreatePlanRequest {
     pub name: String,
     pub price: i32,
     pub max_users: i32,
-    pub features: Option<String>,
+    pub features: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -121,6 +159,10 @@ pub struct Tenant {
     pub created_at: String,
     pub custom_fields: Option<String>, // JSON string
     pub updated_at: Option<String>,
+    pub document: Option<String>,      // CPF/CNPJ, digits only
+    pub document_type: Option<String>, // cpf, cnpj
+    pub paid_until: Option<chrono::NaiveDate>,
+    pub suspended_reason: Option<String>, // manual, overdue
 }
 
 #[derive(Debug, Deserialize)]
@@ -130,6 +172,7 @@ pub struct CreateTenantRequest {
     pub business_type: Option<String>,
     pub owner_email: Option<String>,
     pub owner_password: Option<String>,
+    pub document: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -139,6 +182,8 @@ pub struct UpdateTenantRequest {
     pub status: Option<String>,
     pub business_type: Option<String>,
     pub custom_fields: Option<String>, // JSON string
+    pub document: Option<String>,
+    pub paid_until: Option<chrono::NaiveDate>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -156,7 +201,13 @@ pub struct Customer {
     pub email: Option<String>,
     pub phone: Option<String>,
     pub notes: Option<String>,
+    pub document: Option<String>,      // CPF/CNPJ, digits only
+    pub document_type: Option<String>, // cpf, cnpj
+    pub marketing_consent: bool,
+    pub consent_updated_at: Option<chrono::NaiveDateTime>,
+    pub anonymized_at: Option<chrono::NaiveDateTime>,
     pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
 }
 
 #[derive(Debug, Deserialize)]
@@ -165,4 +216,769 @@ pub struct CreateCustomerRequest {
     pub email: Option<String>,
     pub phone: Option<String>,
     pub notes: Option<String>,
+    pub document: Option<String>,
+    pub marketing_consent: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCustomerRequest {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub notes: Option<String>,
+    pub document: Option<String>, // empty string clears it
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CustomerSearchQuery {
+    pub q: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Coupon {
+    pub id: String,
+    pub tenant_id: String,
+    pub code: String,
+    pub discount_type: String, // percentage, fixed
+    pub discount_value: i32,
+    pub valid_from: Option<chrono::NaiveDateTime>,
+    pub valid_until: Option<chrono::NaiveDateTime>,
+    pub min_basket: i32, /* in cents */
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub times_used: i32,
+    pub active: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCouponRequest {
+    pub code: String,
+    pub discount_type: String,
+    pub discount_value: i32,
+    pub valid_from: Option<chrono::NaiveDateTime>,
+    pub valid_until: Option<chrono::NaiveDateTime>,
+    pub min_basket: Option<i32>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCouponRequest {
+    pub active: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub valid_from: Option<Option<chrono::NaiveDateTime>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub valid_until: Option<Option<chrono::NaiveDateTime>>,
+    pub min_basket: Option<i32>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_uses: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_uses_per_customer: Option<Option<i32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CouponRedemption {
+    pub id: String,
+    pub coupon_id: String,
+    pub sale_id: String,
+    pub customer_id: Option<String>,
+    pub discount_amount: i64,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct GiftCard {
+    pub id: String,
+    pub tenant_id: String,
+    pub code: String,
+    pub kind: String, // gift_card, store_credit
+    pub initial_balance: i64,
+    pub balance: i64,
+    pub customer_id: Option<String>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub status: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct GiftCardTransaction {
+    pub id: String,
+    pub gift_card_id: String,
+    pub sale_id: Option<String>,
+    pub kind: String, // activation, redemption, store_credit, reversal
+    pub amount: i64,
+    pub balance_after: i64,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueStoreCreditRequest {
+    pub customer_id: Option<String>,
+    pub amount: i64,
+    pub sale_id: Option<String>, // refunded sale, if any
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct LoyaltyProgram {
+    pub tenant_id: String,
+    pub points_per_real: i32,
+    pub point_value: i32, /* in cents */
+    pub expiry_days: Option<i32>,
+    pub active: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct LoyaltyTier {
+    pub id: String,
+    pub tenant_id: String,
+    pub name: String,
+    pub min_points: i64,
+    pub multiplier: i32, // percent
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct LoyaltyTransaction {
+    pub id: String,
+    pub tenant_id: String,
+    pub customer_id: String,
+    pub sale_id: Option<String>,
+    pub kind: String, // earn, redeem, expire, reversal
+    pub points: i64,
+    pub remaining: i64,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLoyaltyProgramRequest {
+    pub points_per_real: i32,
+    pub point_value: i32,
+    pub expiry_days: Option<i32>,
+    pub active: bool,
+    pub tiers: Vec<LoyaltyTierRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoyaltyTierRequest {
+    pub name: String,
+    pub min_points: i64,
+    pub multiplier: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DataSubjectRequest {
+    pub id: String,
+    pub tenant_id: String,
+    pub customer_id: Option<String>,
+    pub request_type: String, // export, anonymize, consent
+    pub requested_by: String,
+    pub details: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateConsentRequest {
+    pub marketing_consent: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeCustomersRequest {
+    pub duplicate_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CustomerAddress {
+    pub id: String,
+    pub tenant_id: String,
+    pub customer_id: String,
+    pub label: Option<String>,
+    pub street: String,
+    pub number: Option<String>,
+    pub complement: Option<String>,
+    pub neighborhood: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub zip_code: String,
+    pub reference: Option<String>,
+    pub is_default: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CustomerAddressRequest {
+    pub label: Option<String>,
+    pub street: String,
+    pub number: Option<String>,
+    pub complement: Option<String>,
+    pub neighborhood: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub zip_code: String,
+    pub reference: Option<String>,
+    pub is_default: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DeliveryZone {
+    pub id: String,
+    pub tenant_id: String,
+    pub name: String,
+    pub zip_code_start: String,
+    pub zip_code_end: String,
+    pub fee: i32, /* in cents */
+    pub estimated_minutes: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDeliveryZoneRequest {
+    pub name: String,
+    pub zip_code_start: String,
+    pub zip_code_end: String,
+    pub fee: i32,
+    pub estimated_minutes: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Delivery {
+    pub id: String,
+    pub tenant_id: String,
+    pub sale_id: String,
+    pub customer_id: Option<String>,
+    pub address_id: Option<String>,
+    pub address_text: String,
+    pub zone_id: Option<String>,
+    pub fee: i64,
+    pub courier_name: Option<String>,
+    pub status: String, // received, preparing, out_for_delivery, delivered, cancelled
+    pub dispatched_at: Option<chrono::NaiveDateTime>,
+    pub delivered_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDeliveryRequest {
+    pub address_id: String,
+    pub courier_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDeliveryStatusRequest {
+    pub status: String,
+    pub courier_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CustomerSegment {
+    pub id: String,
+    pub tenant_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub min_spent: Option<i64>, /* in cents */
+    pub inactive_days: Option<i32>,
+    pub category: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CustomerSegmentRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub min_spent: Option<i64>,
+    pub inactive_days: Option<i32>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+    pub refresh_token: String,
+    pub expires_in: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Session {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TenantRole {
+    pub id: String,
+    pub tenant_id: String,
+    pub name: String,
+    pub permissions: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TenantRoleRequest {
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRoleRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct LoginEvent {
+    pub id: String,
+    pub email: String,
+    pub ip: String,
+    pub user_id: Option<String>,
+    pub reason: String, // unknown_user, bad_password, locked
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginEventQuery {
+    pub email: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockRequest {
+    pub email: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
+
+/// Returned by login instead of tokens when a second factor is needed
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    pub two_factor_required: bool,
+    pub setup_required: bool, // policy requires 2FA but the user has not enrolled yet
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorChallengeRequest {
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge_token: String,
+    pub code: String, // authenticator code or recovery code
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub required: bool,
+    pub recovery_codes_left: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorPolicy {
+    pub roles: Vec<String>, // tenant roles that must use 2FA
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Terminal {
+    pub id: String,
+    pub name: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTerminalRequest {
+    pub name: String,
+}
+
+/// The device token is only ever returned here
+#[derive(Debug, Serialize)]
+pub struct TerminalCreated {
+    pub id: String,
+    pub name: String,
+    pub device_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TerminalRequest {
+    pub device_token: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TerminalUser {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PinLoginRequest {
+    pub device_token: String,
+    pub user_id: String,
+    pub pin: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetPinRequest {
+    pub pin: String,
+    pub password: String, // current password, a PIN alone is too weak to change itself
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_by: String,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i32>, // never expires when omitted
+}
+
+/// The key itself is only ever returned here
+#[derive(Debug, Serialize)]
+pub struct ApiKeyCreated {
+    pub id: String,
+    pub name: String,
+    pub key: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SsoProvider {
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SsoAuthorization {
+    pub authorization_url: String, // send the browser here
+}
+
+/// Query parameters the provider added to the redirect URI
+#[derive(Debug, Deserialize)]
+pub struct SsoCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditEntry {
+    pub id: String,
+    pub tenant_id: Option<String>,
+    pub actor_id: Option<String>,
+    pub actor_email: Option<String>, // NULL once the actor is deleted
+    pub actor_role: Option<String>,
+    pub ip: Option<String>,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub tenant_id: Option<String>, // platform admins only
+    pub actor_id: Option<String>,
+    pub action: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>, // inclusive
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanSummary {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageCount {
+    pub used: i64,
+    pub limit: Option<i64>, // None: unlimited
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeatureStatus {
+    pub feature: crate::plans::Feature,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanUsage {
+    pub plan: Option<PlanSummary>,
+    pub users: UsageCount,
+    pub terminals: UsageCount,
+    pub features: Vec<FeatureStatus>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Subscription {
+    pub id: String,
+    pub tenant_id: String,
+    pub plan_id: String,
+    pub plan_name: String,
+    pub billing_cycle: String, // monthly, yearly
+    pub status: String,        // trialing, active, past_due, suspended, cancelled
+    pub trial_ends_at: Option<chrono::NaiveDate>,
+    pub current_period_start: chrono::NaiveDate,
+    pub current_period_end: chrono::NaiveDate, // inclusive
+    pub cancel_at_period_end: bool,
+    pub has_payment_method: bool,
+    pub credit_balance: i64, // cents
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Invoice {
+    pub id: String,
+    pub tenant_id: String,
+    pub status: String, // open, paid, void
+    pub amount: i64,    // cents
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub due_date: chrono::NaiveDate,
+    pub attempt_count: i32,
+    pub next_attempt_at: Option<chrono::NaiveDateTime>,
+    pub paid_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct InvoiceLine {
+    pub description: String,
+    pub amount: i64, // cents, negative for credits
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Payment {
+    pub id: String,
+    pub amount: i64,
+    pub status: String, // succeeded, failed
+    pub method: String,
+    pub reference: Option<String>,
+    pub failure_reason: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BillingOverview {
+    pub subscription: Option<Subscription>,
+    pub invoices: Vec<Invoice>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceDetail {
+    #[serde(flatten)]
+    pub invoice: Invoice,
+    pub lines: Vec<InvoiceLine>,
+    pub payments: Vec<Payment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSubscriptionRequest {
+    pub plan_id: Option<String>, // starting a subscription: defaults to the tenant's plan
+    pub billing_cycle: Option<String>,
+    pub payment_method_token: Option<String>,
+    pub cancel_at_period_end: Option<bool>,
+    pub trial: Option<bool>, // starting a subscription: defaults to true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordPaymentRequest {
+    pub amount: i64, // cents
+    pub method: String,
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CommissionRule {
+    pub rate_bps: i32,         // basis points: 1500 = 15%
+    pub activation_bonus: i64, // cents
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCommissionRuleRequest {
+    pub rate_bps: i32,
+    pub activation_bonus: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CommissionEntry {
+    pub id: String,
+    pub tenant_id: Option<String>,
+    pub tenant_name: Option<String>, // NULL once the store is deleted
+    pub invoice_id: Option<String>,
+    pub kind: String, // subscription, activation
+    pub base_amount: i64,
+    pub amount: i64,
+    pub description: String,
+    pub earned_on: chrono::NaiveDate,
+    pub payout_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Payout {
+    pub id: String,
+    pub reseller_id: String,
+    pub reseller_email: String,
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub amount: i64,
+    pub status: String, // pending, paid
+    pub reference: Option<String>,
+    pub paid_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PayoutDetail {
+    #[serde(flatten)]
+    pub payout: Payout,
+    pub entries: Vec<CommissionEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayoutQuery {
+    pub reseller_id: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayPayoutRequest {
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MonthlyEarnings {
+    pub month: chrono::NaiveDate, // first day
+    pub subscription: i64,
+    pub activation: i64,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct EarningsTotals {
+    pub earned: i64,
+    pub unsettled: i64, // not in a statement yet
+    pub pending: i64,   // in statements not paid yet
+    pub paid: i64,
+    pub stores: i64,
+    pub paying_stores: i64, // with an active subscription
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResellerEarnings {
+    pub rule: Option<CommissionRule>,
+    pub totals: EarningsTotals,
+    pub months: Vec<MonthlyEarnings>, // last 12, newest first
+    pub recent: Vec<CommissionEntry>,
+    pub payouts: Vec<Payout>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyTemplateRequest {
+    pub business_type: Option<String>, // defaults to the store's
+    pub custom_fields: Option<serde_json::Map<String, serde_json::Value>>,
+    pub sample_products: Option<bool>, // defaults to false
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckOffStepRequest {
+    pub done: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentMethod {
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub position: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePaymentMethodRequest {
+    pub name: Option<String>, // required for a new method
+    pub enabled: Option<bool>,
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCategoryRequest {
+    pub name: String,
 }
@@ -0,0 +1,377 @@
+//! OpenID Connect identity providers for single sign-on.
+//!
+//! `OIDC_PROVIDERS` lists provider names (e.g. `okta,azure`). Each provider
+//! is configured through `OIDC_<NAME>_*` variables:
+//! - `ISSUER`: issuer URL; endpoints and keys come from its discovery document
+//! - `CLIENT_ID` / `CLIENT_SECRET`: client credentials (no secret for public clients)
+//! - `REDIRECT_URI`: where the provider sends the browser back to
+//!   (default `FRONTEND_URL/sso/callback`)
+//! - `DISPLAY_NAME`: label for the login button (default the provider name)
+//! - `ROLE_CLAIM` / `ROLE_MAP`: id token claim holding the user's groups and
+//!   `group:role` pairs mapping them to roles; the first matching pair wins
+//! - `DEFAULT_ROLE`: role for users no group maps
+//! - `ALLOWED_DOMAINS`: comma separated email domains accepted from the provider
+
+use crate::auth::{DecodingKey, Validation};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::jwk::JwkSet;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Roles that can sign in through SSO. Shop staff keep password and PIN login.
+pub const SSO_ROLES: [&str; 2] = ["admin", "reseller"];
+
+pub struct Provider {
+    pub name: String,
+    pub display_name: String,
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    role_claim: Option<String>,
+    role_map: Vec<(String, String)>,
+    default_role: Option<String>,
+    allowed_domains: Vec<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Metadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct Discovered {
+    metadata: Metadata,
+    jwks: JwkSet,
+}
+
+pub struct Oidc {
+    providers: Vec<Provider>,
+    client: reqwest::Client,
+    // Discovery documents and keys, fetched on first use
+    discovered: RwLock<HashMap<String, Discovered>>,
+}
+
+/// The user as asserted by a verified id token.
+pub struct Identity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub role: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    email_verified: Option<Value>,
+    nonce: Option<String>,
+    #[serde(flatten)]
+    other: HashMap<String, Value>,
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// PKCE `S256` challenge for a code verifier.
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+impl Provider {
+    fn from_env(name: &str) -> Result<Self, String> {
+        let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+        let var = |key: &str| env::var(format!("{}{}", prefix, key)).ok();
+        let required = |key: &str| var(key).ok_or_else(|| format!("{}{} must be set", prefix, key));
+
+        let redirect_uri = match var("REDIRECT_URI") {
+            Some(uri) => uri,
+            None => {
+                let frontend = env::var("FRONTEND_URL")
+                    .map_err(|_| format!("{}REDIRECT_URI or FRONTEND_URL must be set", prefix))?;
+                format!("{}/sso/callback", frontend.trim_end_matches('/'))
+            }
+        };
+
+        let mut role_map = Vec::new();
+        for pair in list(&var("ROLE_MAP").unwrap_or_default()) {
+            let Some((group, role)) = pair.rsplit_once(':') else {
+                return Err(format!("Invalid {}ROLE_MAP entry: {}", prefix, pair));
+            };
+            role_map.push((group.to_string(), role.to_string()));
+        }
+        let default_role = var("DEFAULT_ROLE");
+        for role in role_map.iter().map(|(_, r)| r).chain(default_role.iter()) {
+            if !SSO_ROLES.contains(&role.as_str()) {
+                return Err(format!("{}: SSO cannot grant the {} role", prefix, role));
+            }
+        }
+
+        Ok(Provider {
+            name: name.to_string(),
+            display_name: var("DISPLAY_NAME").unwrap_or_else(|| name.to_string()),
+            issuer: required("ISSUER")?.trim_end_matches('/').to_string(),
+            client_id: required("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET").filter(|s| !s.is_empty()),
+            redirect_uri,
+            role_claim: var("ROLE_CLAIM").filter(|c| !c.is_empty()),
+            role_map,
+            default_role,
+            allowed_domains: list(&var("ALLOWED_DOMAINS").unwrap_or_default().to_lowercase()),
+        })
+    }
+
+    /// Whether roles come from the provider; if so, users it maps to no
+    /// role are refused even when they already have an account.
+    pub fn maps_roles(&self) -> bool {
+        self.role_claim.is_some() || self.default_role.is_some()
+    }
+
+    pub fn allows_email(&self, email: Option<&str>) -> bool {
+        if self.allowed_domains.is_empty() {
+            return true;
+        }
+        email
+            .and_then(|e| e.rsplit_once('@'))
+            .is_some_and(|(_, domain)| self.allowed_domains.iter().any(|d| d == domain))
+    }
+
+    fn role_for(&self, claims: &HashMap<String, Value>) -> Option<String> {
+        let groups: Vec<&str> = match self.role_claim.as_ref().and_then(|c| claims.get(c)) {
+            Some(Value::String(group)) => vec![group.as_str()],
+            Some(Value::Array(groups)) => groups.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        self.role_map
+            .iter()
+            .find(|(group, _)| groups.contains(&group.as_str()))
+            .map(|(_, role)| role.clone())
+            .or_else(|| self.default_role.clone())
+    }
+}
+
+impl Oidc {
+    pub fn from_env() -> Result<Self, String> {
+        let mut providers: Vec<Provider> = Vec::new();
+        for name in list(&env::var("OIDC_PROVIDERS").unwrap_or_default()) {
+            if providers.iter().any(|p| p.name == name) {
+                return Err(format!("Duplicate OIDC provider: {}", name));
+            }
+            providers.push(Provider::from_env(&name)?);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        Ok(Oidc {
+            providers,
+            client,
+            discovered: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn providers(&self) -> &[Provider] {
+        &self.providers
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&Provider> {
+        self.providers.iter().find(|p| p.name == name)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, String> {
+        self.client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid response from {}: {}", url, e))
+    }
+
+    /// Fetches the discovery document and keys, or refreshes the keys when
+    /// `refresh_keys` is set (the provider rotated its signing key).
+    async fn discover(&self, provider: &Provider, refresh_keys: bool) -> Result<(), String> {
+        if !refresh_keys && self.discovered.read().await.contains_key(&provider.name) {
+            return Ok(());
+        }
+
+        let metadata: Metadata = self
+            .get_json(&format!(
+                "{}/.well-known/openid-configuration",
+                provider.issuer
+            ))
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != provider.issuer {
+            return Err(format!(
+                "Issuer mismatch: expected {}, got {}",
+                provider.issuer, metadata.issuer
+            ));
+        }
+        let jwks: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+
+        self.discovered
+            .write()
+            .await
+            .insert(provider.name.clone(), Discovered { metadata, jwks });
+        Ok(())
+    }
+
+    async fn metadata(&self, provider: &Provider) -> Result<Metadata, String> {
+        self.discover(provider, false).await?;
+        self.discovered
+            .read()
+            .await
+            .get(&provider.name)
+            .map(|d| d.metadata.clone())
+            .ok_or_else(|| "Provider not discovered".to_string())
+    }
+
+    /// URL of the provider's login page for a new sign-in attempt.
+    pub async fn authorization_url(
+        &self,
+        provider: &Provider,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, String> {
+        let metadata = self.metadata(provider).await?;
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| format!("Invalid authorization endpoint: {}", e))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &provider.redirect_uri)
+            .append_pair("scope", "openid email profile")
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &code_challenge(code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// Redeems an authorization code and verifies the id token it returns.
+    pub async fn exchange(
+        &self,
+        provider: &Provider,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<Identity, String> {
+        let metadata = self.metadata(provider).await?;
+        let mut request = self.client.post(&metadata.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ]);
+        if let Some(secret) = &provider.client_secret {
+            request = request.basic_auth(&provider.client_id, Some(secret));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Token request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Token request rejected ({}): {}", status, body));
+        }
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid token response: {}", e))?;
+
+        self.verify_id_token(provider, &tokens.id_token, nonce)
+            .await
+    }
+
+    async fn verify_id_token(
+        &self,
+        provider: &Provider,
+        token: &str,
+        nonce: &str,
+    ) -> Result<Identity, String> {
+        let header =
+            jsonwebtoken::decode_header(token).map_err(|e| format!("Invalid id token: {}", e))?;
+        // Shared-secret algorithms would let anyone holding the client secret mint tokens
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(format!("Unsupported id token algorithm: {:?}", header.alg));
+        }
+
+        let mut key = self.decoding_key(provider, header.kid.as_deref()).await;
+        if key.is_none() {
+            self.discover(provider, true).await?;
+            key = self.decoding_key(provider, header.kid.as_deref()).await;
+        }
+        let key = key.ok_or("No matching signing key")??;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&provider.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(token, &key, &validation)
+            .map_err(|e| format!("Invalid id token: {}", e))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("Id token nonce mismatch".to_string());
+        }
+
+        let email_verified = match claims.email_verified {
+            Some(Value::Bool(verified)) => verified,
+            Some(Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        Ok(Identity {
+            role: provider.role_for(&claims.other),
+            subject: claims.sub,
+            email: claims.email.map(|e| e.trim().to_lowercase()),
+            email_verified,
+        })
+    }
+
+    async fn decoding_key(
+        &self,
+        provider: &Provider,
+        kid: Option<&str>,
+    ) -> Option<Result<DecodingKey, String>> {
+        let discovered = self.discovered.read().await;
+        let keys = &discovered.get(&provider.name)?.jwks.keys;
+        let jwk = match kid {
+            Some(kid) => keys
+                .iter()
+                .find(|k| k.common.key_id.as_deref() == Some(kid))?,
+            // Without a kid the provider must publish exactly one key
+            None if keys.len() == 1 => &keys[0],
+            None => return None,
+        };
+        Some(DecodingKey::from_jwk(jwk).map_err(|e| format!("Invalid signing key: {}", e)))
+    }
+}
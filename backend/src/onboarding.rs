@@ -0,0 +1,293 @@
+//! Store onboarding: seeding a store from its business type template and
+//! tracking the owner's setup checklist.
+//!
+//! [`apply`] runs when a store is created and again whenever the owner
+//! picks a business type in the setup wizard. Seeding only adds what is
+//! missing, so applying twice (or another type later) never duplicates or
+//! overwrites the store's own categories, payment methods or roles. Sample
+//! products go in once, into an empty catalog.
+//!
+//! The checklist mostly follows the store's data (a sale was made, a
+//! terminal registered, ...); any step can also be checked off by hand.
+
+use crate::business_types::{self, BusinessType};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+/// Checklist steps, in the order the wizard shows them.
+pub const STEPS: [&str; 7] = [
+    "business_type",
+    "business_profile",
+    "payment_methods",
+    "products",
+    "team",
+    "terminal",
+    "first_sale",
+];
+
+#[derive(Debug, Default, Serialize)]
+pub struct Seeded {
+    pub categories: u64,
+    pub payment_methods: u64,
+    pub roles: u64,
+    pub products: u64,
+}
+
+/// Seeds the store's defaults from `template` and makes it the store's
+/// business type. `custom_fields` (already validated against the template)
+/// take precedence over the store's current values, which take precedence
+/// over the template defaults.
+pub async fn apply(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    template: &BusinessType,
+    custom_fields: &Map<String, Value>,
+    sample_products: bool,
+) -> Result<Seeded, sqlx::Error> {
+    let mut seeded = Seeded::default();
+
+    sqlx::query(
+        r#"
+        UPDATE tenants SET business_type = $2,
+            custom_fields = $3::jsonb || COALESCE(custom_fields, '{}'::jsonb) || $4::jsonb,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1::uuid
+        "#,
+    )
+    .bind(tenant_id)
+    .bind(template.value)
+    .bind(Value::Object(template.default_custom_fields()))
+    .bind(Value::Object(custom_fields.clone()))
+    .execute(&mut *conn)
+    .await?;
+
+    for name in template.categories {
+        seeded.categories += sqlx::query(
+            "INSERT INTO product_categories (tenant_id, name) VALUES ($1::uuid, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(tenant_id)
+        .bind(name)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    }
+
+    let methods = business_types::BASE_PAYMENT_METHODS
+        .iter()
+        .chain(template.payment_methods);
+    for (position, method) in methods.enumerate() {
+        seeded.payment_methods += sqlx::query(
+            r#"
+            INSERT INTO payment_methods (tenant_id, code, name, position)
+            VALUES ($1::uuid, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(method.code)
+        .bind(method.name)
+        .bind(position as i32)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    }
+
+    for role in template.roles {
+        let permissions: Vec<&str> = role.permissions.iter().map(|p| p.as_str()).collect();
+        seeded.roles += sqlx::query(
+            r#"
+            INSERT INTO tenant_roles (id, tenant_id, name, permissions)
+            VALUES ($1, $2::uuid, $3, $4)
+            ON CONFLICT (tenant_id, name) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(tenant_id)
+        .bind(role.name)
+        .bind(&permissions)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO onboarding (tenant_id, template, seeded_at)
+        VALUES ($1::uuid, $2, CURRENT_TIMESTAMP)
+        ON CONFLICT (tenant_id) DO UPDATE SET template = EXCLUDED.template,
+            seeded_at = COALESCE(onboarding.seeded_at, EXCLUDED.seeded_at)
+        "#,
+    )
+    .bind(tenant_id)
+    .bind(template.value)
+    .execute(&mut *conn)
+    .await?;
+
+    if sample_products {
+        seeded.products = seed_sample_products(&mut *conn, tenant_id, template).await?;
+    }
+    Ok(seeded)
+}
+
+/// Adds the template's sample products to an empty catalog, once.
+async fn seed_sample_products(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    template: &BusinessType,
+) -> Result<u64, sqlx::Error> {
+    // Locks the onboarding row so two requests cannot both seed
+    let empty: bool = sqlx::query_scalar(
+        r#"
+        SELECT o.sample_products_at IS NULL
+            AND NOT EXISTS (SELECT 1 FROM products WHERE tenant_id = $1::uuid)
+        FROM onboarding o WHERE o.tenant_id = $1::uuid
+        FOR UPDATE
+        "#,
+    )
+    .bind(tenant_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if !empty {
+        return Ok(0);
+    }
+
+    for product in template.sample_products {
+        sqlx::query(
+            r#"
+            INSERT INTO products (id, tenant_id, name, price, stock_quantity, category)
+            VALUES ($1, $2::uuid, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(tenant_id)
+        .bind(product.name)
+        .bind(product.price)
+        .bind(product.stock_quantity)
+        .bind(product.category)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    // Samples share the transaction's timestamp, which tells them apart
+    sqlx::query(
+        "UPDATE onboarding SET sample_products_at = CURRENT_TIMESTAMP WHERE tenant_id = $1::uuid",
+    )
+    .bind(tenant_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(template.sample_products.len() as u64)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub key: &'static str,
+    pub done: bool,
+    /// Checked off by hand rather than detected.
+    pub checked_off: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Checklist {
+    pub template: Option<String>,
+    pub seeded_at: Option<chrono::NaiveDateTime>,
+    pub dismissed: bool,
+    pub completed: bool,
+    pub done: usize,
+    pub total: usize,
+    pub steps: Vec<Step>,
+}
+
+#[derive(FromRow)]
+struct Progress {
+    template: Option<String>,
+    seeded_at: Option<chrono::NaiveDateTime>,
+    dismissed: bool,
+    completed_steps: Vec<String>,
+    business_profile: bool,
+    products: bool,
+    team: bool,
+    terminal: bool,
+    first_sale: bool,
+}
+
+/// The store's checklist, from its data and the steps checked off by hand.
+pub async fn checklist(conn: &mut PgConnection, tenant_id: &str) -> Result<Checklist, sqlx::Error> {
+    let progress = sqlx::query_as::<_, Progress>(
+        r#"
+        SELECT o.template, o.seeded_at, o.dismissed_at IS NOT NULL AS dismissed,
+            COALESCE(o.completed_steps, '{}') AS completed_steps,
+            t.document IS NOT NULL AS business_profile,
+            EXISTS (
+                SELECT 1 FROM products p
+                WHERE p.tenant_id = t.id AND p.created_at IS DISTINCT FROM o.sample_products_at
+            ) AS products,
+            (SELECT COUNT(*) FROM users u WHERE u.tenant_id = t.id) > 1 AS team,
+            EXISTS (SELECT 1 FROM terminals r WHERE r.tenant_id = t.id AND r.revoked_at IS NULL) AS terminal,
+            EXISTS (SELECT 1 FROM sales s WHERE s.tenant_id = t.id) AS first_sale
+        FROM tenants t LEFT JOIN onboarding o ON o.tenant_id = t.id
+        WHERE t.id = $1::uuid
+        "#,
+    )
+    .bind(tenant_id)
+    .fetch_one(conn)
+    .await?;
+
+    let steps: Vec<Step> = STEPS
+        .iter()
+        .map(|&key| {
+            let detected = match key {
+                "business_type" => progress.seeded_at.is_some(),
+                "business_profile" => progress.business_profile,
+                "products" => progress.products,
+                "team" => progress.team,
+                "terminal" => progress.terminal,
+                "first_sale" => progress.first_sale,
+                _ => false,
+            };
+            let checked_off = progress.completed_steps.iter().any(|s| s == key);
+            Step {
+                key,
+                done: detected || checked_off,
+                checked_off,
+            }
+        })
+        .collect();
+    let done = steps.iter().filter(|s| s.done).count();
+
+    Ok(Checklist {
+        template: progress.template,
+        seeded_at: progress.seeded_at,
+        dismissed: progress.dismissed,
+        completed: done == steps.len(),
+        done,
+        total: steps.len(),
+        steps,
+    })
+}
+
+/// Checks a step off by hand, or takes the check back.
+pub async fn check_off(
+    conn: &mut PgConnection,
+    tenant_id: &str,
+    step: &str,
+    done: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO onboarding (tenant_id, completed_steps)
+        VALUES ($1::uuid, CASE WHEN $3 THEN ARRAY[$2] ELSE '{}' END)
+        ON CONFLICT (tenant_id) DO UPDATE SET completed_steps = CASE
+            WHEN $3 THEN array_append(array_remove(onboarding.completed_steps, $2), $2)
+            ELSE array_remove(onboarding.completed_steps, $2)
+        END
+        "#,
+    )
+    .bind(tenant_id)
+    .bind(step)
+    .bind(done)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Card charges for subscription billing.
+//!
+//! Billing only talks to a [`PaymentGateway`]; `PAYMENT_GATEWAY` selects the
+//! implementation. The only one so far is `fake` (the default), which never
+//! leaves the process and is meant for development and tests:
+//! - no payment method, or one starting with `tok_decline`: declined
+//! - one starting with `tok_error`: the gateway is unreachable
+//! - anything else: paid
+//!
+//! A real provider goes in as another implementation; charges carry an
+//! idempotency key so a retried request cannot bill a store twice.
+
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub struct Charge<'a> {
+    /// Gateway token of the store's card.
+    pub payment_method: Option<&'a str>,
+    pub amount: i64, // cents
+    /// Shown on the store's card statement.
+    #[allow(dead_code)] // the fake gateway has no statement
+    pub description: String,
+    /// Same key, same charge: the gateway returns the first result.
+    pub idempotency_key: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum ChargeOutcome {
+    Paid { reference: String },
+    Declined { reason: String },
+}
+
+pub trait PaymentGateway: Send + Sync {
+    /// Recorded as the method of the payments it makes.
+    fn name(&self) -> &'static str;
+
+    /// `Err` when the gateway could not be reached; the charge is retried.
+    fn charge<'a>(&'a self, charge: Charge<'a>) -> BoxFuture<'a, Result<ChargeOutcome, String>>;
+}
+
+pub fn from_env() -> Result<Arc<dyn PaymentGateway>, String> {
+    match env::var("PAYMENT_GATEWAY").as_deref() {
+        Err(_) | Ok("") | Ok("fake") => Ok(Arc::new(FakeGateway::default())),
+        Ok(other) => Err(format!("Unknown PAYMENT_GATEWAY: {}", other)),
+    }
+}
+
+#[derive(Default)]
+pub struct FakeGateway {
+    charges: Mutex<HashMap<String, ChargeOutcome>>,
+}
+
+impl PaymentGateway for FakeGateway {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn charge<'a>(&'a self, charge: Charge<'a>) -> BoxFuture<'a, Result<ChargeOutcome, String>> {
+        Box::pin(async move {
+            let mut charges = self.charges.lock().map_err(|e| e.to_string())?;
+            if let Some(outcome) = charges.get(&charge.idempotency_key) {
+                return Ok(outcome.clone());
+            }
+
+            let outcome = match charge.payment_method {
+                _ if charge.amount <= 0 => ChargeOutcome::Declined {
+                    reason: "Invalid amount".to_string(),
+                },
+                None => ChargeOutcome::Declined {
+                    reason: "No payment method".to_string(),
+                },
+                Some(token) if token.starts_with("tok_decline") => ChargeOutcome::Declined {
+                    reason: "Card declined".to_string(),
+                },
+                Some(token) if token.starts_with("tok_error") => {
+                    return Err("Gateway unavailable".to_string());
+                }
+                Some(_) => ChargeOutcome::Paid {
+                    reference: format!("fake_{}", Uuid::new_v4().simple()),
+                },
+            };
+            charges.insert(charge.idempotency_key, outcome.clone());
+            Ok(outcome)
+        })
+    }
+}
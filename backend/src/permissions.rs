@@ -0,0 +1,234 @@
+//! Role based access control.
+//!
+//! Every role maps to a set of fine-grained permissions. `admin`, `reseller`
+//! and `user` (the shop owner) are built in; tenants can define their own
+//! roles in `tenant_roles`, and `manager`, `cashier` and `stockist` ship as
+//! templates a tenant may override. `auth_middleware` resolves the caller's
+//! permissions once per request and handlers declare what they need with the
+//! [`Require`] extractor:
+//!
+//! ```ignore
+//! pub async fn cancel_sale(_: Require<perm::SalesCancel>, ...)
+//! ```
+
+use crate::auth::Claims;
+use axum::{extract::FromRequestParts, http::StatusCode, http::request::Parts};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::marker::PhantomData;
+
+macro_rules! permissions {
+    ($($variant:ident => $name:literal),* $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+        pub enum Permission {
+            $(#[serde(rename = $name)] $variant),*
+        }
+
+        impl Permission {
+            pub const ALL: &'static [Permission] = &[$(Permission::$variant),*];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Permission::$variant => $name),*
+                }
+            }
+
+            pub fn parse(value: &str) -> Option<Permission> {
+                match value {
+                    $($name => Some(Permission::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        /// Marker types for [`Require`], one per permission.
+        #[allow(dead_code)] // permissions only checked at runtime have no extractor use
+        pub mod perm {
+            $(
+                pub struct $variant;
+                impl super::PermissionMarker for $variant {
+                    const PERMISSION: super::Permission = super::Permission::$variant;
+                }
+            )*
+        }
+    };
+}
+
+permissions! {
+    // Platform (admin / reseller back office)
+    PlansView => "plans.view",
+    PlansManage => "plans.manage",
+    TenantsManage => "tenants.manage",
+    ResellersManage => "resellers.manage",
+    UsersManage => "users.manage",
+    BillingManage => "billing.manage",
+    EarningsView => "earnings.view",
+    // Tenant
+    SalesCreate => "sales.create",
+    SalesView => "sales.view",
+    SalesCancel => "sales.cancel",
+    ProductsView => "products.view",
+    ProductsEdit => "products.edit",
+    ProductsEditPrice => "products.edit_price",
+    CustomersView => "customers.view",
+    CustomersEdit => "customers.edit",
+    CustomersDelete => "customers.delete",
+    CustomersPrivacy => "customers.privacy",
+    ReportsView => "reports.view",
+    MarketingManage => "marketing.manage",
+    GiftCardsManage => "gift_cards.manage",
+    DeliveriesDispatch => "deliveries.dispatch",
+    DeliveriesManage => "deliveries.manage",
+    RolesManage => "roles.manage",
+    TerminalsManage => "terminals.manage",
+    ApiKeysManage => "api_keys.manage",
+    AuditView => "audit.view",
+    BillingView => "billing.view",
+    SetupManage => "setup.manage",
+}
+
+pub trait PermissionMarker {
+    const PERMISSION: Permission;
+}
+
+/// Built-in role names, which tenants cannot redefine or assign.
+pub const RESERVED_ROLES: [&str; 3] = ["admin", "reseller", "user"];
+
+impl Permission {
+    pub fn is_platform(self) -> bool {
+        matches!(
+            self,
+            Permission::PlansView
+                | Permission::PlansManage
+                | Permission::TenantsManage
+                | Permission::ResellersManage
+                | Permission::UsersManage
+                | Permission::BillingManage
+                | Permission::EarningsView
+        )
+    }
+
+    /// Permissions a tenant may grant to its own roles.
+    pub fn tenant_permissions() -> impl Iterator<Item = Permission> {
+        Permission::ALL.iter().copied().filter(|p| !p.is_platform())
+    }
+
+    /// Scopes an API key may carry: tenant permissions, minus the ones
+    /// that manage or review access itself.
+    pub fn api_key_scopes() -> impl Iterator<Item = Permission> {
+        Permission::tenant_permissions().filter(|p| {
+            !matches!(
+                p,
+                Permission::RolesManage
+                    | Permission::TerminalsManage
+                    | Permission::ApiKeysManage
+                    | Permission::AuditView
+                    | Permission::BillingView
+            )
+        })
+    }
+}
+
+/// Default permissions for the tenant role templates.
+pub fn role_template(name: &str) -> Option<Vec<Permission>> {
+    use Permission::*;
+    match name {
+        "manager" => Some(
+            Permission::tenant_permissions()
+                .filter(|p| !matches!(p, RolesManage | ApiKeysManage | AuditView | BillingView))
+                .collect(),
+        ),
+        "cashier" => Some(vec![
+            SalesCreate,
+            SalesView,
+            ProductsView,
+            CustomersView,
+            CustomersEdit,
+            DeliveriesDispatch,
+        ]),
+        "stockist" => Some(vec![ProductsView, ProductsEdit]),
+        _ => None,
+    }
+}
+
+/// Permissions granted to the current request's user.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions(Vec<Permission>);
+
+impl Permissions {
+    pub fn has(&self, permission: Permission) -> bool {
+        self.0.contains(&permission)
+    }
+
+    /// Keeps only the permissions in `scopes`.
+    pub fn restrict(self, scopes: &[Permission]) -> Permissions {
+        Permissions(self.0.into_iter().filter(|p| scopes.contains(p)).collect())
+    }
+
+    /// Resolves a role to its permissions. Custom and template roles are
+    /// looked up on every request so edits apply without a new login.
+    pub async fn resolve(pool: &PgPool, claims: &Claims) -> Permissions {
+        let permissions = match claims.role.as_str() {
+            // Commissions are paid to resellers; admins review them per reseller
+            "admin" => Permission::ALL
+                .iter()
+                .copied()
+                .filter(|p| *p != Permission::EarningsView)
+                .collect(),
+            "reseller" => vec![
+                Permission::PlansView,
+                Permission::TenantsManage,
+                Permission::EarningsView,
+            ],
+            "user" => Permission::tenant_permissions().collect(),
+            role => {
+                let custom: Option<Vec<String>> = sqlx::query_scalar(
+                    "SELECT permissions FROM tenant_roles WHERE tenant_id = $1 AND name = $2",
+                )
+                .bind(&claims.tenant_id)
+                .bind(role)
+                .fetch_optional(pool)
+                .await
+                .unwrap_or(None);
+
+                match custom {
+                    Some(names) => names
+                        .iter()
+                        .filter_map(|name| Permission::parse(name))
+                        .filter(|p| !p.is_platform())
+                        .collect(),
+                    None => role_template(role).unwrap_or_default(),
+                }
+            }
+        };
+
+        Permissions(permissions)
+    }
+}
+
+/// Extractor that rejects the request with 403 unless the caller holds `P`.
+pub struct Require<P: PermissionMarker>(PhantomData<P>);
+
+impl<P, S> FromRequestParts<S> for Require<P>
+where
+    P: PermissionMarker,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let allowed = parts
+            .extensions
+            .get::<Permissions>()
+            .is_some_and(|permissions| permissions.has(P::PERMISSION));
+
+        if allowed {
+            Ok(Require(PhantomData))
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                format!("Missing permission: {}", P::PERMISSION.as_str()),
+            ))
+        }
+    }
+}
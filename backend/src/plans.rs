@@ -0,0 +1,313 @@
+//! Plan entitlements.
+//!
+//! A tenant's plan caps how many users it may have (`plans.max_users`) and
+//! lists the features it unlocks (`plans.features`, a JSON array of feature
+//! names). Tenants without a plan, and plans whose feature list is `NULL`,
+//! are not restricted. Routers gate a whole feature with [`require_feature`]:
+//!
+//! ```ignore
+//! .route_layer(from_fn_with_state(pool.clone(), plans::require_feature::<feature::Metrics>))
+//! .route_layer(from_fn_with_state(pool.clone(), middleware::auth_middleware))
+//! ```
+//!
+//! Checkout uses several features at once, so `create_sale` checks the ones
+//! a sale needs itself and stops awarding loyalty points without the plan.
+//!
+//! Refusals carry the plans that would allow the action: 402 when there is
+//! an upgrade to offer, 403 when no plan would.
+
+use crate::auth::Claims;
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+
+macro_rules! features {
+    ($($variant:ident => $name:literal),* $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+        pub enum Feature {
+            $(#[serde(rename = $name)] $variant),*
+        }
+
+        impl Feature {
+            pub const ALL: &'static [Feature] = &[$(Feature::$variant),*];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Feature::$variant => $name),*
+                }
+            }
+
+            pub fn parse(value: &str) -> Option<Feature> {
+                match value {
+                    $($name => Some(Feature::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        /// Marker types for [`require_feature`], one per feature.
+        #[allow(dead_code)] // features only checked inside handlers have no layer
+        pub mod feature {
+            $(
+                pub struct $variant;
+                impl super::FeatureMarker for $variant {
+                    const FEATURE: super::Feature = super::Feature::$variant;
+                }
+            )*
+        }
+    };
+}
+
+features! {
+    Metrics => "metrics",
+    Loyalty => "loyalty",
+    Coupons => "coupons",
+    GiftCards => "gift_cards",
+    Deliveries => "deliveries",
+    Segments => "segments",
+    MultipleRegisters => "multiple_registers",
+}
+
+pub trait FeatureMarker {
+    const FEATURE: Feature;
+}
+
+/// Registers a tenant may have without [`Feature::MultipleRegisters`].
+pub const SINGLE_REGISTER: i64 = 1;
+
+/// What a tenant's plan allows.
+#[derive(Debug, Clone, Default)]
+pub struct Entitlements {
+    pub plan_id: Option<String>,
+    pub plan_name: Option<String>,
+    pub max_users: Option<i32>,
+    features: Option<Vec<Feature>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct PlanRow {
+    id: String,
+    name: String,
+    max_users: i32,
+    features: Option<Value>,
+}
+
+/// Reads a plan's feature list; unknown names are ignored.
+pub fn parse_features(features: &Value) -> Vec<Feature> {
+    features
+        .as_array()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(Value::as_str)
+                .filter_map(Feature::parse)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Entitlements {
+    /// `lock` holds the tenant row until the transaction ends, so concurrent
+    /// requests cannot both take the last free seat.
+    pub async fn load<'e>(
+        executor: impl PgExecutor<'e>,
+        tenant_id: &str,
+        lock: bool,
+    ) -> Result<Entitlements, sqlx::Error> {
+        let plan = sqlx::query_as::<_, PlanRow>(&format!(
+            "SELECT p.id::text AS id, p.name, p.max_users, p.features FROM tenants t JOIN plans p ON p.id = t.plan_id WHERE t.id = $1::uuid{}",
+            if lock { " FOR UPDATE OF t" } else { "" }
+        ))
+        .bind(tenant_id)
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(match plan {
+            Some(plan) => Entitlements {
+                plan_id: Some(plan.id),
+                plan_name: Some(plan.name),
+                max_users: Some(plan.max_users),
+                features: plan.features.as_ref().map(parse_features),
+            },
+            None => Entitlements::default(),
+        })
+    }
+
+    pub fn allows(&self, feature: Feature) -> bool {
+        self.features
+            .as_ref()
+            .is_none_or(|features| features.contains(&feature))
+    }
+
+    /// Registers the plan allows, `None` for no limit.
+    pub fn max_terminals(&self) -> Option<i64> {
+        (!self.allows(Feature::MultipleRegisters)).then_some(SINGLE_REGISTER)
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct UpgradePlan {
+    pub id: String,
+    pub name: String,
+    pub price: i32,
+    pub max_users: i32,
+}
+
+/// A refused action and the plans that would allow it.
+#[derive(Debug, Serialize)]
+pub struct PlanLimit {
+    pub error: &'static str,
+    pub message: String,
+    pub plan: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature: Option<Feature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<i64>,
+    pub upgrade_plans: Vec<UpgradePlan>,
+}
+
+impl IntoResponse for PlanLimit {
+    fn into_response(self) -> Response {
+        let status = if self.upgrade_plans.is_empty() {
+            StatusCode::FORBIDDEN
+        } else {
+            StatusCode::PAYMENT_REQUIRED
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Plans, cheapest first, whose feature list includes `feature`.
+async fn plans_with_feature<'e>(
+    executor: impl PgExecutor<'e>,
+    feature: Feature,
+) -> Result<Vec<UpgradePlan>, sqlx::Error> {
+    sqlx::query_as::<_, UpgradePlan>(
+        "SELECT id::text AS id, name, price, max_users FROM plans WHERE features IS NULL OR features ? $1 ORDER BY price, name",
+    )
+    .bind(feature.as_str())
+    .fetch_all(executor)
+    .await
+}
+
+/// Refusal for a feature missing from the tenant's plan.
+pub async fn feature_limit<'e>(
+    executor: impl PgExecutor<'e>,
+    entitlements: &Entitlements,
+    feature: Feature,
+) -> Result<PlanLimit, sqlx::Error> {
+    Ok(PlanLimit {
+        error: "feature_not_in_plan",
+        message: format!("Your plan does not include {}", feature.as_str()),
+        plan: entitlements.plan_name.clone(),
+        feature: Some(feature),
+        limit: None,
+        usage: None,
+        upgrade_plans: plans_with_feature(executor, feature).await?,
+    })
+}
+
+pub async fn count_users<'e>(
+    executor: impl PgExecutor<'e>,
+    tenant_id: &str,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE tenant_id = $1::uuid")
+        .bind(tenant_id)
+        .fetch_one(executor)
+        .await
+}
+
+pub async fn count_terminals<'e>(
+    executor: impl PgExecutor<'e>,
+    tenant_id: &str,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM terminals WHERE tenant_id = $1::uuid AND revoked_at IS NULL",
+    )
+    .bind(tenant_id)
+    .fetch_one(executor)
+    .await
+}
+
+/// Checks that the tenant has room for `adding` more users. Call inside the
+/// transaction that creates them, which keeps the tenant row locked.
+pub async fn check_user_limit(
+    tx: &mut sqlx::PgConnection,
+    pool: &PgPool,
+    tenant_id: &str,
+    adding: i64,
+) -> Result<Option<PlanLimit>, sqlx::Error> {
+    let entitlements = Entitlements::load(&mut *tx, tenant_id, true).await?;
+    let Some(max_users) = entitlements.max_users.map(i64::from) else {
+        return Ok(None);
+    };
+
+    let users = count_users(&mut *tx, tenant_id).await?;
+    if users + adding <= max_users {
+        return Ok(None);
+    }
+
+    let upgrade_plans = sqlx::query_as::<_, UpgradePlan>(
+        "SELECT id::text AS id, name, price, max_users FROM plans WHERE max_users >= $1 ORDER BY price, name",
+    )
+    .bind(users + adding)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(PlanLimit {
+        error: "user_limit_reached",
+        message: format!("Your plan allows {} users", max_users),
+        plan: entitlements.plan_name,
+        feature: None,
+        limit: Some(max_users),
+        usage: Some(users),
+        upgrade_plans,
+    }))
+}
+
+/// Middleware rejecting tenant requests whose plan lacks `F`. Runs after
+/// `auth_middleware`; platform users have no tenant and are let through.
+pub async fn require_feature<F: FeatureMarker>(
+    State(pool): State<PgPool>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let tenant_id = req
+        .extensions()
+        .get::<Claims>()
+        .and_then(|claims| claims.tenant_id.clone());
+    let Some(tenant_id) = tenant_id else {
+        return next.run(req).await;
+    };
+
+    let refusal = async {
+        let entitlements = Entitlements::load(&pool, &tenant_id, false).await?;
+        if entitlements.allows(F::FEATURE) {
+            Ok(None)
+        } else {
+            feature_limit(&pool, &entitlements, F::FEATURE)
+                .await
+                .map(Some)
+        }
+    }
+    .await;
+
+    match refusal {
+        Ok(None) => next.run(req).await,
+        Ok(Some(limit)) => limit.into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
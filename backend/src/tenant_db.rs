@@ -0,0 +1,104 @@
+//! Database access scoped to one tenant.
+//!
+//! [`begin`] opens a transaction that runs as the `app_tenant` role with
+//! `app.tenant_id` set, so the row-level security policies (migration
+//! `enable_row_level_security`) hide and protect every other tenant's rows.
+//! Both settings are transaction-local and end with it; the pooled
+//! connection goes back as it was.
+//!
+//! Tenant handlers take a [`TenantTx`] and run their queries in it, even
+//! when they also filter by `tenant_id`:
+//!
+//! ```ignore
+//! pub async fn list_products(_: Require<perm::ProductsView>, mut tx: TenantTx) -> impl IntoResponse {
+//!     sqlx::query_as::<_, Product>("SELECT ...").fetch_all(&mut *tx).await
+//! ```
+//!
+//! Use the pool directly only for platform work: admin, login and jobs.
+
+use crate::auth::Claims;
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{StatusCode, request::Parts},
+};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use std::ops::{Deref, DerefMut};
+
+pub const TENANT_ROLE: &str = "app_tenant";
+
+pub async fn begin(
+    pool: &PgPool,
+    tenant_id: &str,
+) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT set_config('role', $1, true), set_config('app.tenant_id', $2, true)")
+        .bind(TENANT_ROLE)
+        .bind(tenant_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(tx)
+}
+
+/// Extractor opening a tenant transaction for the caller's tenant. Nothing
+/// is saved unless the handler calls [`TenantTx::commit`].
+pub struct TenantTx {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl TenantTx {
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await
+    }
+
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.tx.rollback().await
+    }
+
+    /// Commits if `result` is `Ok` and passes it on. An `Err` drops the
+    /// transaction, which rolls it back.
+    pub async fn commit_if_ok<T>(self, result: Result<T, sqlx::Error>) -> Result<T, sqlx::Error> {
+        let value = result?;
+        self.commit().await?;
+        Ok(value)
+    }
+}
+
+impl Deref for TenantTx {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.tx
+    }
+}
+
+impl DerefMut for TenantTx {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+}
+
+impl<S> FromRequestParts<S> for TenantTx
+where
+    PgPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let tenant_id = parts
+            .extensions
+            .get::<Claims>()
+            .and_then(|claims| claims.tenant_id.clone())
+            .ok_or((StatusCode::FORBIDDEN, "Tenant ID missing".to_string()))?;
+
+        let pool = PgPool::from_ref(state);
+        let tx = begin(&pool, &tenant_id).await.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to start transaction: {}", e),
+            )
+        })?;
+
+        Ok(TenantTx { tx })
+    }
+}
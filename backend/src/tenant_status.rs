@@ -0,0 +1,226 @@
+//! Tenant status enforcement.
+//!
+//! `auth_middleware` looks up the caller's store on every request:
+//! - `active`: full access. Once `paid_until` has passed the store is past
+//!   due, and responses carry `X-Tenant-Status: past_due` and
+//!   `X-Tenant-Grace-Until` until the grace period (`TENANT_GRACE_DAYS`,
+//!   default 7) runs out
+//! - `suspended`: read-only (`GET` / `HEAD`) by default, or fully blocked
+//!   with `TENANT_SUSPENDED_ACCESS=blocked`
+//! - `inactive`: blocked
+//!
+//! Refusals are a 403 with a JSON body the frontend turns into a block page.
+//! [`run_overdue_suspensions`] suspends stores whose grace period is over;
+//! [`suspend_overdue`] is the same hook for billing to call directly.
+
+use crate::audit::{AuditContext, Event};
+use axum::{
+    Json,
+    http::{HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+
+pub const ACTIVE: &str = "active";
+pub const INACTIVE: &str = "inactive";
+pub const SUSPENDED: &str = "suspended";
+pub const STATUSES: [&str; 3] = [ACTIVE, INACTIVE, SUSPENDED];
+
+/// Why a store was suspended.
+pub const REASON_MANUAL: &str = "manual";
+pub const REASON_OVERDUE: &str = "overdue";
+
+const DEFAULT_GRACE_DAYS: i64 = 7;
+/// How often overdue stores are looked for.
+const SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuspendedAccess {
+    ReadOnly,
+    Blocked,
+}
+
+pub struct TenantPolicy {
+    pub suspended_access: SuspendedAccess,
+    pub grace_days: i64,
+}
+
+impl TenantPolicy {
+    pub fn from_env() -> Result<Self, String> {
+        let suspended_access = match env::var("TENANT_SUSPENDED_ACCESS").as_deref() {
+            Err(_) | Ok("") | Ok("read_only") => SuspendedAccess::ReadOnly,
+            Ok("blocked") => SuspendedAccess::Blocked,
+            Ok(other) => {
+                return Err(format!(
+                    "TENANT_SUSPENDED_ACCESS must be read_only or blocked, got {}",
+                    other
+                ));
+            }
+        };
+        let grace_days = match env::var("TENANT_GRACE_DAYS") {
+            Ok(days) if !days.is_empty() => days
+                .parse::<i64>()
+                .ok()
+                .filter(|d| *d >= 0)
+                .ok_or_else(|| format!("Invalid TENANT_GRACE_DAYS: {}", days))?,
+            _ => DEFAULT_GRACE_DAYS,
+        };
+        Ok(TenantPolicy {
+            suspended_access,
+            grace_days,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TenantState {
+    status: String,
+    suspended_reason: Option<String>,
+    paid_until: Option<NaiveDate>,
+}
+
+/// Body of a refused request.
+#[derive(Debug, Serialize)]
+pub struct TenantBlocked {
+    pub error: &'static str, // tenant_blocked, tenant_read_only
+    pub status: String,
+    pub reason: Option<String>,
+    pub message: &'static str,
+}
+
+impl IntoResponse for TenantBlocked {
+    fn into_response(self) -> Response {
+        (StatusCode::FORBIDDEN, Json(self)).into_response()
+    }
+}
+
+/// What the middleware does with a request.
+pub enum Access {
+    Allowed,
+    /// Allowed; the response is marked with the end of the grace period.
+    PastDue(NaiveDate),
+    Refused(TenantBlocked),
+}
+
+impl Access {
+    /// Adds the past due headers to an allowed response.
+    pub fn mark(&self, response: &mut Response) {
+        if let Access::PastDue(grace_until) = self
+            && let Ok(value) = HeaderValue::from_str(&grace_until.to_string())
+        {
+            let headers = response.headers_mut();
+            headers.insert("x-tenant-status", HeaderValue::from_static("past_due"));
+            headers.insert("x-tenant-grace-until", value);
+        }
+    }
+}
+
+fn is_read(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Decides whether `tenant_id` may make a `method` request. Stores without a
+/// `tenants` row (self sign-up before onboarding) are active.
+pub async fn check(
+    pool: &PgPool,
+    policy: &TenantPolicy,
+    tenant_id: &str,
+    method: &Method,
+) -> Result<Access, sqlx::Error> {
+    let state = sqlx::query_as::<_, TenantState>(
+        "SELECT status, suspended_reason, paid_until FROM tenants WHERE id = $1::uuid",
+    )
+    .bind(tenant_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some(state) = state else {
+        return Ok(Access::Allowed);
+    };
+
+    let today = Utc::now().date_naive();
+    let access = match state.status.as_str() {
+        SUSPENDED if policy.suspended_access == SuspendedAccess::ReadOnly && is_read(method) => {
+            Access::Allowed
+        }
+        SUSPENDED => Access::Refused(TenantBlocked {
+            error: match policy.suspended_access {
+                SuspendedAccess::ReadOnly => "tenant_read_only",
+                SuspendedAccess::Blocked => "tenant_blocked",
+            },
+            status: state.status,
+            reason: state.suspended_reason,
+            message: match policy.suspended_access {
+                SuspendedAccess::ReadOnly => "This store is suspended and can only view its data",
+                SuspendedAccess::Blocked => "This store is suspended",
+            },
+        }),
+        INACTIVE => Access::Refused(TenantBlocked {
+            error: "tenant_blocked",
+            status: state.status,
+            reason: None,
+            message: "This store is inactive",
+        }),
+        _ => match state.paid_until {
+            Some(paid_until) if paid_until < today => {
+                Access::PastDue(paid_until + Duration::days(policy.grace_days))
+            }
+            _ => Access::Allowed,
+        },
+    };
+    Ok(access)
+}
+
+/// Suspends active stores whose grace period is over and returns their ids.
+pub async fn suspend_overdue(
+    pool: &PgPool,
+    policy: &TenantPolicy,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let suspended: Vec<String> = sqlx::query_scalar(
+        r#"
+        UPDATE tenants
+        SET status = $1, suspended_reason = $2, status_changed_at = CURRENT_TIMESTAMP
+        WHERE status = $3 AND paid_until + $4::int < CURRENT_DATE
+        RETURNING id::text
+        "#,
+    )
+    .bind(SUSPENDED)
+    .bind(REASON_OVERDUE)
+    .bind(ACTIVE)
+    .bind(policy.grace_days as i32)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let system = AuditContext::system();
+    for id in &suspended {
+        let event = Event::new("tenant.suspend", id)
+            .tenant(Some(id))
+            .after(Some(serde_json::json!({
+                "status": SUSPENDED,
+                "suspended_reason": REASON_OVERDUE,
+            })));
+        system.record(&mut *tx, event).await?;
+    }
+
+    tx.commit().await?;
+    Ok(suspended)
+}
+
+/// Background task running [`suspend_overdue`] every hour.
+pub async fn run_overdue_suspensions(pool: PgPool, policy: Arc<TenantPolicy>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(SWEEP_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        match suspend_overdue(&pool, &policy).await {
+            Ok(ids) if !ids.is_empty() => {
+                println!("Suspended {} overdue tenant(s)", ids.len());
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to suspend overdue tenants: {}", e),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+#!/bin/bash
+# Email verification and password reset: the links mailed on sign-up and on
+# request, single use and expiry of their tokens, and the resend cooldown.
+# Starts its own backend with the file mailer so the messages can be read
+# back, which needs the binary built and DATABASE_URL pointing at the
+# database:
+#
+#   cargo build --bin backend
+#   DATABASE_URL=postgres://... ./test_accounts.sh
+BACKEND=${BACKEND:-./target/debug/backend}
+PORT=${PORT:-3102}
+API=http://127.0.0.1:$PORT
+RUN=$(date +%s)
+FAILED=0
+MAIL=$(mktemp -d)
+trap 'stop_backend; rm -rf "$MAIL"' EXIT
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+# check_value <label> <actual> <expected>
+check_value() {
+  if [ "$2" = "$3" ]; then
+    echo "  ok ($1 = $3)"
+  else
+    echo "  FAILED: expected $1 = $3, got $2"
+    FAILED=1
+  fi
+}
+
+start_backend() {
+  PORT=$PORT MAILER=file MAIL_DIR="$MAIL" MAIL_FROM=loja@example.com \
+    FRONTEND_URL=https://app.example.com REQUIRE_EMAIL_VERIFICATION=true \
+    JWT_SECRET=${JWT_SECRET:-$(openssl rand -hex 32)} \
+    "$BACKEND" > "$MAIL/backend.log" 2>&1 &
+  PID=$!
+  for _ in $(seq 50); do
+    curl -s -o /dev/null "$API/" && return 0
+    kill -0 $PID 2>/dev/null || return 1
+    sleep 0.2
+  done
+  return 1
+}
+
+stop_backend() {
+  [ -n "$PID" ] && kill $PID 2>/dev/null && wait $PID 2>/dev/null
+  PID=
+}
+
+# mails_to <email>: messages sent to the address, newest first. Mail goes
+# out in the background, so give it a moment to arrive.
+mails_to() {
+  sleep 0.5
+  grep -l "^To: $1" "$MAIL"/*.eml 2>/dev/null | xargs -r ls -t
+}
+
+# mail_body <file>: the decoded text of a message
+mail_body() {
+  sed '1,/^\r$/d' "$1" | tr -d '\r\n' | base64 -d
+}
+
+# link_token <email> <path>: token of the newest link to <path> mailed to <email>
+link_token() {
+  local file=$(mails_to "$1" | head -n 1)
+  [ -n "$file" ] && mail_body "$file" | grep -o "https://app.example.com/$2?token=[A-Za-z0-9_-]*" | cut -d= -f2
+}
+
+login() {
+  request "" POST /auth/login "{\"email\": \"$1\", \"password\": \"$2\"}"
+}
+
+# backdate <email> <purpose>: moves the user's tokens past the resend cooldown
+backdate() {
+  sql "UPDATE user_tokens SET created_at = created_at - INTERVAL '2 minutes' WHERE purpose = '$2' AND user_id = (SELECT id FROM users WHERE email = '$1')"
+}
+
+start_backend || { echo "Backend failed to start:"; cat "$MAIL/backend.log"; exit 1; }
+
+echo "1. Signing up mails a verification link, and login waits for it..."
+EMAIL="accounts-$RUN@example.com"
+request "" POST /auth/register "{\"email\": \"$EMAIL\", \"password\": \"password123\"}"
+expect 201
+FILE=$(mails_to "$EMAIL" | head -n 1)
+check_value "mails" "$(mails_to "$EMAIL" | wc -l)" 1
+check_value "From" "$(grep -m1 '^From:' "$FILE" | tr -d '\r')" "From: loja@example.com"
+check_value "Subject" "$(grep -m1 '^Subject:' "$FILE" | tr -d '\r')" "Subject: Confirme seu email"
+FIRST_TOKEN=$(link_token "$EMAIL" verify-email)
+check_value "token length" "${#FIRST_TOKEN}" 43
+login "$EMAIL" password123
+expect 403 "Email not verified"
+
+echo "2. A resend within the cooldown sends nothing, after it a new link..."
+request "" POST /auth/resend-verification "{\"email\": \"$EMAIL\"}"
+expect 200 "If the email awaits verification"
+check_value "mails" "$(mails_to "$EMAIL" | wc -l)" 1
+backdate "$EMAIL" email_verification
+request "" POST /auth/resend-verification "{\"email\": \"$EMAIL\"}"
+expect 200 "If the email awaits verification"
+check_value "mails" "$(mails_to "$EMAIL" | wc -l)" 2
+TOKEN=$(link_token "$EMAIL" verify-email)
+request "" POST /auth/resend-verification "{\"email\": \"accounts-nobody-$RUN@example.com\"}"
+expect 200 "If the email awaits verification"
+
+echo "3. Only the newest link verifies, and only once..."
+request "" POST /auth/verify-email "{\"token\": \"$FIRST_TOKEN\"}"
+expect 400 "Invalid or expired token"
+request "" POST /auth/verify-email "{\"token\": \"$TOKEN\"}"
+expect 200 "Email verified"
+request "" POST /auth/verify-email "{\"token\": \"$TOKEN\"}"
+expect 400 "Invalid or expired token"
+login "$EMAIL" password123
+expect 200
+SESSION=$(echo "$BODY" | jq -r .token)
+request "" POST /auth/resend-verification "{\"email\": \"$EMAIL\"}"
+check_value "mails" "$(mails_to "$EMAIL" | wc -l)" 2
+
+echo "4. Forgot password answers the same for unknown emails..."
+request "" POST /auth/forgot-password "{\"email\": \"accounts-nobody-$RUN@example.com\"}"
+expect 200 "If the email is registered"
+check_value "mails" "$(mails_to "accounts-nobody-$RUN@example.com" | wc -l)" 0
+request "" POST /auth/forgot-password "{\"email\": \"$EMAIL\"}"
+expect 200 "If the email is registered"
+check_value "mails" "$(mails_to "$EMAIL" | wc -l)" 3
+FILE=$(mails_to "$EMAIL" | head -n 1)
+check_value "Subject" "$(grep -m1 '^Subject:' "$FILE" | tr -d '\r')" "Subject: =?UTF-8?B?$(echo -n 'Redefinição de senha' | base64)?="
+RESET=$(link_token "$EMAIL" reset-password)
+
+echo "5. The reset link sets a new password and logs out every device..."
+request "" POST /auth/reset-password "{\"token\": \"$RESET\", \"password\": \"short\"}"
+expect 400 "at least 8 characters"
+request "" POST /auth/reset-password "{\"token\": \"$RESET\", \"password\": \"new-password123\"}"
+expect 200 "Password updated"
+request "" POST /auth/reset-password "{\"token\": \"$RESET\", \"password\": \"other-password123\"}"
+expect 400 "Invalid or expired token"
+request "$SESSION" GET /auth/sessions
+expect 401
+login "$EMAIL" password123
+expect 401
+login "$EMAIL" new-password123
+expect 200
+check_sql "SELECT COUNT(*) FROM audit_log WHERE action = 'auth.password_reset' AND entity_id = (SELECT id::text FROM users WHERE email = '$EMAIL')" 1
+
+echo "6. An expired reset link is refused..."
+backdate "$EMAIL" password_reset
+request "" POST /auth/forgot-password "{\"email\": \"$EMAIL\"}"
+RESET=$(link_token "$EMAIL" reset-password)
+sql "UPDATE user_tokens SET expires_at = CURRENT_TIMESTAMP - INTERVAL '1 minute' WHERE purpose = 'password_reset' AND used_at IS NULL AND user_id = (SELECT id FROM users WHERE email = '$EMAIL')"
+request "" POST /auth/reset-password "{\"token\": \"$RESET\", \"password\": \"other-password123\"}"
+expect 400 "Invalid or expired token"
+
+echo "7. Resetting the password also confirms an unverified address..."
+OTHER="accounts-other-$RUN@example.com"
+request "" POST /auth/register "{\"email\": \"$OTHER\", \"password\": \"password123\"}"
+request "" POST /auth/forgot-password "{\"email\": \"$OTHER\"}"
+RESET=$(link_token "$OTHER" reset-password)
+request "" POST /auth/reset-password "{\"token\": \"$RESET\", \"password\": \"new-password123\"}"
+expect 200
+login "$OTHER" new-password123
+expect 200
+
+if [ "$FAILED" = 0 ]; then
+  echo "All account checks passed."
+else
+  echo "Some account checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,209 @@
+#!/bin/bash
+# API keys: creating scoped keys, calling the API with them, and the
+# refusals for missing scopes, missing permissions, other stores, revoked
+# and expired keys. Needs the backend running and DATABASE_URL pointing at
+# its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_api_keys.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# key_request <key> <method> <path> [json body]: same as request, with the
+# key in X-API-Key
+key_request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "X-API-Key: $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+echo "1. Two stores, one with a cashier and an integrations manager..."
+ADMIN_EMAIL="api-keys-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="api-keys-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"API keys $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(sql "SELECT tenant_id FROM users WHERE email = '$OWNER'")
+TOKEN=$(login "$OWNER")
+OTHER_OWNER="api-keys-other-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"API keys other $RUN\", \"owner_email\": \"$OTHER_OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+OTHER_TOKEN=$(login "$OTHER_OWNER")
+CASHIER_EMAIL="api-keys-cashier-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/users \
+  "{\"email\": \"$CASHIER_EMAIL\", \"password\": \"password123\", \"role\": \"cashier\", \"tenant_id\": \"$TENANT\"}"
+expect 201
+CASHIER_TOKEN=$(login "$CASHIER_EMAIL")
+request "$TOKEN" POST /roles '{"name": "Integrations", "permissions": ["api_keys.manage", "customers.view"]}'
+expect 201
+ROLE=$(echo "$BODY" | jq -r .)
+STAFF_EMAIL="api-keys-staff-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/users \
+  "{\"email\": \"$STAFF_EMAIL\", \"password\": \"password123\", \"role\": \"cashier\", \"tenant_id\": \"$TENANT\"}"
+STAFF=$(sql "SELECT id FROM users WHERE email = '$STAFF_EMAIL'")
+request "$TOKEN" PUT "/roles/users/$STAFF" '{"role": "integrations"}'
+expect 200
+STAFF_TOKEN=$(login "$STAFF_EMAIL")
+request "$TOKEN" POST /customers '{"name": "Maria"}'
+expect 201
+request "$OTHER_TOKEN" POST /customers '{"name": "Joana"}'
+expect 201
+
+echo "2. Keys are validated against the scope list..."
+request "$TOKEN" GET /api-keys/scopes
+expect 200
+check 'index("customers.view") != null' true
+check 'index("api_keys.manage")' null
+check 'index("tenants.manage")' null
+request "$TOKEN" POST /api-keys '{"name": " ", "scopes": ["customers.view"]}'
+expect 400 "Name must have"
+request "$TOKEN" POST /api-keys '{"name": "Loja online", "scopes": []}'
+expect 400 "At least one scope"
+request "$TOKEN" POST /api-keys '{"name": "Loja online", "scopes": ["roles.manage"]}'
+expect 400 "Invalid scope: roles.manage"
+request "$TOKEN" POST /api-keys '{"name": "Loja online", "scopes": ["customers.view"], "expires_in_days": 0}'
+expect 400 "Expiry must be between"
+
+echo "3. A key is shown once and only its hash is stored..."
+request "$TOKEN" POST /api-keys '{"name": "Loja online", "scopes": ["customers.view", "customers.view"]}'
+expect 201
+check '.scopes | join(",")' customers.view
+check .expires_at null
+KEY=$(echo "$BODY" | jq -r .key)
+KEY_ID=$(echo "$BODY" | jq -r .id)
+check '.key | startswith("pdv_")' true
+check_sql "SELECT key_hash = encode(sha256('$KEY'::bytea), 'hex') FROM api_keys WHERE id = '$KEY_ID'" t
+check_sql "SELECT COUNT(*) FROM api_keys WHERE key_hash = '$KEY'" 0
+request "$TOKEN" GET /api-keys
+expect 200
+check length 1
+check '.[0].prefix' "${KEY:0:12}"
+check '.[0].key' null
+check '.[0].last_used_at' null
+
+echo "4. The key reads the store's customers, in either header..."
+request "$KEY" GET /customers
+expect 200
+check 'map(.name) | join(",")' Maria
+key_request "$KEY" GET /customers
+expect 200
+check length 1
+request "$TOKEN" GET /api-keys
+check '.[0].last_used_at != null' true
+
+echo "5. It is refused outside its scopes and on key management..."
+request "$KEY" POST /customers '{"name": "Ana"}'
+expect 403
+request "$KEY" GET /sales
+expect 403
+request "$KEY" GET /api-keys
+expect 403
+request "$KEY" POST /api-keys '{"name": "Copia", "scopes": ["customers.view"]}'
+expect 403
+
+echo "6. Only staff allowed to manage keys create them, within their own permissions..."
+request "$CASHIER_TOKEN" GET /api-keys
+expect 403
+request "$CASHIER_TOKEN" POST /api-keys '{"name": "Caixa", "scopes": ["sales.create"]}'
+expect 403
+request "$STAFF_TOKEN" POST /api-keys '{"name": "ERP", "scopes": ["customers.edit"]}'
+expect 403 "Missing permission: customers.edit"
+request "$STAFF_TOKEN" POST /api-keys '{"name": "ERP", "scopes": ["customers.view"], "expires_in_days": 30}'
+expect 201
+check '.expires_at != null' true
+STAFF_KEY=$(echo "$BODY" | jq -r .key)
+request "$STAFF_KEY" GET /customers
+expect 200
+
+echo "7. A key loses what its creator loses..."
+request "$TOKEN" PUT "/roles/$ROLE" '{"name": "Integrations", "permissions": ["api_keys.manage"]}'
+expect 200
+request "$STAFF_KEY" GET /customers
+expect 403
+
+echo "8. Other stores can neither see nor revoke the key..."
+request "$OTHER_TOKEN" GET /api-keys
+expect 200
+check length 0
+request "$OTHER_TOKEN" DELETE "/api-keys/$KEY_ID"
+expect 404
+request "$KEY" GET /customers
+expect 200
+
+echo "9. Revoked, expired and unknown keys are refused..."
+request "$TOKEN" DELETE "/api-keys/$KEY_ID"
+expect 200 "API key revoked"
+request "$TOKEN" DELETE "/api-keys/$KEY_ID"
+expect 404
+request "$KEY" GET /customers
+expect 401
+request "$TOKEN" POST /api-keys '{"name": "Temporaria", "scopes": ["customers.view"], "expires_in_days": 1}'
+EXPIRED_KEY=$(echo "$BODY" | jq -r .key)
+sql "UPDATE api_keys SET expires_at = CURRENT_TIMESTAMP - INTERVAL '1 minute' WHERE id = '$(echo "$BODY" | jq -r .id)'"
+request "$EXPIRED_KEY" GET /customers
+expect 401
+request "pdv_unknown$RUN" GET /customers
+expect 401
+
+if [ "$FAILED" = 0 ]; then
+  echo "All API key checks passed."
+else
+  echo "Some API key checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,159 @@
+#!/bin/bash
+# Audit log: entries written by sensitive actions, who may read them and
+# which tenant's, the filters, and that the table refuses edits. Needs the
+# backend running and DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_audit_log.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+
+echo "1. An admin opens two stores, one with a cashier..."
+ADMIN_EMAIL="audit-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+ADMIN=$(sql "SELECT id FROM users WHERE email = '$ADMIN_EMAIL'")
+OWNER="audit-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Audit $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(sql "SELECT tenant_id FROM users WHERE email = '$OWNER'")
+OWNER_ID=$(sql "SELECT id FROM users WHERE email = '$OWNER'")
+TOKEN=$(login "$OWNER")
+OTHER_OWNER="audit-other-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Audit other $RUN\", \"owner_email\": \"$OTHER_OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+OTHER_TENANT=$(sql "SELECT tenant_id FROM users WHERE email = '$OTHER_OWNER'")
+OTHER_TOKEN=$(login "$OTHER_OWNER")
+CASHIER_EMAIL="audit-cashier-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/users \
+  "{\"email\": \"$CASHIER_EMAIL\", \"password\": \"password123\", \"role\": \"cashier\", \"tenant_id\": \"$TENANT\"}"
+expect 201
+CASHIER_TOKEN=$(login "$CASHIER_EMAIL")
+
+echo "2. The owner changes a price..."
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 10}'
+expect 201
+PRODUCT=$(sql "SELECT id FROM products WHERE tenant_id = '$TENANT'")
+request "$TOKEN" PUT "/products/$PRODUCT" '{"price": 2500}'
+expect 200
+request "$TOKEN" PUT "/products/$PRODUCT" '{"name": "Caderno grande"}'
+expect 200
+
+echo "3. The owner reads their store's log, filtered..."
+request "$TOKEN" GET "/audit-log?action=product.price_change"
+expect 200
+check .total 1
+check '.items[0].entity_id' "$PRODUCT"
+check '.items[0].actor_id' "$OWNER_ID"
+check '.items[0].actor_email' "$OWNER"
+check '.items[0].actor_role' user
+check '.items[0].before.price' 2000
+check '.items[0].after.price' 2500
+check '.items[0].ip != null' true
+request "$TOKEN" GET "/audit-log?entity_type=user&per_page=1&page=2"
+expect 200
+check .total 2
+check '.items | length' 1
+check .page 2
+request "$TOKEN" GET "/audit-log?action=user.create&actor_id=$ADMIN"
+check .total 2
+check '.items[0].after.email' "$CASHIER_EMAIL"
+check '.items[0].after | has("password_hash")' false
+request "$TOKEN" GET "/audit-log?actor_id=nope"
+expect 400 "Invalid actor_id"
+
+echo "4. Other stores and staff without audit.view see none of it..."
+request "$OTHER_TOKEN" GET "/audit-log?action=product.price_change"
+expect 200
+check .total 0
+request "$OTHER_TOKEN" GET "/audit-log?tenant_id=$TENANT"
+expect 200
+check "[.items[] | select(.tenant_id != \"$OTHER_TENANT\")] | length" 0
+request "$CASHIER_TOKEN" GET /audit-log
+expect 403
+
+echo "5. The platform admin reads every store, or one..."
+request "$ADMIN_TOKEN" GET "/audit-log?action=tenant.create&actor_id=$ADMIN"
+expect 200
+check .total 2
+request "$ADMIN_TOKEN" GET "/audit-log?tenant_id=$OTHER_TENANT&action=tenant.create"
+check .total 1
+check '.items[0].entity_id' "$OTHER_TENANT"
+
+echo "6. Entries cannot be edited or removed, even straight in the database..."
+COUNT=$(sql "SELECT COUNT(*) FROM audit_log WHERE tenant_id = '$TENANT'")
+for statement in \
+  "UPDATE audit_log SET action = 'product.create' WHERE tenant_id = '$TENANT'" \
+  "DELETE FROM audit_log WHERE tenant_id = '$TENANT'" \
+  "TRUNCATE audit_log"; do
+  if psql "$DATABASE_URL" -qtA -c "$statement" 2>&1 | grep -q "audit_log is append-only"; then
+    echo "  ok (refused: ${statement%% *})"
+  else
+    echo "  FAILED: expected ${statement%% *} to be refused"
+    FAILED=1
+  fi
+done
+check_sql "SELECT COUNT(*) FROM audit_log WHERE tenant_id = '$TENANT' AND action = 'product.price_change'" 1
+check_sql "SELECT COUNT(*) FROM audit_log WHERE tenant_id = '$TENANT'" "$COUNT"
+
+if [ "$FAILED" = 0 ]; then
+  echo "All audit log checks passed."
+else
+  echo "Some audit log checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,191 @@
+#!/bin/bash
+# Subscription billing against the fake payment gateway: trial, renewal,
+# prorated plan changes, failed charges, suspension and manual payment.
+# Periods are moved back with SQL instead of waiting for them to end. Needs
+# the backend running (PAYMENT_GATEWAY unset or `fake`) and DATABASE_URL
+# pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_billing.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($VALUE)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# end_period: the store's current billing period ended yesterday
+end_period() {
+  sql "UPDATE subscriptions SET current_period_start = CURRENT_DATE - 30, current_period_end = CURRENT_DATE - 1 WHERE tenant_id = '$TENANT'"
+}
+
+echo "1. A store is created on the Basic plan..."
+ADMIN_EMAIL="billing-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+request "$ADMIN_TOKEN" POST /admin/plans "{\"name\": \"Basic $RUN\", \"price\": 3000, \"max_users\": 5}"
+BASIC=$(echo "$BODY" | jq -r .)
+request "$ADMIN_TOKEN" POST /admin/plans "{\"name\": \"Pro $RUN\", \"price\": 9000, \"max_users\": 5}"
+PRO=$(echo "$BODY" | jq -r .)
+OWNER="billing-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Billing $RUN\", \"plan_id\": \"$BASIC\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(echo "$BODY" | jq -r .)
+TOKEN=$(login "$OWNER")
+
+echo "2. It starts on a trial, paid up to its end..."
+request "$TOKEN" GET /billing
+expect 200
+check .subscription.status trialing
+check .subscription.trial_ends_at "$(date -d '+13 days' +%F)"
+check '.invoices | length' 0
+check_sql "SELECT paid_until FROM tenants WHERE id = '$TENANT'" "$(date -d '+13 days' +%F)"
+
+echo "3. The admin adds a card; the trial ends and the first month is charged..."
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT/subscription" '{"payment_method_token": "tok_visa"}'
+expect 200
+check .subscription.has_payment_method true
+end_period
+request "$ADMIN_TOKEN" POST /admin/billing/run
+expect 200
+request "$TOKEN" GET /billing
+check .subscription.status active
+check .subscription.current_period_start "$(date +%F)"
+check '.invoices[0].status' paid
+check '.invoices[0].amount' 3000
+
+echo "4. An upgrade to Pro invoices the rest of the month at the difference..."
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT" "{\"plan_id\": \"$PRO\"}"
+expect 200
+request "$TOKEN" GET /billing
+check .subscription.plan_id "$PRO"
+check '.invoices[0].status' open
+check '.invoices[0].amount' 6000
+UPGRADE=$(echo "$BODY" | jq -r '.invoices[0].id')
+request "$TOKEN" GET "/billing/invoices/$UPGRADE"
+expect 200 "Unused time on Basic $RUN"
+check '.lines | map(.amount) | add' 6000
+request "$ADMIN_TOKEN" POST "/admin/invoices/$UPGRADE/charge"
+expect 200
+check .result paid
+
+echo "5. A downgrade back to Basic leaves the difference as credit..."
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT" "{\"plan_id\": \"$BASIC\"}"
+expect 200
+request "$TOKEN" GET /billing
+check .subscription.credit_balance 6000
+
+echo "6. The credit comes off the next invoice; the card is declined..."
+sql "UPDATE subscriptions SET credit_balance = 1000 WHERE tenant_id = '$TENANT'"
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT/subscription" '{"payment_method_token": "tok_decline"}'
+end_period
+request "$ADMIN_TOKEN" POST /admin/billing/run
+expect 200
+check .failed 1
+request "$TOKEN" GET /billing
+check .subscription.status past_due
+check .subscription.credit_balance 0
+check '.invoices[0].amount' 2000
+check '.invoices[0].attempt_count' 1
+check '.invoices[0].next_attempt_at != null' true
+DECLINED=$(echo "$BODY" | jq -r '.invoices[0].id')
+request "$TOKEN" GET "/billing/invoices/$DECLINED"
+expect 200 "Card declined"
+check '.lines | map(.description) | index("Account credit") != null' true
+
+echo "7. Past the grace period the store and its subscription are suspended..."
+sql "UPDATE tenants SET paid_until = CURRENT_DATE - 60 WHERE id = '$TENANT'"
+request "$ADMIN_TOKEN" POST /admin/billing/run
+expect 200 "$TENANT"
+request "$TOKEN" GET /billing
+expect 200
+check .subscription.status suspended
+request "$TOKEN" POST /customers "{\"name\": \"Blocked $RUN\"}"
+expect 403
+
+echo "8. A bank transfer pays the invoice and reactivates the store..."
+request "$ADMIN_TOKEN" POST "/admin/invoices/$DECLINED/payments" '{"amount": 2000, "method": "bank_transfer", "reference": "TED-1"}'
+expect 201
+check .status paid
+request "$TOKEN" GET /billing
+check .subscription.status active
+check_sql "SELECT status FROM tenants WHERE id = '$TENANT'" active
+request "$TOKEN" POST /customers "{\"name\": \"Back $RUN\"}"
+expect 201
+request "$ADMIN_TOKEN" POST "/admin/invoices/$DECLINED/payments" '{"amount": 2000, "method": "bank_transfer"}'
+expect 409
+
+echo "9. Another store sees none of it..."
+OTHER="billing-other-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Authorization: Bearer $ADMIN_TOKEN" -H "Content-Type: application/json" \
+  -d "{\"name\": \"Other $RUN\", \"owner_email\": \"$OTHER\", \"owner_password\": \"password123\"}" $API/admin/tenants
+OTHER_TOKEN=$(login "$OTHER")
+request "$OTHER_TOKEN" GET /billing
+expect 200
+check .subscription null
+check '.invoices | length' 0
+request "$OTHER_TOKEN" GET "/billing/invoices/$DECLINED"
+expect 404
+request "$OTHER_TOKEN" GET "/admin/tenants/$TENANT/billing"
+expect 403
+
+echo "10. A cancellation takes effect when the period ends..."
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT/subscription" '{"cancel_at_period_end": true}'
+expect 200
+end_period
+request "$ADMIN_TOKEN" POST /admin/billing/run
+expect 200
+request "$ADMIN_TOKEN" GET "/admin/tenants/$TENANT/billing"
+check .subscription.status cancelled
+check_sql "SELECT status FROM tenants WHERE id = '$TENANT'" inactive
+
+exit $FAILED
@@ -0,0 +1,189 @@
+#!/bin/bash
+# Cancelling a sale undoes what the sale did: the coupon use is given
+# back, gift cards it sold are voided and the loyalty points it earned no
+# longer count towards the customer's tier, nor does the sale count as
+# revenue. Needs the backend running and DATABASE_URL pointing at its
+# database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_cancel_sale.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# sell <json fields>: rings up one unit of the product, the sale id in $SALE
+sell() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]${1:+, $1}}"
+  SALE=$(echo "$BODY" | jq -r . 2>/dev/null)
+}
+
+echo "1. A store with a product..."
+ADMIN_EMAIL="cancel-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="cancel-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Cancel $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100}'
+expect 201
+PRODUCT=$(echo "$BODY" | jq -r .)
+
+echo "2. Cancelling gives a single-use coupon back..."
+request "$TOKEN" POST /coupons '{"code": "once", "discount_type": "fixed", "discount_value": 500, "max_uses": 1}'
+expect 201
+COUPON=$(echo "$BODY" | jq -r .)
+sell '"coupon_code": "ONCE"'
+expect 201
+sell '"coupon_code": "ONCE"'
+expect 400 "exhausted"
+request "$TOKEN" POST "/sales/$(sql "SELECT sale_id FROM coupon_redemptions WHERE coupon_id = '$COUPON'")/cancel"
+expect 200
+check_sql "SELECT times_used FROM coupons WHERE id = '$COUPON'" 0
+check_sql "SELECT COUNT(*) FROM coupon_redemptions WHERE coupon_id = '$COUPON'" 0
+sell '"coupon_code": "ONCE"'
+expect 201
+
+echo "3. Cancelling voids the gift cards the sale activated..."
+request "$TOKEN" POST /products '{"name": "Vale-presente", "price": 5000, "stock_quantity": 100, "is_gift_card": true}'
+expect 201
+CARD_PRODUCT=$(echo "$BODY" | jq -r .)
+# sell_card <code>: sells a gift card, the sale id in $SALE
+sell_card() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$CARD_PRODUCT\", \"quantity\": 1, \"gift_card_code\": \"$1\"}]}"
+  SALE=$(echo "$BODY" | jq -r . 2>/dev/null)
+}
+sell_card "VOID$RUN"
+expect 201
+request "$TOKEN" POST "/sales/$SALE/cancel"
+expect 200
+request "$TOKEN" GET "/gift-cards/VOID$RUN/balance"
+expect 200 '"status":"cancelled"'
+check_sql "SELECT balance FROM gift_cards WHERE code = 'VOID$RUN'" 0
+check_sql "SELECT string_agg(t.kind || ':' || t.amount, ',' ORDER BY t.created_at) FROM gift_card_transactions t JOIN gift_cards c ON c.id = t.gift_card_id WHERE c.code = 'VOID$RUN'" "activation:5000,reversal:-5000"
+sell "\"gift_card_payments\": [{\"code\": \"VOID$RUN\", \"amount\": 1000}]"
+expect 400 "not valid"
+
+echo "4. A gift card already spent cannot be cancelled..."
+sell_card "SPENT$RUN"
+expect 201
+CARD_SALE=$SALE
+sell "\"gift_card_payments\": [{\"code\": \"SPENT$RUN\", \"amount\": 1000}]"
+expect 201
+SPENDING_SALE=$SALE
+request "$TOKEN" POST "/sales/$CARD_SALE/cancel"
+expect 400 "already been used"
+check_sql "SELECT status || ':' || balance FROM gift_cards WHERE code = 'SPENT$RUN'" "active:4000"
+request "$TOKEN" POST "/sales/$SPENDING_SALE/cancel"
+expect 200
+request "$TOKEN" POST "/sales/$CARD_SALE/cancel"
+expect 200
+check_sql "SELECT status || ':' || balance FROM gift_cards WHERE code = 'SPENT$RUN'" "cancelled:0"
+
+echo "5. A cancelled sale no longer counts towards the loyalty tier..."
+request "$TOKEN" PUT /loyalty/program \
+  '{"points_per_real": 1, "point_value": 1, "active": true, "tiers": [{"name": "Ouro", "min_points": 300, "multiplier": 100}]}'
+expect 200
+request "$TOKEN" POST /customers '{"name": "Ana Souza"}'
+expect 201
+CUSTOMER=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /sales \
+  "{\"payment_method\": \"cash\", \"customer_id\": \"$CUSTOMER\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 20}]}"
+expect 201
+BIG_SALE=$(echo "$BODY" | jq -r .)
+# Some of its points are spent before the sale is cancelled
+sell "\"customer_id\": \"$CUSTOMER\", \"loyalty_points\": 100"
+expect 201
+request "$TOKEN" GET "/loyalty/customers/$CUSTOMER"
+check .tier.name Ouro
+request "$TOKEN" POST "/sales/$BIG_SALE/cancel"
+expect 200
+request "$TOKEN" GET "/loyalty/customers/$CUSTOMER"
+check .tier null
+check .lifetime_points "$(sql "SELECT SUM(points) FROM loyalty_transactions WHERE customer_id = '$CUSTOMER' AND kind = 'earn' AND sale_id <> '$BIG_SALE'")"
+
+echo "6. A cancelled sale drops out of the dashboard and the reports..."
+sell
+expect 201
+request "$TOKEN" GET /sales/stats
+REVENUE=$(echo "$BODY" | jq -r .total_revenue)
+COUNT=$(echo "$BODY" | jq -r .sales_count)
+request "$TOKEN" GET /metrics/top-products
+SOLD=$(echo "$BODY" | jq -r ".[] | select(.product_id == \"$PRODUCT\") | .quantity_sold")
+request "$TOKEN" POST "/sales/$SALE/cancel"
+expect 200
+request "$TOKEN" GET /sales/stats
+expect 200
+check .total_revenue $((REVENUE - 2000))
+check .sales_count $((COUNT - 1))
+request "$TOKEN" GET /metrics/overview
+expect 200
+check .total_revenue $((REVENUE - 2000))
+check .sales_count $((COUNT - 1))
+request "$TOKEN" GET /metrics/sales-trend
+expect 200
+check '.[-1].revenue' $((REVENUE - 2000))
+request "$TOKEN" GET /metrics/top-products
+expect 200
+check ".[] | select(.product_id == \"$PRODUCT\") | .quantity_sold" $((SOLD - 1))
+
+if [ "$FAILED" = 0 ]; then
+  echo "All cancel sale checks passed."
+else
+  echo "Some cancel sale checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,159 @@
+#!/bin/bash
+# Reseller commissions: a rule earns a share of every paid invoice of the
+# reseller's stores plus an activation bonus, ended months close into
+# payout statements and the platform pays them out. Periods and months are
+# moved back with SQL instead of waiting. Needs the backend running
+# (PAYMENT_GATEWAY unset or `fake`) and DATABASE_URL pointing at its
+# database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_commissions.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# reseller <name>: creates a reseller and prints their id
+reseller() {
+  curl -s -o /dev/null -X POST -H "Authorization: Bearer $ADMIN_TOKEN" -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1-$RUN@example.com\", \"password\": \"password123\"}" $API/admin/resellers
+  sql "SELECT id FROM users WHERE email = '$1-$RUN@example.com'"
+}
+
+# end_period: the store's current billing period ended yesterday
+end_period() {
+  sql "UPDATE subscriptions SET current_period_start = CURRENT_DATE - 30, current_period_end = CURRENT_DATE - 1 WHERE tenant_id = '$TENANT'"
+}
+
+echo "1. The admin sets up a plan and a reseller's commission..."
+ADMIN_EMAIL="commissions-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+request "$ADMIN_TOKEN" POST /admin/plans "{\"name\": \"Reseller plan $RUN\", \"price\": 10000, \"max_users\": 5}"
+PLAN=$(echo "$BODY" | jq -r .)
+RESELLER=$(reseller partner)
+RESELLER_TOKEN=$(login "partner-$RUN@example.com")
+request "$ADMIN_TOKEN" PUT "/admin/resellers/$RESELLER/commission" '{"rate_bps": 20000, "activation_bonus": 0}'
+expect 400
+request "$ADMIN_TOKEN" PUT "/admin/resellers/$(sql "SELECT gen_random_uuid()")/commission" '{"rate_bps": 1500, "activation_bonus": 5000}'
+expect 404
+request "$ADMIN_TOKEN" PUT "/admin/resellers/$RESELLER/commission" '{"rate_bps": 1500, "activation_bonus": 5000}'
+expect 200
+
+echo "2. The reseller signs up two stores; one pays its first month..."
+request "$RESELLER_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Partner store $RUN\", \"plan_id\": \"$PLAN\", \"owner_email\": \"partner-store-$RUN@example.com\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(echo "$BODY" | jq -r .)
+request "$RESELLER_TOKEN" POST /admin/tenants "{\"name\": \"Partner trial $RUN\", \"plan_id\": \"$PLAN\"}"
+expect 201
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT/subscription" '{"payment_method_token": "tok_visa"}'
+expect 200
+end_period
+request "$ADMIN_TOKEN" POST /admin/billing/run
+check .paid 1
+
+echo "3. The reseller earns 15% of it plus the activation bonus..."
+request "$RESELLER_TOKEN" GET /reseller/earnings
+expect 200
+check .rule.rate_bps 1500
+check .totals.earned 6500
+check .totals.unsettled 6500
+check .totals.stores 2
+check .totals.paying_stores 1
+check '.recent | map(.kind) | sort | join(",")' activation,subscription
+check '.months[0].total' 6500
+
+echo "4. The next month earns the share again, but no second bonus..."
+end_period
+request "$ADMIN_TOKEN" POST /admin/billing/run
+check .paid 1
+request "$RESELLER_TOKEN" GET /reseller/earnings
+check .totals.earned 8000
+check '.recent | length' 3
+
+echo "5. Once the month is over it closes into a payout statement..."
+sql "UPDATE commissions SET earned_on = date_trunc('month', CURRENT_DATE)::date - 1 WHERE reseller_id = '$RESELLER'"
+request "$ADMIN_TOKEN" POST /admin/billing/run
+expect 200
+request "$RESELLER_TOKEN" GET /reseller/earnings
+check .totals.unsettled 0
+check .totals.pending 8000
+check '.payouts | length' 1
+check '.payouts[0].amount' 8000
+check '.payouts[0].period_start' "$(date -d "$(date +%Y-%m-01) -1 month" +%F)"
+PAYOUT=$(echo "$BODY" | jq -r '.payouts[0].id')
+request "$RESELLER_TOKEN" GET "/reseller/payouts/$PAYOUT"
+expect 200
+check '.entries | length' 3
+request "$ADMIN_TOKEN" GET "/admin/payouts?reseller_id=$RESELLER&status=pending"
+expect 200
+check 'length' 1
+
+echo "6. Other resellers, store owners and admins cannot see it..."
+reseller other-partner > /dev/null
+OTHER_TOKEN=$(login "other-partner-$RUN@example.com")
+request "$OTHER_TOKEN" GET "/reseller/payouts/$PAYOUT"
+expect 404
+request "$OTHER_TOKEN" GET /reseller/earnings
+check .totals.earned 0
+check .rule null
+OWNER_TOKEN=$(login "partner-store-$RUN@example.com")
+request "$OWNER_TOKEN" GET /reseller/earnings
+expect 403
+request "$ADMIN_TOKEN" GET /reseller/earnings
+expect 403 "earnings.view"
+request "$RESELLER_TOKEN" POST "/admin/payouts/$PAYOUT/pay" '{}'
+expect 403
+
+echo "7. The admin pays the statement out..."
+request "$ADMIN_TOKEN" POST "/admin/payouts/$PAYOUT/pay" '{"reference": "PIX-1"}'
+expect 200
+check .status paid
+request "$ADMIN_TOKEN" POST "/admin/payouts/$PAYOUT/pay" '{"reference": "PIX-2"}'
+expect 409
+request "$ADMIN_TOKEN" GET "/admin/resellers/$RESELLER/earnings"
+expect 200
+check .totals.paid 8000
+check .totals.pending 0
+
+exit $FAILED
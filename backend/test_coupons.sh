@@ -0,0 +1,133 @@
+#!/bin/bash
+# Coupon rules: invalid codes, windows and limits are rejected, a
+# deactivated coupon no longer applies and limits can be removed. Needs the backend running and
+# DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_coupons.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# sell <json fields>: rings up one unit of the product, the sale id in $SALE
+sell() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]${1:+, $1}}"
+  SALE=$(echo "$BODY" | jq -r . 2>/dev/null)
+}
+
+echo "1. A store with a product..."
+ADMIN_EMAIL="coupons-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="coupons-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Coupons $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caneta", "price": 1000, "stock_quantity": 100}'
+expect 201
+PRODUCT=$(echo "$BODY" | jq -r .)
+
+echo "2. Invalid coupons are rejected..."
+request "$TOKEN" POST /coupons '{"code": "   ", "discount_type": "fixed", "discount_value": 100}'
+expect 400 "Code"
+request "$TOKEN" POST /coupons \
+  '{"code": "late", "discount_type": "fixed", "discount_value": 100, "valid_from": "2030-02-01T00:00:00", "valid_until": "2030-01-01T00:00:00"}'
+expect 400 "valid_until"
+request "$TOKEN" POST /coupons '{"code": "never", "discount_type": "fixed", "discount_value": 100, "max_uses": 0}'
+expect 400 "max_uses"
+request "$TOKEN" POST /coupons '{"code": "never", "discount_type": "fixed", "discount_value": 100, "max_uses_per_customer": -1}'
+expect 400 "max_uses_per_customer"
+
+echo "3. A deactivated coupon no longer applies..."
+request "$TOKEN" POST /coupons '{"code": "promo", "discount_type": "percentage", "discount_value": 10}'
+expect 201
+COUPON=$(echo "$BODY" | jq -r .)
+request "$TOKEN" PUT "/coupons/$COUPON" '{"valid_from": "2030-02-01T00:00:00", "valid_until": "2030-01-01T00:00:00"}'
+expect 400 "valid_until"
+request "$TOKEN" PUT "/coupons/$COUPON" '{"max_uses": 0}'
+expect 400 "max_uses"
+request "$TOKEN" PUT "/coupons/$(sql "SELECT gen_random_uuid()")" '{"active": false}'
+expect 404
+sell '"coupon_code": "PROMO"'
+expect 201
+request "$TOKEN" PUT "/coupons/$COUPON" '{"active": false}'
+expect 200 '"active":false'
+sell '"coupon_code": "PROMO"'
+expect 400 "not valid"
+request "$TOKEN" PUT "/coupons/$COUPON" '{"active": true, "max_uses": 5}'
+expect 200 '"max_uses":5'
+sell '"coupon_code": "PROMO"'
+expect 201
+
+echo "4. Limits can be lifted again by sending null..."
+request "$TOKEN" PUT "/coupons/$COUPON" '{"max_uses": 2, "max_uses_per_customer": 3}'
+expect 200
+sell '"coupon_code": "PROMO"'
+expect 400 "exhausted"
+request "$TOKEN" PUT "/coupons/$COUPON" '{"active": true}'
+check .max_uses 2
+check .max_uses_per_customer 3
+request "$TOKEN" PUT "/coupons/$COUPON" '{"max_uses": null, "max_uses_per_customer": null}'
+expect 200
+check .max_uses null
+check .max_uses_per_customer null
+sell '"coupon_code": "PROMO"'
+expect 201
+request "$TOKEN" PUT "/coupons/$COUPON" '{"valid_until": "2020-01-01T00:00:00"}'
+expect 200
+sell '"coupon_code": "PROMO"'
+expect 400 "not valid"
+request "$TOKEN" PUT "/coupons/$COUPON" '{"valid_until": null}'
+expect 200
+check .valid_until null
+sell '"coupon_code": "PROMO"'
+expect 201
+
+if [ "$FAILED" = 0 ]; then
+  echo "All coupon checks passed."
+else
+  echo "Some coupon checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,108 @@
+#!/bin/bash
+# Customer lookups at the POS: the list, search by name or phone (LIKE
+# wildcards in the term match themselves) and the customer profile with
+# its sales. Needs the backend running and DATABASE_URL pointing at its
+# database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_customers.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+echo "1. A store with customers..."
+ADMIN_EMAIL="customers-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="customers-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Customers $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /customers '{"name": "Ana Souza", "phone": "(11) 98765-4321"}'
+expect 201
+ANA=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers '{"name": "Bruno Lima", "email": "bruno@example.com"}'
+expect 201
+
+echo "2. Listing and fetching customers..."
+request "$TOKEN" GET /customers
+expect 200
+check length 2
+request "$TOKEN" GET "/customers/$ANA"
+expect 200
+check .name "Ana Souza"
+
+echo "3. Searching by name and phone..."
+request "$TOKEN" GET "/customers/search?q=souza"
+expect 200
+check .total 1
+check '.items[0].id' "$ANA"
+request "$TOKEN" GET "/customers/search?q=98765-4321"
+expect 200
+check .total 1
+request "$TOKEN" GET "/customers/search?q=%25"
+expect 200
+check .total 0
+request "$TOKEN" GET "/customers/search?q=_"
+expect 200
+check .total 0
+
+echo "4. The profile lists the customer's sales..."
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100}'
+expect 201
+PRODUCT=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /sales \
+  "{\"payment_method\": \"cash\", \"customer_id\": \"$ANA\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 2}]}"
+expect 201
+request "$TOKEN" GET "/customers/$ANA/profile"
+expect 200
+check .lifetime_value 4000
+check '.sales | length' 1
+
+if [ "$FAILED" = 0 ]; then
+  echo "All customer checks passed."
+else
+  echo "Some customer checks FAILED."
+  exit 1
+fi
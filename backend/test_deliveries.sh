@@ -0,0 +1,117 @@
+#!/bin/bash
+# Deliveries: customer addresses, delivery zones, a sale delivered to an
+# address and the dispatch board. Needs the backend running and
+# DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_deliveries.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+echo "1. A store with a customer and a delivery zone..."
+ADMIN_EMAIL="deliveries-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="deliveries-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Deliveries $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100}'
+expect 201
+PRODUCT=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers '{"name": "Ana Souza", "phone": "(11) 98765-4321"}'
+expect 201
+CUSTOMER=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /deliveries/zones \
+  '{"name": "Centro", "zip_code_start": "01000000", "zip_code_end": "01999999", "fee": 800}'
+expect 201
+request "$TOKEN" GET /deliveries/zones
+expect 200
+check '.[0].name' Centro
+
+echo "2. Addresses are listed with the default first..."
+request "$TOKEN" POST "/customers/$CUSTOMER/addresses" \
+  '{"street": "Rua Augusta", "number": "100", "city": "São Paulo", "state": "SP", "zip_code": "01305-000"}'
+expect 201
+ADDRESS=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST "/customers/$CUSTOMER/addresses" \
+  '{"street": "Rua Longe", "number": "1", "city": "Campinas", "state": "SP", "zip_code": "13010-000"}'
+expect 201
+FAR_ADDRESS=$(echo "$BODY" | jq -r .)
+request "$TOKEN" GET "/customers/$CUSTOMER/addresses"
+expect 200
+check '.[0].id' "$ADDRESS"
+check '.[0].zip_code' 01305000
+
+echo "3. A delivery sale charges the zone fee..."
+# deliver <address id>: sells one unit for delivery to the address
+deliver() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"customer_id\": \"$CUSTOMER\", \"delivery\": {\"address_id\": \"$1\"}, \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]}"
+}
+deliver "$FAR_ADDRESS"
+expect 400 "outside the delivery zones"
+deliver "$ADDRESS"
+expect 201
+DELIVERY=$(sql "SELECT id FROM deliveries WHERE customer_id = '$CUSTOMER'")
+request "$TOKEN" GET /deliveries/board
+expect 200
+check '.received[0].address_text | startswith("Rua Augusta, 100")' true
+check '.received[0].fee' 800
+
+echo "4. Deliveries move through the pipeline..."
+request "$TOKEN" PUT "/deliveries/$DELIVERY/status" '{"status": "preparing"}'
+expect 200
+request "$TOKEN" PUT "/deliveries/$DELIVERY/status" '{"status": "received"}'
+expect 400
+request "$TOKEN" GET /deliveries/board
+check '.preparing | length' 1
+
+if [ "$FAILED" = 0 ]; then
+  echo "All delivery checks passed."
+else
+  echo "Some delivery checks FAILED."
+  exit 1
+fi
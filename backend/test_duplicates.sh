@@ -0,0 +1,156 @@
+#!/bin/bash
+# Customer deduplication: candidates found by phone, email, document or
+# the same name (ignoring case, accents and punctuation), and merging
+# duplicates into one record. Needs the backend running and DATABASE_URL
+# pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_duplicates.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# sell <json fields>: rings up one unit of the product, the sale id in $SALE
+sell() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]${1:+, $1}}"
+  SALE=$(echo "$BODY" | jq -r . 2>/dev/null)
+}
+
+# customer <json>: registers a customer, its id in $CUSTOMER
+customer() {
+  request "$TOKEN" POST /customers "$1"
+  expect 201
+  CUSTOMER=$(echo "$BODY" | jq -r .)
+}
+
+echo "1. A store with customers registered twice..."
+ADMIN_EMAIL="duplicates-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="duplicates-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Duplicates $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100}'
+expect 201
+PRODUCT=$(echo "$BODY" | jq -r .)
+customer '{"name": "Ana Souza", "phone": "(11) 98765-4321", "document": "529.982.247-25"}'
+ANA=$CUSTOMER
+customer '{"name": "Ana Souza", "phone": "+55 11 98765-4321", "email": "ana@example.com"}'
+ANA_AGAIN=$CUSTOMER
+customer '{"name": "Bruno Lima", "email": "Bruno@Example.com"}'
+BRUNO=$CUSTOMER
+customer '{"name": "B. Lima", "email": "bruno@example.com "}'
+BRUNO_AGAIN=$CUSTOMER
+customer '{"name": "Carla Dias"}'
+CARLA=$CUSTOMER
+customer '{"name": "CARLA  DIAS."}'
+CARLA_AGAIN=$CUSTOMER
+
+echo "2. Duplicates are paired with why they matched..."
+request "$TOKEN" GET /customers/duplicates
+expect 200
+check .total 3
+# pair <id> <id>: the reasons the two customers were paired for
+pair() {
+  check "[.items[] | select([.customers[].id] | sort == ([\"$1\", \"$2\"] | sort)) | .reasons | sort | join(\",\")] | first" "$3"
+}
+pair "$ANA" "$ANA_AGAIN" "name,phone"
+pair "$BRUNO" "$BRUNO_AGAIN" email
+pair "$CARLA" "$CARLA_AGAIN" name
+check '.items[0].customers[0].id' "$ANA"
+# Single reasons come by name similarity, Carla's identical names first
+request "$TOKEN" GET "/customers/duplicates?page=3&per_page=1"
+expect 200
+check '.items | length' 1
+check '.items[0].customers[0].id' "$BRUNO"
+
+echo "3. Merging moves the duplicate's history and addresses to the survivor..."
+request "$TOKEN" POST /deliveries/zones \
+  '{"name": "Centro", "zip_code_start": "01000000", "zip_code_end": "01999999", "fee": 800}'
+expect 201
+request "$TOKEN" POST "/customers/$ANA/addresses" \
+  '{"street": "Rua Augusta", "number": "100", "city": "São Paulo", "state": "SP", "zip_code": "01305-000"}'
+expect 201
+ADDRESS=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST "/customers/$ANA_AGAIN/addresses" \
+  '{"street": "Rua Frei Caneca", "number": "5", "city": "São Paulo", "state": "SP", "zip_code": "01307-000"}'
+expect 201
+OTHER_ADDRESS=$(echo "$BODY" | jq -r .)
+sell "\"customer_id\": \"$ANA_AGAIN\", \"delivery\": {\"address_id\": \"$OTHER_ADDRESS\"}"
+expect 201
+request "$TOKEN" POST "/customers/$ANA/merge" "{\"duplicate_ids\": [\"$ANA_AGAIN\"]}"
+expect 200
+check_sql "SELECT COUNT(*) FROM sales WHERE customer_id = '$ANA'" 1
+check_sql "SELECT COUNT(*) FROM deliveries WHERE customer_id = '$ANA'" 1
+request "$TOKEN" GET "/customers/$ANA/addresses"
+check '[.[] | select(.is_default) | .id] | join(",")' "$ADDRESS"
+check length 2
+check_sql "SELECT COUNT(*) FROM customers WHERE id = '$ANA_AGAIN'" 0
+request "$TOKEN" GET "/customers/$ANA"
+check .email ana@example.com
+check .document 52998224725
+request "$TOKEN" POST "/customers/$ANA/merge" "{\"duplicate_ids\": [\"$ANA\"]}"
+expect 400
+request "$TOKEN" GET /customers/duplicates
+check .total 2
+
+if [ "$FAILED" = 0 ]; then
+  echo "All duplicate checks passed."
+else
+  echo "Some duplicate checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,150 @@
+#!/bin/bash
+# Plan entitlements at checkout: a store moved to a plan without coupons,
+# loyalty, gift cards or deliveries can no longer use them in a sale.
+# Needs the backend running and DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_entitlements.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# sell <json fields>: rings up one unit of the product for the customer
+sell() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"customer_id\": \"$CUSTOMER\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]${1:+, $1}}"
+}
+
+echo "1. A store on a plan with every feature sets them up..."
+ADMIN_EMAIL="entitlements-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+request "$ADMIN_TOKEN" POST /admin/plans "{\"name\": \"Full $RUN\", \"price\": 9000, \"max_users\": 5}"
+FULL=$(echo "$BODY" | jq -r .)
+request "$ADMIN_TOKEN" POST /admin/plans "{\"name\": \"Basic $RUN\", \"price\": 3000, \"max_users\": 5, \"features\": []}"
+BASIC=$(echo "$BODY" | jq -r .)
+OWNER="entitlements-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Entitlements $RUN\", \"plan_id\": \"$FULL\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(echo "$BODY" | jq -r .)
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100}'
+PRODUCT=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /products '{"name": "Vale-presente", "price": 5000, "stock_quantity": 100, "is_gift_card": true}'
+CARD_PRODUCT=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers '{"name": "Ana Souza"}'
+CUSTOMER=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /coupons '{"code": "promo", "discount_type": "fixed", "discount_value": 500}'
+expect 201
+request "$TOKEN" PUT /loyalty/program '{"points_per_real": 1, "point_value": 1, "active": true, "tiers": []}'
+expect 200
+request "$TOKEN" POST /deliveries/zones \
+  '{"name": "Centro", "zip_code_start": "01000000", "zip_code_end": "01999999", "fee": 800}'
+expect 201
+request "$TOKEN" POST "/customers/$CUSTOMER/addresses" \
+  '{"street": "Rua Augusta", "number": "100", "city": "São Paulo", "state": "SP", "zip_code": "01305-000"}'
+ADDRESS=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /sales \
+  "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$CARD_PRODUCT\", \"quantity\": 1, \"gift_card_code\": \"CARD$RUN\"}]}"
+expect 201
+sell '"coupon_code": "PROMO"'
+expect 201
+check_sql "SELECT SUM(points) FROM loyalty_transactions WHERE customer_id = '$CUSTOMER'" 15
+
+echo "2. Moved to a plan without them, the store can still sell..."
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT" "{\"plan_id\": \"$BASIC\"}"
+expect 200
+sell
+expect 201
+
+echo "3. ...but each paid feature is refused at checkout, with the upgrade..."
+sell '"coupon_code": "PROMO"'
+expect 402 "feature_not_in_plan"
+check .feature coupons
+check '.upgrade_plans | map(.name) | index("Full '"$RUN"'") != null' true
+sell '"loyalty_points": 10'
+expect 402
+check .feature loyalty
+sell "\"gift_card_payments\": [{\"code\": \"CARD$RUN\", \"amount\": 1000}]"
+expect 402
+check .feature gift_cards
+request "$TOKEN" POST /sales \
+  "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$CARD_PRODUCT\", \"quantity\": 1, \"gift_card_code\": \"MORE$RUN\"}]}"
+expect 402
+check .feature gift_cards
+sell "\"delivery\": {\"address_id\": \"$ADDRESS\"}"
+expect 402
+check .feature deliveries
+
+echo "4. Refused sales leave nothing behind and no points are earned..."
+check_sql "SELECT COUNT(*) FROM sales WHERE tenant_id = '$TENANT'" 3
+check_sql "SELECT times_used FROM coupons WHERE tenant_id = '$TENANT'" 1
+check_sql "SELECT balance FROM gift_cards WHERE code = 'CARD$RUN'" 5000
+check_sql "SELECT COUNT(*) FROM gift_cards WHERE code = 'MORE$RUN'" 0
+check_sql "SELECT COUNT(*) FROM deliveries WHERE tenant_id = '$TENANT'" 0
+check_sql "SELECT stock_quantity FROM products WHERE id = '$PRODUCT'" 98
+check_sql "SELECT SUM(points) FROM loyalty_transactions WHERE customer_id = '$CUSTOMER'" 15
+
+echo "5. Back on the full plan, the features work again..."
+request "$ADMIN_TOKEN" PUT "/admin/tenants/$TENANT" "{\"plan_id\": \"$FULL\"}"
+sell '"coupon_code": "PROMO", "loyalty_points": 10'
+expect 201
+
+if [ "$FAILED" = 0 ]; then
+  echo "All entitlement checks passed."
+else
+  echo "Some entitlement checks FAILED."
+  exit 1
+fi
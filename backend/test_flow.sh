@@ -1,4 +1,6 @@
 #!/bin/bash
+# Run the server with REQUIRE_EMAIL_VERIFICATION=false, or confirm the
+# registration link first (MAILER=log prints it)
 # 1. Register (ignore error if exists)
 echo "1. Registering..."
 curl -s -X POST -H "Content-Type: application/json" -d '{"email": "sales@example.com", "password": "password123"}' http://127.0.0.1:3000/auth/register
@@ -0,0 +1,180 @@
+#!/bin/bash
+# JWT signing keys from configuration: HS256, RS256 and EdDSA keys, the
+# published JWKS, and rotation with JWT_PREVIOUS_KEYS. Starts its own
+# backend for each key setup, so it needs the binary built and
+# DATABASE_URL pointing at the database:
+#
+#   cargo build --bin backend
+#   DATABASE_URL=postgres://... ./test_jwt_keys.sh
+BACKEND=${BACKEND:-./target/debug/backend}
+PORT=${PORT:-3100}
+API=http://127.0.0.1:$PORT
+RUN=$(date +%s)
+FAILED=0
+KEYS=$(mktemp -d)
+trap 'stop_backend; rm -rf "$KEYS"' EXIT
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# start_backend [VAR=value...]: runs the backend with only these JWT settings
+start_backend() {
+  env -u JWT_ALGORITHM -u JWT_KEY_ID -u JWT_SECRET -u JWT_PRIVATE_KEY \
+    -u JWT_PUBLIC_KEY -u JWT_PREVIOUS_KEYS PORT=$PORT "$@" \
+    "$BACKEND" > "$KEYS/backend.log" 2>&1 &
+  PID=$!
+  for _ in $(seq 50); do
+    curl -s -o /dev/null "$API/" && return 0
+    kill -0 $PID 2>/dev/null || return 1
+    sleep 0.2
+  done
+  return 1
+}
+
+stop_backend() {
+  [ -n "$PID" ] && kill $PID 2>/dev/null && wait $PID 2>/dev/null
+  PID=
+}
+
+login() {
+  request "" POST /auth/login "{\"email\": \"$EMAIL\", \"password\": \"password123\"}"
+  echo "$BODY" | jq -r .token
+}
+
+# header <token>: the token's decoded JOSE header
+header() {
+  local part=$(echo "$1" | cut -d. -f1 | tr '_-' '/+')
+  while [ $(( ${#part} % 4 )) != 0 ]; do part="$part="; done
+  echo "$part" | base64 -d
+}
+
+base64url() {
+  base64 -w 0 | tr '/+' '_-' | tr -d '='
+}
+
+SECRET_1="first-secret-$RUN-0123456789abcdef"
+openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out "$KEYS/rsa.pem" 2> /dev/null
+openssl pkey -in "$KEYS/rsa.pem" -pubout -out "$KEYS/rsa.pub.pem"
+openssl genpkey -algorithm ED25519 -out "$KEYS/ed.pem"
+openssl pkey -in "$KEYS/ed.pem" -pubout -out "$KEYS/ed.pub.pem"
+
+echo "1. A short HS256 secret is refused at startup..."
+if start_backend JWT_SECRET=too-short; then
+  echo "  FAILED: backend started with a short secret"
+  FAILED=1
+  stop_backend
+elif grep -q "JWT_SECRET must be at least 32 characters" "$KEYS/backend.log"; then
+  echo "  ok (refused)"
+else
+  echo "  FAILED: unexpected startup error $(tail -n 3 "$KEYS/backend.log")"
+  FAILED=1
+fi
+
+echo "2. HS256 tokens carry the key id and the secret is never published..."
+start_backend JWT_SECRET="$SECRET_1" JWT_KEY_ID=k1 || { echo "  FAILED: backend did not start"; exit 1; }
+EMAIL="jwt-keys-$RUN@example.com"
+request "" POST /auth/register "{\"email\": \"$EMAIL\", \"password\": \"password123\"}"
+sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$EMAIL'"
+HS_TOKEN=$(login)
+BODY=$(header "$HS_TOKEN")
+check .alg HS256
+check .kid k1
+request "$HS_TOKEN" GET /auth/sessions
+expect 200
+request "" GET /.well-known/jwks.json
+expect 200
+check '.keys | length' 0
+stop_backend
+
+echo "3. Rotating to RS256 keeps the old HS256 tokens valid..."
+start_backend JWT_ALGORITHM=RS256 JWT_KEY_ID=k2 JWT_PRIVATE_KEY="$KEYS/rsa.pem" \
+  JWT_PUBLIC_KEY="$KEYS/rsa.pub.pem" JWT_PREVIOUS_KEYS="k1:HS256:$SECRET_1" \
+  || { echo "  FAILED: backend did not start"; exit 1; }
+RS_TOKEN=$(login)
+BODY=$(header "$RS_TOKEN")
+check .alg RS256
+check .kid k2
+request "$RS_TOKEN" GET /auth/sessions
+expect 200
+request "$HS_TOKEN" GET /auth/sessions
+expect 200
+request "" GET /.well-known/jwks.json
+check '.keys | length' 1
+check '.keys[0].kid' k2
+check '.keys[0].kty' RSA
+check '.keys[0].use' sig
+
+echo "4. Tokens naming an unknown key or the wrong algorithm are refused..."
+PAYLOAD=$(echo "$RS_TOKEN" | cut -d. -f2)
+# The same claims signed with the retired HS256 key are still good
+KNOWN_HEADER=$(echo -n '{"typ":"JWT","alg":"HS256","kid":"k1"}' | base64url)
+SIGNATURE=$(echo -n "$KNOWN_HEADER.$PAYLOAD" | openssl dgst -sha256 -hmac "$SECRET_1" -binary | base64url)
+request "$KNOWN_HEADER.$PAYLOAD.$SIGNATURE" GET /auth/sessions
+expect 200
+UNKNOWN_HEADER=$(echo -n '{"typ":"JWT","alg":"HS256","kid":"nope"}' | base64url)
+SIGNATURE=$(echo -n "$UNKNOWN_HEADER.$PAYLOAD" | openssl dgst -sha256 -hmac "$SECRET_1" -binary | base64url)
+request "$UNKNOWN_HEADER.$PAYLOAD.$SIGNATURE" GET /auth/sessions
+expect 401
+# HS256 "signed" with the published RSA key, the classic algorithm confusion
+CONFUSED_HEADER=$(echo -n '{"typ":"JWT","alg":"HS256","kid":"k2"}' | base64url)
+SIGNATURE=$(echo -n "$CONFUSED_HEADER.$PAYLOAD" \
+  | openssl dgst -sha256 -hmac "$(cat "$KEYS/rsa.pub.pem")" -binary | base64url)
+request "$CONFUSED_HEADER.$PAYLOAD.$SIGNATURE" GET /auth/sessions
+expect 401
+stop_backend
+
+echo "5. Rotating again to EdDSA retires the HS256 key..."
+start_backend JWT_ALGORITHM=EdDSA JWT_KEY_ID=k3 JWT_PRIVATE_KEY="$KEYS/ed.pem" \
+  JWT_PUBLIC_KEY="$KEYS/ed.pub.pem" JWT_PREVIOUS_KEYS="k2:RS256:$KEYS/rsa.pub.pem" \
+  || { echo "  FAILED: backend did not start"; exit 1; }
+ED_TOKEN=$(login)
+BODY=$(header "$ED_TOKEN")
+check .alg EdDSA
+check .kid k3
+request "$ED_TOKEN" GET /auth/sessions
+expect 200
+request "$RS_TOKEN" GET /auth/sessions
+expect 200
+request "$HS_TOKEN" GET /auth/sessions
+expect 401
+request "" GET /.well-known/jwks.json
+check '.keys | map(.kid) | join(",")' k3,k2
+check '.keys[0].kty' OKP
+check '.keys[0].crv' Ed25519
+stop_backend
+
+if [ "$FAILED" = 0 ]; then
+  echo "All JWT key checks passed."
+else
+  echo "Some JWT key checks FAILED."
+  exit 1
+fi
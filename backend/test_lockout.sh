@@ -0,0 +1,224 @@
+#!/bin/bash
+# Login brute-force protection: free attempts per account and per IP, the
+# doubling backoff with 429 and Retry-After, the failed login log and the
+# admin unlock. Starts its own backend with the Postgres throttle store,
+# trusted X-Forwarded-For and no 2FA for admins, so each run uses fresh
+# addresses and never blocks 127.0.0.1 for the other scripts. Needs the
+# binary built and DATABASE_URL pointing at the database:
+#
+#   cargo build --bin backend
+#   DATABASE_URL=postgres://... ./test_lockout.sh
+BACKEND=${BACKEND:-./target/debug/backend}
+PORT=${PORT:-3101}
+API=http://127.0.0.1:$PORT
+RUN=$(date +%s)
+FAILED=0
+JWT_SECRET=${JWT_SECRET:-$(openssl rand -hex 32)}
+LOG=$(mktemp)
+trap 'stop_backend; rm -f "$LOG"' EXIT
+
+# Addresses unique to this run, since counters outlive the backend
+NET="10.$(( RUN / 256 % 256 )).$(( RUN % 256 ))"
+IP_A="$NET.1"
+IP_B="$NET.2"
+IP_C="$NET.3"
+IP_D="$NET.4"
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+# login_from <ip> <email> <password>: also keeps the Retry-After header
+login_from() {
+  RESPONSE=$(curl -s -D "$LOG.headers" -w '\n%{http_code}' -X POST \
+    -H "Content-Type: application/json" -H "X-Forwarded-For: $1" \
+    -d "{\"email\": \"$2\", \"password\": \"$3\"}" "$API/auth/login")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+  RETRY_AFTER=$(grep -i '^retry-after:' "$LOG.headers" | awk '{print $2}' | tr -d '\r')
+  rm -f "$LOG.headers"
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+start_backend() {
+  PORT=$PORT LOGIN_THROTTLE_STORE=postgres TRUST_PROXY_HEADERS=true TWO_FACTOR_REQUIRED_ROLES= \
+    JWT_SECRET=$JWT_SECRET \
+    "$BACKEND" > "$LOG" 2>&1 &
+  PID=$!
+  for _ in $(seq 50); do
+    curl -s -o /dev/null "$API/" && return 0
+    kill -0 $PID 2>/dev/null || return 1
+    sleep 0.2
+  done
+  return 1
+}
+
+stop_backend() {
+  [ -n "$PID" ] && kill $PID 2>/dev/null && wait $PID 2>/dev/null
+  PID=
+}
+
+# register <email>: a verified user with password123
+register() {
+  curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/register
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+}
+
+# locked_for_sql <key>: query for the length of the current block in seconds
+locked_for_sql() {
+  echo "SELECT EXTRACT(EPOCH FROM locked_until - last_failure_at)::INT FROM login_throttle WHERE key = '$1'"
+}
+
+start_backend || { echo "Backend failed to start:"; cat "$LOG"; exit 1; }
+
+EMAIL="lockout-$RUN@example.com"
+register "$EMAIL"
+ADMIN_EMAIL="lockout-admin-$RUN@example.com"
+register "$ADMIN_EMAIL"
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+login_from "$IP_C" "$ADMIN_EMAIL" password123
+ADMIN_TOKEN=$(echo "$BODY" | jq -r .token)
+
+echo "1. The first three wrong passwords are free..."
+for _ in 1 2 3; do
+  login_from "$IP_A" "$EMAIL" wrong-password
+  expect 401 "Invalid credentials"
+done
+check_sql "SELECT locked_until IS NULL FROM login_throttle WHERE key = 'account:$EMAIL'" t
+
+echo "2. The fourth blocks the account, even for the right password..."
+login_from "$IP_A" "$EMAIL" wrong-password
+expect 401 "Invalid credentials"
+check_sql "$(locked_for_sql "account:$EMAIL")" 1
+login_from "$IP_A" "$EMAIL" password123
+expect 429 "Too many failed login attempts"
+if [ "$RETRY_AFTER" -ge 1 ] 2>/dev/null; then
+  echo "  ok (Retry-After: $RETRY_AFTER)"
+else
+  echo "  FAILED: expected a Retry-After header, got '$RETRY_AFTER'"
+  FAILED=1
+fi
+
+echo "3. Each further failure doubles the block..."
+sleep 1.2
+login_from "$IP_A" "$EMAIL" wrong-password
+expect 401
+check_sql "$(locked_for_sql "account:$EMAIL")" 2
+sleep 2.2
+login_from "$IP_A" "$EMAIL" wrong-password
+expect 401
+check_sql "$(locked_for_sql "account:$EMAIL")" 4
+
+echo "4. Once the block ends the right password works and clears the count..."
+sleep 4.2
+login_from "$IP_A" "$EMAIL" password123
+expect 200
+check .email "$EMAIL"
+check_sql "SELECT COUNT(*) FROM login_throttle WHERE key = 'account:$EMAIL'" 0
+login_from "$IP_A" "$EMAIL" wrong-password
+expect 401 "Invalid credentials"
+
+echo "5. Unknown emails are throttled the same way..."
+GHOST="lockout-ghost-$RUN@example.com"
+for _ in 1 2 3 4; do
+  login_from "$IP_D" "$GHOST" wrong-password
+  expect 401 "Invalid credentials"
+done
+login_from "$IP_D" "$GHOST" wrong-password
+expect 429
+
+echo "6. Failures are logged, and only admins can read the log..."
+request "$ADMIN_TOKEN" GET "/admin/login-events?email=$EMAIL"
+expect 200
+check '[.[] | select(.reason == "bad_password")] | length' 7
+check '[.[] | select(.reason == "locked")] | length' 1
+check ".[0].ip" "$IP_A"
+request "$ADMIN_TOKEN" GET "/admin/login-events?email=$GHOST"
+check '[.[] | select(.reason == "unknown_user")] | length' 4
+check '.[0].user_id' null
+login_from "$IP_C" "$EMAIL" password123
+TOKEN=$(echo "$BODY" | jq -r .token)
+request "$TOKEN" GET /admin/login-events
+expect 403
+
+echo "7. Ten failures from one address block it for every account..."
+OTHER="lockout-other-$RUN@example.com"
+register "$OTHER"
+for i in $(seq 11); do
+  login_from "$IP_B" "lockout-spray-$i-$RUN@example.com" wrong-password
+done
+expect 401
+check_sql "SELECT failures FROM login_throttle WHERE key = 'ip:$IP_B'" 11
+login_from "$IP_B" "$OTHER" password123
+expect 429
+login_from "$IP_C" "$OTHER" password123
+expect 200
+
+echo "8. A block survives a restart and an admin can lift it..."
+sql "INSERT INTO login_throttle (key, failures, last_failure_at, locked_until)
+     SELECT key, 20, NOW() AT TIME ZONE 'UTC', NOW() AT TIME ZONE 'UTC' + INTERVAL '10 minutes'
+     FROM (VALUES ('account:$EMAIL'), ('ip:$IP_B')) AS keys (key)
+     ON CONFLICT (key) DO UPDATE SET locked_until = EXCLUDED.locked_until"
+stop_backend
+start_backend || { echo "Backend failed to restart:"; cat "$LOG"; exit 1; }
+login_from "$IP_C" "$EMAIL" password123
+expect 429
+login_from "$IP_B" "$OTHER" password123
+expect 429
+request "$TOKEN" POST /admin/lockouts/unlock "{\"email\": \"$EMAIL\"}"
+expect 403
+request "$ADMIN_TOKEN" POST /admin/lockouts/unlock '{}'
+expect 400 "Provide an email or an ip"
+request "$ADMIN_TOKEN" POST /admin/lockouts/unlock "{\"email\": \"$EMAIL\", \"ip\": \"$IP_B\"}"
+expect 200 "Unlocked"
+login_from "$IP_C" "$EMAIL" password123
+expect 200
+login_from "$IP_B" "$OTHER" password123
+expect 200
+check_sql "SELECT COUNT(*) FROM audit_log WHERE action = 'auth.unlock' AND entity_id = '$EMAIL'" 1
+
+if [ "$FAILED" = 0 ]; then
+  echo "All lockout checks passed."
+else
+  echo "Some lockout checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,180 @@
+#!/bin/bash
+# Store onboarding: a new store is seeded from its business type template,
+# the owner applies a template again (with sample products) from the setup
+# wizard and works through the checklist. Needs the backend running and
+# DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_onboarding.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# step <key>: checks the done flag of a checklist step in the last response
+step() {
+  check "[.steps[] | select(.key == \"$1\")][0].done" "$2"
+}
+
+echo "1. A new restaurant is seeded from the food template..."
+ADMIN_EMAIL="onboarding-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+request "$ADMIN_TOKEN" POST /admin/tenants "{\"name\": \"Bistro $RUN\", \"business_type\": \"spaceship\"}"
+expect 400 "Unknown business type"
+OWNER="onboarding-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Bistro $RUN\", \"business_type\": \"food\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(echo "$BODY" | jq -r .)
+OWNER_TOKEN=$(login "$OWNER")
+request "$OWNER_TOKEN" GET /products/categories
+check 'sort | join(",")' "Bebidas,Entradas,Pratos Principais,Sobremesas"
+request "$OWNER_TOKEN" GET /payment-methods
+check '[.[].code] | join(",")' "cash,credit_card,debit_card,pix,meal_voucher"
+check_sql "SELECT string_agg(name, ',' ORDER BY name) FROM tenant_roles WHERE tenant_id = '$TENANT'" "courier,waiter"
+check_sql "SELECT custom_fields->>'has_tables' FROM tenants WHERE id = '$TENANT'" "false"
+request "$OWNER_TOKEN" GET /onboarding
+check .template food
+step business_type true
+check .done 1
+check .total 7
+request "$OWNER_TOKEN" GET /onboarding/templates
+check '[.templates[].value] | length' 15
+
+echo "2. The owner applies the template again with sample products..."
+request "$OWNER_TOKEN" POST /onboarding/apply '{"custom_fields": {"has_tables": true, "num_tables": 12}, "sample_products": true}'
+expect 200
+check .seeded.products 3
+check .seeded.categories 0
+check .seeded.payment_methods 0
+check .checklist.done 1
+check_sql "SELECT custom_fields->>'num_tables' || ',' || (custom_fields->>'has_tables') FROM tenants WHERE id = '$TENANT'" "12,true"
+request "$OWNER_TOKEN" POST /onboarding/apply '{"business_type": "food", "sample_products": true}'
+expect 200
+check .seeded.products 0
+check_sql "SELECT COUNT(*) FROM products WHERE tenant_id = '$TENANT'" 3
+check_sql "SELECT custom_fields->>'num_tables' FROM tenants WHERE id = '$TENANT'" 12
+
+echo "3. Invalid templates and custom fields are rejected..."
+request "$OWNER_TOKEN" POST /onboarding/apply '{"business_type": "spaceship"}'
+expect 400 "Unknown business type"
+request "$OWNER_TOKEN" POST /onboarding/apply '{"custom_fields": {"num_tables": "many"}}'
+expect 400 "num_tables"
+request "$OWNER_TOKEN" POST /onboarding/apply '{"custom_fields": {"warranty_days": 90}}'
+expect 400 "Unknown custom field"
+
+echo "4. The checklist follows the store's data and manual check-offs..."
+request "$OWNER_TOKEN" PUT /onboarding/steps/payment_methods '{"done": true}'
+expect 200
+step payment_methods true
+request "$OWNER_TOKEN" PUT /onboarding/steps/payment_methods '{"done": false}'
+step payment_methods false
+request "$OWNER_TOKEN" PUT /onboarding/steps/payment_methods '{"done": true}'
+request "$OWNER_TOKEN" PUT /onboarding/steps/launch '{"done": true}'
+expect 404
+request "$OWNER_TOKEN" POST /products/categories '{"name": "Bebidas"}'
+expect 409
+request "$OWNER_TOKEN" POST /products/categories '{"name": "Porções"}'
+expect 201
+request "$OWNER_TOKEN" POST /products '{"name": "Batata Frita", "price": 2500, "stock_quantity": 50, "category": "Porções"}'
+PRODUCT=$(echo "$BODY" | jq -r .)
+request "$ADMIN_TOKEN" POST /admin/users "{\"email\": \"onboarding-waiter-$RUN@example.com\", \"password\": \"password123\", \"tenant_id\": \"$TENANT\"}"
+request "$OWNER_TOKEN" POST /sales "{\"payment_method\": \"meal_voucher\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]}"
+expect 201
+request "$OWNER_TOKEN" GET /onboarding
+step products true
+step team true
+step first_sale true
+step terminal false
+check .completed false
+
+echo "5. A disabled payment method rejects sales..."
+request "$OWNER_TOKEN" PUT /payment-methods/meal_voucher '{"enabled": false}'
+expect 200
+check .enabled false
+request "$OWNER_TOKEN" POST /sales "{\"payment_method\": \"meal_voucher\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]}"
+expect 400 "Payment method not accepted"
+request "$OWNER_TOKEN" PUT /payment-methods/store_credit '{"enabled": true}'
+expect 400 "needs a name"
+request "$OWNER_TOKEN" PUT /payment-methods/store_credit '{"name": "Crediário"}'
+expect 200
+check .position 5
+request "$OWNER_TOKEN" POST /sales "{\"payment_method\": \"store_credit\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]}"
+expect 201
+
+echo "6. The owner dismisses the checklist..."
+request "$OWNER_TOKEN" POST /onboarding/dismiss
+expect 200
+check .dismissed true
+request "$OWNER_TOKEN" GET /onboarding
+check .dismissed true
+
+echo "7. Another store sees none of it..."
+OTHER="onboarding-other-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants "{\"name\": \"Shop $RUN\", \"owner_email\": \"$OTHER\", \"owner_password\": \"password123\"}"
+OTHER_TOKEN=$(login "$OTHER")
+request "$OTHER_TOKEN" GET /payment-methods
+check '[.[].code] | join(",")' "cash,credit_card,debit_card,pix"
+request "$OTHER_TOKEN" GET /products/categories
+check 'index("Porções")' null
+request "$OTHER_TOKEN" GET /onboarding
+check .template retail
+check .dismissed false
+
+if [ "$FAILED" = 0 ]; then
+  echo "All onboarding checks passed."
+else
+  echo "Some onboarding checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,137 @@
+#!/bin/bash
+# LGPD tools: a customer's data export, anonymisation (addresses and
+# delivery snapshots included) and the audit trail of requests. Needs the backend running and DATABASE_URL pointing at its
+# database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_privacy.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# check_sql <query> <expected>
+check_sql() {
+  VALUE=$(sql "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($2)"
+  else
+    echo "  FAILED: expected $2 from $1, got $VALUE"
+    FAILED=1
+  fi
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# sell <json fields>: rings up one unit of the product, the sale id in $SALE
+sell() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"items\": [{\"product_id\": \"$PRODUCT\", \"quantity\": 1}]${1:+, $1}}"
+  SALE=$(echo "$BODY" | jq -r . 2>/dev/null)
+}
+
+echo "1. A store with a customer who had something delivered..."
+ADMIN_EMAIL="privacy-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="privacy-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Privacy $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100}'
+expect 201
+PRODUCT=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers \
+  '{"name": "Ana Souza", "email": "ana@example.com", "phone": "(11) 98765-4321", "document": "529.982.247-25"}'
+expect 201
+CUSTOMER=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /deliveries/zones \
+  '{"name": "Centro", "zip_code_start": "01000000", "zip_code_end": "01999999", "fee": 800}'
+expect 201
+request "$TOKEN" POST "/customers/$CUSTOMER/addresses" \
+  '{"street": "Rua Augusta", "number": "100", "city": "São Paulo", "state": "SP", "zip_code": "01305-000"}'
+expect 201
+ADDRESS=$(echo "$BODY" | jq -r .)
+sell "\"customer_id\": \"$CUSTOMER\", \"delivery\": {\"address_id\": \"$ADDRESS\"}"
+expect 201
+DELIVERY=$(sql "SELECT id FROM deliveries WHERE sale_id = '$SALE'")
+
+echo "2. The export holds the customer and their sales..."
+request "$TOKEN" GET "/customers/$CUSTOMER/export"
+expect 200
+check .customer.name "Ana Souza"
+check '.sales | length' 1
+check '.sale_items[0].product_name' Caderno
+check '.addresses[0].street' "Rua Augusta"
+check '.deliveries[0].address_text | startswith("Rua Augusta, 100")' true
+check '.requests[0].request_type' export
+
+echo "3. Anonymising waits for deliveries on their way..."
+request "$TOKEN" POST "/customers/$CUSTOMER/anonymize"
+expect 409 "in progress"
+request "$TOKEN" PUT "/deliveries/$DELIVERY/status" '{"status": "preparing"}'
+expect 200
+request "$TOKEN" PUT "/deliveries/$DELIVERY/status" '{"status": "out_for_delivery", "courier_name": "Rafael"}'
+expect 200
+request "$TOKEN" PUT "/deliveries/$DELIVERY/status" '{"status": "delivered"}'
+expect 200
+
+echo "4. Anonymising scrubs personal fields and addresses but keeps the sales..."
+request "$TOKEN" POST "/customers/$CUSTOMER/anonymize"
+expect 200
+check_sql "SELECT name || ':' || COALESCE(email, '') || COALESCE(phone, '') || COALESCE(document, '') FROM customers WHERE id = '$CUSTOMER'" "Anonymized customer:"
+check_sql "SELECT COUNT(*) FROM sales WHERE customer_id = '$CUSTOMER'" 1
+check_sql "SELECT COUNT(*) FROM customer_addresses WHERE customer_id = '$CUSTOMER'" 0
+check_sql "SELECT address_text FROM deliveries WHERE id = '$DELIVERY'" "Anonymized address"
+request "$TOKEN" POST "/customers/$CUSTOMER/anonymize"
+expect 404
+
+echo "5. Both requests are on the audit trail..."
+request "$TOKEN" GET /customers/privacy-requests
+expect 200
+check '[.[] | select(.customer_id == "'"$CUSTOMER"'") | .request_type] | sort | join(",")' "anonymize,export"
+
+if [ "$FAILED" = 0 ]; then
+  echo "All privacy checks passed."
+else
+  echo "Some privacy checks FAILED."
+  exit 1
+fi
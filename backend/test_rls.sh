@@ -0,0 +1,143 @@
+#!/bin/bash
+# Tenant isolation: requests from one store never reach another store's
+# rows, and neither does SQL running under the app_tenant role. Needs the
+# backend running and DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_rls.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+# as_tenant <tenant id> <sql>: runs the statement the way tenant requests do
+as_tenant() {
+  BODY=$(psql "$DATABASE_URL" -qtA -v ON_ERROR_STOP=1 2>&1 <<SQL
+BEGIN;
+SELECT set_config('role', 'app_tenant', true), set_config('app.tenant_id', '$1', true) \gset
+$2;
+ROLLBACK;
+SQL
+)
+  STATUS=$?
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+expect_without() {
+  if [ "$STATUS" = "$1" ] && ! echo "$BODY" | grep -q "$2"; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 without $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+login() {
+  psql "$DATABASE_URL" -q -c "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# shop <name>: creates a store with an owner and prints the owner's token
+shop() {
+  EMAIL="$1-$RUN@example.com"
+  curl -s -o /dev/null -X POST -H "Authorization: Bearer $ADMIN_TOKEN" -H "Content-Type: application/json" \
+    -d "{\"name\": \"$1 $RUN\", \"owner_email\": \"$EMAIL\", \"owner_password\": \"password123\"}" $API/admin/tenants
+  login "$EMAIL"
+}
+
+tenant_of() {
+  psql "$DATABASE_URL" -tA -c "SELECT tenant_id FROM users WHERE email = '$1-$RUN@example.com'"
+}
+
+echo "1. Two stores are created..."
+ADMIN_EMAIL="rls-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+psql "$DATABASE_URL" -q -c "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+TOKEN_A=$(shop rls-a)
+TOKEN_B=$(shop rls-b)
+TENANT_A=$(tenant_of rls-a)
+TENANT_B=$(tenant_of rls-b)
+echo "  A=$TENANT_A B=$TENANT_B"
+
+echo "2. Store A adds a product, a customer and an API key..."
+request "$TOKEN_A" POST /products "{\"name\": \"Secret A $RUN\", \"price\": 1000, \"stock_quantity\": 5}"
+expect 201
+PRODUCT_A=$(echo "$BODY" | jq -r .)
+request "$TOKEN_A" POST /customers "{\"name\": \"Customer A $RUN\"}"
+expect 201
+CUSTOMER_A=$(echo "$BODY" | jq -r .)
+request "$TOKEN_A" POST /api-keys "{\"name\": \"Key A $RUN\", \"scopes\": [\"products.view\"]}"
+expect 201
+KEY_A=$(echo "$BODY" | jq -r .id)
+request "$TOKEN_B" POST /customers "{\"name\": \"Customer B $RUN\"}"
+expect 201
+
+echo "3. Store B does not see them..."
+request "$TOKEN_B" GET /api-keys
+expect_without 200 "Key A $RUN"
+request "$TOKEN_B" GET "/customers/$CUSTOMER_A"
+expect 404
+
+echo "4. Store B cannot change or delete them..."
+request "$TOKEN_B" PUT "/products/$PRODUCT_A" '{"stock_quantity": 0}'
+expect 404
+request "$TOKEN_B" PUT "/customers/$CUSTOMER_A" '{"name": "Taken over"}'
+expect 404
+request "$TOKEN_B" DELETE "/customers/$CUSTOMER_A"
+expect 404
+request "$TOKEN_B" DELETE "/api-keys/$KEY_A"
+expect 404
+
+echo "5. Store A still has them unchanged..."
+request "$TOKEN_A" GET /api-keys
+expect 200 "Key A $RUN"
+as_tenant "$TENANT_A" "SELECT p.stock_quantity, c.name FROM products p, customers c WHERE p.id = '$PRODUCT_A' AND c.id = '$CUSTOMER_A'"
+expect 0 "^5|Customer A $RUN$"
+
+echo "6. SQL as store B sees none of store A's rows, even unfiltered..."
+as_tenant "$TENANT_B" "SELECT COUNT(*) FROM products WHERE id = '$PRODUCT_A'"
+expect 0 '^0$'
+as_tenant "$TENANT_B" "SELECT COUNT(*) FROM customers WHERE tenant_id <> '$TENANT_B'"
+expect 0 '^0$'
+
+echo "7. SQL as store B cannot update store A's rows..."
+as_tenant "$TENANT_B" "UPDATE products SET price = 1 WHERE id = '$PRODUCT_A' RETURNING id"
+expect 0 '^$'
+
+echo "8. SQL as store B cannot write rows into store A..."
+as_tenant "$TENANT_B" "INSERT INTO customers (id, tenant_id, name) VALUES (gen_random_uuid(), '$TENANT_A', 'Planted')"
+expect 3 "row-level security"
+as_tenant "$TENANT_B" "UPDATE customers SET tenant_id = '$TENANT_A' RETURNING id"
+expect 3 "row-level security"
+
+echo "9. Without a tenant nothing is visible..."
+as_tenant "" "SELECT COUNT(*) FROM products"
+expect 0 '^0$'
+
+echo "10. Platform tables and tokens are out of reach..."
+as_tenant "$TENANT_B" "SELECT refresh_token_hash FROM sessions"
+expect 3 "permission denied"
+as_tenant "$TENANT_B" "UPDATE tenants SET status = 'active'"
+expect 3 "permission denied"
+as_tenant "$TENANT_B" "SELECT * FROM login_events"
+expect 3 "permission denied"
+
+exit $FAILED
@@ -0,0 +1,139 @@
+#!/bin/bash
+# Tenant roles: defining a custom role, listing roles and staff, and
+# assigning a role to a staff member. Needs the backend running and
+# DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_roles.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+echo "1. A store with an owner and a cashier..."
+ADMIN_EMAIL="roles-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="roles-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Roles $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(sql "SELECT tenant_id FROM users WHERE email = '$OWNER'")
+TOKEN=$(login "$OWNER")
+STAFF_EMAIL="roles-staff-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/users \
+  "{\"email\": \"$STAFF_EMAIL\", \"password\": \"password123\", \"role\": \"cashier\", \"tenant_id\": \"$TENANT\"}"
+expect 201
+STAFF=$(sql "SELECT id FROM users WHERE email = '$STAFF_EMAIL'")
+STAFF_TOKEN=$(login "$STAFF_EMAIL")
+
+echo "2. Only the owner manages roles..."
+request "$STAFF_TOKEN" GET /roles
+expect 403
+request "$TOKEN" GET /roles/permissions
+expect 200
+check '.templates.cashier | index("sales.create") != null' true
+check '.permissions | index("tenants.manage")' null
+
+echo "3. Custom roles are validated and saved..."
+request "$TOKEN" POST /roles '{"name": "Admin", "permissions": []}'
+expect 400 "built-in role"
+request "$TOKEN" POST /roles '{"name": "Night shift", "permissions": ["tenants.manage"]}'
+expect 400 "Unknown permission"
+request "$TOKEN" POST /roles '{"name": "Night shift", "permissions": ["sales.create", "products.view", "products.edit", "sales.create"]}'
+expect 201
+ROLE=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /roles '{"name": "night shift", "permissions": []}'
+expect 409
+
+echo "4. Listing roles and staff..."
+request "$TOKEN" GET /roles
+expect 200
+check length 1
+check '.[0].id' "$ROLE"
+check '.[0].name' "night shift"
+check '.[0].permissions | join(",")' "sales.create,products.view,products.edit"
+request "$TOKEN" GET /roles/users
+expect 200
+check length 2
+check ".[] | select(.id == \"$STAFF\") | .role" cashier
+check '.[0].password_hash' null
+
+echo "5. Assigning the role logs the staff member out..."
+request "$TOKEN" PUT "/roles/users/$STAFF" '{"role": "supervisor"}'
+expect 400 "Unknown role"
+request "$TOKEN" PUT "/roles/users/$STAFF" '{"role": "admin"}'
+expect 400 "Unknown role"
+request "$TOKEN" PUT "/roles/users/$STAFF" '{"role": "Night Shift"}'
+expect 200
+request "$STAFF_TOKEN" GET /products
+expect 401
+request "$TOKEN" GET /roles/users
+check ".[] | select(.id == \"$STAFF\") | .role" "night shift"
+STAFF_TOKEN=$(login "$STAFF_EMAIL")
+request "$STAFF_TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 10}'
+expect 201
+request "$STAFF_TOKEN" GET /roles
+expect 403
+
+echo "6. Edits to the role apply on the next request..."
+request "$TOKEN" PUT "/roles/$ROLE" '{"name": "Night shift", "permissions": ["sales.create", "products.view"]}'
+expect 200
+request "$STAFF_TOKEN" POST /products '{"name": "Caneta", "price": 500, "stock_quantity": 10}'
+expect 403
+
+echo "7. A role in use cannot be deleted..."
+request "$TOKEN" DELETE "/roles/$ROLE"
+expect 409
+request "$TOKEN" PUT "/roles/users/$STAFF" '{"role": "cashier"}'
+expect 200
+request "$TOKEN" DELETE "/roles/$ROLE"
+expect 200
+request "$TOKEN" GET /roles
+check length 0
+
+if [ "$FAILED" = 0 ]; then
+  echo "All role checks passed."
+else
+  echo "Some role checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,156 @@
+#!/bin/bash
+# Saved customer segments: creating them, listing them, their members and
+# the CSV export. Needs the backend running and DATABASE_URL pointing at
+# its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_segments.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# sell <customer id> <product id> <quantity>
+sell() {
+  request "$TOKEN" POST /sales \
+    "{\"payment_method\": \"cash\", \"customer_id\": \"$1\", \"items\": [{\"product_id\": \"$2\", \"quantity\": $3}]}"
+}
+
+echo "1. A store with customers who bought different things..."
+ADMIN_EMAIL="segments-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="segments-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Segments $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TOKEN=$(login "$OWNER")
+request "$TOKEN" POST /products '{"name": "Caderno", "price": 2000, "stock_quantity": 100, "category": "Papelaria"}'
+PAPER=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /products '{"name": "Caneta", "price": 500, "stock_quantity": 100, "category": "Escrita"}'
+PEN=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers '{"name": "Ana Souza", "email": "ana@example.com", "marketing_consent": true}'
+ANA=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers '{"name": "Bruno Lima"}'
+BRUNO=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /customers '{"name": "=Carla, \"Dias\""}'
+expect 201
+sell "$ANA" "$PAPER" 2
+expect 201
+sell "$BRUNO" "$PEN" 1
+expect 201
+sql "UPDATE sales SET created_at = CURRENT_TIMESTAMP - INTERVAL '60 days' WHERE customer_id = '$BRUNO'"
+
+echo "2. Segments are validated and saved..."
+request "$TOKEN" POST /segments '{"name": " "}'
+expect 400 "Segment name is required"
+request "$TOKEN" POST /segments '{"name": "Big spenders", "min_spent": -1}'
+expect 400 "min_spent must not be negative"
+request "$TOKEN" POST /segments '{"name": "Big spenders", "min_spent": 1000}'
+expect 201
+BIG=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /segments '{"name": "Stationery buyers", "category": "papelaria"}'
+expect 201
+STATIONERY=$(echo "$BODY" | jq -r .)
+request "$TOKEN" POST /segments '{"name": "Inactive", "description": "No purchase in 30 days", "inactive_days": 30}'
+expect 201
+INACTIVE=$(echo "$BODY" | jq -r .)
+
+echo "3. Listing the segments..."
+request "$TOKEN" GET /segments
+expect 200
+check length 3
+check '.[0].name' "Big spenders"
+check '.[0].id' "$BIG"
+check '.[0].min_spent' 1000
+check '.[1].name' "Inactive"
+
+echo "4. Each segment's customers match its filters..."
+request "$TOKEN" GET "/segments/$BIG/customers"
+expect 200
+check length 1
+check '.[0].customer_id' "$ANA"
+check '.[0].total_spent' 4000
+request "$TOKEN" GET "/segments/$STATIONERY/customers"
+expect 200
+check length 1
+check '.[0].name' "Ana Souza"
+request "$TOKEN" GET "/segments/$INACTIVE/customers"
+expect 200
+check length 2
+check '.[0].customer_id' "$BRUNO"
+
+echo "5. Exporting a segment as CSV..."
+CSV=$(curl -s -D /tmp/segment-headers -H "Authorization: Bearer $TOKEN" "$API/segments/$INACTIVE/export")
+if grep -qi "content-type: text/csv" /tmp/segment-headers; then echo "  ok (text/csv)"; else echo "  FAILED: not a CSV download"; FAILED=1; fi
+if [ "$(echo "$CSV" | head -n 1)" = "customer_id,name,email,phone,marketing_consent,total_spent_cents,orders,last_purchase_at" ]; then
+  echo "  ok (header)"
+else
+  echo "  FAILED: unexpected header $(echo "$CSV" | head -n 1)"
+  FAILED=1
+fi
+if [ "$(echo "$CSV" | wc -l)" = 3 ] && echo "$CSV" | grep -q "^$BRUNO,Bruno Lima,,,false,500,1," \
+  && echo "$CSV" | grep -q ",\"'=Carla, \"\"Dias\"\"\",,,false,0,0,$"; then
+  echo "  ok (rows)"
+else
+  echo "  FAILED: unexpected rows $CSV"
+  FAILED=1
+fi
+
+echo "6. Updating and deleting a segment..."
+request "$TOKEN" PUT "/segments/$BIG" '{"name": "Big spenders", "min_spent": 10000}'
+expect 200
+request "$TOKEN" GET "/segments/$BIG/customers"
+check length 0
+request "$TOKEN" DELETE "/segments/$STATIONERY"
+expect 200
+request "$TOKEN" GET "/segments/$STATIONERY/export"
+expect 404
+request "$TOKEN" GET /segments
+check length 2
+
+if [ "$FAILED" = 0 ]; then
+  echo "All segment checks passed."
+else
+  echo "Some segment checks FAILED."
+  exit 1
+fi
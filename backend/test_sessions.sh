@@ -0,0 +1,154 @@
+#!/bin/bash
+# Login sessions: refreshing and rotating tokens, spotting a replayed
+# refresh token, logging out and revoking devices. Needs the backend
+# running and DATABASE_URL pointing at its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_sessions.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# login <email>: logs in from a new device, setting TOKEN and REFRESH
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  request "" POST /auth/login "{\"email\": \"$1\", \"password\": \"password123\"}"
+  TOKEN=$(echo "$BODY" | jq -r .token)
+  REFRESH=$(echo "$BODY" | jq -r .refresh_token)
+}
+
+refresh() {
+  request "" POST /auth/refresh "{\"refresh_token\": \"$1\"}"
+}
+
+echo "1. Logging in opens a session..."
+EMAIL="sessions-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$EMAIL\", \"password\": \"password123\"}" $API/auth/register
+login "$EMAIL"
+expect 200
+USER_ID=$(sql "SELECT id FROM users WHERE email = '$EMAIL'")
+request "$TOKEN" GET /auth/sessions
+expect 200
+check 'length' 1
+check '.[0].current' true
+
+echo "2. The refresh token buys a new access token and is rotated..."
+FIRST=$REFRESH
+refresh "$FIRST"
+expect 200
+check '.token | length > 0' true
+REFRESH=$(echo "$BODY" | jq -r .refresh_token)
+TOKEN=$(echo "$BODY" | jq -r .token)
+if [ "$REFRESH" != "$FIRST" ]; then echo "  ok (rotated)"; else echo "  FAILED: refresh token not rotated"; FAILED=1; fi
+request "$TOKEN" GET /auth/sessions
+expect 200
+check 'length' 1
+PREVIOUS=$REFRESH
+refresh "$PREVIOUS"
+expect 200
+REFRESH=$(echo "$BODY" | jq -r .refresh_token)
+
+echo "3. A demotion applies on the next refresh..."
+sql "UPDATE users SET role = 'cashier' WHERE id = '$USER_ID'"
+PREVIOUS=$REFRESH
+refresh "$PREVIOUS"
+expect 200
+REFRESH=$(echo "$BODY" | jq -r .refresh_token)
+ROLE=$(echo "$BODY" | jq -r .token | cut -d. -f2 | tr '_-' '/+' | base64 -d 2>/dev/null | jq -r .role)
+if [ "$ROLE" = "cashier" ]; then echo "  ok (role = cashier)"; else echo "  FAILED: expected role cashier, got $ROLE"; FAILED=1; fi
+sql "UPDATE users SET role = 'user' WHERE id = '$USER_ID'"
+
+echo "4. Replaying a rotated token kills the session..."
+refresh "$PREVIOUS"
+expect 401 "Invalid refresh token"
+refresh "$REFRESH"
+expect 401 "Session expired"
+request "$TOKEN" GET /auth/sessions
+expect 401
+
+echo "5. Logout ends the session on that device only..."
+login "$EMAIL"
+PHONE_TOKEN=$TOKEN
+PHONE_REFRESH=$REFRESH
+login "$EMAIL"
+request "$TOKEN" POST /auth/logout
+expect 200
+request "$TOKEN" GET /auth/sessions
+expect 401
+refresh "$REFRESH"
+expect 401
+request "$PHONE_TOKEN" GET /auth/sessions
+expect 200
+check 'length' 1
+
+echo "6. A device can be logged out from another one..."
+login "$EMAIL"
+PHONE_SESSION=$(sql "SELECT id FROM sessions WHERE user_id = '$USER_ID' AND revoked_at IS NULL ORDER BY created_at ASC LIMIT 1")
+request "$TOKEN" DELETE "/auth/sessions/$PHONE_SESSION"
+expect 200
+request "$TOKEN" DELETE "/auth/sessions/$PHONE_SESSION"
+expect 404
+request "$PHONE_TOKEN" GET /auth/sessions
+expect 401
+refresh "$PHONE_REFRESH"
+expect 401
+
+echo "7. An admin logs a user out everywhere..."
+ADMIN_EMAIL="sessions-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+USER_TOKEN=$TOKEN
+USER_REFRESH=$REFRESH
+login "$ADMIN_EMAIL"
+request "$USER_TOKEN" GET "/admin/users/$USER_ID/sessions"
+expect 403
+request "$TOKEN" GET "/admin/users/$USER_ID/sessions"
+expect 200
+check 'length' 1
+request "$TOKEN" DELETE "/admin/users/$USER_ID/sessions"
+expect 200 "1 sessions revoked"
+request "$USER_TOKEN" GET /auth/sessions
+expect 401
+refresh "$USER_REFRESH"
+expect 401
+
+if [ "$FAILED" = 0 ]; then
+  echo "All session checks passed."
+else
+  echo "Some session checks FAILED."
+  exit 1
+fi
@@ -0,0 +1,82 @@
+#!/bin/bash
+# Single sign-on against the mock OIDC issuer. Start both first:
+#
+#   cargo run --bin mock_oidc
+#   OIDC_PROVIDERS=mock \
+#   OIDC_MOCK_ISSUER=http://127.0.0.1:9000 \
+#   OIDC_MOCK_CLIENT_ID=pdv \
+#   OIDC_MOCK_REDIRECT_URI=http://localhost:5173/sso/callback \
+#   OIDC_MOCK_ROLE_CLAIM=groups \
+#   OIDC_MOCK_ROLE_MAP=pdv-admins:admin,pdv-resellers:reseller \
+#   TWO_FACTOR_REQUIRED_ROLES= \
+#   cargo run --bin backend
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# sso_login <email> <groups> [extra authorize params]
+# Walks the redirect by hand and prints the callback status and body
+sso_login() {
+  AUTH_URL=$(curl -s -X POST $API/auth/sso/mock/authorize | jq -r .authorization_url)
+  LOCATION=$(curl -s -o /dev/null -w '%{redirect_url}' "$AUTH_URL&email=$1&groups=$2$3")
+  CODE=$(echo "$LOCATION" | sed -n 's/.*[?&]code=\([^&]*\).*/\1/p')
+  STATE=$(echo "$LOCATION" | sed -n 's/.*[?&]state=\([^&]*\).*/\1/p')
+  callback
+}
+
+callback() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X POST -H "Content-Type: application/json" \
+    -d "{\"code\": \"$CODE\", \"state\": \"$STATE\"}" $API/auth/sso/mock/callback)
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+echo "1. Listing providers..."
+curl -s $API/auth/sso/providers | jq -c
+echo
+
+echo "2. New reseller is created from the group mapping..."
+sso_login "partner-$RUN@example.com" "pdv-resellers"
+expect 200 '"role":"reseller"'
+
+echo "3. Same identity signs in again..."
+sso_login "partner-$RUN@example.com" "pdv-resellers"
+expect 200 '"role":"reseller"'
+
+echo "4. Replaying the code and state is refused..."
+callback
+expect 400
+
+echo "5. Group change updates the role..."
+sso_login "partner-$RUN@example.com" "pdv-admins"
+expect 200 '"role":"admin"'
+
+echo "6. Identity without a mapped group is refused..."
+sso_login "nobody-$RUN@example.com" "sales"
+expect 403
+
+echo "7. Unverified email is not matched to an account..."
+sso_login "unverified-$RUN@example.com" "pdv-resellers" "&email_verified=false"
+expect 403
+
+echo "8. Shop accounts cannot use SSO..."
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"shop-$RUN@example.com\", \"password\": \"password123\"}" $API/auth/register
+sso_login "shop-$RUN@example.com" "pdv-admins"
+expect 403
+
+echo "9. Unknown state is refused..."
+STATE=invalid
+callback
+expect 400
+
+exit $FAILED
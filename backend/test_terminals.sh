@@ -0,0 +1,117 @@
+#!/bin/bash
+# Shared POS terminals: a manager registers the device and staff switch in
+# with their PIN, unless they are under two-factor authentication. Needs the backend running and DATABASE_URL pointing at
+# its database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_terminals.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  curl -s -X POST -H "Content-Type: application/json" \
+    -d "{\"email\": \"$1\", \"password\": \"password123\"}" $API/auth/login | jq -r .token
+}
+
+# pin_login <pin>: switches the cashier in on the terminal
+pin_login() {
+  request "" POST /auth/pin-login \
+    "{\"device_token\": \"$DEVICE\", \"user_id\": \"$CASHIER\", \"pin\": \"$1\"}"
+}
+
+echo "1. A store with a cashier and a registered terminal..."
+ADMIN_EMAIL="terminals-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+ADMIN_TOKEN=$(login "$ADMIN_EMAIL")
+OWNER="terminals-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Terminals $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+TENANT=$(sql "SELECT tenant_id FROM users WHERE email = '$OWNER'")
+TOKEN=$(login "$OWNER")
+CASHIER_EMAIL="terminals-cashier-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/users \
+  "{\"email\": \"$CASHIER_EMAIL\", \"password\": \"password123\", \"tenant_id\": \"$TENANT\"}"
+expect 201
+CASHIER=$(sql "SELECT id FROM users WHERE email = '$CASHIER_EMAIL'")
+CASHIER_TOKEN=$(login "$CASHIER_EMAIL")
+request "$CASHIER_TOKEN" PUT /auth/pin '{"pin": "4321", "password": "password123"}'
+expect 200
+request "$TOKEN" POST /terminals '{"name": "Caixa 1"}'
+expect 201
+DEVICE=$(echo "$BODY" | jq -r .device_token)
+
+echo "2. The cashier switches in with their PIN..."
+request "" POST /auth/terminal/users "{\"device_token\": \"$DEVICE\"}"
+expect 200
+check '.[0].email' "$CASHIER_EMAIL"
+pin_login 1111
+expect 401
+pin_login 4321
+expect 200
+check '.token | length > 0' true
+
+echo "3. An unverified email cannot switch in..."
+sql "UPDATE users SET email_verified_at = NULL WHERE id = '$CASHIER'"
+pin_login 4321
+expect 403 "Email not verified"
+sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE id = '$CASHIER'"
+
+echo "4. Nor can a cashier the store requires 2FA for..."
+ROLE=$(sql "SELECT role FROM users WHERE id = '$CASHIER'")
+request "$TOKEN" PUT /roles/two-factor-policy "{\"roles\": [\"$ROLE\"]}"
+expect 200
+pin_login 4321
+expect 403 "log in with your password"
+request "$TOKEN" PUT /roles/two-factor-policy '{"roles": []}'
+expect 200
+pin_login 4321
+expect 200
+
+echo "5. Nor one who turned 2FA on..."
+sql "UPDATE users SET totp_secret = 'GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ', totp_enabled_at = CURRENT_TIMESTAMP WHERE id = '$CASHIER'"
+pin_login 4321
+expect 403 "log in with your password"
+
+if [ "$FAILED" = 0 ]; then
+  echo "All terminal checks passed."
+else
+  echo "Some terminal checks FAILED."
+  exit 1
+fi
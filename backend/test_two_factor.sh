@@ -0,0 +1,150 @@
+#!/bin/bash
+# Two-factor authentication: enrolling TOTP, logging in with a code or a
+# recovery code, and being walked through enrolment when the store
+# requires it. Needs the backend running and DATABASE_URL pointing at its
+# database:
+#
+#   DATABASE_URL=postgres://... cargo run --bin backend
+#   DATABASE_URL=postgres://... ./test_two_factor.sh
+API=http://127.0.0.1:3000
+RUN=$(date +%s)
+FAILED=0
+
+# request <token> <method> <path> [json body]
+request() {
+  RESPONSE=$(curl -s -w '\n%{http_code}' -X "$2" -H "Authorization: Bearer $1" \
+    -H "Content-Type: application/json" ${4:+-d "$4"} "$API$3")
+  STATUS=$(echo "$RESPONSE" | tail -n 1)
+  BODY=$(echo "$RESPONSE" | sed '$d')
+}
+
+expect() {
+  if [ "$STATUS" = "$1" ] && { [ -z "$2" ] || echo "$BODY" | grep -q "$2"; }; then
+    echo "  ok ($STATUS)"
+  else
+    echo "  FAILED: expected $1 $2, got $STATUS $BODY"
+    FAILED=1
+  fi
+}
+
+# check <jq filter> <expected>: checks a value of the last response body
+check() {
+  VALUE=$(echo "$BODY" | jq -r "$1")
+  if [ "$VALUE" = "$2" ]; then
+    echo "  ok ($1 = $2)"
+  else
+    echo "  FAILED: expected $1 = $2, got $VALUE"
+    FAILED=1
+  fi
+}
+
+sql() {
+  psql "$DATABASE_URL" -qtA -c "$1"
+}
+
+# password_login <email>: first login step, leaves the response in BODY
+password_login() {
+  sql "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE email = '$1'"
+  request "" POST /auth/login "{\"email\": \"$1\", \"password\": \"password123\"}"
+}
+
+# totp <base32 secret> <step offset>: the authenticator code, RFC 6238
+# with SHA-1, 30 second steps and 6 digits
+totp() {
+  local padded=$1
+  while [ $(( ${#padded} % 8 )) != 0 ]; do padded="$padded="; done
+  local key=$(echo -n "$padded" | base32 -d | xxd -p | tr -d '\n')
+  local counter=$(printf '%016x' $(( $(date +%s) / 30 + $2 )))
+  local mac=$(echo -n "$counter" | xxd -r -p \
+    | openssl dgst -sha1 -mac HMAC -macopt "hexkey:$key" | awk '{print $NF}')
+  local offset=$(( 0x${mac:39:1} * 2 ))
+  printf '%06d' $(( (0x${mac:$offset:8} & 0x7fffffff) % 1000000 ))
+}
+
+echo "1. A user enrols an authenticator..."
+EMAIL="two-factor-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$EMAIL\", \"password\": \"password123\"}" $API/auth/register
+password_login "$EMAIL"
+expect 200
+TOKEN=$(echo "$BODY" | jq -r .token)
+request "$TOKEN" POST /auth/2fa/setup
+expect 200
+SECRET=$(echo "$BODY" | jq -r .secret)
+request "$TOKEN" POST /auth/2fa/enable '{"code": "000000x"}'
+expect 400 "Invalid code"
+request "$TOKEN" POST /auth/2fa/enable "{\"code\": \"$(totp "$SECRET" -1)\"}"
+expect 200
+check '.recovery_codes | length' 10
+RECOVERY=$(echo "$BODY" | jq -r '.recovery_codes[0]')
+
+echo "2. Their password alone no longer logs them in..."
+password_login "$EMAIL"
+expect 200
+check .two_factor_required true
+check .setup_required false
+check .token null
+CHALLENGE=$(echo "$BODY" | jq -r .challenge_token)
+
+echo "3. A wrong code is refused, the current one completes the login..."
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"12345\"}"
+expect 401 "Invalid code"
+CODE=$(totp "$SECRET" 0)
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"$CODE\"}"
+expect 200
+check .email "$EMAIL"
+TOKEN=$(echo "$BODY" | jq -r .token)
+request "$TOKEN" GET /auth/2fa
+expect 200
+check .enabled true
+
+echo "4. The challenge and the code cannot be replayed..."
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"$CODE\"}"
+expect 401 "Invalid or expired challenge"
+password_login "$EMAIL"
+CHALLENGE=$(echo "$BODY" | jq -r .challenge_token)
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"$CODE\"}"
+expect 401 "Invalid code"
+
+echo "5. A recovery code works once..."
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"$RECOVERY\"}"
+expect 200
+check .email "$EMAIL"
+password_login "$EMAIL"
+CHALLENGE=$(echo "$BODY" | jq -r .challenge_token)
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"$RECOVERY\"}"
+expect 401 "Invalid code"
+
+echo "6. A store requiring 2FA walks its staff through enrolment at login..."
+ADMIN_EMAIL="two-factor-admin-$RUN@example.com"
+curl -s -o /dev/null -X POST -H "Content-Type: application/json" \
+  -d "{\"email\": \"$ADMIN_EMAIL\", \"password\": \"password123\"}" $API/auth/register
+sql "UPDATE users SET role = 'admin', tenant_id = NULL WHERE email = '$ADMIN_EMAIL'"
+password_login "$ADMIN_EMAIL"
+ADMIN_TOKEN=$(echo "$BODY" | jq -r .token)
+OWNER="two-factor-owner-$RUN@example.com"
+request "$ADMIN_TOKEN" POST /admin/tenants \
+  "{\"name\": \"Two Factor $RUN\", \"owner_email\": \"$OWNER\", \"owner_password\": \"password123\"}"
+expect 201
+password_login "$OWNER"
+OWNER_TOKEN=$(echo "$BODY" | jq -r .token)
+request "$OWNER_TOKEN" PUT /roles/two-factor-policy '{"roles": ["user"]}'
+expect 200
+password_login "$OWNER"
+expect 200
+check .setup_required true
+CHALLENGE=$(echo "$BODY" | jq -r .challenge_token)
+request "" POST /auth/2fa/login/setup "{\"challenge_token\": \"$CHALLENGE\"}"
+expect 200
+SECRET=$(echo "$BODY" | jq -r .secret)
+request "" POST /auth/2fa/login "{\"challenge_token\": \"$CHALLENGE\", \"code\": \"$(totp "$SECRET" 0)\"}"
+expect 200
+check .email "$OWNER"
+check '.recovery_codes | length' 10
+
+if [ "$FAILED" = 0 ]; then
+  echo "All two-factor checks passed."
+else
+  echo "Some two-factor checks FAILED."
+  exit 1
+fi
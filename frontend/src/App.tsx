@@ -1,6 +1,12 @@
 import { BrowserRouter, Routes, Route, Navigate, Outlet } from "react-router-dom"
 import Login from "@/pages/Login"
 import Register from "@/pages/Register"
+import ForgotPassword from "@/pages/ForgotPassword"
+import ResetPassword from "@/pages/ResetPassword"
+import VerifyEmail from "@/pages/VerifyEmail"
+import TerminalLogin from "@/pages/TerminalLogin"
+import SsoCallback from "@/pages/SsoCallback"
+import StoreBlocked from "@/pages/StoreBlocked"
 import { useAuthStore } from "@/store/auth"
 
 function ProtectedRoute() {
@@ -30,8 +36,15 @@ function App() {
       <Routes>
         <Route path="/login" element={<Login />} />
         <Route path="/register" element={<Register />} />
+        <Route path="/forgot-password" element={<ForgotPassword />} />
+        <Route path="/reset-password" element={<ResetPassword />} />
+        <Route path="/verify-email" element={<VerifyEmail />} />
+        <Route path="/terminal" element={<TerminalLogin />} />
+        <Route path="/sso/callback" element={<SsoCallback />} />
 
         <Route element={<ProtectedRoute />}>
+          <Route path="/blocked" element={<StoreBlocked />} />
+
           {/* App (Operation) Layout */}
           <Route element={<Layout />}>
             <Route path="/" element={<Dashboard />} />
@@ -1,5 +1,5 @@
 import { Outlet, Link, useLocation } from "react-router-dom";
-import { useAuthStore } from "@/store/auth";
+import { logout } from "@/lib/api";
 import { Button } from "@/components/ui/button";
 import {
     LayoutDashboard,
@@ -16,7 +16,6 @@ const navItems = [
 ];
 
 export default function AdminLayout() {
-    const logout = useAuthStore((state) => state.logout);
     const location = useLocation();
 
     return (
@@ -1,5 +1,6 @@
 import { Outlet, Link, useLocation } from "react-router-dom";
 import { useAuthStore } from "@/store/auth";
+import { logout } from "@/lib/api";
 import { Button } from "@/components/ui/button";
 import {
     LayoutDashboard,
@@ -10,7 +11,6 @@ import {
 } from "lucide-react";
 
 export default function BackofficeLayout() {
-    const logout = useAuthStore((state) => state.logout);
     const role = useAuthStore((state) => state.role);
     const location = useLocation();
 
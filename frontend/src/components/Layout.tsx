@@ -1,5 +1,6 @@
 import { Outlet, Link, useLocation } from "react-router-dom";
 import { useAuthStore } from "@/store/auth";
+import { logout } from "@/lib/api";
 import {
     LayoutDashboard,
     Package,
@@ -22,7 +23,6 @@ const navItems = [
 
 export default function Layout() {
     const { pathname } = useLocation();
-    const logout = useAuthStore((state) => state.logout);
     const tenantType = useAuthStore((state) => state.tenantType);
 
     return (
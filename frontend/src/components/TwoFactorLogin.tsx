@@ -0,0 +1,101 @@
+import { useEffect, useState } from "react";
+import api from "@/lib/api";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+
+interface TotpSetup {
+    secret: string;
+    otpauth_uri: string;
+}
+
+interface Props {
+    challengeToken: string;
+    setupRequired: boolean;
+    onSuccess: (data: any) => void;
+}
+
+// Second login step: asks for the authenticator code, or walks the user
+// through enrolment when a policy requires 2FA they have not set up yet.
+export default function TwoFactorLogin({ challengeToken, setupRequired, onSuccess }: Props) {
+    const [setup, setSetup] = useState<TotpSetup | null>(null);
+    const [code, setCode] = useState("");
+    const [recoveryCodes, setRecoveryCodes] = useState<string[] | null>(null);
+    const [result, setResult] = useState<any>(null);
+    const [error, setError] = useState("");
+
+    useEffect(() => {
+        if (!setupRequired) return;
+        api.post("/auth/2fa/login/setup", { challenge_token: challengeToken })
+            .then((response) => setSetup(response.data))
+            .catch(() => setError("Sessão expirada. Faça login novamente."));
+    }, [challengeToken, setupRequired]);
+
+    async function submit(e: React.FormEvent) {
+        e.preventDefault();
+        setError("");
+        try {
+            const response = await api.post("/auth/2fa/login", {
+                challenge_token: challengeToken,
+                code,
+            });
+            if (response.data.recovery_codes) {
+                // Shown once: the user must save them before continuing
+                setRecoveryCodes(response.data.recovery_codes);
+                setResult(response.data);
+            } else {
+                onSuccess(response.data);
+            }
+        } catch (err: any) {
+            if (err.response?.status === 429) {
+                setError("Muitas tentativas. Aguarde um pouco e tente novamente.");
+            } else if (err.response?.data === "Invalid or expired challenge") {
+                setError("Sessão expirada. Faça login novamente.");
+            } else {
+                setError("Código inválido.");
+            }
+        }
+    }
+
+    if (recoveryCodes) {
+        return (
+            <div className="space-y-4">
+                <p className="text-sm">
+                    Guarde estes códigos de recuperação em local seguro. Cada um pode ser usado uma vez caso você perca acesso ao aplicativo autenticador.
+                </p>
+                <div className="grid grid-cols-2 gap-2 font-mono text-sm bg-muted p-3 rounded">
+                    {recoveryCodes.map((c) => <span key={c}>{c}</span>)}
+                </div>
+                <Button className="w-full" onClick={() => onSuccess(result)}>Já guardei, continuar</Button>
+            </div>
+        );
+    }
+
+    return (
+        <form onSubmit={submit} className="space-y-4">
+            {setupRequired ? (
+                <div className="space-y-2 text-sm">
+                    <p>Sua conta exige autenticação em dois fatores. Adicione-a no seu aplicativo autenticador:</p>
+                    {setup && (
+                        <>
+                            <a href={setup.otpauth_uri} className="text-primary hover:underline break-all">Abrir no aplicativo</a>
+                            <p>Ou digite a chave manualmente:</p>
+                            <p className="font-mono bg-muted p-2 rounded break-all">{setup.secret}</p>
+                        </>
+                    )}
+                    <p>Depois, informe o código de 6 dígitos gerado.</p>
+                </div>
+            ) : (
+                <p className="text-sm">Informe o código do seu aplicativo autenticador ou um código de recuperação.</p>
+            )}
+            <Input
+                value={code}
+                onChange={(e) => setCode(e.target.value)}
+                placeholder="123456"
+                autoComplete="one-time-code"
+                autoFocus
+            />
+            {error && <p className="text-sm text-red-500 font-medium text-center">{error}</p>}
+            <Button type="submit" className="w-full" disabled={!code}>Verificar</Button>
+        </form>
+    );
+}
@@ -1,8 +1,10 @@
-import axios from 'axios';
+import axios, { type InternalAxiosRequestConfig } from 'axios';
 import { useAuthStore } from '@/store/auth';
 
+const baseURL = import.meta.env.VITE_API_URL || 'http://localhost:3000';
+
 const api = axios.create({
-    baseURL: import.meta.env.VITE_API_URL || 'http://localhost:3000',
+    baseURL,
 });
 
 api.interceptors.request.use((config) => {
@@ -13,4 +15,65 @@ api.interceptors.request.use((config) => {
     return config;
 });
 
+// Access tokens are short-lived: on a 401, trade the refresh token for a new
+// pair once and replay the request. Concurrent 401s share the same refresh.
+let refreshing: Promise<string | null> | null = null;
+
+function refreshAccessToken(): Promise<string | null> {
+    const { refreshToken, setToken, setRefreshToken, logout } = useAuthStore.getState();
+    if (!refreshToken) {
+        return Promise.resolve(null);
+    }
+    if (!refreshing) {
+        refreshing = axios
+            .post(`${baseURL}/auth/refresh`, { refresh_token: refreshToken })
+            .then((response) => {
+                setToken(response.data.token);
+                setRefreshToken(response.data.refresh_token);
+                return response.data.token as string;
+            })
+            .catch(() => {
+                logout();
+                return null;
+            })
+            .finally(() => {
+                refreshing = null;
+            });
+    }
+    return refreshing;
+}
+
+api.interceptors.response.use(
+    (response) => response,
+    async (error) => {
+        const original = error.config as (InternalAxiosRequestConfig & { _retried?: boolean }) | undefined;
+        if (error.response?.status === 401 && original && !original._retried) {
+            original._retried = true;
+            const token = await refreshAccessToken();
+            if (token) {
+                original.headers.Authorization = `Bearer ${token}`;
+                return api(original);
+            }
+        }
+        // Suspended or inactive store: nothing but the block page works
+        if (
+            error.response?.status === 403 &&
+            error.response.data?.error === 'tenant_blocked' &&
+            window.location.pathname !== '/blocked'
+        ) {
+            window.location.assign('/blocked');
+        }
+        return Promise.reject(error);
+    }
+);
+
+export async function logout() {
+    try {
+        await api.post('/auth/logout');
+    } catch {
+        // The session is dropped locally either way
+    }
+    useAuthStore.getState().logout();
+}
+
 export default api;
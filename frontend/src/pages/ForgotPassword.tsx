@@ -0,0 +1,94 @@
+import { useForm } from "react-hook-form";
+import { zodResolver } from "@hookform/resolvers/zod";
+import * as z from "zod";
+import { Link } from "react-router-dom";
+import api from "@/lib/api";
+import { Button } from "@/components/ui/button";
+import {
+    Card,
+    CardContent,
+    CardDescription,
+    CardFooter,
+    CardHeader,
+    CardTitle,
+} from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import {
+    Form,
+    FormControl,
+    FormField,
+    FormItem,
+    FormLabel,
+    FormMessage,
+} from "@/components/ui/form";
+import { useState } from "react";
+
+const formSchema = z.object({
+    email: z.string().email("Email inválido"),
+});
+
+export default function ForgotPassword() {
+    const [sent, setSent] = useState(false);
+    const [error, setError] = useState("");
+
+    const form = useForm<z.infer<typeof formSchema>>({
+        resolver: zodResolver(formSchema),
+        defaultValues: {
+            email: "",
+        },
+    });
+
+    async function onSubmit(values: z.infer<typeof formSchema>) {
+        try {
+            await api.post("/auth/forgot-password", values);
+            setSent(true);
+        } catch (err) {
+            setError("Erro ao enviar o link. Tente novamente.");
+        }
+    }
+
+    return (
+        <div className="flex items-center justify-center min-h-screen bg-gray-100 dark:bg-gray-900">
+            <Card className="w-[400px] shadow-lg">
+                <CardHeader className="space-y-1">
+                    <CardTitle className="text-2xl font-bold text-center">Esqueceu a senha?</CardTitle>
+                    <CardDescription className="text-center">
+                        Informe seu email para receber um link de redefinição
+                    </CardDescription>
+                </CardHeader>
+                <CardContent>
+                    {sent ? (
+                        <p className="text-sm text-center">
+                            Se o email estiver cadastrado, você receberá um link em instantes. O link expira em 1 hora.
+                        </p>
+                    ) : (
+                        <Form {...form}>
+                            <form onSubmit={form.handleSubmit(onSubmit)} className="space-y-4">
+                                <FormField
+                                    control={form.control}
+                                    name="email"
+                                    render={({ field }) => (
+                                        <FormItem>
+                                            <FormLabel>Email</FormLabel>
+                                            <FormControl>
+                                                <Input placeholder="seu@email.com" {...field} />
+                                            </FormControl>
+                                            <FormMessage />
+                                        </FormItem>
+                                    )}
+                                />
+                                {error && <p className="text-sm text-red-500 font-medium text-center">{error}</p>}
+                                <Button type="submit" className="w-full">Enviar link</Button>
+                            </form>
+                        </Form>
+                    )}
+                </CardContent>
+                <CardFooter className="flex justify-center">
+                    <div className="text-sm text-muted-foreground">
+                        <Link to="/login" className="text-primary hover:underline">Voltar para o login</Link>
+                    </div>
+                </CardFooter>
+            </Card>
+        </div>
+    );
+}
@@ -1,7 +1,7 @@
 import { useForm } from "react-hook-form";
 import { zodResolver } from "@hookform/resolvers/zod";
 import * as z from "zod";
-import { Link, useNavigate } from "react-router-dom";
+import { Link, useLocation, useNavigate } from "react-router-dom";
 import api from "@/lib/api";
 import { useAuthStore } from "@/store/auth";
 import { Button } from "@/components/ui/button";
@@ -22,7 +22,9 @@ import {
     FormLabel,
     FormMessage,
 } from "@/components/ui/form";
-import { useState } from "react";
+import { useEffect, useState } from "react";
+import TwoFactorLogin from "@/components/TwoFactorLogin";
+import { SSO_PROVIDER_KEY } from "@/pages/SsoCallback";
 
 const formSchema = z.object({
     email: z.string().min(1, "Usuário ou Email é obrigatório"),
@@ -31,10 +33,21 @@ const formSchema = z.object({
 
 export default function Login() {
     const navigate = useNavigate();
+    const location = useLocation();
+    const registered = Boolean((location.state as { registered?: boolean } | null)?.registered);
     const setToken = useAuthStore((state) => state.setToken);
+    const setRefreshToken = useAuthStore((state) => state.setRefreshToken);
     const setTenantType = useAuthStore((state) => state.setTenantType);
     const setRole = useAuthStore((state) => state.setRole);
     const [error, setError] = useState("");
+    const [challenge, setChallenge] = useState<{ token: string; setupRequired: boolean } | null>(null);
+    const [providers, setProviders] = useState<{ name: string; display_name: string }[]>([]);
+
+    useEffect(() => {
+        api.get("/auth/sso/providers")
+            .then((response) => setProviders(response.data))
+            .catch(() => setProviders([]));
+    }, []);
 
     const form = useForm<z.infer<typeof formSchema>>({
         resolver: zodResolver(formSchema),
@@ -44,23 +57,51 @@ export default function Login() {
         },
     });
 
+    function finishLogin(data: any) {
+        setToken(data.token);
+        setRefreshToken(data.refresh_token);
+        setRole(data.role);
+        setTenantType(data.business_type);
+
+        // Redirect based on role
+        if (data.role === 'reseller') {
+            navigate("/reseller/dashboard");
+        } else if (data.role === 'admin') {
+            navigate("/admin"); // Redirect to Management Hub
+        } else {
+            navigate("/");
+        }
+    }
+
+    async function startSso(provider: string) {
+        try {
+            const response = await api.post(`/auth/sso/${provider}/authorize`);
+            sessionStorage.setItem(SSO_PROVIDER_KEY, provider);
+            window.location.href = response.data.authorization_url;
+        } catch {
+            setError("Provedor de login indisponível. Tente novamente.");
+        }
+    }
+
     async function onSubmit(values: z.infer<typeof formSchema>) {
         try {
             const response = await api.post("/auth/login", values);
-            setToken(response.data.token);
-            setRole(response.data.role);
-            setTenantType(response.data.business_type);
-
-            // Redirect based on role
-            if (response.data.role === 'reseller') {
-                navigate("/reseller/dashboard");
-            } else if (response.data.role === 'admin') {
-                navigate("/admin"); // Redirect to Management Hub
+            if (response.data.two_factor_required) {
+                setChallenge({
+                    token: response.data.challenge_token,
+                    setupRequired: response.data.setup_required,
+                });
+                return;
+            }
+            finishLogin(response.data);
+        } catch (err: any) {
+            if (err.response?.status === 403 && err.response?.data === "Email not verified") {
+                setError("Confirme seu email antes de entrar. Verifique sua caixa de entrada.");
+            } else if (err.response?.status === 429) {
+                setError("Muitas tentativas. Aguarde um pouco e tente novamente.");
             } else {
-                navigate("/");
+                setError("Credenciais inválidas. Tente novamente.");
             }
-        } catch (err) {
-            setError("Credenciais inválidas. Tente novamente.");
         }
     }
 
@@ -74,6 +115,13 @@ export default function Login() {
                     </CardDescription>
                 </CardHeader>
                 <CardContent>
+                    {challenge ? (
+                        <TwoFactorLogin
+                            challengeToken={challenge.token}
+                            setupRequired={challenge.setupRequired}
+                            onSuccess={finishLogin}
+                        />
+                    ) : (
                     <Form {...form}>
                         <form onSubmit={form.handleSubmit(onSubmit)} className="space-y-4">
                             <FormField
@@ -102,10 +150,30 @@ export default function Login() {
                                     </FormItem>
                                 )}
                             />
+                            {registered && !error && (
+                                <p className="text-sm text-green-600 font-medium text-center">
+                                    Conta criada! Enviamos um link de confirmação para o seu email.
+                                </p>
+                            )}
                             {error && <p className="text-sm text-red-500 font-medium text-center">{error}</p>}
                             <Button type="submit" className="w-full">Entrar</Button>
+                            <div className="text-sm text-center">
+                                <Link to="/forgot-password" className="text-primary hover:underline">Esqueceu a senha?</Link>
+                            </div>
+                            {providers.map((provider) => (
+                                <Button
+                                    key={provider.name}
+                                    type="button"
+                                    variant="outline"
+                                    className="w-full"
+                                    onClick={() => startSso(provider.name)}
+                                >
+                                    Entrar com {provider.display_name}
+                                </Button>
+                            ))}
                         </form>
                     </Form>
+                    )}
                 </CardContent>
                 <CardFooter className="flex justify-center">
                     <div className="text-sm text-muted-foreground">
@@ -25,8 +25,8 @@ import { useState } from "react";
 
 const formSchema = z.object({
     email: z.string().email("Email inválido"),
-    password: z.string().min(6, "A senha deve ter pelo menos 6 caracteres"),
-    confirmPassword: z.string().min(6, "A senha deve ter pelo menos 6 caracteres"),
+    password: z.string().min(8, "A senha deve ter pelo menos 8 caracteres"),
+    confirmPassword: z.string().min(8, "A senha deve ter pelo menos 8 caracteres"),
 }).refine((data) => data.password === data.confirmPassword, {
     message: "As senhas não coincidem",
     path: ["confirmPassword"],
@@ -51,7 +51,7 @@ export default function Register() {
                 email: values.email,
                 password: values.password
             });
-            navigate("/login");
+            navigate("/login", { state: { registered: true } });
         } catch (err: any) {
             if (err.response?.status === 409) {
                 setError("Este email já está cadastrado.");
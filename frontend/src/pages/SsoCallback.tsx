@@ -0,0 +1,97 @@
+import { Link, useNavigate, useSearchParams } from "react-router-dom";
+import api from "@/lib/api";
+import { useAuthStore } from "@/store/auth";
+import {
+    Card,
+    CardContent,
+    CardFooter,
+    CardHeader,
+    CardTitle,
+} from "@/components/ui/card";
+import { useEffect, useRef, useState } from "react";
+import TwoFactorLogin from "@/components/TwoFactorLogin";
+
+// Set by the login page before leaving for the identity provider
+export const SSO_PROVIDER_KEY = "sso-provider";
+
+export default function SsoCallback() {
+    const navigate = useNavigate();
+    const [searchParams] = useSearchParams();
+    const setToken = useAuthStore((state) => state.setToken);
+    const setRefreshToken = useAuthStore((state) => state.setRefreshToken);
+    const setTenantType = useAuthStore((state) => state.setTenantType);
+    const setRole = useAuthStore((state) => state.setRole);
+    const [error, setError] = useState("");
+    const [challenge, setChallenge] = useState<{ token: string; setupRequired: boolean } | null>(null);
+    // Codes are single-use, so StrictMode's double effect must not send twice
+    const sent = useRef(false);
+
+    function finishLogin(data: any) {
+        setToken(data.token);
+        setRefreshToken(data.refresh_token);
+        setRole(data.role);
+        setTenantType(data.business_type);
+        navigate(data.role === "reseller" ? "/reseller/dashboard" : "/admin");
+    }
+
+    useEffect(() => {
+        if (sent.current) return;
+        sent.current = true;
+
+        const provider = sessionStorage.getItem(SSO_PROVIDER_KEY);
+        sessionStorage.removeItem(SSO_PROVIDER_KEY);
+        const code = searchParams.get("code");
+        const state = searchParams.get("state");
+        if (!provider || !code || !state) {
+            setError(searchParams.get("error_description") ?? "Login cancelado ou inválido.");
+            return;
+        }
+
+        api.post(`/auth/sso/${provider}/callback`, { code, state })
+            .then((response) => {
+                if (response.data.two_factor_required) {
+                    setChallenge({
+                        token: response.data.challenge_token,
+                        setupRequired: response.data.setup_required,
+                    });
+                } else {
+                    finishLogin(response.data);
+                }
+            })
+            .catch((err) => {
+                if (err.response?.status === 403) {
+                    setError("Sua conta não tem acesso por login corporativo.");
+                } else {
+                    setError("Não foi possível concluir o login. Tente novamente.");
+                }
+            });
+    }, [searchParams]);
+
+    return (
+        <div className="flex items-center justify-center min-h-screen bg-gray-100 dark:bg-gray-900">
+            <Card className="w-[400px] shadow-lg">
+                <CardHeader className="space-y-1">
+                    <CardTitle className="text-2xl font-bold text-center">Login Corporativo</CardTitle>
+                </CardHeader>
+                <CardContent>
+                    {challenge ? (
+                        <TwoFactorLogin
+                            challengeToken={challenge.token}
+                            setupRequired={challenge.setupRequired}
+                            onSuccess={finishLogin}
+                        />
+                    ) : (
+                        <p className={`text-sm text-center ${error ? "text-red-500 font-medium" : ""}`}>
+                            {error || "Concluindo login..."}
+                        </p>
+                    )}
+                </CardContent>
+                <CardFooter className="flex justify-center">
+                    <div className="text-sm text-muted-foreground">
+                        <Link to="/login" className="text-primary hover:underline">Voltar para o login</Link>
+                    </div>
+                </CardFooter>
+            </Card>
+        </div>
+    );
+}
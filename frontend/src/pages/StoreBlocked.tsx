@@ -0,0 +1,38 @@
+import { useNavigate } from "react-router-dom";
+import { logout } from "@/lib/api";
+import { Button } from "@/components/ui/button";
+import {
+    Card,
+    CardContent,
+    CardFooter,
+    CardHeader,
+    CardTitle,
+} from "@/components/ui/card";
+
+export default function StoreBlocked() {
+    const navigate = useNavigate();
+
+    async function handleLogout() {
+        await logout();
+        navigate("/login");
+    }
+
+    return (
+        <div className="flex items-center justify-center min-h-screen bg-gray-100 dark:bg-gray-900">
+            <Card className="w-[400px] shadow-lg">
+                <CardHeader className="space-y-1">
+                    <CardTitle className="text-2xl font-bold text-center">Loja bloqueada</CardTitle>
+                </CardHeader>
+                <CardContent className="space-y-2 text-sm text-center">
+                    <p>O acesso a esta loja está suspenso.</p>
+                    <p className="text-muted-foreground">
+                        Regularize a assinatura ou entre em contato com o suporte para reativá-la.
+                    </p>
+                </CardContent>
+                <CardFooter className="flex justify-center">
+                    <Button variant="outline" onClick={handleLogout}>Sair</Button>
+                </CardFooter>
+            </Card>
+        </div>
+    );
+}
@@ -0,0 +1,132 @@
+import { useEffect, useState } from "react";
+import { useNavigate } from "react-router-dom";
+import api from "@/lib/api";
+import { useAuthStore } from "@/store/auth";
+import { Button } from "@/components/ui/button";
+import {
+    Card,
+    CardContent,
+    CardDescription,
+    CardHeader,
+    CardTitle,
+} from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+
+// Kept outside the auth store so it survives cashiers logging out
+const DEVICE_TOKEN_KEY = "terminal-device-token";
+
+interface TerminalUser {
+    id: string;
+    email: string;
+    role: string;
+}
+
+export default function TerminalLogin() {
+    const navigate = useNavigate();
+    const setToken = useAuthStore((state) => state.setToken);
+    const setRefreshToken = useAuthStore((state) => state.setRefreshToken);
+    const setTenantType = useAuthStore((state) => state.setTenantType);
+    const setRole = useAuthStore((state) => state.setRole);
+    const [deviceToken, setDeviceToken] = useState(() => localStorage.getItem(DEVICE_TOKEN_KEY) ?? "");
+    const [tokenInput, setTokenInput] = useState("");
+    const [users, setUsers] = useState<TerminalUser[]>([]);
+    const [selected, setSelected] = useState<TerminalUser | null>(null);
+    const [pin, setPin] = useState("");
+    const [error, setError] = useState("");
+
+    useEffect(() => {
+        if (!deviceToken) return;
+        api.post("/auth/terminal/users", { device_token: deviceToken })
+            .then((response) => setUsers(response.data))
+            .catch((err) => {
+                if (err.response?.status === 401) {
+                    // Terminal was revoked
+                    localStorage.removeItem(DEVICE_TOKEN_KEY);
+                    setDeviceToken("");
+                }
+                setError("Terminal não autorizado.");
+            });
+    }, [deviceToken]);
+
+    function saveDeviceToken() {
+        localStorage.setItem(DEVICE_TOKEN_KEY, tokenInput.trim());
+        setDeviceToken(tokenInput.trim());
+        setError("");
+    }
+
+    async function login(e: React.FormEvent) {
+        e.preventDefault();
+        if (!selected) return;
+        try {
+            const response = await api.post("/auth/pin-login", {
+                device_token: deviceToken,
+                user_id: selected.id,
+                pin,
+            });
+            setToken(response.data.token);
+            setRefreshToken(response.data.refresh_token);
+            setRole(response.data.role);
+            setTenantType(response.data.business_type);
+            navigate("/pos");
+        } catch (err: any) {
+            setPin("");
+            if (err.response?.status === 429) {
+                setError("Muitas tentativas. Aguarde um pouco e tente novamente.");
+            } else if (err.response?.status === 403) {
+                setError("PIN bloqueado. Entre com email e senha para criar um novo.");
+            } else {
+                setError("PIN inválido.");
+            }
+        }
+    }
+
+    return (
+        <div className="flex items-center justify-center min-h-screen bg-gray-100 dark:bg-gray-900">
+            <Card className="w-[400px] shadow-lg">
+                <CardHeader className="space-y-1">
+                    <CardTitle className="text-2xl font-bold text-center">Terminal</CardTitle>
+                    <CardDescription className="text-center">
+                        {deviceToken ? "Selecione seu usuário e digite o PIN" : "Informe o token de registro deste terminal"}
+                    </CardDescription>
+                </CardHeader>
+                <CardContent className="space-y-4">
+                    {!deviceToken ? (
+                        <>
+                            <Input value={tokenInput} onChange={(e) => setTokenInput(e.target.value)} placeholder="Token do terminal" />
+                            <Button className="w-full" onClick={saveDeviceToken} disabled={!tokenInput.trim()}>Registrar</Button>
+                        </>
+                    ) : !selected ? (
+                        <div className="grid gap-2">
+                            {users.map((user) => (
+                                <Button key={user.id} variant="outline" onClick={() => { setSelected(user); setError(""); }}>
+                                    {user.email}
+                                </Button>
+                            ))}
+                            {users.length === 0 && !error && (
+                                <p className="text-sm text-center text-muted-foreground">Nenhum usuário com PIN cadastrado.</p>
+                            )}
+                        </div>
+                    ) : (
+                        <form onSubmit={login} className="space-y-4">
+                            <p className="text-sm text-center font-medium">{selected.email}</p>
+                            <Input
+                                type="password"
+                                inputMode="numeric"
+                                value={pin}
+                                onChange={(e) => setPin(e.target.value.replace(/\D/g, ""))}
+                                placeholder="PIN"
+                                maxLength={8}
+                                autoFocus
+                            />
+                            <Button type="submit" className="w-full" disabled={pin.length < 4}>Entrar</Button>
+                            <Button type="button" variant="ghost" className="w-full" onClick={() => { setSelected(null); setPin(""); }}>
+                                Trocar usuário
+                            </Button>
+                        </form>
+                    )}
+                    {error && <p className="text-sm text-red-500 font-medium text-center">{error}</p>}
+                </CardContent>
+            </Card>
+        </div>
+    );
+}
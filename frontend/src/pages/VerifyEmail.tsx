@@ -0,0 +1,48 @@
+import { Link, useSearchParams } from "react-router-dom";
+import api from "@/lib/api";
+import {
+    Card,
+    CardContent,
+    CardFooter,
+    CardHeader,
+    CardTitle,
+} from "@/components/ui/card";
+import { useEffect, useRef, useState } from "react";
+
+export default function VerifyEmail() {
+    const [searchParams] = useSearchParams();
+    const token = searchParams.get("token") ?? "";
+    const [status, setStatus] = useState<"loading" | "done" | "error">("loading");
+    // Tokens are single-use, so StrictMode's double effect must not send twice
+    const sent = useRef(false);
+
+    useEffect(() => {
+        if (sent.current) return;
+        sent.current = true;
+        api.post("/auth/verify-email", { token })
+            .then(() => setStatus("done"))
+            .catch(() => setStatus("error"));
+    }, [token]);
+
+    return (
+        <div className="flex items-center justify-center min-h-screen bg-gray-100 dark:bg-gray-900">
+            <Card className="w-[400px] shadow-lg">
+                <CardHeader className="space-y-1">
+                    <CardTitle className="text-2xl font-bold text-center">Confirmação de Email</CardTitle>
+                </CardHeader>
+                <CardContent>
+                    <p className="text-sm text-center">
+                        {status === "loading" && "Confirmando seu email..."}
+                        {status === "done" && "Email confirmado! Você já pode entrar."}
+                        {status === "error" && "Link inválido ou expirado."}
+                    </p>
+                </CardContent>
+                <CardFooter className="flex justify-center">
+                    <div className="text-sm text-muted-foreground">
+                        <Link to="/login" className="text-primary hover:underline">Ir para o login</Link>
+                    </div>
+                </CardFooter>
+            </Card>
+        </div>
+    );
+}
@@ -28,6 +28,8 @@ import {
     FormMessage,
 } from "@/components/ui/form";
 import { Input } from "@/components/ui/input";
+import { Checkbox } from "@/components/ui/checkbox";
+import { Badge } from "@/components/ui/badge";
 import { Plus } from "lucide-react";
 
 interface Plan {
@@ -35,12 +37,25 @@ interface Plan {
     name: string;
     price: number;
     max_users: number;
+    features: string[] | null;
 }
 
+// Same names as the backend's plans::Feature
+const FEATURES: { value: string; label: string }[] = [
+    { value: "metrics", label: "Relatórios e métricas" },
+    { value: "loyalty", label: "Programa de fidelidade" },
+    { value: "coupons", label: "Cupons" },
+    { value: "gift_cards", label: "Vale-presente" },
+    { value: "deliveries", label: "Delivery" },
+    { value: "segments", label: "Segmentação de clientes" },
+    { value: "multiple_registers", label: "Múltiplos caixas" },
+];
+
 const formSchema = z.object({
     name: z.string().min(1, "Nome é obrigatório"),
     price: z.coerce.number().min(0),
     max_users: z.coerce.number().min(1),
+    features: z.array(z.string()),
 });
 
 export default function Plans() {
@@ -53,6 +68,7 @@ export default function Plans() {
             name: "",
             price: 0,
             max_users: 1,
+            features: [],
         },
     });
 
@@ -135,6 +151,33 @@ export default function Plans() {
                                         </FormItem>
                                     )}
                                 />
+                                <FormField
+                                    control={form.control as any}
+                                    name="features"
+                                    render={({ field }) => (
+                                        <FormItem>
+                                            <FormLabel>Recursos incluídos</FormLabel>
+                                            <div className="grid grid-cols-2 gap-2">
+                                                {FEATURES.map((feature) => (
+                                                    <label key={feature.value} className="flex items-center gap-2 text-sm">
+                                                        <Checkbox
+                                                            checked={field.value.includes(feature.value)}
+                                                            onCheckedChange={(checked) =>
+                                                                field.onChange(
+                                                                    checked
+                                                                        ? [...field.value, feature.value]
+                                                                        : field.value.filter((f: string) => f !== feature.value)
+                                                                )
+                                                            }
+                                                        />
+                                                        {feature.label}
+                                                    </label>
+                                                ))}
+                                            </div>
+                                            <FormMessage />
+                                        </FormItem>
+                                    )}
+                                />
                                 <Button type="submit" className="w-full">Salvar</Button>
                             </form>
                         </Form>
@@ -149,6 +192,7 @@ export default function Plans() {
                             <TableHead>Nome</TableHead>
                             <TableHead>Preço</TableHead>
                             <TableHead>Max Usuários</TableHead>
+                            <TableHead>Recursos</TableHead>
                         </TableRow>
                     </TableHeader>
                     <TableBody>
@@ -157,6 +201,15 @@ export default function Plans() {
                                 <TableCell className="font-medium">{plan.name}</TableCell>
                                 <TableCell>R$ {(plan.price / 100).toFixed(2)}</TableCell>
                                 <TableCell>{plan.max_users}</TableCell>
+                                <TableCell className="space-x-1">
+                                    {plan.features === null ? (
+                                        <Badge variant="secondary">Todos</Badge>
+                                    ) : (
+                                        FEATURES.filter((f) => plan.features?.includes(f.value)).map((f) => (
+                                            <Badge key={f.value} variant="outline">{f.label}</Badge>
+                                        ))
+                                    )}
+                                </TableCell>
                             </TableRow>
                         ))}
                     </TableBody>
@@ -3,9 +3,11 @@ import { persist } from 'zustand/middleware';
 
 interface AuthState {
     token: string | null;
+    refreshToken: string | null;
     tenantType: string | null;
     role: string | null;
     setToken: (token: string | null) => void;
+    setRefreshToken: (refreshToken: string | null) => void;
     setTenantType: (type: string | null) => void;
     setRole: (role: string | null) => void;
     logout: () => void;
@@ -15,12 +17,14 @@ export const useAuthStore = create<AuthState>()(
     persist(
         (set) => ({
             token: null,
+            refreshToken: null,
             tenantType: null,
             role: null,
             setToken: (token) => set({ token }),
+            setRefreshToken: (refreshToken) => set({ refreshToken }),
             setTenantType: (type) => set({ tenantType: type }),
             setRole: (role) => set({ role }),
-            logout: () => set({ token: null, tenantType: null, role: null }),
+            logout: () => set({ token: null, refreshToken: null, tenantType: null, role: null }),
         }),
         {
             name: 'auth-storage',